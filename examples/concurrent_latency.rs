@@ -0,0 +1,161 @@
+/*!
+ * 这个示例测量长时间运行的查询执行期间，其他小请求的响应延迟。
+ *
+ * 服务器运行在单线程运行时上，通过内存管道收发JSON-RPC消息。先提交一个运行数秒的查询，
+ * 然后依次发送`tools/list`请求和`SELECT 1`查询，统计每个请求从发送到收到响应的时间。
+ * `tools/list`不访问数据库，应当立即返回；`SELECT 1`由只读连接池中的空闲连接执行，同样不需要等待长查询。
+ * 使用`--readers 0`对应的配置（`RouterOptions::readers`为`0`）时，`SELECT 1`需要在长查询之后排队。
+ *
+ * ```bash
+ * cargo run --release --example concurrent_latency
+ * ```
+ */
+
+use std::time::{Duration, Instant};
+
+use mcp_sqlite::{serve::serve, SQLiteRouter};
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+/// 运行时间较长的查询，由`timeout_ms`在指定时间后中断
+const LONG_QUERY: &str =
+    "WITH RECURSIVE c(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM c) SELECT count(*) FROM c";
+
+/// 长查询的运行时间
+const LONG_QUERY_MS: u64 = 3000;
+
+/// 每种小请求发送的次数
+const SAMPLES: usize = 10;
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> anyhow::Result<()> {
+    let router = SQLiteRouter::new(":memory:")?;
+    let (client, server) = tokio::io::duplex(64 * 1024);
+    let (server_reader, server_writer) = tokio::io::split(server);
+    let server_task = tokio::spawn(serve(router, server_reader, server_writer));
+
+    let (client_reader, mut client_writer) = tokio::io::split(client);
+    let mut responses = BufReader::new(client_reader).lines();
+    let mut next_id = 1u64;
+
+    println!("baseline (no long query):");
+    report(
+        "tools/list",
+        &measure(&mut client_writer, &mut responses, &mut next_id, tools_list).await?,
+    );
+    report(
+        "query SELECT 1",
+        &measure(&mut client_writer, &mut responses, &mut next_id, select_one).await?,
+    );
+
+    // 提交长查询但不等待它的响应
+    let long_id = next_id;
+    next_id += 1;
+    let started = Instant::now();
+    send(
+        &mut client_writer,
+        call_tool(
+            long_id,
+            "query",
+            json!({ "query": LONG_QUERY, "timeout_ms": LONG_QUERY_MS }),
+        ),
+    )
+    .await?;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    println!("during a {} ms query:", LONG_QUERY_MS);
+    report(
+        "tools/list",
+        &measure(&mut client_writer, &mut responses, &mut next_id, tools_list).await?,
+    );
+    report(
+        "query SELECT 1",
+        &measure(&mut client_writer, &mut responses, &mut next_id, select_one).await?,
+    );
+    wait_for(&mut responses, long_id).await?;
+    println!(
+        "long query finished after {} ms",
+        started.elapsed().as_millis()
+    );
+
+    drop(client_writer);
+    drop(responses);
+    server_task.await??;
+    Ok(())
+}
+
+fn tools_list(id: u64) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "method": "tools/list" })
+}
+
+fn select_one(id: u64) -> Value {
+    call_tool(id, "query", json!({ "query": "SELECT 1" }))
+}
+
+fn call_tool(id: u64, name: &str, arguments: Value) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "method": "tools/call",
+        "params": { "name": name, "arguments": arguments }
+    })
+}
+
+async fn send<W: AsyncWriteExt + Unpin>(writer: &mut W, message: Value) -> anyhow::Result<()> {
+    writer
+        .write_all(format!("{}\n", message).as_bytes())
+        .await?;
+    Ok(())
+}
+
+/// 依次发送`SAMPLES`个请求，返回每个请求的延迟
+async fn measure<W, R>(
+    writer: &mut W,
+    responses: &mut tokio::io::Lines<R>,
+    next_id: &mut u64,
+    request: fn(u64) -> Value,
+) -> anyhow::Result<Vec<Duration>>
+where
+    W: AsyncWriteExt + Unpin,
+    R: tokio::io::AsyncBufRead + Unpin,
+{
+    let mut latencies = Vec::with_capacity(SAMPLES);
+    for _ in 0..SAMPLES {
+        let id = *next_id;
+        *next_id += 1;
+        let sent = Instant::now();
+        send(writer, request(id)).await?;
+        wait_for(responses, id).await?;
+        latencies.push(sent.elapsed());
+    }
+    Ok(latencies)
+}
+
+/// 读取响应直到收到指定请求的响应，期间收到的其他响应被忽略
+async fn wait_for<R>(responses: &mut tokio::io::Lines<R>, id: u64) -> anyhow::Result<()>
+where
+    R: tokio::io::AsyncBufRead + Unpin,
+{
+    loop {
+        let line = responses
+            .next_line()
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("server closed the connection"))?;
+        let response: Value = serde_json::from_str(&line)?;
+        if response["id"] == json!(id) {
+            return Ok(());
+        }
+    }
+}
+
+fn report(label: &str, latencies: &[Duration]) {
+    let mut sorted = latencies.to_vec();
+    sorted.sort();
+    println!(
+        "  {:<16} min {:>8.2?}  median {:>8.2?}  max {:>8.2?}",
+        label,
+        sorted[0],
+        sorted[sorted.len() / 2],
+        sorted[sorted.len() - 1]
+    );
+}
@@ -0,0 +1,208 @@
+/*!
+ * 这个示例在同一个进程中启动HTTP/SSE传输的服务器，并用一个最小的客户端驱动它：
+ * 打开事件流、读取`endpoint`事件、向消息地址POST请求，再从事件流读取响应。
+ *
+ * ```bash
+ * cargo run --example sse_client
+ * ```
+ */
+
+use std::net::SocketAddr;
+
+use mcp_sqlite::{sse::serve_sse, SQLiteRouter};
+use serde_json::{json, Value};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{
+        tcp::{OwnedReadHalf, OwnedWriteHalf},
+        TcpListener, TcpStream,
+    },
+};
+
+/// 通过HTTP/SSE传输与服务器通信的客户端
+struct SseClient {
+    address: SocketAddr,
+    events: BufReader<OwnedReadHalf>,
+    /// 事件流期间不再发送数据，但要保留写入端，关闭它会被服务器视为断开连接
+    _stream: OwnedWriteHalf,
+    endpoint: String,
+    next_id: u64,
+}
+
+impl SseClient {
+    /// 打开事件流并读取消息地址
+    async fn connect(address: SocketAddr) -> anyhow::Result<Self> {
+        let stream = TcpStream::connect(address).await?;
+        let (reader, mut writer) = stream.into_split();
+        writer
+            .write_all(
+                format!(
+                    "GET /sse HTTP/1.1\r\nHost: {}\r\nAccept: text/event-stream\r\n\r\n",
+                    address
+                )
+                .as_bytes(),
+            )
+            .await?;
+        let mut events = BufReader::new(reader);
+        let status = read_line(&mut events).await?;
+        anyhow::ensure!(status.contains(" 200 "), "unexpected status: {}", status);
+        while !read_line(&mut events).await?.is_empty() {}
+
+        let mut client = Self {
+            address,
+            events,
+            _stream: writer,
+            endpoint: String::new(),
+            next_id: 1,
+        };
+        let (event, data) = client.next_event().await?;
+        anyhow::ensure!(
+            event == "endpoint",
+            "expected endpoint event, got {}",
+            event
+        );
+        client.endpoint = data;
+        Ok(client)
+    }
+
+    /// 读取下一个事件，跳过保活注释
+    async fn next_event(&mut self) -> anyhow::Result<(String, String)> {
+        let mut event = String::from("message");
+        let mut data = Vec::new();
+        loop {
+            let line = read_line(&mut self.events).await?;
+            if line.is_empty() {
+                if !data.is_empty() {
+                    return Ok((event, data.join("\n")));
+                }
+            } else if let Some(value) = line.strip_prefix("event: ") {
+                event = value.to_string();
+            } else if let Some(value) = line.strip_prefix("data: ") {
+                data.push(value.to_string());
+            }
+        }
+    }
+
+    /// 向消息地址POST一条消息
+    async fn post(&self, message: &Value) -> anyhow::Result<()> {
+        let body = message.to_string();
+        let mut stream = TcpStream::connect(self.address).await?;
+        stream
+            .write_all(
+                format!(
+                    "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    self.endpoint,
+                    self.address,
+                    body.len(),
+                    body
+                )
+                .as_bytes(),
+            )
+            .await?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await?;
+        anyhow::ensure!(
+            response.starts_with("HTTP/1.1 202"),
+            "unexpected response: {}",
+            response
+        );
+        Ok(())
+    }
+
+    /// 发送请求并等待它的响应
+    async fn request(&mut self, method: &str, params: Value) -> anyhow::Result<Value> {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.post(&json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }))
+            .await?;
+        loop {
+            let (_, data) = self.next_event().await?;
+            let message: Value = serde_json::from_str(&data)?;
+            if message["id"] == json!(id) {
+                return Ok(message);
+            }
+        }
+    }
+
+    /// 调用工具并返回文本结果
+    async fn call_tool(&mut self, name: &str, arguments: Value) -> anyhow::Result<String> {
+        let response = self
+            .request(
+                "tools/call",
+                json!({ "name": name, "arguments": arguments }),
+            )
+            .await?;
+        Ok(response["result"]["content"][0]["text"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string())
+    }
+}
+
+async fn read_line(reader: &mut BufReader<OwnedReadHalf>) -> anyhow::Result<String> {
+    let mut line = String::new();
+    anyhow::ensure!(
+        reader.read_line(&mut line).await? > 0,
+        "server closed the connection"
+    );
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    // 在随机端口上启动服务器
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let address = listener.local_addr()?;
+    let router = SQLiteRouter::new(":memory:")?;
+    tokio::spawn(serve_sse(move || router.session(), listener));
+    println!("服务器监听: {}", address);
+
+    let mut client = SseClient::connect(address).await?;
+    println!("消息地址: {}", client.endpoint);
+
+    let initialize = client
+        .request(
+            "initialize",
+            json!({
+                "protocolVersion": "2024-11-05",
+                "capabilities": {},
+                "clientInfo": { "name": "sse-example", "version": "1.0.0" }
+            }),
+        )
+        .await?;
+    println!("服务器信息: {}", initialize["result"]["serverInfo"]);
+    client
+        .post(&json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }))
+        .await?;
+
+    let result = client
+        .call_tool(
+            "execute",
+            json!({ "statement": "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT)" }),
+        )
+        .await?;
+    println!("创建表: {}", result);
+
+    let result = client
+        .call_tool(
+            "executemany",
+            json!({
+                "statement": "INSERT INTO users (name) VALUES (?)",
+                "params_list": [["张三"], ["李四"]]
+            }),
+        )
+        .await?;
+    println!("插入数据: {}", result);
+
+    // 第二个客户端共享同一个数据库
+    let mut other = SseClient::connect(address).await?;
+    let result = other
+        .call_tool("query", json!({ "query": "SELECT * FROM users" }))
+        .await?;
+    println!("另一个客户端查询: {}", result);
+    let rows: Value = serde_json::from_str(&result)?;
+    assert_eq!(rows["rows"].as_array().map(Vec::len), Some(2));
+
+    println!("示例完成");
+    Ok(())
+}
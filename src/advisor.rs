@@ -0,0 +1,490 @@
+/*!
+ * # 索引建议
+ *
+ * `suggest_indexes`工具参照SQLite命令行的`.expert`为一组查询推荐索引：
+ *
+ * 1. 把`main`数据库（或者`database`参数选择的数据库）的表、索引、视图和`sqlite_stat1`统计信息复制到一个内存数据库中，不复制数据
+ * 2. 在副本中预编译每个查询，通过授权回调记录查询读取的表和列
+ * 3. 为这些列创建候选索引：每一列的单列索引，以及两列的组合索引（每张表最多考虑前8列）
+ * 4. 再次生成查询计划，查询优化器用来把扫描改为`SEARCH`或者用来避免临时B树排序的候选索引成为建议
+ *
+ * 每条建议包含`CREATE INDEX`语句、受益的查询以及预期收益：
+ *
+ * ```json
+ * {
+ *     "table": "users",
+ *     "columns": ["email"],
+ *     "sql": "CREATE INDEX \"idx_users_email\" ON \"users\" (\"email\")",
+ *     "table_rows": 120000,
+ *     "queries": [
+ *         { "query": 0, "full_scans": [1, 0], "temp_btrees": [0, 0] }
+ *     ]
+ * }
+ * ```
+ *
+ * `full_scans`和`temp_btrees`分别是使用所有候选索引之前和之后计划中全表扫描和临时B树排序的数量。
+ * `table_rows`来自`sqlite_stat1`，没有统计信息时为`null`，此时查询优化器按默认的行数估计，
+ * 建议先执行`ANALYZE`。建议按表的行数从大到小排列。
+ *
+ * 选择`test`时，每个建议的索引单独在新的副本中创建，返回受益查询修改前后的计划树。
+ *
+ * 限制：只分析`main`数据库；在副本中无法创建的对象（如模块不可用的虚拟表）会被跳过，
+ * 引用这些对象的查询出现在`skipped`中；候选索引最多包含两列。
+ */
+
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+};
+
+use rusqlite::{
+    hooks::{AuthAction, AuthContext, Authorization},
+    Connection,
+};
+use serde_json::{json, Value};
+
+use crate::{
+    explain::{self, PlanStep},
+    schema::quote_identifier,
+};
+
+/// 每张表最多为多少列生成候选索引
+pub const MAX_CANDIDATE_COLUMNS: usize = 8;
+
+/// 副本中候选索引名称的前缀
+const CANDIDATE_PREFIX: &str = "mcp_candidate_";
+
+/// 基于查询计划的索引顾问
+///
+/// 创建时读取数据库结构和统计信息，之后的分析只在内存中的副本上进行
+#[derive(Debug, Clone)]
+pub struct IndexAdvisor {
+    /// 复制到副本中的建表、建索引和建视图语句
+    schema: Vec<String>,
+    /// `sqlite_stat1`中的统计信息：表名、索引名和统计值
+    stats: Vec<(String, Option<String>, String)>,
+    /// 已有的索引：表名和键列
+    indexes: Vec<(String, Vec<String>)>,
+    /// 已有的索引名称
+    index_names: HashSet<String>,
+    /// 作为rowid别名的`INTEGER PRIMARY KEY`列：表名和列名，索引总是包含rowid，不为它们生成候选索引
+    rowid_columns: Vec<(String, String)>,
+}
+
+/// 候选索引
+#[derive(Debug, Clone)]
+struct Candidate {
+    table: String,
+    columns: Vec<String>,
+}
+
+/// 一条建议对一个查询的收益
+#[derive(Debug, Clone)]
+struct Benefit {
+    query: usize,
+    full_scans: (usize, usize),
+    temp_btrees: (usize, usize),
+}
+
+impl IndexAdvisor {
+    /// 读取`main`数据库的结构和统计信息
+    ///
+    /// # 示例
+    ///
+    /// ```
+    /// use mcp_sqlite::advisor::IndexAdvisor;
+    /// use rusqlite::Connection;
+    ///
+    /// let conn = Connection::open_in_memory().unwrap();
+    /// conn.execute_batch(
+    ///     "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT, email TEXT);
+    ///      CREATE TABLE orders (id INTEGER PRIMARY KEY, user_id INTEGER, total REAL);",
+    /// )
+    /// .unwrap();
+    ///
+    /// let advisor = IndexAdvisor::new(&conn).unwrap();
+    /// let result = advisor
+    ///     .suggest(&["SELECT * FROM users WHERE email = ?", "SELECT id FROM users"], false)
+    ///     .unwrap();
+    /// let suggestions = result["suggestions"].as_array().unwrap();
+    /// assert_eq!(suggestions.len(), 1);
+    /// assert_eq!(suggestions[0]["table"], "users");
+    /// assert_eq!(suggestions[0]["columns"][0], "email");
+    /// assert_eq!(suggestions[0]["queries"][0]["full_scans"], serde_json::json!([1, 0]));
+    /// ```
+    pub fn new(conn: &Connection) -> rusqlite::Result<Self> {
+        Self::for_database(conn, "main")
+    }
+
+    /// 读取指定数据库（如附加的数据库）的结构和统计信息
+    ///
+    /// 副本中该数据库成为`main`，因此查询应当使用不带数据库名的表名
+    pub fn for_database(conn: &Connection, database: &str) -> rusqlite::Result<Self> {
+        let master = format!("{}.sqlite_master", quote_identifier(database));
+        let mut stmt = conn.prepare(&format!(
+            "SELECT sql FROM {}
+             WHERE sql IS NOT NULL AND type IN ('table', 'index', 'view') AND name NOT LIKE 'sqlite\\_%' ESCAPE '\\'
+             ORDER BY CASE type WHEN 'table' THEN 0 WHEN 'index' THEN 1 ELSE 2 END, rowid",
+            master
+        ))?;
+        let schema = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+
+        let has_stats: bool = conn.query_row(
+            &format!(
+                "SELECT EXISTS (SELECT 1 FROM {} WHERE type = 'table' AND name = 'sqlite_stat1')",
+                master
+            ),
+            [],
+            |row| row.get(0),
+        )?;
+        let stats = if has_stats {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT tbl, idx, stat FROM {}.sqlite_stat1",
+                quote_identifier(database)
+            ))?;
+            let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?;
+            rows.collect::<rusqlite::Result<Vec<_>>>()?
+        } else {
+            Vec::new()
+        };
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT m.tbl_name, m.name, i.name FROM {} AS m, pragma_index_info(m.name, ?1) AS i
+             WHERE m.type = 'index' ORDER BY m.name, i.seqno",
+            master
+        ))?;
+        let mut indexes: Vec<(String, String, Vec<String>)> = Vec::new();
+        let mut rows = stmt.query([database])?;
+        while let Some(row) = rows.next()? {
+            let table: String = row.get(0)?;
+            let index: String = row.get(1)?;
+            // 表达式索引的列名为NULL
+            let column: Option<String> = row.get(2)?;
+            match indexes.last_mut() {
+                Some((_, name, columns)) if *name == index => columns.extend(column),
+                _ => indexes.push((table, index, column.into_iter().collect())),
+            }
+        }
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT m.name, p.name FROM {} AS m, pragma_table_info(m.name, ?1) AS p
+             WHERE m.type = 'table' AND p.pk = 1 AND upper(p.type) = 'INTEGER'
+               AND (SELECT count(*) FROM pragma_table_info(m.name, ?1) WHERE pk > 0) = 1",
+            master
+        ))?;
+        let rowid_columns = stmt
+            .query_map([database], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(Self {
+            schema,
+            stats,
+            rowid_columns,
+            index_names: indexes.iter().map(|(_, name, _)| name.clone()).collect(),
+            indexes: indexes
+                .into_iter()
+                .map(|(table, _, columns)| (table, columns))
+                .collect(),
+        })
+    }
+
+    /// 分析查询并推荐索引
+    ///
+    /// # 参数
+    ///
+    /// * `queries` - 要分析的查询，可以包含未绑定的参数
+    /// * `test` - 是否单独测试每个建议的索引并返回修改前后的计划树
+    ///
+    /// # 返回值
+    ///
+    /// 包含`suggestions`、每个查询当前的计划`plans`、无法分析的查询`skipped`，
+    /// 以及是否有统计信息`statistics`的对象
+    pub fn suggest<S: AsRef<str>>(&self, queries: &[S], test: bool) -> rusqlite::Result<Value> {
+        let reads = Arc::new(Mutex::new(Vec::<(String, String)>::new()));
+        let copy = self.open_copy()?;
+        let recorder = reads.clone();
+        copy.authorizer(Some(move |ctx: AuthContext<'_>| {
+            if let AuthAction::Read {
+                table_name,
+                column_name,
+            } = ctx.action
+            {
+                if !table_name.starts_with("sqlite_") && !column_name.is_empty() {
+                    if let Ok(mut reads) = recorder.lock() {
+                        let read = (table_name.to_string(), column_name.to_string());
+                        if !reads.contains(&read) {
+                            reads.push(read);
+                        }
+                    }
+                }
+            }
+            Authorization::Allow
+        }));
+
+        // 当前的计划和查询读取的列
+        let mut plans = Vec::new();
+        let mut before = Vec::new();
+        let mut skipped = Vec::new();
+        for (i, query) in queries.iter().enumerate() {
+            match query_plan(&copy, query.as_ref()) {
+                Ok(steps) => {
+                    plans.push(json!({
+                        "query": i,
+                        "plan": explain::plan_tree(&steps),
+                        "warnings": explain::plan_warnings(&steps),
+                    }));
+                    before.push(Some(steps));
+                }
+                Err(e) => {
+                    skipped.push(json!({ "query": i, "error": e.to_string() }));
+                    before.push(None);
+                }
+            }
+        }
+        copy.authorizer(None::<fn(AuthContext<'_>) -> Authorization>);
+
+        // 在副本中创建所有候选索引
+        let reads = std::mem::take(&mut *reads.lock().unwrap_or_else(|e| e.into_inner()));
+        let mut candidates = Vec::new();
+        for candidate in self.candidates(&reads) {
+            let name = format!("{}{}", CANDIDATE_PREFIX, candidates.len());
+            // 视图等无法建立索引的对象被跳过
+            if copy.execute_batch(&candidate.create_sql(&name)).is_ok() {
+                candidates.push(candidate);
+            }
+        }
+
+        // 统计候选索引对每个查询的收益
+        let mut benefits: Vec<Vec<Benefit>> = vec![Vec::new(); candidates.len()];
+        for (i, (query, steps)) in queries.iter().zip(&before).enumerate() {
+            let Some(steps) = steps else { continue };
+            let Ok(after) = query_plan(&copy, query.as_ref()) else {
+                continue;
+            };
+            let full_scans = (count_full_scans(steps), count_full_scans(&after));
+            let temp_btrees = (count_temp_btrees(steps), count_temp_btrees(&after));
+            // 之前被扫描的表，计划中以别名表示
+            let scanned: HashSet<&str> = steps
+                .iter()
+                .filter(|step| step.detail.starts_with("SCAN "))
+                .filter_map(|step| step_table(&step.detail))
+                .collect();
+            let mut used = HashSet::new();
+            for step in &after {
+                let Some(index) = candidate_index(&step.detail) else {
+                    continue;
+                };
+                let replaces_scan = step.detail.starts_with("SEARCH ")
+                    && step_table(&step.detail).is_some_and(|table| scanned.contains(table));
+                if replaces_scan || temp_btrees.1 < temp_btrees.0 {
+                    used.insert(index);
+                }
+            }
+            for index in used {
+                if let Some(benefit) = benefits.get_mut(index) {
+                    benefit.push(Benefit {
+                        query: i,
+                        full_scans,
+                        temp_btrees,
+                    });
+                }
+            }
+        }
+
+        let mut suggestions: Vec<(Option<u64>, usize, Value)> = Vec::new();
+        for (candidate, benefit) in candidates.iter().zip(&benefits) {
+            if benefit.is_empty() {
+                continue;
+            }
+            let name = self.index_name(candidate);
+            let table_rows = self.table_rows(&candidate.table);
+            let mut suggestion = json!({
+                "table": candidate.table,
+                "columns": candidate.columns,
+                "sql": candidate.create_sql(&name),
+                "table_rows": table_rows,
+                "queries": benefit.iter().map(|b| json!({
+                    "query": b.query,
+                    "full_scans": [b.full_scans.0, b.full_scans.1],
+                    "temp_btrees": [b.temp_btrees.0, b.temp_btrees.1],
+                })).collect::<Vec<_>>(),
+            });
+            if test {
+                suggestion["plan_changes"] =
+                    self.test_candidate(candidate, queries, &before, benefit)?;
+            }
+            suggestions.push((table_rows, benefit.len(), suggestion));
+        }
+        // 行数多的表和受益查询多的索引排在前面，没有统计信息的排在最后
+        suggestions.sort_by(|a, b| b.0.cmp(&a.0).then(b.1.cmp(&a.1)));
+
+        Ok(json!({
+            "suggestions": suggestions.into_iter().map(|(_, _, s)| s).collect::<Vec<_>>(),
+            "plans": plans,
+            "skipped": skipped,
+            "statistics": !self.stats.is_empty(),
+        }))
+    }
+
+    /// 创建只包含结构和统计信息的内存副本
+    fn open_copy(&self) -> rusqlite::Result<Connection> {
+        let copy = Connection::open_in_memory()?;
+        for sql in &self.schema {
+            // 模块不可用的虚拟表等无法复制的对象被跳过
+            let _ = copy.execute_batch(sql);
+        }
+        if !self.stats.is_empty() {
+            // ANALYZE创建sqlite_stat1，写入统计信息后重新加载
+            copy.execute_batch("ANALYZE; DELETE FROM sqlite_stat1;")?;
+            let mut stmt =
+                copy.prepare("INSERT INTO sqlite_stat1 (tbl, idx, stat) VALUES (?, ?, ?)")?;
+            for (table, index, stat) in &self.stats {
+                stmt.execute((table, index, stat))?;
+            }
+            copy.execute_batch("ANALYZE sqlite_schema")?;
+        }
+        Ok(copy)
+    }
+
+    /// 根据查询读取的列生成候选索引，跳过与已有索引前缀相同的组合
+    fn candidates(&self, reads: &[(String, String)]) -> Vec<Candidate> {
+        let mut tables: Vec<(&str, Vec<&str>)> = Vec::new();
+        for (table, column) in reads {
+            let rowid = self
+                .rowid_columns
+                .iter()
+                .any(|(t, c)| t.eq_ignore_ascii_case(table) && c.eq_ignore_ascii_case(column));
+            if rowid {
+                continue;
+            }
+            match tables.iter_mut().find(|(name, _)| *name == table) {
+                Some((_, columns)) if columns.len() < MAX_CANDIDATE_COLUMNS => columns.push(column),
+                Some(_) => {}
+                None => tables.push((table, vec![column])),
+            }
+        }
+
+        let mut candidates = Vec::new();
+        for (table, columns) in tables {
+            let mut combinations: Vec<Vec<&str>> = columns.iter().map(|c| vec![*c]).collect();
+            for first in &columns {
+                for second in &columns {
+                    if first != second {
+                        combinations.push(vec![*first, *second]);
+                    }
+                }
+            }
+            for combination in combinations {
+                let indexed = self.indexes.iter().any(|(indexed_table, columns)| {
+                    indexed_table.eq_ignore_ascii_case(table)
+                        && columns.len() >= combination.len()
+                        && columns
+                            .iter()
+                            .zip(&combination)
+                            .all(|(a, b)| a.eq_ignore_ascii_case(b))
+                });
+                if !indexed {
+                    candidates.push(Candidate {
+                        table: table.to_string(),
+                        columns: combination.into_iter().map(str::to_string).collect(),
+                    });
+                }
+            }
+        }
+        candidates
+    }
+
+    /// 单独测试候选索引，返回受益查询修改前后的计划树
+    fn test_candidate<S: AsRef<str>>(
+        &self,
+        candidate: &Candidate,
+        queries: &[S],
+        before: &[Option<Vec<PlanStep>>],
+        benefit: &[Benefit],
+    ) -> rusqlite::Result<Value> {
+        let copy = self.open_copy()?;
+        copy.execute_batch(&candidate.create_sql(&self.index_name(candidate)))?;
+        let changes: Vec<Value> = benefit
+            .iter()
+            .filter_map(|b| {
+                let steps = before[b.query].as_ref()?;
+                let after = query_plan(&copy, queries[b.query].as_ref()).ok()?;
+                Some(json!({
+                    "query": b.query,
+                    "before": explain::plan_tree(steps),
+                    "after": explain::plan_tree(&after),
+                }))
+            })
+            .collect();
+        Ok(json!(changes))
+    }
+
+    /// 建议的索引名称，如`idx_users_email`，与已有的索引重名时加上序号
+    fn index_name(&self, candidate: &Candidate) -> String {
+        let base: String = std::iter::once("idx")
+            .chain(std::iter::once(candidate.table.as_str()))
+            .chain(candidate.columns.iter().map(String::as_str))
+            .collect::<Vec<_>>()
+            .join("_")
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        let mut name = base.clone();
+        let mut n = 2;
+        while self.index_names.contains(&name) {
+            name = format!("{}_{}", base, n);
+            n += 1;
+        }
+        name
+    }
+
+    /// `sqlite_stat1`中记录的表的行数
+    fn table_rows(&self, table: &str) -> Option<u64> {
+        self.stats
+            .iter()
+            .filter(|(name, _, _)| name.eq_ignore_ascii_case(table))
+            .find_map(|(_, _, stat)| stat.split_whitespace().next()?.parse().ok())
+    }
+}
+
+impl Candidate {
+    fn create_sql(&self, name: &str) -> String {
+        let columns: Vec<String> = self.columns.iter().map(|c| quote_identifier(c)).collect();
+        format!(
+            "CREATE INDEX {} ON {} ({})",
+            quote_identifier(name),
+            quote_identifier(&self.table),
+            columns.join(", ")
+        )
+    }
+}
+
+/// 在连接上生成查询计划，查询中的参数保持未绑定
+fn query_plan(conn: &Connection, query: &str) -> rusqlite::Result<Vec<PlanStep>> {
+    let mut stmt = conn.prepare(&format!("EXPLAIN QUERY PLAN {}", query))?;
+    explain::read_plan(stmt.raw_query())
+}
+
+/// 步骤使用的候选索引的序号
+fn candidate_index(detail: &str) -> Option<usize> {
+    let (_, rest) = detail.split_once(CANDIDATE_PREFIX)?;
+    rest.split(|c: char| !c.is_ascii_digit())
+        .next()?
+        .parse()
+        .ok()
+}
+
+/// `SCAN`或`SEARCH`步骤访问的表或别名
+fn step_table(detail: &str) -> Option<&str> {
+    detail.split_whitespace().nth(1)
+}
+
+fn count_full_scans(steps: &[PlanStep]) -> usize {
+    steps.iter().filter(|step| step.is_full_scan()).count()
+}
+
+fn count_temp_btrees(steps: &[PlanStep]) -> usize {
+    steps.iter().filter(|step| step.uses_temp_btree()).count()
+}
@@ -0,0 +1,258 @@
+/*!
+ * # 写入审批
+ *
+ * 启用审批后，`execute`、`executemany`和`executescript`先预演写入（见[`crate::preview`]），
+ * 根据预演的结果判断写入是否具有破坏性：
+ *
+ * - 修改数据库结构，如`DROP TABLE`、`CREATE INDEX`、`ALTER TABLE`（临时对象除外）
+ * - 删除了表中的所有行，如不带`WHERE`的`DELETE`
+ * - 插入、更新和删除的总行数超过阈值
+ *
+ * 不具有破坏性的写入直接执行。否则写入不会执行，工具返回审批令牌、原因和预览：
+ *
+ * ```json
+ * {
+ *     "approval_required": true,
+ *     "approval_token": "approval-3f9c0e…",
+ *     "expires_in_ms": 300000,
+ *     "reasons": ["Deletes all 120 rows of main.users"],
+ *     "rowcount": 120,
+ *     "changes": [],
+ *     "schema_changes": []
+ * }
+ * ```
+ *
+ * 确认后调用`confirm`工具并传入`approval_token`，服务器按原来的参数执行写入，不再审批。
+ * 令牌只属于创建它的会话，只能使用一次，超过有效期后失效。
+ * 确认时数据可能已经变化，执行的是原来的语句而不是预演的结果。
+ *
+ * 用备份替换数据库全部内容的`restore`工具（见[`crate::backup`]）不预演，启用审批时总是需要确认。
+ */
+
+use std::{
+    collections::{hash_map::RandomState, HashMap},
+    hash::{BuildHasher, Hasher},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use rusqlite::Connection;
+use serde_json::Value;
+
+use crate::{digest::sha256_hex, preview::Preview, schema::quote_identifier};
+
+/// 超过该行数的写入默认需要审批
+pub const DEFAULT_ROW_THRESHOLD: u64 = 100;
+
+/// 审批令牌默认的有效期
+pub const DEFAULT_TOKEN_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// 用于生成令牌的计数器
+static TOKENS: AtomicU64 = AtomicU64::new(1);
+
+/// 判断写入是否需要审批的规则
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApprovalRules {
+    /// 插入、更新和删除的总行数超过该值时需要审批，`None`表示不按行数审批
+    pub row_threshold: Option<u64>,
+    /// 审批令牌的有效期
+    pub token_timeout: Duration,
+}
+
+impl Default for ApprovalRules {
+    fn default() -> Self {
+        Self {
+            row_threshold: Some(DEFAULT_ROW_THRESHOLD),
+            token_timeout: DEFAULT_TOKEN_TIMEOUT,
+        }
+    }
+}
+
+impl ApprovalRules {
+    /// 根据预演的结果判断写入是否需要审批，返回需要审批的原因，不需要审批时为空
+    ///
+    /// 必须在预演回滚之后调用：判断是否删除了所有行时读取的是表中当前的行数
+    ///
+    /// # 示例
+    ///
+    /// ```
+    /// use mcp_sqlite::{approval::ApprovalRules, policy, preview::dry_run};
+    /// use rusqlite::Connection;
+    ///
+    /// let conn = Connection::open_in_memory().unwrap();
+    /// policy::install(&conn, false, None, Default::default());
+    /// conn.execute_batch("CREATE TABLE t (x); INSERT INTO t VALUES (1), (2);").unwrap();
+    ///
+    /// let rules = ApprovalRules::default();
+    /// let (_, preview) = dry_run(&conn, 10, None, || conn.execute("DELETE FROM t WHERE x = 1", [])).unwrap();
+    /// assert!(rules.assess(&conn, &preview).is_empty());
+    ///
+    /// let (_, preview) = dry_run(&conn, 10, None, || conn.execute("DELETE FROM t", [])).unwrap();
+    /// assert_eq!(rules.assess(&conn, &preview), ["Deletes all 2 rows of main.t"]);
+    ///
+    /// let (_, preview) = dry_run(&conn, 10, None, || conn.execute("DROP TABLE t", [])).unwrap();
+    /// assert_eq!(rules.assess(&conn, &preview), ["Schema change: DROP TABLE t"]);
+    /// ```
+    pub fn assess(&self, conn: &Connection, preview: &Preview) -> Vec<String> {
+        let mut reasons: Vec<String> = preview
+            .schema_changes
+            .iter()
+            .map(|change| format!("Schema change: {}", change))
+            .collect();
+
+        for table in &preview.changes {
+            let (Some(schema), Some(name), Some(deleted)) = (
+                table["schema"].as_str(),
+                table["table"].as_str(),
+                table["deleted"].as_u64(),
+            ) else {
+                continue;
+            };
+            if deleted == 0 {
+                continue;
+            }
+            // 无法读取行数（如被访问策略拒绝）时不按这条规则判断
+            let sql = format!(
+                "SELECT count(*) FROM {}.{}",
+                quote_identifier(schema),
+                quote_identifier(name)
+            );
+            if let Ok(count) = conn.query_row(&sql, [], |row| row.get::<_, u64>(0)) {
+                if count > 0 && deleted >= count {
+                    reasons.push(format!("Deletes all {} rows of {}.{}", count, schema, name));
+                }
+            }
+        }
+
+        let changed = preview.changed_rows();
+        if let Some(threshold) = self.row_threshold {
+            if changed > threshold {
+                reasons.push(format!(
+                    "Modifies {} rows, more than the approval threshold of {}",
+                    changed, threshold
+                ));
+            }
+        }
+        reasons
+    }
+}
+
+/// 等待确认的写入
+#[derive(Debug, Clone)]
+pub struct PendingApproval {
+    /// 写入工具的名称
+    pub tool: String,
+    /// 写入工具的调用参数
+    pub arguments: Value,
+    /// 需要审批的原因
+    pub reasons: Vec<String>,
+    created: Instant,
+}
+
+/// 审批令牌管理器
+#[derive(Debug, Clone)]
+pub struct Approvals {
+    pending: Arc<Mutex<HashMap<String, PendingApproval>>>,
+    timeout: Duration,
+}
+
+impl Approvals {
+    /// 创建审批令牌管理器
+    ///
+    /// # 参数
+    ///
+    /// * `timeout` - 令牌的有效期
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            pending: Arc::new(Mutex::new(HashMap::new())),
+            timeout,
+        }
+    }
+
+    /// 令牌的有效期
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// 保存等待确认的写入并返回审批令牌
+    ///
+    /// # 参数
+    ///
+    /// * `tool` - 写入工具的名称
+    /// * `arguments` - 写入工具的调用参数
+    /// * `reasons` - 需要审批的原因
+    pub fn request(&self, tool: &str, arguments: Value, reasons: Vec<String>) -> String {
+        let token = new_token();
+        self.lock().insert(
+            token.clone(),
+            PendingApproval {
+                tool: tool.to_string(),
+                arguments,
+                reasons,
+                created: Instant::now(),
+            },
+        );
+        token
+    }
+
+    /// 获取等待确认的写入但不使用令牌，令牌不存在或已经过期时返回`None`
+    pub fn get(&self, token: &str) -> Option<PendingApproval> {
+        self.lock().get(token).cloned()
+    }
+
+    /// 使用令牌，取出等待确认的写入；令牌不存在、已经使用或已经过期时返回`None`
+    ///
+    /// # 示例
+    ///
+    /// ```
+    /// use std::time::Duration;
+    ///
+    /// use mcp_sqlite::approval::Approvals;
+    /// use serde_json::json;
+    ///
+    /// let approvals = Approvals::new(Duration::from_secs(60));
+    /// let token = approvals.request("execute", json!({ "statement": "DROP TABLE t" }), vec![]);
+    /// assert_eq!(approvals.take(&token).unwrap().tool, "execute");
+    /// assert!(approvals.take(&token).is_none());
+    /// ```
+    pub fn take(&self, token: &str) -> Option<PendingApproval> {
+        self.lock().remove(token)
+    }
+
+    /// 获取令牌表，同时清除已经过期的令牌
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, PendingApproval>> {
+        let mut pending = self
+            .pending
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let timeout = self.timeout;
+        pending.retain(|_, approval| approval.created.elapsed() < timeout);
+        pending
+    }
+}
+
+impl Default for Approvals {
+    fn default() -> Self {
+        Self::new(DEFAULT_TOKEN_TIMEOUT)
+    }
+}
+
+/// 生成不可预测的令牌
+///
+/// 每个`RandomState`带有随机的哈希密钥，与计数器和当前时间一起摘要，令牌无法由之前的令牌推出
+fn new_token() -> String {
+    let mut seed = Vec::new();
+    for _ in 0..4 {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u64(TOKENS.fetch_add(1, Ordering::Relaxed));
+        seed.extend_from_slice(&hasher.finish().to_le_bytes());
+    }
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_nanos());
+    seed.extend_from_slice(&nanos.to_le_bytes());
+    format!("approval-{}", &sha256_hex(&seed)[..32])
+}
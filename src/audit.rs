@@ -0,0 +1,531 @@
+/*!
+ * # 审计日志
+ *
+ * 记录每一次工具调用：工具名称、SQL文本、绑定参数、行数、耗时、错误以及会话ID。记录写入JSON Lines文件
+ * （每行一条记录），或者写入单独的SQLite数据库中的`audit_log`表。审计数据库不应与被访问的数据库相同，
+ * 否则客户端可以通过SQL修改审计记录。
+ *
+ * 记录由专用的线程按顺序写入，调用方不会被磁盘IO阻塞。写入失败只记录错误日志，不影响工具调用本身。
+ *
+ * ## 哈希链
+ *
+ * 每条记录带有从1开始连续的序号`seq`、上一条记录的哈希`prev_hash`（第一条记录为[`GENESIS_HASH`]）
+ * 和本条记录的哈希`hash`，`hash`是`hash`字段为空字符串时记录的JSON序列化的SHA-256摘要。
+ * 修改任何一条记录都会使它的`hash`不再匹配，删除或插入记录会使序号或`prev_hash`不再连续，
+ * 这些都可以由[`verify`]检测出来。
+ *
+ * 哈希链无法检测从末尾截断的记录：如果需要，应当把[`verify`]返回的最后一条记录的哈希保存在别处，
+ * 下次校验时进行比较。
+ *
+ * 服务器重启后从日志中最后一条记录继续哈希链。
+ *
+ * ## 记录格式
+ *
+ * ```json
+ * {"seq":1,"timestamp":"2025-03-01T08:00:00.000Z","session":1,"tool":"execute","sql":"DELETE FROM users WHERE id = ?","params":[42],"row_count":1,"duration_ms":0.412,"error":null,"prev_hash":"0000…","hash":"9f2c…"}
+ * ```
+ *
+ * - `timestamp`：记录时间（UTC，RFC 3339）
+ * - `session`：会话ID，见[`SQLiteRouter::session_id`](crate::SQLiteRouter::session_id)
+ * - `sql`：`query`、`execute`、`executemany`和`executescript`的SQL，`fetch`所读取游标的查询；其他工具为`null`
+ * - `params`：`params`或`params_list`参数，启用参数脱敏时所有的值被替换为`"[REDACTED]"`
+ * - `row_count`：写入工具受影响的行数，或者查询返回的行数（文本格式的结果为`null`）
+ * - `error`：调用失败时的错误信息
+ */
+
+use std::{
+    fmt,
+    fs::{File, OpenOptions},
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    sync::mpsc,
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use chrono::{SecondsFormat, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::error;
+
+use crate::digest::sha256_hex;
+
+/// 第一条记录的`prev_hash`
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// 审计数据库中保存记录的表
+pub const AUDIT_TABLE: &str = "audit_log";
+
+/// 启用参数脱敏时替换参数值的文本
+pub const REDACTED: &str = "[REDACTED]";
+
+/// 审计日志的写入位置
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuditDestination {
+    /// JSON Lines文件，每行一条记录
+    File(PathBuf),
+    /// SQLite数据库，记录写入`audit_log`表
+    Database(PathBuf),
+}
+
+impl fmt::Display for AuditDestination {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuditDestination::File(path) => write!(f, "{}", path.display()),
+            AuditDestination::Database(path) => write!(f, "{}#{}", path.display(), AUDIT_TABLE),
+        }
+    }
+}
+
+/// 审计日志错误
+#[derive(Debug)]
+pub enum AuditError {
+    /// 读写日志文件失败
+    Io(io::Error),
+    /// 读写审计数据库失败
+    Sql(rusqlite::Error),
+    /// 日志中的记录无法解析
+    Invalid(String),
+    /// 哈希链被破坏，日志被修改过
+    Broken(String),
+}
+
+impl fmt::Display for AuditError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuditError::Io(e) => write!(f, "Failed to access audit log: {}", e),
+            AuditError::Sql(e) => write!(f, "Failed to access audit database: {}", e),
+            AuditError::Invalid(message) => write!(f, "Invalid audit log: {}", message),
+            AuditError::Broken(message) => write!(f, "Audit log hash chain is broken: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for AuditError {}
+
+impl From<io::Error> for AuditError {
+    fn from(e: io::Error) -> Self {
+        AuditError::Io(e)
+    }
+}
+
+impl From<rusqlite::Error> for AuditError {
+    fn from(e: rusqlite::Error) -> Self {
+        AuditError::Sql(e)
+    }
+}
+
+/// 一次工具调用的审计信息，由[`AuditLog::record`]补充序号、时间和哈希后写入
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AuditEvent {
+    /// 会话ID
+    pub session: u64,
+    /// 工具名称
+    pub tool: String,
+    /// 执行的SQL
+    pub sql: Option<String>,
+    /// 绑定参数
+    pub params: Option<Value>,
+    /// 受影响或返回的行数
+    pub row_count: Option<u64>,
+    /// 调用耗时
+    pub duration: Duration,
+    /// 调用失败时的错误信息
+    pub error: Option<String>,
+}
+
+/// 审计日志中的一条记录
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AuditRecord {
+    /// 从1开始连续的序号
+    pub seq: u64,
+    /// 记录时间（UTC，RFC 3339）
+    pub timestamp: String,
+    /// 会话ID
+    pub session: u64,
+    /// 工具名称
+    pub tool: String,
+    /// 执行的SQL
+    pub sql: Option<String>,
+    /// 绑定参数
+    pub params: Option<Value>,
+    /// 受影响或返回的行数
+    pub row_count: Option<u64>,
+    /// 调用耗时（毫秒）
+    pub duration_ms: f64,
+    /// 调用失败时的错误信息
+    pub error: Option<String>,
+    /// 上一条记录的哈希
+    pub prev_hash: String,
+    /// 本条记录的哈希
+    pub hash: String,
+}
+
+impl AuditRecord {
+    /// 计算记录的哈希：`hash`字段为空字符串时记录的JSON序列化的SHA-256摘要
+    pub fn compute_hash(&self) -> String {
+        let unsigned = AuditRecord {
+            hash: String::new(),
+            ..self.clone()
+        };
+        sha256_hex(
+            serde_json::to_string(&unsigned)
+                .unwrap_or_default()
+                .as_bytes(),
+        )
+    }
+}
+
+/// 哈希链的末端
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainHead {
+    /// 记录数，也是最后一条记录的序号
+    pub records: u64,
+    /// 最后一条记录的哈希，没有记录时为[`GENESIS_HASH`]
+    pub last_hash: String,
+}
+
+impl Default for ChainHead {
+    fn default() -> Self {
+        Self {
+            records: 0,
+            last_hash: GENESIS_HASH.to_string(),
+        }
+    }
+}
+
+/// 审计日志
+///
+/// 最后一个引用被释放时等待所有已提交的记录写入完毕
+///
+/// # 示例
+///
+/// ```
+/// use mcp_sqlite::audit::{verify, AuditDestination, AuditEvent, AuditLog};
+///
+/// let path = std::env::temp_dir().join(format!("mcp-sqlite-audit-{}.jsonl", std::process::id()));
+/// let destination = AuditDestination::File(path.clone());
+///
+/// let log = AuditLog::open(destination.clone(), false).unwrap();
+/// log.record(AuditEvent {
+///     tool: "execute".into(),
+///     sql: Some("DELETE FROM users".into()),
+///     row_count: Some(3),
+///     ..Default::default()
+/// });
+/// drop(log);
+///
+/// assert_eq!(verify(&destination).unwrap().records, 1);
+///
+/// // 修改记录会破坏哈希链
+/// let text = std::fs::read_to_string(&path).unwrap();
+/// std::fs::write(&path, text.replace("\"row_count\":3", "\"row_count\":0")).unwrap();
+/// assert!(verify(&destination).is_err());
+/// # std::fs::remove_file(&path).unwrap();
+/// ```
+#[derive(Debug)]
+pub struct AuditLog {
+    destination: AuditDestination,
+    redact_params: bool,
+    events: Option<mpsc::Sender<AuditEvent>>,
+    writer: Option<JoinHandle<()>>,
+}
+
+impl AuditLog {
+    /// 打开审计日志并启动写入线程，从已有记录的末尾继续哈希链
+    ///
+    /// # 参数
+    ///
+    /// * `destination` - 写入位置，文件或数据库不存在时创建
+    /// * `redact_params` - 是否把记录中的参数值替换为`"[REDACTED]"`
+    ///
+    /// # 返回值
+    ///
+    /// 无法打开日志，或者最后一条记录无法解析时返回错误
+    pub fn open(destination: AuditDestination, redact_params: bool) -> Result<Self, AuditError> {
+        let (mut sink, mut head) = Sink::open(&destination)?;
+        let (events, rx) = mpsc::channel::<AuditEvent>();
+        let writer = thread::Builder::new()
+            .name("mcp-sqlite-audit".to_string())
+            .spawn(move || {
+                for event in rx {
+                    let record = chain(&head, event);
+                    match sink.write(&record) {
+                        Ok(()) => {
+                            head = ChainHead {
+                                records: record.seq,
+                                last_hash: record.hash,
+                            }
+                        }
+                        Err(e) => error!("Failed to write audit record {}: {}", record.seq, e),
+                    }
+                }
+            })?;
+        Ok(Self {
+            destination,
+            redact_params,
+            events: Some(events),
+            writer: Some(writer),
+        })
+    }
+
+    /// 写入位置
+    pub fn destination(&self) -> &AuditDestination {
+        &self.destination
+    }
+
+    /// 提交一条记录，不等待写入完成
+    pub fn record(&self, mut event: AuditEvent) {
+        if self.redact_params {
+            event.params = event.params.map(redact);
+        }
+        if let Some(events) = &self.events {
+            let _ = events.send(event);
+        }
+    }
+}
+
+impl Drop for AuditLog {
+    fn drop(&mut self) {
+        self.events.take();
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+    }
+}
+
+/// 校验审计日志的哈希链
+///
+/// # 返回值
+///
+/// 成功时返回记录数和最后一条记录的哈希；记录无法解析或哈希链被破坏时返回错误，错误信息指出第一条有问题的记录
+pub fn verify(destination: &AuditDestination) -> Result<ChainHead, AuditError> {
+    let mut head = ChainHead::default();
+    match destination {
+        AuditDestination::File(path) => {
+            let reader = BufReader::new(File::open(path)?);
+            for (i, line) in reader.lines().enumerate() {
+                let line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let record = parse_line(&line)
+                    .map_err(|e| AuditError::Invalid(format!("line {}: {}", i + 1, e)))?;
+                head = check_link(&head, &record)
+                    .map_err(|e| AuditError::Broken(format!("line {}: {}", i + 1, e)))?;
+            }
+        }
+        AuditDestination::Database(path) => {
+            let conn =
+                Connection::open_with_flags(path, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+            let mut stmt = conn.prepare(&format!(
+                "SELECT seq, timestamp, session, tool, sql, params, row_count, duration_ms, error, prev_hash, hash FROM {} ORDER BY seq",
+                AUDIT_TABLE
+            ))?;
+            let mut rows = stmt.query([])?;
+            while let Some(row) = rows.next()? {
+                let record = read_row(row)?;
+                head = check_link(&head, &record)
+                    .map_err(|e| AuditError::Broken(format!("record {}: {}", record.seq, e)))?;
+            }
+        }
+    }
+    Ok(head)
+}
+
+/// 为事件分配序号、时间和哈希，接在`head`之后
+fn chain(head: &ChainHead, event: AuditEvent) -> AuditRecord {
+    let mut record = AuditRecord {
+        seq: head.records + 1,
+        timestamp: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+        session: event.session,
+        tool: event.tool,
+        sql: event.sql,
+        params: event.params,
+        row_count: event.row_count,
+        // 保留到微秒
+        duration_ms: (event.duration.as_secs_f64() * 1_000_000.0).round() / 1000.0,
+        error: event.error,
+        prev_hash: head.last_hash.clone(),
+        hash: String::new(),
+    };
+    record.hash = record.compute_hash();
+    record
+}
+
+/// 检查记录能否接在`head`之后，返回新的末端
+fn check_link(head: &ChainHead, record: &AuditRecord) -> Result<ChainHead, String> {
+    if record.seq != head.records + 1 {
+        return Err(format!(
+            "expected sequence number {}, found {}",
+            head.records + 1,
+            record.seq
+        ));
+    }
+    if record.prev_hash != head.last_hash {
+        return Err("prev_hash does not match the previous record".to_string());
+    }
+    if record.hash != record.compute_hash() {
+        return Err("hash does not match the record contents".to_string());
+    }
+    Ok(ChainHead {
+        records: record.seq,
+        last_hash: record.hash.clone(),
+    })
+}
+
+/// 把参数中的所有值替换为`"[REDACTED]"`，保留数组和对象的结构
+fn redact(value: Value) -> Value {
+    match value {
+        Value::Array(items) => Value::Array(items.into_iter().map(redact).collect()),
+        Value::Object(map) => Value::Object(map.into_iter().map(|(k, v)| (k, redact(v))).collect()),
+        _ => Value::String(REDACTED.to_string()),
+    }
+}
+
+fn parse_line(line: &str) -> Result<AuditRecord, String> {
+    serde_json::from_str(line).map_err(|e| e.to_string())
+}
+
+fn read_row(row: &rusqlite::Row) -> Result<AuditRecord, AuditError> {
+    let seq: i64 = row.get(0)?;
+    let params = row
+        .get::<_, Option<String>>(5)?
+        .map(|text| serde_json::from_str(&text))
+        .transpose()
+        .map_err(|e| AuditError::Invalid(format!("record {}: params: {}", seq, e)))?;
+    Ok(AuditRecord {
+        seq: seq as u64,
+        timestamp: row.get(1)?,
+        session: row.get::<_, i64>(2)? as u64,
+        tool: row.get(3)?,
+        sql: row.get(4)?,
+        params,
+        row_count: row.get::<_, Option<i64>>(6)?.map(|n| n as u64),
+        duration_ms: row.get(7)?,
+        error: row.get(8)?,
+        prev_hash: row.get(9)?,
+        hash: row.get(10)?,
+    })
+}
+
+/// 审计记录的写入目标
+enum Sink {
+    File(File),
+    Database(Connection),
+}
+
+impl Sink {
+    /// 打开写入目标，返回已有记录的末端
+    fn open(destination: &AuditDestination) -> Result<(Self, ChainHead), AuditError> {
+        match destination {
+            AuditDestination::File(path) => {
+                let head = last_file_record(path)?;
+                let file = OpenOptions::new().create(true).append(true).open(path)?;
+                Ok((Sink::File(file), head))
+            }
+            AuditDestination::Database(path) => {
+                let conn = Connection::open(path)?;
+                conn.execute_batch(&format!(
+                    "CREATE TABLE IF NOT EXISTS {} (
+                        seq INTEGER PRIMARY KEY,
+                        timestamp TEXT NOT NULL,
+                        session INTEGER NOT NULL,
+                        tool TEXT NOT NULL,
+                        sql TEXT,
+                        params TEXT,
+                        row_count INTEGER,
+                        duration_ms REAL NOT NULL,
+                        error TEXT,
+                        prev_hash TEXT NOT NULL,
+                        hash TEXT NOT NULL
+                    )",
+                    AUDIT_TABLE
+                ))?;
+                let head = conn
+                    .query_row(
+                        &format!(
+                            "SELECT seq, hash FROM {} ORDER BY seq DESC LIMIT 1",
+                            AUDIT_TABLE
+                        ),
+                        [],
+                        |row| {
+                            Ok(ChainHead {
+                                records: row.get::<_, i64>(0)? as u64,
+                                last_hash: row.get(1)?,
+                            })
+                        },
+                    )
+                    .optional()?
+                    .unwrap_or_default();
+                Ok((Sink::Database(conn), head))
+            }
+        }
+    }
+
+    fn write(&mut self, record: &AuditRecord) -> Result<(), AuditError> {
+        match self {
+            Sink::File(file) => {
+                let mut line = serde_json::to_string(record)
+                    .map_err(|e| AuditError::Invalid(e.to_string()))?;
+                line.push('\n');
+                // 整行一次写入，追加模式下不会与其他写入交错
+                file.write_all(line.as_bytes())?;
+                file.flush()?;
+            }
+            Sink::Database(conn) => {
+                conn.execute(
+                    &format!(
+                        "INSERT INTO {} (seq, timestamp, session, tool, sql, params, row_count, duration_ms, error, prev_hash, hash)
+                         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+                        AUDIT_TABLE
+                    ),
+                    params![
+                        record.seq as i64,
+                        record.timestamp,
+                        record.session as i64,
+                        record.tool,
+                        record.sql,
+                        record.params.as_ref().map(Value::to_string),
+                        record.row_count.map(|n| n as i64),
+                        record.duration_ms,
+                        record.error,
+                        record.prev_hash,
+                        record.hash,
+                    ],
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 读取日志文件中最后一条记录，文件不存在或为空时返回初始的末端
+fn last_file_record(path: &Path) -> Result<ChainHead, AuditError> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(ChainHead::default()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut last = None;
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            last = Some(line);
+        }
+    }
+    match last {
+        None => Ok(ChainHead::default()),
+        Some(line) => {
+            let record = parse_line(&line).map_err(|e| {
+                AuditError::Invalid(format!("cannot resume from the last record: {}", e))
+            })?;
+            Ok(ChainHead {
+                records: record.seq,
+                last_hash: record.hash,
+            })
+        }
+    }
+}
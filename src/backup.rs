@@ -0,0 +1,401 @@
+/*!
+ * # 在线备份
+ *
+ * `backup`工具把正在使用的数据库复制到备份目录中的文件，`restore`工具用备份文件替换数据库的内容，
+ * `list_backups`工具列出备份目录中的备份。备份目录由运营者配置（见[`crate::server::RouterOptions::backup_dir`]），
+ * 工具只接受目录中的文件名，不能读写目录以外的文件。
+ *
+ * 支持两种备份方式：
+ *
+ * - `backup`：使用SQLite的在线备份API逐批复制数据库页，复制期间其他连接可以继续读取，
+ *   每一批的进度写入日志，结果中返回复制的页数和批数
+ * - `vacuum`：执行`VACUUM INTO`，生成不含空闲页的紧凑副本，但在一条语句中完成，没有进度
+ *
+ * 备份复制的是整个数据库文件，不经过访问策略和数据脱敏。恢复同样直接覆盖数据库页，不经过授权回调，
+ * 可以撤销策略禁止的删除或结构变更；因此启用访问策略时，只有策略对所有表都允许`insert`、`delete`、
+ * `create`和`drop`（见[`crate::policy::Policy::allows_restore`]），`restore`工具才可用。
+ */
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use rusqlite::{
+    backup::{Backup, StepResult},
+    Connection, DatabaseName, OpenFlags,
+};
+use tracing::info;
+
+use crate::{policy, schema::quote_identifier};
+
+/// 在线备份每一批默认复制的页数
+pub const DEFAULT_PAGES_PER_STEP: i32 = 100;
+
+/// 数据库被锁定时重试一批复制的间隔
+const BUSY_PAUSE: Duration = Duration::from_millis(50);
+
+/// 数据库被锁定时最多重试的次数，超过后放弃复制
+const BUSY_RETRIES: u32 = 100;
+
+/// SQLite数据库文件的文件头
+const SQLITE_HEADER: &[u8; 16] = b"SQLite format 3\0";
+
+/// 备份操作错误
+#[derive(Debug)]
+pub enum BackupError {
+    /// 备份文件名无效
+    InvalidName(String),
+    /// 备份文件已经存在
+    Exists(String),
+    /// 备份文件不存在
+    NotFound(String),
+    /// 数据库一直被其他连接锁定
+    Busy,
+    /// 文件系统错误
+    Io(io::Error),
+    /// SQLite错误
+    Sql(rusqlite::Error),
+}
+
+impl std::fmt::Display for BackupError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidName(name) => write!(
+                f,
+                "Invalid backup name: {}; use a file name without directories",
+                name
+            ),
+            Self::Exists(name) => write!(f, "Backup already exists: {}", name),
+            Self::NotFound(name) => write!(f, "Backup not found: {}", name),
+            Self::Busy => write!(
+                f,
+                "Database is locked by another connection; try again later"
+            ),
+            Self::Io(e) => write!(f, "Backup directory error: {}", e),
+            Self::Sql(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for BackupError {}
+
+impl From<io::Error> for BackupError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<rusqlite::Error> for BackupError {
+    fn from(e: rusqlite::Error) -> Self {
+        Self::Sql(e)
+    }
+}
+
+/// 备份方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BackupMethod {
+    /// SQLite在线备份API
+    #[default]
+    Online,
+    /// `VACUUM INTO`
+    Vacuum,
+}
+
+impl BackupMethod {
+    /// 解析`method`参数，`backup`或`vacuum`，不区分大小写
+    pub fn parse(method: &str) -> Option<Self> {
+        match method.to_ascii_lowercase().as_str() {
+            "backup" => Some(Self::Online),
+            "vacuum" => Some(Self::Vacuum),
+            _ => None,
+        }
+    }
+
+    /// 方式的名称
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Online => "backup",
+            Self::Vacuum => "vacuum",
+        }
+    }
+}
+
+/// 复制的进度
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Progress {
+    /// 数据库的总页数
+    pub pages: i32,
+    /// 复制的批数
+    pub steps: u32,
+}
+
+/// 备份目录中的一个备份
+#[derive(Debug, Clone)]
+pub struct BackupFile {
+    /// 文件名
+    pub name: String,
+    /// 文件大小（字节）
+    pub size: u64,
+    /// 最后修改时间
+    pub modified: SystemTime,
+}
+
+impl BackupFile {
+    /// RFC 3339格式的最后修改时间
+    pub fn modified_rfc3339(&self) -> String {
+        DateTime::<Utc>::from(self.modified).to_rfc3339_opts(SecondsFormat::Secs, true)
+    }
+}
+
+/// 存放备份的目录
+#[derive(Debug, Clone)]
+pub struct BackupDirectory {
+    root: PathBuf,
+}
+
+impl BackupDirectory {
+    /// 使用指定的目录
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// 备份目录
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// 备份文件在目录中的路径
+    ///
+    /// 名称必须是不含目录的文件名，不能以`.`开头
+    ///
+    /// # 示例
+    ///
+    /// ```
+    /// use mcp_sqlite::backup::BackupDirectory;
+    ///
+    /// let backups = BackupDirectory::new("backups");
+    /// assert!(backups.path("before-migration.db").is_ok());
+    /// assert!(backups.path("../app.db").is_err());
+    /// assert!(backups.path("nested/app.db").is_err());
+    /// ```
+    pub fn path(&self, name: &str) -> Result<PathBuf, BackupError> {
+        let is_file_name = !name.starts_with('.')
+            && !name.contains(['/', '\\'])
+            && Path::new(name).file_name().is_some_and(|file| file == name);
+        if is_file_name {
+            Ok(self.root.join(name))
+        } else {
+            Err(BackupError::InvalidName(name.to_string()))
+        }
+    }
+
+    /// 为数据库生成一个目录中还不存在的备份文件名，如`main-20250101T080000Z.db`
+    pub fn default_name(&self, database: &str) -> String {
+        let stamp = Utc::now().format("%Y%m%dT%H%M%SZ");
+        let mut name = format!("{}-{}.db", database, stamp);
+        let mut n = 1;
+        while self.root.join(&name).exists() {
+            name = format!("{}-{}-{}.db", database, stamp, n);
+            n += 1;
+        }
+        name
+    }
+
+    /// 列出目录中的备份，最新的在前
+    ///
+    /// 只列出以SQLite文件头开始的文件，`-wal`等附属文件和其他文件被忽略
+    pub fn list(&self) -> Result<Vec<BackupFile>, BackupError> {
+        let mut backups = Vec::new();
+        for entry in fs::read_dir(&self.root)? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            let Ok(name) = entry.file_name().into_string() else {
+                continue;
+            };
+            if !metadata.is_file() || name.starts_with('.') || !is_database_file(&entry.path()) {
+                continue;
+            }
+            backups.push(BackupFile {
+                name,
+                size: metadata.len(),
+                modified: metadata.modified()?,
+            });
+        }
+        backups.sort_by(|a, b| b.modified.cmp(&a.modified).then(a.name.cmp(&b.name)));
+        Ok(backups)
+    }
+}
+
+/// 使用在线备份API把数据库复制到新文件
+///
+/// # 参数
+///
+/// * `conn` - 要备份的连接
+/// * `database` - 要备份的数据库，如`main`或附加数据库的名称
+/// * `target` - 备份文件，不能已经存在
+/// * `pages_per_step` - 每一批复制的页数，负数表示一次复制全部
+///
+/// # 示例
+///
+/// ```
+/// use mcp_sqlite::backup;
+/// use rusqlite::Connection;
+///
+/// let conn = Connection::open_in_memory().unwrap();
+/// conn.execute_batch("CREATE TABLE t(x); INSERT INTO t VALUES (1), (2);").unwrap();
+///
+/// let target = std::env::temp_dir().join(format!("mcp-sqlite-backup-{}.db", std::process::id()));
+/// let progress = backup::backup(&conn, "main", &target, 1).unwrap();
+/// assert_eq!(progress.steps as i32, progress.pages);
+///
+/// let copy = Connection::open(&target).unwrap();
+/// let n: i64 = copy.query_row("SELECT count(*) FROM t", [], |row| row.get(0)).unwrap();
+/// assert_eq!(n, 2);
+/// # std::fs::remove_file(&target).unwrap();
+/// ```
+pub fn backup(
+    conn: &Connection,
+    database: &str,
+    target: &Path,
+    pages_per_step: i32,
+) -> Result<Progress, BackupError> {
+    let mut dest = create_target(target)?;
+    let result =
+        Backup::new_with_names(conn, database_name(database), &mut dest, DatabaseName::Main)
+            .map_err(BackupError::from)
+            .and_then(|backup| run_backup(&backup, pages_per_step, "Backup", database))
+            .and_then(|progress| {
+                // 副本带有源数据库的WAL标志，改回回滚日志，使备份是不依赖-wal文件的单个文件
+                dest.pragma_update(None, "journal_mode", "DELETE")?;
+                Ok(progress)
+            });
+    drop(dest);
+    // 没有完成的备份不应留在目录中被当作可用的备份
+    if result.is_err() {
+        let _ = fs::remove_file(target);
+    }
+    result
+}
+
+/// 使用`VACUUM INTO`把数据库复制到新文件
+///
+/// 语句经过连接上的授权回调，但附加目标文件被放行，见[`policy::with_backup_target`]
+pub fn vacuum_into(conn: &Connection, database: &str, target: &Path) -> Result<(), BackupError> {
+    if target.exists() {
+        return Err(BackupError::Exists(file_label(target)));
+    }
+    let sql = format!("VACUUM {} INTO ?1", quote_identifier(database));
+    let target = target.to_string_lossy();
+    policy::with_backup_target(&target, || conn.execute(&sql, [&target]))?;
+    Ok(())
+}
+
+/// 用备份文件替换数据库的内容
+///
+/// 复制在一个写事务中完成，失败时数据库保持原来的内容
+pub fn restore(
+    conn: &mut Connection,
+    database: &str,
+    source: &Path,
+    pages_per_step: i32,
+) -> Result<Progress, BackupError> {
+    if !source.is_file() {
+        return Err(BackupError::NotFound(file_label(source)));
+    }
+    let src = Connection::open_with_flags(
+        source,
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )?;
+    let backup = Backup::new_with_names(&src, DatabaseName::Main, conn, database_name(database))?;
+    run_backup(&backup, pages_per_step, "Restore", database)
+}
+
+/// 逐批复制直到完成，每一批的进度写入日志
+fn run_backup(
+    backup: &Backup<'_, '_>,
+    pages_per_step: i32,
+    label: &str,
+    database: &str,
+) -> Result<Progress, BackupError> {
+    let mut steps = 0;
+    let mut retries = 0;
+    loop {
+        let result = backup.step(pages_per_step)?;
+        let progress = backup.progress();
+        match result {
+            StepResult::Done => {
+                steps += 1;
+                info!(
+                    "{} of {}: {} pages done",
+                    label, database, progress.pagecount
+                );
+                return Ok(Progress {
+                    pages: progress.pagecount,
+                    steps,
+                });
+            }
+            StepResult::More => {
+                steps += 1;
+                retries = 0;
+                info!(
+                    "{} of {}: {}/{} pages",
+                    label,
+                    database,
+                    progress.pagecount - progress.remaining,
+                    progress.pagecount
+                );
+            }
+            // Busy和Locked：数据库被其他连接锁定，稍后重试这一批
+            _ => {
+                retries += 1;
+                if retries > BUSY_RETRIES {
+                    return Err(BackupError::Busy);
+                }
+                thread::sleep(BUSY_PAUSE);
+            }
+        }
+    }
+}
+
+/// 创建备份文件，文件已经存在时返回错误
+fn create_target(target: &Path) -> Result<Connection, BackupError> {
+    if target.exists() {
+        return Err(BackupError::Exists(file_label(target)));
+    }
+    Ok(Connection::open_with_flags(
+        target,
+        OpenFlags::SQLITE_OPEN_READ_WRITE
+            | OpenFlags::SQLITE_OPEN_CREATE
+            | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )?)
+}
+
+fn database_name(database: &str) -> DatabaseName<'_> {
+    if database.eq_ignore_ascii_case("main") {
+        DatabaseName::Main
+    } else {
+        DatabaseName::Attached(database)
+    }
+}
+
+/// 错误信息中使用的文件名，备份目录以外的路径不出现在工具结果中
+fn file_label(path: &Path) -> String {
+    path.file_name()
+        .unwrap_or(path.as_os_str())
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// 文件是否以SQLite文件头开始
+fn is_database_file(path: &Path) -> bool {
+    use std::io::Read;
+
+    let mut header = [0u8; 16];
+    fs::File::open(path)
+        .and_then(|mut file| file.read_exact(&mut header))
+        .is_ok_and(|()| &header == SQLITE_HEADER)
+}
@@ -0,0 +1,199 @@
+/*!
+ * # 查询游标
+ *
+ * 本模块保存服务器端游标，用于分页读取大型查询结果。每个游标有自己的线程和只读连接，
+ * 线程在调用之间保持执行中的语句，读取下一页时从上次停下的位置继续单步执行，
+ * 因此读完所有结果的总开销与行数成正比，并且所有页面来自同一个读取快照。
+ *
+ * 只有只读语句可以打开游标。游标在结果读完后自动关闭，空闲超过指定时间后失效，
+ * 线程随之结束并释放连接。
+ */
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use mcp_core_fishcode2025::handler::ToolError;
+use serde_json::{json, Value};
+
+use crate::{
+    format::{self, OutputFormat},
+    interrupt::CancelToken,
+};
+
+/// 游标默认的空闲超时时间
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// 未指定`limit`时每页返回的行数
+pub const DEFAULT_PAGE_SIZE: u64 = 100;
+
+/// 一个会话最多同时打开的游标数，每个游标占用一个线程和一个连接
+pub const MAX_OPEN_CURSORS: usize = 16;
+
+/// 查询结果中的一页
+#[derive(Debug, Clone, Default)]
+pub struct QueryPage {
+    /// 列名
+    pub columns: Vec<String>,
+    /// 行，每个值已经按输出方式转换为JSON
+    pub rows: Vec<Vec<Value>>,
+    /// 是否还有剩余结果
+    pub has_more: bool,
+}
+
+impl QueryPage {
+    /// 按输出格式生成工具返回值，文本格式的结果放在`text`字段中
+    pub fn to_json(&self, format: OutputFormat, rows_fetched: u64) -> Value {
+        let rows_key = if format.is_text() { "text" } else { "rows" };
+        let mut result = json!({
+            "columns": self.columns,
+            "has_more": self.has_more,
+            "rows_fetched": rows_fetched,
+        });
+        result[rows_key] = format::format_rows(format, &self.columns, &self.rows);
+        result
+    }
+}
+
+/// 发给游标线程的读取请求
+#[derive(Debug)]
+pub struct PageRequest {
+    /// 最多读取的行数
+    pub limit: u64,
+    /// 读取的超时时间，`None`表示不限制
+    pub timeout: Option<Duration>,
+    /// 调用方的取消令牌
+    pub token: Option<CancelToken>,
+    /// 接收读取结果
+    pub reply: mpsc::SyncSender<Result<QueryPage, ToolError>>,
+}
+
+/// 一个打开的游标
+#[derive(Debug, Clone)]
+pub struct Cursor {
+    /// 游标对应的查询
+    pub sql: String,
+    /// 已经读取的行数，包括打开游标时跳过的行
+    pub position: u64,
+    /// 打开游标时选择的输出格式，`fetch`未指定格式时沿用
+    pub format: OutputFormat,
+    /// 是否输出带存储类型的值，并按类型化表示解析绑定参数
+    pub typed: bool,
+    /// 查询限定的数据库，`fetch`沿用
+    pub database: Option<String>,
+    /// 向持有语句的游标线程发送读取请求
+    pub pages: mpsc::Sender<PageRequest>,
+    last_activity: Instant,
+}
+
+/// 游标管理器
+#[derive(Debug, Clone)]
+pub struct Cursors {
+    cursors: Arc<Mutex<HashMap<String, Cursor>>>,
+    next_id: Arc<AtomicU64>,
+    idle_timeout: Duration,
+}
+
+impl Cursors {
+    /// 创建游标管理器
+    ///
+    /// # 参数
+    ///
+    /// * `idle_timeout` - 游标空闲多久后失效
+    pub fn new(idle_timeout: Duration) -> Self {
+        Self {
+            cursors: Arc::new(Mutex::new(HashMap::new())),
+            next_id: Arc::new(AtomicU64::new(1)),
+            idle_timeout,
+        }
+    }
+
+    /// 游标的空闲超时时间，游标线程等待请求的时间不超过它
+    pub fn idle_timeout(&self) -> Duration {
+        self.idle_timeout
+    }
+
+    /// 是否还能打开新的游标
+    pub fn has_capacity(&self) -> bool {
+        self.lock().len() < MAX_OPEN_CURSORS
+    }
+
+    /// 登记一个游标并返回其ID
+    ///
+    /// # 参数
+    ///
+    /// * `sql` - 游标对应的查询
+    /// * `position` - 已经读取的行数
+    /// * `format` - 输出格式
+    /// * `typed` - 是否使用类型化的值
+    /// * `database` - 查询限定的数据库
+    /// * `pages` - 向游标线程发送读取请求的通道
+    pub fn open(
+        &self,
+        sql: &str,
+        position: u64,
+        format: OutputFormat,
+        typed: bool,
+        database: Option<String>,
+        pages: mpsc::Sender<PageRequest>,
+    ) -> String {
+        let id = format!("cursor-{}", self.next_id.fetch_add(1, Ordering::Relaxed));
+        let mut cursors = self.lock();
+        cursors.insert(
+            id.clone(),
+            Cursor {
+                sql: sql.to_string(),
+                position,
+                format,
+                typed,
+                database,
+                pages,
+                last_activity: Instant::now(),
+            },
+        );
+        id
+    }
+
+    /// 获取游标，游标不存在或已经过期时返回`None`
+    pub fn get(&self, cursor_id: &str) -> Option<Cursor> {
+        let mut cursors = self.lock();
+        let cursor = cursors.get_mut(cursor_id)?;
+        cursor.last_activity = Instant::now();
+        Some(cursor.clone())
+    }
+
+    /// 更新游标已经读取的行数
+    pub fn advance(&self, cursor_id: &str, position: u64) {
+        if let Some(cursor) = self.lock().get_mut(cursor_id) {
+            cursor.position = position;
+            cursor.last_activity = Instant::now();
+        }
+    }
+
+    /// 关闭游标，游标线程收不到更多请求后结束
+    pub fn close(&self, cursor_id: &str) {
+        self.lock().remove(cursor_id);
+    }
+
+    /// 获取游标表，同时清除已经过期的游标
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Cursor>> {
+        let mut cursors = self
+            .cursors
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let idle_timeout = self.idle_timeout;
+        cursors.retain(|_, cursor| cursor.last_activity.elapsed() < idle_timeout);
+        cursors
+    }
+}
+
+impl Default for Cursors {
+    fn default() -> Self {
+        Self::new(DEFAULT_IDLE_TIMEOUT)
+    }
+}
@@ -0,0 +1,50 @@
+/*!
+ * # SHA-256摘要
+ *
+ * 数据脱敏中的加盐哈希、审计日志的哈希链和审批令牌都使用SHA-256，摘要由[`sha2`] crate计算，
+ * 本模块只负责把摘要转换为小写十六进制文本。
+ */
+
+use std::fmt::Write as _;
+
+pub use sha2::{Digest, Sha256};
+
+/// 计算数据的SHA-256摘要，返回小写十六进制表示
+///
+/// # 示例
+///
+/// ```
+/// use mcp_sqlite::digest::sha256_hex;
+///
+/// assert_eq!(
+///     sha256_hex(b""),
+///     "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+/// );
+/// ```
+pub fn sha256_hex(data: &[u8]) -> String {
+    to_hex(&Sha256::digest(data))
+}
+
+/// 把摘要转换为小写十六进制表示
+///
+/// # 示例
+///
+/// ```
+/// use mcp_sqlite::digest::{to_hex, Digest, Sha256};
+///
+/// let mut hasher = Sha256::new();
+/// hasher.update(b"a");
+/// hasher.update(b"bc");
+/// assert_eq!(
+///     to_hex(&hasher.finalize()),
+///     "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+/// );
+/// ```
+pub fn to_hex(digest: &[u8]) -> String {
+    digest
+        .iter()
+        .fold(String::with_capacity(digest.len() * 2), |mut hex, byte| {
+            let _ = write!(hex, "{:02x}", byte);
+            hex
+        })
+}
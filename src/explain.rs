@@ -0,0 +1,159 @@
+/*!
+ * # 查询计划
+ *
+ * `explain`工具执行`EXPLAIN QUERY PLAN`，把SQLite返回的扁平步骤按`id`和`parent`组织成树：
+ *
+ * ```json
+ * [
+ *     {
+ *         "id": 2, "parent": 0, "detail": "SCAN users", "full_scan": true, "temp_btree": false,
+ *         "children": []
+ *     },
+ *     {
+ *         "id": 12, "parent": 0, "detail": "USE TEMP B-TREE FOR ORDER BY", "full_scan": false, "temp_btree": true,
+ *         "children": []
+ *     }
+ * ]
+ * ```
+ *
+ * - `full_scan`：不使用索引扫描整张表（`SCAN users`），使用索引的扫描和`SEARCH`不计入
+ * - `temp_btree`：为`ORDER BY`、`GROUP BY`、`DISTINCT`等建立临时B树排序
+ *
+ * 步骤的文本由SQLite生成，不同版本之间可能略有差异。
+ * 可选的`bytecode`是`EXPLAIN`输出的虚拟机指令，每条指令包含`addr`、`opcode`、`p1`到`p5`和`comment`。
+ */
+
+use rusqlite::Rows;
+use serde_json::{json, Value};
+
+use crate::{masking::MaskPlan, server::extract_row_values};
+
+/// `EXPLAIN QUERY PLAN`返回的一个步骤
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlanStep {
+    /// 步骤ID
+    pub id: i64,
+    /// 父步骤的ID，顶层步骤为`0`
+    pub parent: i64,
+    /// 步骤的描述，如`SCAN users`
+    pub detail: String,
+}
+
+impl PlanStep {
+    /// 步骤是否不使用索引扫描整张表
+    ///
+    /// # 示例
+    ///
+    /// ```
+    /// use mcp_sqlite::explain::PlanStep;
+    ///
+    /// let step = |detail: &str| PlanStep { id: 2, parent: 0, detail: detail.to_string() };
+    /// assert!(step("SCAN users").is_full_scan());
+    /// assert!(!step("SCAN users USING COVERING INDEX idx_users_email").is_full_scan());
+    /// assert!(!step("SEARCH users USING INTEGER PRIMARY KEY (rowid=?)").is_full_scan());
+    /// assert!(!step("SCAN CONSTANT ROW").is_full_scan());
+    /// ```
+    pub fn is_full_scan(&self) -> bool {
+        self.detail.starts_with("SCAN ")
+            && !self.detail.contains(" USING ")
+            && !self.detail.contains("CONSTANT ROW")
+            && !self.detail.contains("VIRTUAL TABLE")
+    }
+
+    /// 步骤是否建立临时B树排序
+    pub fn uses_temp_btree(&self) -> bool {
+        self.detail.contains("TEMP B-TREE")
+    }
+}
+
+/// 读取`EXPLAIN QUERY PLAN`的结果
+///
+/// 结果的列依次为`id`、`parent`、`notused`和`detail`
+pub fn read_plan(mut rows: Rows<'_>) -> rusqlite::Result<Vec<PlanStep>> {
+    let mut steps = Vec::new();
+    while let Some(row) = rows.next()? {
+        steps.push(PlanStep {
+            id: row.get(0)?,
+            parent: row.get(1)?,
+            detail: row.get(3)?,
+        });
+    }
+    Ok(steps)
+}
+
+/// 读取`EXPLAIN`输出的虚拟机指令，每条指令是以列名为键的对象
+pub fn read_bytecode(rows: Rows<'_>) -> rusqlite::Result<Vec<Value>> {
+    let column_names: Vec<String> = rows
+        .as_ref()
+        .map(|stmt| stmt.column_names().iter().map(|s| s.to_string()).collect())
+        .unwrap_or_default();
+    let plan = MaskPlan::none();
+    rows.mapped(|row| Ok(extract_row_values(row, &column_names, &plan)))
+        .collect()
+}
+
+/// 把扁平的步骤按父子关系组织成树，返回顶层步骤
+///
+/// 父步骤不存在的步骤作为顶层步骤，兄弟步骤保持SQLite返回的顺序
+///
+/// # 示例
+///
+/// ```
+/// use mcp_sqlite::explain::{plan_tree, PlanStep};
+///
+/// let step = |id, parent, detail: &str| PlanStep { id, parent, detail: detail.to_string() };
+/// let tree = plan_tree(&[
+///     step(2, 0, "CO-ROUTINE sub"),
+///     step(5, 2, "SCAN orders"),
+///     step(10, 0, "SCAN sub"),
+///     step(20, 0, "USE TEMP B-TREE FOR ORDER BY"),
+/// ]);
+/// assert_eq!(tree.len(), 3);
+/// assert_eq!(tree[0]["children"][0]["detail"], "SCAN orders");
+/// assert_eq!(tree[0]["children"][0]["full_scan"], true);
+/// assert_eq!(tree[2]["temp_btree"], true);
+/// ```
+pub fn plan_tree(steps: &[PlanStep]) -> Vec<Value> {
+    steps
+        .iter()
+        .filter(|step| {
+            !steps
+                .iter()
+                .any(|parent| parent.id == step.parent && parent.id < step.id)
+        })
+        .map(|step| plan_node(steps, step))
+        .collect()
+}
+
+fn plan_node(steps: &[PlanStep], step: &PlanStep) -> Value {
+    let children: Vec<Value> = steps
+        .iter()
+        // SQLite按生成顺序分配步骤ID，只把ID更大的步骤当作子步骤，异常的输入也不会形成环
+        .filter(|child| child.parent == step.id && child.id > step.id)
+        .map(|child| plan_node(steps, child))
+        .collect();
+    json!({
+        "id": step.id,
+        "parent": step.parent,
+        "detail": step.detail,
+        "full_scan": step.is_full_scan(),
+        "temp_btree": step.uses_temp_btree(),
+        "children": children,
+    })
+}
+
+/// 计划中值得注意的步骤：全表扫描和临时B树排序
+pub fn plan_warnings(steps: &[PlanStep]) -> Vec<String> {
+    steps
+        .iter()
+        .filter_map(|step| {
+            if step.is_full_scan() {
+                Some(format!("Full table scan: {}", step.detail))
+            } else if step.uses_temp_btree() {
+                Some(format!("Temporary B-tree: {}", step.detail))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
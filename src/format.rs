@@ -0,0 +1,179 @@
+/*!
+ * # 查询结果格式
+ *
+ * 本模块将查询结果转换为客户端选择的输出格式：
+ *
+ * - `objects`：每行是以列名为键的JSON对象（默认）。列名重复时只保留最后一列
+ * - `arrays`：每行是按列顺序排列的JSON数组，保留重复的列名
+ * - `csv`：带表头的CSV文本，按RFC 4180对字段加引号
+ * - `tsv`：带表头的TSV文本，制表符、换行符和反斜杠被转义为`\t`、`\n`和`\\`
+ * - `markdown`：Markdown表格
+ *
+ * 在文本格式中，`NULL`输出为空字段。
+ */
+
+use serde_json::{Map, Value};
+
+/// 查询结果的输出格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    /// 以列名为键的JSON对象
+    #[default]
+    Objects,
+    /// 按列顺序排列的JSON数组
+    Arrays,
+    /// CSV文本
+    Csv,
+    /// TSV文本
+    Tsv,
+    /// Markdown表格
+    Markdown,
+}
+
+impl OutputFormat {
+    /// 所有支持的格式名称
+    pub const NAMES: &'static [&'static str] = &["objects", "arrays", "csv", "tsv", "markdown"];
+
+    /// 从字符串解析输出格式，不区分大小写
+    pub fn parse(format: &str) -> Option<Self> {
+        match format.to_ascii_lowercase().as_str() {
+            "objects" => Some(Self::Objects),
+            "arrays" => Some(Self::Arrays),
+            "csv" => Some(Self::Csv),
+            "tsv" => Some(Self::Tsv),
+            "markdown" => Some(Self::Markdown),
+            _ => None,
+        }
+    }
+
+    /// 格式是否输出为文本而不是JSON行
+    pub fn is_text(self) -> bool {
+        matches!(self, Self::Csv | Self::Tsv | Self::Markdown)
+    }
+}
+
+/// 按指定格式转换查询结果
+///
+/// # 参数
+///
+/// * `format` - 输出格式
+/// * `columns` - 列名
+/// * `rows` - 按列顺序排列的行
+///
+/// # 返回值
+///
+/// `objects`和`arrays`返回JSON数组，文本格式返回JSON字符串
+///
+/// # 示例
+///
+/// ```
+/// use mcp_sqlite::format::{format_rows, OutputFormat};
+/// use serde_json::json;
+///
+/// let columns = vec!["id".to_string(), "id".to_string()];
+/// let rows = vec![vec![json!(1), json!("a,b")]];
+///
+/// assert_eq!(
+///     format_rows(OutputFormat::Arrays, &columns, &rows),
+///     json!([[1, "a,b"]])
+/// );
+/// assert_eq!(
+///     format_rows(OutputFormat::Csv, &columns, &rows),
+///     json!("id,id\n1,\"a,b\"\n")
+/// );
+/// assert_eq!(
+///     format_rows(OutputFormat::Markdown, &columns, &rows),
+///     json!("| id | id |\n| --- | --- |\n| 1 | a,b |\n")
+/// );
+/// ```
+pub fn format_rows(format: OutputFormat, columns: &[String], rows: &[Vec<Value>]) -> Value {
+    match format {
+        OutputFormat::Objects => Value::Array(
+            rows.iter()
+                .map(|row| {
+                    let mut object = Map::new();
+                    for (name, value) in columns.iter().zip(row) {
+                        object.insert(name.clone(), value.clone());
+                    }
+                    Value::Object(object)
+                })
+                .collect(),
+        ),
+        OutputFormat::Arrays => Value::Array(rows.iter().cloned().map(Value::Array).collect()),
+        OutputFormat::Csv => Value::String(delimited(columns, rows, ",", csv_field)),
+        OutputFormat::Tsv => Value::String(delimited(columns, rows, "\t", tsv_field)),
+        OutputFormat::Markdown => Value::String(markdown(columns, rows)),
+    }
+}
+
+/// 生成带表头的分隔文本，每行以换行符结尾
+fn delimited(
+    columns: &[String],
+    rows: &[Vec<Value>],
+    separator: &str,
+    field: fn(&str) -> String,
+) -> String {
+    let mut out = String::new();
+    let header: Vec<String> = columns.iter().map(|c| field(c)).collect();
+    out.push_str(&header.join(separator));
+    out.push('\n');
+    for row in rows {
+        let fields: Vec<String> = row.iter().map(|v| field(&cell_text(v))).collect();
+        out.push_str(&fields.join(separator));
+        out.push('\n');
+    }
+    out
+}
+
+/// 生成Markdown表格
+fn markdown(columns: &[String], rows: &[Vec<Value>]) -> String {
+    let mut out = String::new();
+    let header: Vec<String> = columns.iter().map(|c| markdown_cell(c)).collect();
+    out.push_str(&format!("| {} |\n", header.join(" | ")));
+    out.push_str(&format!("|{}\n", " --- |".repeat(columns.len())));
+    for row in rows {
+        let cells: Vec<String> = row.iter().map(|v| markdown_cell(&cell_text(v))).collect();
+        out.push_str(&format!("| {} |\n", cells.join(" | ")));
+    }
+    out
+}
+
+/// 单元格的文本形式，`NULL`为空字符串
+fn cell_text(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// 按RFC 4180为CSV字段加引号
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+/// 转义TSV字段中的制表符、换行符和反斜杠
+fn tsv_field(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\t' => out.push_str("\\t"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// 转义Markdown表格单元格中的竖线和换行符
+fn markdown_cell(s: &str) -> String {
+    s.replace('|', "\\|")
+        .replace("\r\n", "<br>")
+        .replace(['\n', '\r'], "<br>")
+}
@@ -0,0 +1,305 @@
+/*!
+ * # HTTP/1.1基础设施
+ *
+ * 网络传输只需要HTTP/1.1的一个很小的子集：按`Content-Length`读取请求体、写出普通响应，
+ * 以及写出不带长度、直到连接关闭为止的事件流。本模块直接在tokio的字节流上实现这些功能，
+ * 不支持分块编码的请求体。
+ */
+
+use std::fmt::Write as _;
+
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// 请求行和请求头的最大总长度
+const MAX_HEAD_SIZE: usize = 64 * 1024;
+
+/// 请求体的最大长度
+pub const MAX_BODY_SIZE: usize = 16 * 1024 * 1024;
+
+/// HTTP请求
+#[derive(Debug, Clone)]
+pub struct Request {
+    /// 请求方法，如`GET`、`POST`
+    pub method: String,
+    /// 请求路径，不含查询字符串
+    pub path: String,
+    /// 查询字符串，不含`?`
+    pub query: Option<String>,
+    headers: Vec<(String, String)>,
+    /// 请求体
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    /// 获取请求头的值，名称不区分大小写
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// 获取查询参数的值，不做百分号解码
+    pub fn query_param(&self, name: &str) -> Option<&str> {
+        self.query.as_deref()?.split('&').find_map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (key == name).then_some(value)
+        })
+    }
+
+    /// 处理完请求后是否应该保持连接
+    pub fn keep_alive(&self) -> bool {
+        !self
+            .header("connection")
+            .is_some_and(|value| value.eq_ignore_ascii_case("close"))
+    }
+}
+
+/// 请求无法解析时的错误，包含应当返回给客户端的状态码
+#[derive(Debug)]
+pub enum RequestError {
+    /// 读取连接失败
+    Io(std::io::Error),
+    /// 请求格式错误
+    Invalid(u16, &'static str),
+}
+
+impl From<std::io::Error> for RequestError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+/// 读取一个HTTP请求，连接在请求开始前关闭时返回`None`
+pub async fn read_request<R>(reader: &mut R) -> Result<Option<Request>, RequestError>
+where
+    R: AsyncBufRead + Unpin,
+{
+    let mut head_size = 0;
+    let request_line = loop {
+        let Some(line) = read_head_line(reader, &mut head_size).await? else {
+            return Ok(None);
+        };
+        // 按照RFC 9112，请求行之前的空行应当被忽略
+        if !line.trim().is_empty() {
+            break line;
+        }
+    };
+
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(method), Some(target), Some(version)) if version.starts_with("HTTP/1.") => {
+            (method.to_string(), target.to_string())
+        }
+        _ => return Err(RequestError::Invalid(400, "Malformed request line")),
+    };
+    let (path, query) = match target.split_once('?') {
+        Some((path, query)) => (path.to_string(), Some(query.to_string())),
+        None => (target, None),
+    };
+
+    let mut headers = Vec::new();
+    loop {
+        let line = read_head_line(reader, &mut head_size)
+            .await?
+            .ok_or(RequestError::Invalid(400, "Unexpected end of request"))?;
+        let line = line.trim_end_matches(['\r', '\n']);
+        if line.is_empty() {
+            break;
+        }
+        let (name, value) = line
+            .split_once(':')
+            .ok_or(RequestError::Invalid(400, "Malformed header"))?;
+        headers.push((name.trim().to_string(), value.trim().to_string()));
+    }
+
+    let mut request = Request {
+        method,
+        path,
+        query,
+        headers,
+        body: Vec::new(),
+    };
+    if request.header("transfer-encoding").is_some() {
+        return Err(RequestError::Invalid(
+            411,
+            "Chunked request bodies are not supported; send Content-Length",
+        ));
+    }
+    let length = match request.header("content-length") {
+        Some(value) => value
+            .parse::<usize>()
+            .map_err(|_| RequestError::Invalid(400, "Invalid Content-Length"))?,
+        None => 0,
+    };
+    if length > MAX_BODY_SIZE {
+        return Err(RequestError::Invalid(413, "Request body too large"));
+    }
+    request.body.resize(length, 0);
+    reader.read_exact(&mut request.body).await?;
+    Ok(Some(request))
+}
+
+/// 读取请求行或一个请求头，连接关闭时返回`None`
+///
+/// 每次读取最多只比剩余的请求头长度多一个字节，没有换行的超长行不会无限占用内存
+async fn read_head_line<R>(
+    reader: &mut R,
+    head_size: &mut usize,
+) -> Result<Option<String>, RequestError>
+where
+    R: AsyncBufRead + Unpin,
+{
+    let remaining = MAX_HEAD_SIZE.saturating_sub(*head_size) as u64;
+    let mut line = String::new();
+    let read = (&mut *reader)
+        .take(remaining + 1)
+        .read_line(&mut line)
+        .await?;
+    *head_size += read;
+    if *head_size > MAX_HEAD_SIZE {
+        return Err(RequestError::Invalid(431, "Request header too large"));
+    }
+    if read == 0 {
+        return Ok(None);
+    }
+    if !line.ends_with('\n') {
+        return Err(RequestError::Invalid(400, "Unexpected end of request"));
+    }
+    Ok(Some(line))
+}
+
+/// HTTP响应
+#[derive(Debug, Clone)]
+pub struct Response {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Response {
+    /// 创建没有响应体的响应
+    pub fn new(status: u16) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: Vec::new(),
+        }
+    }
+
+    /// 创建纯文本响应
+    pub fn text(status: u16, body: impl Into<String>) -> Self {
+        Self::new(status)
+            .header("Content-Type", "text/plain; charset=utf-8")
+            .body(body.into().into_bytes())
+    }
+
+    /// 创建JSON响应
+    pub fn json(status: u16, body: &impl serde::Serialize) -> Self {
+        Self::new(status)
+            .header("Content-Type", "application/json")
+            .body(serde_json::to_vec(body).unwrap_or_default())
+    }
+
+    /// 添加响应头
+    pub fn header(mut self, name: &str, value: impl Into<String>) -> Self {
+        self.headers.push((name.to_string(), value.into()));
+        self
+    }
+
+    /// 设置响应体
+    pub fn body(mut self, body: Vec<u8>) -> Self {
+        self.body = body;
+        self
+    }
+
+    /// 写出完整的响应
+    pub async fn write_to<W: AsyncWrite + Unpin>(&self, writer: &mut W) -> std::io::Result<()> {
+        let mut head = status_line(self.status);
+        for (name, value) in &self.headers {
+            let _ = write!(head, "{}: {}\r\n", name, value);
+        }
+        let _ = write!(head, "Content-Length: {}\r\n\r\n", self.body.len());
+        writer.write_all(head.as_bytes()).await?;
+        writer.write_all(&self.body).await?;
+        writer.flush().await
+    }
+
+    /// 写出事件流的响应头，之后的内容直接写入连接，直到连接关闭
+    pub async fn write_stream_head<W: AsyncWrite + Unpin>(
+        &self,
+        writer: &mut W,
+    ) -> std::io::Result<()> {
+        let mut head = status_line(self.status);
+        for (name, value) in &self.headers {
+            let _ = write!(head, "{}: {}\r\n", name, value);
+        }
+        head.push_str("Connection: close\r\n\r\n");
+        writer.write_all(head.as_bytes()).await?;
+        writer.flush().await
+    }
+}
+
+/// 把消息编码为一个SSE事件
+///
+/// # 示例
+///
+/// ```
+/// use mcp_sqlite::http::sse_event;
+///
+/// assert_eq!(sse_event(Some("message"), "a\nb"), "event: message\ndata: a\ndata: b\n\n");
+/// ```
+pub fn sse_event(event: Option<&str>, data: &str) -> String {
+    let mut out = String::new();
+    if let Some(event) = event {
+        let _ = writeln!(out, "event: {}", event);
+    }
+    for line in data.split('\n') {
+        let _ = writeln!(out, "data: {}", line);
+    }
+    out.push('\n');
+    out
+}
+
+fn status_line(status: u16) -> String {
+    let reason = match status {
+        200 => "OK",
+        202 => "Accepted",
+        204 => "No Content",
+        400 => "Bad Request",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        406 => "Not Acceptable",
+        411 => "Length Required",
+        413 => "Payload Too Large",
+        415 => "Unsupported Media Type",
+        431 => "Request Header Fields Too Large",
+        _ => "Internal Server Error",
+    };
+    format!("HTTP/1.1 {} {}\r\n", status, reason)
+}
+
+/// `Origin`是否指向本机
+///
+/// # 示例
+///
+/// ```
+/// use mcp_sqlite::http::is_local_origin;
+///
+/// assert!(is_local_origin("http://localhost:3000"));
+/// assert!(is_local_origin("http://[::1]:8080"));
+/// assert!(!is_local_origin("https://example.com"));
+/// assert!(!is_local_origin("null"));
+/// ```
+pub fn is_local_origin(origin: &str) -> bool {
+    let Some((_, rest)) = origin.split_once("://") else {
+        return false;
+    };
+    let authority = rest.split('/').next().unwrap_or_default();
+    let host = match authority.strip_prefix('[') {
+        Some(ipv6) => ipv6.split(']').next().unwrap_or_default(),
+        None => authority.split(':').next().unwrap_or_default(),
+    };
+    matches!(host, "localhost" | "127.0.0.1" | "::1")
+}
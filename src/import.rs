@@ -0,0 +1,324 @@
+/*!
+ * # CSV导入
+ *
+ * `import_csv`工具把CSV数据批量插入表中。本模块负责与数据库无关的部分：
+ *
+ * - 解析CSV（[RFC 4180](https://www.rfc-editor.org/rfc/rfc4180)）：字段可以用双引号包围，
+ *   引号中的`""`表示一个双引号，引号中可以包含分隔符和换行；分隔符可以配置
+ * - 推断列类型：列中所有非空值都是整数时为`INTEGER`，都是数字时为`REAL`，否则为`TEXT`。
+ *   带有前导零的数字（如邮政编码`00501`）和超出64位整数范围的整数按文本处理，避免丢失信息
+ * - 按推断的类型把字段转换为SQLite值，与空值标记相同的字段转换为`NULL`
+ *
+ * 空值标记只匹配没有引号的字段，因此默认设置下空字段是`NULL`，而`""`是空字符串。
+ * 引号不完整或者字段数与列数不同的行不会插入，工具结果中返回它们的行号和原因。
+ */
+
+use rusqlite::types::Value as SqlValue;
+use serde::Serialize;
+use std::{iter::Peekable, str::Chars};
+
+/// 工具结果中最多列出的被拒绝的行数
+pub const MAX_REJECTED_LINES: usize = 20;
+
+/// CSV中的一个字段
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CsvField {
+    /// 去掉引号后的文本
+    pub text: String,
+    /// 字段是否用双引号包围
+    pub quoted: bool,
+}
+
+/// CSV中的一条记录
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsvRecord {
+    /// 记录开始的行号，从1开始
+    pub line: usize,
+    /// 字段
+    pub fields: Vec<CsvField>,
+}
+
+/// 没有插入的行
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RejectedLine {
+    /// 行号，从1开始
+    pub line: usize,
+    /// 原因
+    pub reason: String,
+}
+
+/// 解析后的CSV
+#[derive(Debug, Clone, Default)]
+pub struct ParsedCsv {
+    /// 格式正确的记录，空行被跳过
+    pub records: Vec<CsvRecord>,
+    /// 格式错误的行
+    pub rejected: Vec<RejectedLine>,
+}
+
+/// 推断的列类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ColumnType {
+    /// 64位整数
+    Integer,
+    /// 浮点数
+    Real,
+    /// 文本
+    Text,
+}
+
+impl ColumnType {
+    /// 建表时使用的类型名
+    pub fn as_sql(self) -> &'static str {
+        match self {
+            Self::Integer => "INTEGER",
+            Self::Real => "REAL",
+            Self::Text => "TEXT",
+        }
+    }
+
+    /// 单个值的类型
+    fn of(text: &str) -> Self {
+        let text = text.trim();
+        let digits = text.strip_prefix(['+', '-']).unwrap_or(text);
+        // 0以外以0开头的数字是编号而不是数值
+        if digits.starts_with('0') && digits[1..].starts_with(|c: char| c.is_ascii_digit()) {
+            return Self::Text;
+        }
+        if !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()) {
+            return match text.parse::<i64>() {
+                Ok(_) => Self::Integer,
+                Err(_) => Self::Text,
+            };
+        }
+        // 排除inf、NaN等f64可以解析的单词
+        let numeric = digits.starts_with(|c: char| c.is_ascii_digit() || c == '.')
+            && text.parse::<f64>().is_ok_and(f64::is_finite);
+        if numeric {
+            Self::Real
+        } else {
+            Self::Text
+        }
+    }
+}
+
+/// 写入与已有行冲突时的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Conflict {
+    /// 中止导入并撤销所有已插入的行
+    #[default]
+    Fail,
+    /// 跳过冲突的行
+    Ignore,
+    /// 用新行替换冲突的行
+    Replace,
+}
+
+impl Conflict {
+    /// 解析`on_conflict`参数，`fail`、`ignore`或`replace`，不区分大小写
+    pub fn parse(conflict: &str) -> Option<Self> {
+        match conflict.to_ascii_lowercase().as_str() {
+            "fail" => Some(Self::Fail),
+            "ignore" => Some(Self::Ignore),
+            "replace" => Some(Self::Replace),
+            _ => None,
+        }
+    }
+
+    /// 对应的`INSERT`语句开头
+    pub fn insert_verb(self) -> &'static str {
+        match self {
+            Self::Fail => "INSERT INTO",
+            Self::Ignore => "INSERT OR IGNORE INTO",
+            Self::Replace => "INSERT OR REPLACE INTO",
+        }
+    }
+}
+
+/// 解析CSV文本
+///
+/// 开头的UTF-8 BOM被忽略；记录以`\n`、`\r\n`或`\r`结束
+///
+/// # 示例
+///
+/// ```
+/// use mcp_sqlite::import;
+///
+/// let csv = import::parse("id,name\n1,\"Smith, J\"\n2,\"say \"\"hi\"\"\"\n3,\"open\n", ',');
+/// assert_eq!(csv.records.len(), 3);
+/// assert_eq!(csv.records[1].fields[1].text, "Smith, J");
+/// assert_eq!(csv.records[2].fields[1].text, "say \"hi\"");
+/// assert_eq!(csv.rejected[0].line, 4);
+/// ```
+pub fn parse(text: &str, delimiter: char) -> ParsedCsv {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let mut parsed = ParsedCsv::default();
+    let mut chars = text.chars().peekable();
+    let mut line = 1;
+    while chars.peek().is_some() {
+        let start = line;
+        match parse_record(&mut chars, delimiter, &mut line) {
+            Ok(fields) if is_blank(&fields) => {}
+            Ok(fields) => parsed.records.push(CsvRecord {
+                line: start,
+                fields,
+            }),
+            Err(reason) => parsed.rejected.push(RejectedLine {
+                line: start,
+                reason,
+            }),
+        }
+    }
+    parsed
+}
+
+/// 解析一条记录，`line`随记录中的换行增加
+fn parse_record(
+    chars: &mut Peekable<Chars<'_>>,
+    delimiter: char,
+    line: &mut usize,
+) -> Result<Vec<CsvField>, String> {
+    let mut fields = Vec::new();
+    let mut field = CsvField::default();
+    let mut error = None;
+    loop {
+        if chars.next_if_eq(&'"').is_some() {
+            field.quoted = true;
+            loop {
+                match chars.next() {
+                    None => return Err("Unterminated quoted field".to_string()),
+                    Some('"') if chars.next_if_eq(&'"').is_some() => field.text.push('"'),
+                    Some('"') => break,
+                    Some(c) => {
+                        if c == '\n' || (c == '\r' && chars.peek() != Some(&'\n')) {
+                            *line += 1;
+                        }
+                        field.text.push(c);
+                    }
+                }
+            }
+        }
+
+        loop {
+            match chars.next() {
+                Some(c) if c == delimiter => {
+                    fields.push(std::mem::take(&mut field));
+                    break;
+                }
+                end @ (None | Some('\n') | Some('\r')) => {
+                    if end == Some('\r') {
+                        chars.next_if_eq(&'\n');
+                    }
+                    *line += 1;
+                    fields.push(field);
+                    return match error {
+                        Some(reason) => Err(reason),
+                        None => Ok(fields),
+                    };
+                }
+                Some(c) if field.quoted => {
+                    error.get_or_insert_with(|| {
+                        format!(
+                            "Unexpected character {:?} after closing quote in field {}",
+                            c,
+                            fields.len() + 1
+                        )
+                    });
+                }
+                Some(c) => field.text.push(c),
+            }
+        }
+    }
+}
+
+/// 空行解析为一个没有引号的空字段
+fn is_blank(fields: &[CsvField]) -> bool {
+    matches!(fields, [field] if !field.quoted && field.text.is_empty())
+}
+
+/// 由表头生成列名，没有表头时列名为`column1`、`column2`……
+///
+/// 表头中的空名称同样用序号命名，重复的名称（不区分大小写）返回错误
+///
+/// # 示例
+///
+/// ```
+/// use mcp_sqlite::import::{self, CsvField};
+///
+/// let header: Vec<CsvField> = ["id", " name ", ""]
+///     .iter()
+///     .map(|text| CsvField { text: text.to_string(), quoted: false })
+///     .collect();
+/// assert_eq!(import::column_names(Some(&header), 3).unwrap(), ["id", "name", "column3"]);
+/// assert_eq!(import::column_names(None, 2).unwrap(), ["column1", "column2"]);
+/// ```
+pub fn column_names(header: Option<&[CsvField]>, count: usize) -> Result<Vec<String>, String> {
+    let mut names: Vec<String> = Vec::with_capacity(count);
+    for i in 0..count {
+        let name = header
+            .and_then(|header| header.get(i))
+            .map(|field| field.text.trim())
+            .filter(|name| !name.is_empty())
+            .map_or_else(|| format!("column{}", i + 1), str::to_string);
+        if names
+            .iter()
+            .any(|existing| existing.eq_ignore_ascii_case(&name))
+        {
+            return Err(format!("Duplicate column name in header: {}", name));
+        }
+        names.push(name);
+    }
+    Ok(names)
+}
+
+/// 字段是否为空值
+pub fn is_null(field: &CsvField, null_tokens: &[String]) -> bool {
+    !field.quoted && null_tokens.contains(&field.text)
+}
+
+/// 推断每一列的类型，只考虑字段数为`count`的记录中的非空值
+///
+/// # 示例
+///
+/// ```
+/// use mcp_sqlite::import::{self, ColumnType};
+///
+/// let csv = import::parse("1,1.5,00501,x\n2,2,,\n", ',');
+/// let types = import::infer_types(&csv.records, 4, &[String::new()]);
+/// assert_eq!(
+///     types,
+///     [ColumnType::Integer, ColumnType::Real, ColumnType::Text, ColumnType::Text]
+/// );
+/// ```
+pub fn infer_types(records: &[CsvRecord], count: usize, null_tokens: &[String]) -> Vec<ColumnType> {
+    let mut types: Vec<Option<ColumnType>> = vec![None; count];
+    for record in records.iter().filter(|record| record.fields.len() == count) {
+        for (column, field) in types.iter_mut().zip(&record.fields) {
+            if *column == Some(ColumnType::Text) || is_null(field, null_tokens) {
+                continue;
+            }
+            let value = ColumnType::of(&field.text);
+            *column = Some(column.map_or(value, |current| current.max(value)));
+        }
+    }
+    types
+        .into_iter()
+        .map(|column| column.unwrap_or(ColumnType::Text))
+        .collect()
+}
+
+/// 按列类型把字段转换为SQLite值
+///
+/// 不能按列类型解析的字段保持为文本，由SQLite的类型亲和性处理
+pub fn to_value(field: &CsvField, column: ColumnType, null_tokens: &[String]) -> SqlValue {
+    if is_null(field, null_tokens) {
+        return SqlValue::Null;
+    }
+    let text = field.text.trim();
+    let value = match column {
+        ColumnType::Integer => text.parse().ok().map(SqlValue::Integer),
+        ColumnType::Real => text.parse().ok().map(SqlValue::Real),
+        ColumnType::Text => None,
+    };
+    value.unwrap_or_else(|| SqlValue::Text(field.text.clone()))
+}
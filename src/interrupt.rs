@@ -0,0 +1,196 @@
+/*!
+ * # 语句超时与取消
+ *
+ * 本模块通过SQLite的进度回调（progress handler）中断运行时间过长或已被客户端取消的语句。
+ * 进度回调每执行一定数量的虚拟机指令被调用一次，检查当前操作的截止时间和取消令牌，
+ * 需要中断时返回`true`，语句随即以`SQLITE_INTERRUPT`失败。
+ *
+ * 中断的原因记录在[`Watchdog`]中，由路由器据此生成说明超时或取消的错误信息。
+ * 同一个监视器可以安装在多个连接上，每个执行语句的线程分别记录自己的操作和中断原因。
+ *
+ * 取消令牌通过任务局部变量传递：服务循环在处理请求时用[`with_cancel_token`]设置令牌，
+ * 收到`notifications/cancelled`通知时取消对应请求的令牌。操作被提交到数据库工作线程时，
+ * 令牌随操作一起传递，并在执行期间通过线程局部变量提供给[`CancelToken::current`]。
+ */
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    future::Future,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, ThreadId},
+    time::{Duration, Instant},
+};
+
+use rusqlite::{Connection, ErrorCode};
+
+/// 两次调用进度回调之间执行的虚拟机指令数
+const PROGRESS_INTERVAL: i32 = 1000;
+
+tokio::task_local! {
+    static CANCEL_TOKEN: CancelToken;
+}
+
+thread_local! {
+    static THREAD_CANCEL_TOKEN: RefCell<Option<CancelToken>> = const { RefCell::new(None) };
+}
+
+/// 请求的取消令牌
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    /// 取消请求
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    /// 请求是否已被取消
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    /// 当前任务或当前数据库操作的取消令牌，没有设置令牌时返回`None`
+    pub fn current() -> Option<Self> {
+        CANCEL_TOKEN
+            .try_with(Clone::clone)
+            .ok()
+            .or_else(|| THREAD_CANCEL_TOKEN.with(|token| token.borrow().clone()))
+    }
+}
+
+/// 在设置了取消令牌的任务上下文中运行`future`
+pub async fn with_cancel_token<F: Future>(token: CancelToken, future: F) -> F::Output {
+    CANCEL_TOKEN.scope(token, future).await
+}
+
+/// 在当前线程上设置取消令牌并同步执行`f`，供数据库工作线程使用
+pub fn with_thread_cancel_token<T>(token: Option<CancelToken>, f: impl FnOnce() -> T) -> T {
+    let previous = THREAD_CANCEL_TOKEN.with(|current| current.replace(token));
+    let result = f();
+    THREAD_CANCEL_TOKEN.with(|current| *current.borrow_mut() = previous);
+    result
+}
+
+/// 语句被中断的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interruption {
+    /// 超过了超时时间
+    Timeout(Duration),
+    /// 客户端取消了请求
+    Cancelled,
+}
+
+impl std::fmt::Display for Interruption {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Timeout(timeout) => write!(
+                f,
+                "Statement timed out after {} ms and was interrupted",
+                timeout.as_millis()
+            ),
+            Self::Cancelled => write!(f, "Statement was cancelled by the client"),
+        }
+    }
+}
+
+/// 正在执行的操作
+#[derive(Debug)]
+struct Operation {
+    deadline: Option<(Instant, Duration)>,
+    token: Option<CancelToken>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    current: Option<Operation>,
+    interrupted: Option<Interruption>,
+}
+
+/// 监视正在执行的语句，超时或被取消时将其中断
+///
+/// 操作按线程记录：进度回调在执行语句的线程上运行，只检查该线程正在监视的操作
+#[derive(Debug, Clone, Default)]
+pub struct Watchdog(Arc<Mutex<HashMap<ThreadId, State>>>);
+
+impl Watchdog {
+    /// 在连接上安装进度回调，连接上的语句由这个监视器监视
+    pub fn install(&self, conn: &Connection) {
+        let states = self.0.clone();
+        conn.progress_handler(
+            PROGRESS_INTERVAL,
+            Some(move || {
+                let mut states = match states.lock() {
+                    Ok(states) => states,
+                    Err(_) => return false,
+                };
+                let state = match states.get_mut(&thread::current().id()) {
+                    Some(state) => state,
+                    None => return false,
+                };
+                let reason = match &state.current {
+                    Some(op) if op.token.as_ref().is_some_and(CancelToken::is_cancelled) => {
+                        Interruption::Cancelled
+                    }
+                    Some(Operation {
+                        deadline: Some((deadline, timeout)),
+                        ..
+                    }) if Instant::now() >= *deadline => Interruption::Timeout(*timeout),
+                    _ => return false,
+                };
+                // 中断后停止监视，以免同一次调用中的清理语句（如回滚）也被中断
+                state.current = None;
+                state.interrupted = Some(reason);
+                true
+            }),
+        );
+    }
+
+    /// 开始监视当前线程上的一个操作，返回的守卫被释放时停止监视
+    ///
+    /// # 参数
+    ///
+    /// * `timeout` - 操作的超时时间，`None`表示不限制
+    /// * `token` - 操作的取消令牌
+    pub fn watch(&self, timeout: Option<Duration>, token: Option<CancelToken>) -> WatchGuard<'_> {
+        let mut states = self.lock();
+        let state = states.entry(thread::current().id()).or_default();
+        state.current = Some(Operation {
+            deadline: timeout.map(|timeout| (Instant::now() + timeout, timeout)),
+            token,
+        });
+        state.interrupted = None;
+        WatchGuard(self)
+    }
+
+    /// 如果错误是由中断引起的，返回当前线程上中断的原因
+    pub fn interruption(&self, e: &rusqlite::Error) -> Option<Interruption> {
+        if e.sqlite_error_code() != Some(ErrorCode::OperationInterrupted) {
+            return None;
+        }
+        self.lock()
+            .get_mut(&thread::current().id())
+            .and_then(|state| state.interrupted.take())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<ThreadId, State>> {
+        self.0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// 操作监视守卫，释放时停止监视
+#[derive(Debug)]
+pub struct WatchGuard<'a>(&'a Watchdog);
+
+impl Drop for WatchGuard<'_> {
+    fn drop(&mut self) {
+        if let Some(state) = self.0.lock().get_mut(&thread::current().id()) {
+            state.current = None;
+        }
+    }
+}
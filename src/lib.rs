@@ -8,10 +8,27 @@
  *
  * 服务器提供以下MCP方法：
  *
- * - `query`: 执行SQL查询并返回结果
+ * - `query`: 执行SQL查询并返回结果，支持`limit`/`offset`分页和游标模式
+ * - `fetch`: 从游标读取下一页查询结果
+ * - `explain`: 返回查询计划树，标记全表扫描和临时B树排序
+ * - `suggest_indexes`: 根据查询计划和统计信息推荐索引
  * - `execute`: 执行SQL语句
  * - `executemany`: 使用不同参数多次执行SQL语句
  * - `executescript`: 执行SQL脚本
+ * - `confirm`: 确认需要审批的写入（启用`--require-approval`时）
+ * - `begin_transaction`、`commit`、`rollback`、`savepoint`、`release`: 跨调用的显式事务
+ * - `list_tables`: 列出表、视图和虚拟表
+ * - `describe_table`: 返回表的列定义
+ * - `list_indexes`: 列出表上的索引
+ * - `list_foreign_keys`: 列出表上的外键
+ * - `list_databases`: 列出`main`和附加的数据库
+ * - `open_database`、`close_database`、`list_open_databases`: 在运行时打开和关闭允许的目录中的数据库（启用`--allow-open`时）
+ * - `backup`、`restore`、`list_backups`: 使用在线备份API或`VACUUM INTO`备份数据库，从备份恢复（启用`--backup-dir`时）
+ * - `import_csv`: 把CSV文件或文本导入表中，推断列类型，在需要时建表，并在一个事务中批量插入
+ *
+ * 数据库结构还以MCP资源的形式提供：`sqlite://schema`、`sqlite://table/{name}/schema`、
+ * `sqlite://table/{name}/sample`和`sqlite://view/{name}/definition`；
+ * 附加数据库的资源带有`db/{database}/`前缀，如`sqlite://db/analytics/schema`。
  *
  * ## 使用方法
  *
@@ -49,19 +66,93 @@
  *
  * # 使用指定的SQLite数据库文件
  * ./mcp-sqlite --db path/to/database.db
+ *
+ * # 同时附加名为analytics的数据库
+ * ./mcp-sqlite --db app.db --db analytics=stats.db
  * ```
  *
  * ## 命令行选项
  *
- * - `--db`: SQLite数据库文件路径（默认为内存数据库`:memory:`）
+ * - `--db`: SQLite数据库文件路径（默认为内存数据库`:memory:`）；可以重复指定，`NAME=PATH`形式的数据库启动时以`NAME`附加
+ * - `--allow-open`: 允许`open_database`工具打开其中的数据库文件的目录，可以重复指定
+ * - `--backup-dir`: 备份目录，启用`backup`、`restore`和`list_backups`工具，不存在时自动创建
+ * - `--import-dir`: 允许`import_csv`工具读取其中的CSV文件的目录，可以重复指定
+ * - `--read-only`: 只读模式，禁止任何修改数据库的操作
+ * - `--policy`: 访问策略文件（JSON），按规则允许或拒绝SQL动作
+ * - `--masking`: 数据脱敏配置文件（JSON），对查询结果中的敏感列和匹配的文本脱敏
+ * - `--audit-log`: 审计日志文件（JSON Lines），记录每次工具调用，记录之间以哈希链相连
+ * - `--audit-db`: 审计数据库（SQLite），记录写入其中的`audit_log`表
+ * - `--audit-redact-params`: 审计记录中不保存绑定参数的值
+ * - `--verify-audit`: 校验审计日志的哈希链后退出
+ * - `--require-approval`: 破坏性的写入需要通过`confirm`工具确认后才执行
+ * - `--approval-row-threshold`: 修改超过该行数的写入需要确认（默认为`100`，`0`表示不按行数确认）
+ * - `--approval-timeout`: 审批令牌的有效秒数（默认为`300`）
+ * - `--transaction-timeout`: 显式事务的空闲超时秒数，超时后自动回滚（默认为`60`）
+ * - `--cursor-timeout`: 查询游标的空闲超时秒数（默认为`300`）
+ * - `--statement-timeout-ms`: 执行SQL的工具的默认超时毫秒数（默认为`0`，即不限制）
+ * - `--readers`: 只读连接池的大小（默认为`4`，`0`表示所有调用使用同一个连接）
+ * - `--no-wal`: 不为数据库文件自动启用WAL日志模式
+ * - `--transport`: 传输方式，`stdio`（默认）、`sse`或`streamable-http`
+ * - `--listen`: 网络传输监听的地址（默认为`127.0.0.1:8080`）
+ * - `--listen-unix`: 在指定路径的Unix域套接字上监听，每个连接是一个独立的会话
+ * - `--socket-mode`: Unix域套接字文件的八进制权限（默认为`600`）
  * - `--log-level`: 日志级别（默认为`info`）
  */
 
 // 注释掉这一行，因为它需要nightly版本的Rust
 // #![cfg_attr(docsrs, feature(doc_cfg))]
 
+/// 索引建议
+pub mod advisor;
+/// 写入审批
+pub mod approval;
+/// 审计日志
+pub mod audit;
+/// 在线备份
+pub mod backup;
+/// 查询游标
+pub mod cursor;
+/// SHA-256摘要
+pub mod digest;
+/// 查询计划
+pub mod explain;
+/// 查询结果格式
+pub mod format;
+/// HTTP/1.1基础设施
+pub mod http;
+/// CSV导入
+pub mod import;
+/// 语句超时与取消
+pub mod interrupt;
+/// 数据脱敏
+pub mod masking;
+/// SQL访问策略
+pub mod policy;
+/// 写入预览
+pub mod preview;
+/// 数据库注册表
+pub mod registry;
+/// 数据库结构资源
+pub mod resources;
+/// 数据库结构内省
+pub mod schema;
+/// 并发服务循环
+pub mod serve;
 /// SQLite MCP服务器实现
 pub mod server;
+/// HTTP/SSE传输
+pub mod sse;
+/// Streamable HTTP传输
+pub mod streamable;
+/// 显式事务
+pub mod transaction;
+/// Unix域套接字传输
+#[cfg(unix)]
+pub mod unix;
+/// SQLite值与JSON之间的转换
+pub mod value;
+/// 数据库工作线程
+pub mod worker;
 
 // 重新导出主要类型，方便用户使用
-pub use server::SQLiteRouter;
+pub use server::{RouterOptions, SQLiteRouter};
@@ -8,10 +8,24 @@
  *
  * 服务器提供以下MCP方法：
  *
- * - `query`: 执行SQL查询并返回结果
+ * - `query`: 执行SQL查询并返回结果，支持`limit`/`offset`分页和游标模式
+ * - `fetch`: 从游标读取下一页查询结果
  * - `execute`: 执行SQL语句
  * - `executemany`: 使用不同参数多次执行SQL语句
  * - `executescript`: 执行SQL脚本
+ * - `begin_transaction`、`commit`、`rollback`、`savepoint`、`release`: 跨调用的显式事务
+ * - `list_tables`: 列出表、视图和虚拟表
+ * - `describe_table`: 返回表的列定义
+ * - `list_indexes`: 列出表上的索引
+ * - `list_foreign_keys`: 列出表上的外键
+ * - `list_databases`: 列出`main`和附加的数据库
+ * - `open_database`、`close_database`、`list_open_databases`: 在运行时打开和关闭允许的目录中的数据库（启用`--allow-open`时）
+ * - `backup`、`restore`、`list_backups`: 备份和恢复数据库（启用`--backup-dir`时）
+ * - `import_csv`: 把CSV文件或文本导入表中，推断列类型并在需要时建表
+ *
+ * 数据库结构还以MCP资源的形式提供：`sqlite://schema`、`sqlite://table/{name}/schema`、
+ * `sqlite://table/{name}/sample`和`sqlite://view/{name}/definition`；
+ * 附加数据库的资源带有`db/{database}/`前缀，如`sqlite://db/analytics/schema`。
  *
  * ## 使用方法
  *
@@ -21,31 +35,195 @@
  *
  * # 使用指定的SQLite数据库文件
  * ./mcp-sqlite --db path/to/database.db
+ *
+ * # 通过HTTP/SSE提供服务，多个客户端共享同一个服务器
+ * ./mcp-sqlite --db path/to/database.db --transport sse --listen 127.0.0.1:8080
+ *
+ * # 通过Streamable HTTP提供服务，端点为/mcp，每个会话有独立的事务和游标
+ * ./mcp-sqlite --db path/to/database.db --transport streamable-http
+ *
+ * # 把每次工具调用记录到带有哈希链的审计日志中，并校验日志是否被修改
+ * ./mcp-sqlite --db path/to/database.db --audit-log audit.jsonl
+ * ./mcp-sqlite --audit-log audit.jsonl --verify-audit
+ *
+ * # 附加命名的数据库，SQL中用analytics.events访问，工具通过database参数限定数据库
+ * ./mcp-sqlite --db app.db --db analytics=stats.db
+ *
+ * # 结构变更、删除整张表的数据和修改超过50行的写入需要通过confirm工具确认
+ * ./mcp-sqlite --db path/to/database.db --require-approval --approval-row-threshold 50
+ *
+ * # 在Unix域套接字上为同一台机器上的多个客户端提供服务，收到SIGTERM后平滑关闭
+ * ./mcp-sqlite --db path/to/database.db --listen-unix /run/mcp-sqlite.sock --socket-mode 660
  * ```
  *
  * ## 命令行选项
  *
- * - `--db`: SQLite数据库文件路径（默认为内存数据库`:memory:`）
+ * - `--db`: SQLite数据库文件路径（默认为内存数据库`:memory:`）；可以重复指定，`NAME=PATH`形式的数据库启动时以`NAME`附加
+ * - `--allow-open`: 允许`open_database`工具打开其中的数据库文件的目录，可以重复指定
+ * - `--backup-dir`: 备份目录，启用`backup`、`restore`和`list_backups`工具
+ * - `--import-dir`: 允许`import_csv`工具读取其中的CSV文件的目录，可以重复指定
+ * - `--read-only`: 只读模式，禁止任何修改数据库的操作
+ * - `--policy`: 访问策略文件（JSON），按规则允许或拒绝SQL动作
+ * - `--masking`: 数据脱敏配置文件（JSON），对查询结果中的敏感列和匹配的文本脱敏
+ * - `--audit-log`: 审计日志文件（JSON Lines），记录每次工具调用，记录之间以哈希链相连
+ * - `--audit-db`: 审计数据库（SQLite），记录写入其中的`audit_log`表
+ * - `--audit-redact-params`: 审计记录中不保存绑定参数的值
+ * - `--verify-audit`: 校验审计日志的哈希链后退出
+ * - `--require-approval`: 破坏性的写入需要通过`confirm`工具确认后才执行
+ * - `--approval-row-threshold`: 修改超过该行数的写入需要确认（默认为`100`，`0`表示不按行数确认）
+ * - `--approval-timeout`: 审批令牌的有效秒数（默认为`300`）
+ * - `--transaction-timeout`: 显式事务的空闲超时秒数，超时后自动回滚（默认为`60`）
+ * - `--cursor-timeout`: 查询游标的空闲超时秒数（默认为`300`）
+ * - `--statement-timeout-ms`: 执行SQL的工具的默认超时毫秒数（默认为`0`，即不限制）
+ * - `--readers`: 只读连接池的大小（默认为`4`，`0`表示所有调用使用同一个连接）
+ * - `--no-wal`: 不为数据库文件自动启用WAL日志模式
+ * - `--transport`: 传输方式，`stdio`（默认）、`sse`或`streamable-http`
+ * - `--listen`: 网络传输监听的地址（默认为`127.0.0.1:8080`）
+ * - `--listen-unix`: 在指定路径的Unix域套接字上监听，每个连接是一个独立的会话
+ * - `--socket-mode`: Unix域套接字文件的八进制权限（默认为`600`）
  * - `--log-level`: 日志级别（默认为`info`）
  */
 
-mod server;
+use std::{
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
 
-use clap::Parser;
-use mcp_server_fishcode2025::{router::RouterService, ByteTransport, Server};
-use server::SQLiteRouter;
-use tokio::io::{stdin, stdout};
+use clap::{Parser, ValueEnum};
+use mcp_sqlite::{
+    approval::{self, ApprovalRules},
+    audit::{self, AuditDestination, AuditLog},
+    masking::Masking,
+    policy::Policy,
+    serve::serve,
+    server::AttachedDatabase,
+    sse::serve_sse,
+    streamable::serve_streamable_http,
+    RouterOptions, SQLiteRouter,
+};
+use tokio::{
+    io::{stdin, stdout},
+    net::TcpListener,
+};
 use tracing::{error, info};
 use tracing_appender::rolling::{RollingFileAppender, Rotation};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
 
+/// 服务器使用的传输方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Transport {
+    /// 通过标准输入输出交换按行分隔的JSON-RPC消息
+    Stdio,
+    /// HTTP with SSE：`GET /sse`打开事件流，向`POST /message`发送消息
+    Sse,
+    /// Streamable HTTP：所有消息发送到`/mcp`，通过`Mcp-Session-Id`头区分会话
+    StreamableHttp,
+}
+
+/// `--db`参数：不带名称的是`main`数据库，其余的以名称附加
+#[derive(Debug, Clone)]
+struct DatabaseArg {
+    name: Option<String>,
+    path: String,
+}
+
 /// SQLite MCP服务器命令行参数
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
-    /// SQLite数据库文件路径，使用":memory:"表示内存数据库
-    #[arg(short, long, default_value = ":memory:")]
-    db: String,
+    /// SQLite数据库文件路径，使用":memory:"表示内存数据库；可以重复指定，NAME=PATH形式的数据库以NAME附加
+    #[arg(short, long, value_name = "[NAME=]PATH", value_parser = parse_database)]
+    db: Vec<DatabaseArg>,
+
+    /// 允许open_database工具打开其中的数据库文件的目录，可以重复指定；未指定时不能在运行时打开数据库
+    #[arg(long, value_name = "DIR")]
+    allow_open: Vec<PathBuf>,
+
+    /// 备份目录：启用backup、restore和list_backups工具，备份文件只能位于该目录中；目录不存在时自动创建
+    #[arg(long, value_name = "DIR")]
+    backup_dir: Option<PathBuf>,
+
+    /// 允许import_csv工具读取其中的CSV文件的目录，可以重复指定；未指定时只能导入工具参数中的CSV文本
+    #[arg(long, value_name = "DIR")]
+    import_dir: Vec<PathBuf>,
+
+    /// 只读模式：以只读方式打开数据库，隐藏写入工具，并拒绝任何修改数据库的语句
+    #[arg(long)]
+    read_only: bool,
+
+    /// 访问策略文件（JSON），按规则允许或拒绝读取、写入、结构变更、PRAGMA和函数调用等动作
+    #[arg(long)]
+    policy: Option<PathBuf>,
+
+    /// 数据脱敏配置文件（JSON），按来源列删除、哈希、部分遮盖或替换查询结果中的值，并用正则表达式检测未标注的敏感数据
+    #[arg(long)]
+    masking: Option<PathBuf>,
+
+    /// 审计日志文件（JSON Lines），记录每次工具调用的SQL、参数、行数、耗时和错误，记录之间以哈希链相连
+    #[arg(long, conflicts_with = "audit_db")]
+    audit_log: Option<PathBuf>,
+
+    /// 审计数据库（SQLite），记录写入其中的audit_log表；不应与--db相同
+    #[arg(long)]
+    audit_db: Option<PathBuf>,
+
+    /// 审计记录中不保存绑定参数的值，只保留参数的结构
+    #[arg(long)]
+    audit_redact_params: bool,
+
+    /// 校验--audit-log或--audit-db指定的审计日志的哈希链，输出结果后退出
+    #[arg(long)]
+    verify_audit: bool,
+
+    /// 结构变更、删除表中所有行和修改大量行的写入不直接执行，返回审批令牌，由confirm工具确认后执行
+    #[arg(long)]
+    require_approval: bool,
+
+    /// 启用审批时，修改超过该行数的写入需要确认；0表示不按行数确认
+    #[arg(long, default_value_t = approval::DEFAULT_ROW_THRESHOLD)]
+    approval_row_threshold: u64,
+
+    /// 审批令牌的有效秒数
+    #[arg(long, default_value_t = approval::DEFAULT_TOKEN_TIMEOUT.as_secs())]
+    approval_timeout: u64,
+
+    /// 显式事务的空闲超时秒数，超时后事务被自动回滚
+    #[arg(long, default_value_t = 60)]
+    transaction_timeout: u64,
+
+    /// 查询游标的空闲超时秒数，超时后游标失效
+    #[arg(long, default_value_t = 300)]
+    cursor_timeout: u64,
+
+    /// 执行SQL的工具的默认超时毫秒数，超时后语句被中断；0表示不限制
+    #[arg(long, default_value_t = 0)]
+    statement_timeout_ms: u64,
+
+    /// 只读连接池的大小，只读查询在这些连接上并发执行；0表示所有调用使用同一个连接
+    #[arg(long, default_value_t = mcp_sqlite::server::DEFAULT_READERS)]
+    readers: usize,
+
+    /// 不为数据库文件自动启用WAL日志模式
+    #[arg(long)]
+    no_wal: bool,
+
+    /// 传输方式
+    #[arg(long, value_enum, default_value_t = Transport::Stdio)]
+    transport: Transport,
+
+    /// 网络传输监听的地址
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    listen: SocketAddr,
+
+    /// 在指定路径的Unix域套接字上监听，每个连接是一个独立的会话；不能与--transport同时使用
+    #[arg(long, conflicts_with = "transport")]
+    listen_unix: Option<PathBuf>,
+
+    /// Unix域套接字文件的权限，八进制，如660
+    #[arg(long, default_value = "600", value_parser = parse_mode)]
+    socket_mode: u32,
 
     /// 日志级别，可选值：trace, debug, info, warn, error
     #[arg(long, default_value = "info")]
@@ -60,6 +238,25 @@ async fn main() -> anyhow::Result<()> {
     // 解析命令行参数
     let args = Args::parse();
 
+    let audit_destination = match (&args.audit_log, &args.audit_db) {
+        (Some(path), _) => Some(AuditDestination::File(path.clone())),
+        (None, Some(path)) => Some(AuditDestination::Database(path.clone())),
+        (None, None) => None,
+    };
+    if args.verify_audit {
+        let Some(destination) = &audit_destination else {
+            return Err(anyhow::anyhow!(
+                "--verify-audit requires --audit-log or --audit-db"
+            ));
+        };
+        let head = audit::verify(destination)?;
+        println!(
+            "Audit log {} is intact: {} records, last hash {}",
+            destination, head.records, head.last_hash
+        );
+        return Ok(());
+    }
+
     // 设置日志
     let file_appender = RollingFileAppender::new(Rotation::DAILY, "logs", "mcp-sqlite.log");
     let (non_blocking, _guard) = tracing_appender::non_blocking(file_appender);
@@ -72,10 +269,99 @@ async fn main() -> anyhow::Result<()> {
         .init();
 
     info!("启动SQLite MCP服务器");
-    info!("数据库路径: {}", args.db);
+    let (db_path, databases) = split_databases(&args.db)?;
+    info!("数据库路径: {}", db_path);
+    for database in &databases {
+        info!("附加数据库: {} = {}", database.name, database.path);
+    }
+    for dir in &args.allow_open {
+        if !dir.is_dir() {
+            return Err(anyhow::anyhow!(
+                "--allow-open directory does not exist: {}",
+                dir.display()
+            ));
+        }
+        info!("允许打开的目录: {}", dir.display());
+    }
+    if let Some(dir) = &args.backup_dir {
+        std::fs::create_dir_all(dir).map_err(|e| {
+            anyhow::anyhow!("Failed to create backup directory {}: {}", dir.display(), e)
+        })?;
+        info!("备份目录: {}", dir.display());
+    }
+    for dir in &args.import_dir {
+        if !dir.is_dir() {
+            return Err(anyhow::anyhow!(
+                "--import-dir directory does not exist: {}",
+                dir.display()
+            ));
+        }
+        info!("允许导入的目录: {}", dir.display());
+    }
+    if args.read_only {
+        info!("以只读模式运行");
+    }
+
+    let policy = match &args.policy {
+        Some(path) => {
+            let policy = Policy::load(path)
+                .map_err(|e| anyhow::anyhow!("加载访问策略{}失败: {}", path.display(), e))?;
+            info!(
+                "访问策略: {}，共{}条规则",
+                path.display(),
+                policy.rules.len()
+            );
+            Some(Arc::new(policy))
+        }
+        None => None,
+    };
+    let masking = match &args.masking {
+        Some(path) => {
+            let masking = Masking::load(path)
+                .map_err(|e| anyhow::anyhow!("加载脱敏配置{}失败: {}", path.display(), e))?;
+            info!(
+                "脱敏配置: {}，共{}条列规则和{}个检测器",
+                path.display(),
+                masking.columns.len(),
+                masking.detectors.len()
+            );
+            Some(Arc::new(masking))
+        }
+        None => None,
+    };
+
+    let audit = match audit_destination {
+        Some(destination) => {
+            let log = AuditLog::open(destination, args.audit_redact_params)
+                .map_err(|e| anyhow::anyhow!("打开审计日志失败: {}", e))?;
+            info!("审计日志: {}", log.destination());
+            Some(Arc::new(log))
+        }
+        None => None,
+    };
 
     // 创建SQLite路由器
-    let router = match SQLiteRouter::new(&args.db) {
+    let options = RouterOptions {
+        read_only: args.read_only,
+        transaction_timeout: Duration::from_secs(args.transaction_timeout),
+        cursor_timeout: Duration::from_secs(args.cursor_timeout),
+        statement_timeout: (args.statement_timeout_ms > 0)
+            .then(|| Duration::from_millis(args.statement_timeout_ms)),
+        readers: args.readers,
+        wal: !args.no_wal,
+        policy,
+        masking,
+        audit,
+        approval: args.require_approval.then(|| ApprovalRules {
+            row_threshold: (args.approval_row_threshold > 0).then_some(args.approval_row_threshold),
+            token_timeout: Duration::from_secs(args.approval_timeout),
+        }),
+        databases,
+        open_roots: args.allow_open.clone(),
+        backup_dir: args.backup_dir.clone(),
+        import_roots: args.import_dir.clone(),
+    };
+    let router = match SQLiteRouter::with_options(&db_path, options) {
         Ok(router) => router,
         Err(e) => {
             error!("创建SQLite路由器失败: {}", e);
@@ -83,16 +369,129 @@ async fn main() -> anyhow::Result<()> {
         }
     };
 
-    // 创建路由服务
-    let router_service = RouterService(router);
+    if let Some(path) = &args.listen_unix {
+        return serve_unix_socket(router, path, args.socket_mode).await;
+    }
+
+    match args.transport {
+        Transport::Stdio => {
+            // 使用标准输入输出作为传输层运行服务器，请求并发处理并支持取消
+            info!("服务器已启动，使用stdio传输");
+            Ok(serve(router, stdin(), stdout()).await?)
+        }
+        Transport::Sse => {
+            let listener = TcpListener::bind(args.listen).await?;
+            info!(
+                "服务器已启动，使用HTTP/SSE传输，监听{}",
+                listener.local_addr()?
+            );
+            // 每个会话共享数据库，但拥有独立的事务和游标
+            Ok(serve_sse(move || router.session(), listener).await?)
+        }
+        Transport::StreamableHttp => {
+            let listener = TcpListener::bind(args.listen).await?;
+            info!(
+                "服务器已启动，使用Streamable HTTP传输，监听{}",
+                listener.local_addr()?
+            );
+            // 每个会话共享数据库，但拥有独立的事务和游标
+            Ok(serve_streamable_http(move || router.session(), listener).await?)
+        }
+    }
+}
+
+/// 在Unix域套接字上运行服务器，收到SIGTERM或SIGINT后等待正在执行的语句完成再退出
+#[cfg(unix)]
+async fn serve_unix_socket(router: SQLiteRouter, path: &Path, mode: u32) -> anyhow::Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let listener = mcp_sqlite::unix::bind(path, mode)
+        .map_err(|e| anyhow::anyhow!("Failed to bind {}: {}", path.display(), e))?;
+    let mut terminate = signal(SignalKind::terminate())?;
+    let shutdown = async move {
+        tokio::select! {
+            _ = terminate.recv() => info!("收到SIGTERM，开始关闭"),
+            _ = tokio::signal::ctrl_c() => info!("收到SIGINT，开始关闭"),
+        }
+    };
+    info!("服务器已启动，使用Unix域套接字传输，监听{}", path.display());
+    // 每个连接共享数据库，但拥有独立的事务和游标
+    Ok(mcp_sqlite::unix::serve_unix(move || router.session(), listener, shutdown).await?)
+}
+
+#[cfg(not(unix))]
+async fn serve_unix_socket(_router: SQLiteRouter, _path: &Path, _mode: u32) -> anyhow::Result<()> {
+    Err(anyhow::anyhow!(
+        "Unix domain sockets are not supported on this platform"
+    ))
+}
 
-    // 创建服务器
-    let server = Server::new(router_service);
+/// 解析`--db`参数
+///
+/// 等号前是标识符时作为数据库名称，否则整个参数是路径，如`C:=x.db`或`file:a.db?mode=ro`
+fn parse_database(value: &str) -> Result<DatabaseArg, String> {
+    if let Some((name, path)) = value.split_once('=') {
+        let is_identifier = name
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if is_identifier && !path.is_empty() {
+            return Ok(DatabaseArg {
+                name: Some(name.to_string()),
+                path: path.to_string(),
+            });
+        }
+    }
+    Ok(DatabaseArg {
+        name: None,
+        path: value.to_string(),
+    })
+}
 
-    // 使用标准输入输出作为传输层
-    let transport = ByteTransport::new(stdin(), stdout());
+/// 把`--db`参数分为`main`数据库的路径和附加的数据库
+///
+/// 不带名称或名称为`main`的参数指定`main`数据库，默认为内存数据库
+fn split_databases(args: &[DatabaseArg]) -> anyhow::Result<(String, Vec<AttachedDatabase>)> {
+    let mut main: Option<String> = None;
+    let mut databases: Vec<AttachedDatabase> = Vec::new();
+    for arg in args {
+        match arg.name.as_deref() {
+            Some(name) if name.eq_ignore_ascii_case("temp") => {
+                return Err(anyhow::anyhow!(
+                    "--db cannot use the reserved name: {}",
+                    name
+                ))
+            }
+            Some(name) if !name.eq_ignore_ascii_case("main") => {
+                if databases
+                    .iter()
+                    .any(|database| database.name.eq_ignore_ascii_case(name))
+                {
+                    return Err(anyhow::anyhow!("Duplicate --db name: {}", name));
+                }
+                databases.push(AttachedDatabase {
+                    name: name.to_string(),
+                    path: arg.path.clone(),
+                });
+            }
+            _ => {
+                if main.replace(arg.path.clone()).is_some() {
+                    return Err(anyhow::anyhow!(
+                        "Only one --db can specify the main database"
+                    ));
+                }
+            }
+        }
+    }
+    Ok((main.unwrap_or_else(|| ":memory:".to_string()), databases))
+}
 
-    // 运行服务器
-    info!("服务器已启动，使用stdio传输");
-    Ok(server.run(transport).await?)
+/// 解析八进制的文件权限
+fn parse_mode(value: &str) -> Result<u32, String> {
+    let digits = value.strip_prefix("0o").unwrap_or(value);
+    match u32::from_str_radix(digits, 8) {
+        Ok(mode) if mode <= 0o777 => Ok(mode),
+        _ => Err(format!("Invalid socket mode: {}", value)),
+    }
 }
@@ -0,0 +1,490 @@
+/*!
+ * # 数据脱敏
+ *
+ * 在查询结果离开服务器之前对敏感数据进行脱敏。脱敏配置从JSON文件加载，包含两部分：
+ *
+ * - 列规则（`columns`）：按来源的`表.列`匹配结果列，表名和列名支持`*`和`?`通配符，不区分大小写
+ * - 检测器（`detectors`）：没有列规则的列中，文本值里匹配正则表达式的部分被脱敏，用于覆盖未标注的列
+ *
+ * 每条规则或检测器指定一个`action`：
+ *
+ * | 动作 | 列规则 | 检测器 |
+ * | --- | --- | --- |
+ * | `drop` | 从结果中删除该列 | 整个值替换为`null` |
+ * | `hash` | 替换为加盐的SHA-256十六进制摘要 | 匹配部分替换为摘要 |
+ * | `partial` | 只保留开头`keep_start`个和结尾`keep_end`个字符，其余替换为`mask_char`（默认为`*`） | 对匹配部分做同样的处理 |
+ * | `replace` | 替换为`value` | 匹配部分替换为`value`的文本 |
+ *
+ * 结果列的来源通过SQLite的列元数据确定，别名和视图不影响匹配；由表达式计算的列（如`lower(email)`）
+ * 没有来源，只受检测器约束。相同的值在加盐后总是得到相同的摘要，仍然可以用于比较和分组。
+ *
+ * ```json
+ * {
+ *     "salt": "change-me",
+ *     "columns": [
+ *         { "table": "users", "column": "email", "action": "hash" },
+ *         { "table": "users", "column": "phone", "action": "partial", "keep_start": 3, "keep_end": 2 },
+ *         { "table": "*", "column": "*token*", "action": "drop" }
+ *     ],
+ *     "detectors": [
+ *         { "name": "email", "pattern": "[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\\.[A-Za-z]{2,}", "action": "replace", "value": "[EMAIL]" }
+ *     ]
+ * }
+ * ```
+ */
+
+use std::{
+    borrow::Cow,
+    ffi::{CStr, CString},
+    path::Path,
+    ptr,
+};
+
+use regex::Regex;
+use rusqlite::{
+    ffi,
+    types::{Value as SqlValue, ValueRef},
+    Connection,
+};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{
+    digest::{self, Digest, Sha256},
+    policy::glob_match,
+    value,
+};
+
+/// 脱敏动作
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "action", rename_all = "lowercase")]
+pub enum MaskAction {
+    /// 删除列，检测器中表示把值替换为`null`
+    Drop,
+    /// 替换为加盐的SHA-256摘要
+    Hash,
+    /// 只保留开头和结尾的部分字符
+    Partial {
+        /// 保留开头的字符数
+        #[serde(default)]
+        keep_start: usize,
+        /// 保留结尾的字符数
+        #[serde(default)]
+        keep_end: usize,
+        /// 替换其余字符的掩码字符
+        #[serde(default = "default_mask_char")]
+        mask_char: char,
+    },
+    /// 替换为固定的值
+    Replace {
+        /// 替换后的值
+        value: Value,
+    },
+}
+
+fn default_mask_char() -> char {
+    '*'
+}
+
+/// 按来源列匹配的脱敏规则
+#[derive(Debug, Clone, Deserialize)]
+pub struct ColumnRule {
+    /// 表名模式
+    pub table: String,
+    /// 列名模式
+    pub column: String,
+    /// 脱敏动作
+    #[serde(flatten)]
+    pub action: MaskAction,
+}
+
+/// 按正则表达式检测文本中敏感数据的检测器
+#[derive(Debug, Clone)]
+pub struct Detector {
+    /// 检测器名称
+    pub name: String,
+    /// 匹配敏感数据的正则表达式
+    pub pattern: Regex,
+    /// 对匹配部分执行的脱敏动作
+    pub action: MaskAction,
+}
+
+#[derive(Deserialize)]
+struct RawDetector {
+    name: String,
+    pattern: String,
+    #[serde(flatten)]
+    action: MaskAction,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawMasking {
+    #[serde(default)]
+    salt: String,
+    #[serde(default)]
+    columns: Vec<ColumnRule>,
+    #[serde(default)]
+    detectors: Vec<RawDetector>,
+}
+
+/// 加载脱敏配置失败
+#[derive(Debug)]
+pub enum MaskingError {
+    /// 读取配置文件失败
+    Io(std::io::Error),
+    /// 配置内容无效
+    Invalid(String),
+}
+
+impl std::fmt::Display for MaskingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "Failed to read masking file: {}", e),
+            Self::Invalid(message) => write!(f, "Invalid masking configuration: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for MaskingError {}
+
+/// 脱敏配置
+#[derive(Debug, Clone)]
+pub struct Masking {
+    /// 计算摘要时附加在值前面的盐
+    pub salt: String,
+    /// 列规则，按顺序匹配，第一条匹配的规则生效
+    pub columns: Vec<ColumnRule>,
+    /// 检测器，按顺序应用于没有列规则的列
+    pub detectors: Vec<Detector>,
+}
+
+impl Masking {
+    /// 从JSON文本解析脱敏配置
+    ///
+    /// # 示例
+    ///
+    /// ```
+    /// use mcp_sqlite::masking::Masking;
+    ///
+    /// let masking = Masking::from_json(r#"{
+    ///     "salt": "s",
+    ///     "columns": [{ "table": "users", "column": "phone", "action": "partial", "keep_end": 4 }],
+    ///     "detectors": [{ "name": "email", "pattern": "\\S+@\\S+", "action": "replace", "value": "[EMAIL]" }]
+    /// }"#).unwrap();
+    /// assert_eq!(masking.columns.len(), 1);
+    /// assert_eq!(masking.detectors[0].name, "email");
+    /// ```
+    pub fn from_json(json: &str) -> Result<Self, MaskingError> {
+        let raw: RawMasking =
+            serde_json::from_str(json).map_err(|e| MaskingError::Invalid(e.to_string()))?;
+        for rule in &raw.columns {
+            check_action(&rule.action).map_err(|e| {
+                MaskingError::Invalid(format!("{}.{}: {}", rule.table, rule.column, e))
+            })?;
+        }
+        let detectors = raw
+            .detectors
+            .into_iter()
+            .map(|detector| {
+                check_action(&detector.action).map_err(|e| {
+                    MaskingError::Invalid(format!("detector {}: {}", detector.name, e))
+                })?;
+                let pattern = Regex::new(&detector.pattern).map_err(|e| {
+                    MaskingError::Invalid(format!("detector {}: {}", detector.name, e))
+                })?;
+                Ok(Detector {
+                    name: detector.name,
+                    pattern,
+                    action: detector.action,
+                })
+            })
+            .collect::<Result<_, MaskingError>>()?;
+        Ok(Self {
+            salt: raw.salt,
+            columns: raw.columns,
+            detectors,
+        })
+    }
+
+    /// 从JSON文件加载脱敏配置
+    pub fn load(path: &Path) -> Result<Self, MaskingError> {
+        let json = std::fs::read_to_string(path).map_err(MaskingError::Io)?;
+        Self::from_json(&json)
+    }
+
+    /// 为一条查询语句生成脱敏计划
+    ///
+    /// 结果列的来源表和列通过SQLite的列元数据确定。
+    ///
+    /// # 参数
+    ///
+    /// * `conn` - 执行查询的连接
+    /// * `sql` - 查询语句
+    pub fn plan(&self, conn: &Connection, sql: &str) -> rusqlite::Result<MaskPlan<'_>> {
+        let columns = column_origins(conn, sql)?
+            .into_iter()
+            .map(|origin| {
+                let rule = origin.and_then(|(table, column)| {
+                    self.columns.iter().find(|rule| {
+                        glob_match(&rule.table, &table) && glob_match(&rule.column, &column)
+                    })
+                });
+                match rule {
+                    Some(rule) => ColumnMask::Rule(&rule.action),
+                    None => ColumnMask::Detect,
+                }
+            })
+            .collect();
+        Ok(MaskPlan {
+            masking: Some(self),
+            columns,
+        })
+    }
+
+    /// 计算加盐的摘要
+    fn hash(&self, data: &[u8]) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.salt.as_bytes());
+        hasher.update(data);
+        digest::to_hex(&hasher.finalize())
+    }
+}
+
+/// 检查动作的参数是否有效
+fn check_action(action: &MaskAction) -> Result<(), String> {
+    match action {
+        MaskAction::Replace { value }
+            if !matches!(
+                value,
+                Value::Null | Value::Bool(_) | Value::Number(_) | Value::String(_)
+            ) =>
+        {
+            Err("replacement value must be a scalar".to_string())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// 一列的脱敏方式
+#[derive(Debug, Clone, Copy)]
+enum ColumnMask<'a> {
+    /// 按列规则脱敏
+    Rule(&'a MaskAction),
+    /// 没有列规则，由检测器检查
+    Detect,
+}
+
+/// 一条语句结果的脱敏计划
+#[derive(Debug, Clone, Default)]
+pub struct MaskPlan<'a> {
+    masking: Option<&'a Masking>,
+    columns: Vec<ColumnMask<'a>>,
+}
+
+/// 对一个值脱敏的结果
+#[derive(Debug, Clone, PartialEq)]
+pub enum Masked<'v> {
+    /// 列被删除
+    Dropped,
+    /// 值没有改变
+    Unchanged(ValueRef<'v>),
+    /// 脱敏后的值
+    Masked(SqlValue),
+}
+
+impl<'a> MaskPlan<'a> {
+    /// 不做任何脱敏的计划
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    /// 可选的脱敏配置生成计划，未配置脱敏时返回[`MaskPlan::none`]
+    pub fn for_query(
+        masking: Option<&'a Masking>,
+        conn: &Connection,
+        sql: &str,
+    ) -> rusqlite::Result<Self> {
+        match masking {
+            Some(masking) => masking.plan(conn, sql),
+            None => Ok(Self::none()),
+        }
+    }
+
+    /// 去掉被删除的列名
+    pub fn columns(&self, names: Vec<String>) -> Vec<String> {
+        names
+            .into_iter()
+            .enumerate()
+            .filter(|(i, _)| !self.is_dropped(*i))
+            .map(|(_, name)| name)
+            .collect()
+    }
+
+    fn is_dropped(&self, index: usize) -> bool {
+        matches!(
+            self.columns.get(index),
+            Some(ColumnMask::Rule(MaskAction::Drop))
+        )
+    }
+
+    /// 对第`index`列的值脱敏
+    pub fn apply<'v>(&self, index: usize, value: ValueRef<'v>) -> Masked<'v> {
+        let Some(masking) = self.masking else {
+            return Masked::Unchanged(value);
+        };
+        match self.columns.get(index) {
+            Some(ColumnMask::Rule(action)) => mask_value(masking, action, value),
+            Some(ColumnMask::Detect) | None => detect(masking, value),
+        }
+    }
+}
+
+/// 按列规则对整个值脱敏，`null`保持不变
+fn mask_value<'v>(masking: &Masking, action: &MaskAction, value: ValueRef<'v>) -> Masked<'v> {
+    if value == ValueRef::Null && *action != MaskAction::Drop {
+        return Masked::Unchanged(value);
+    }
+    match action {
+        MaskAction::Drop => Masked::Dropped,
+        MaskAction::Hash => {
+            let bytes = match value {
+                ValueRef::Blob(bytes) | ValueRef::Text(bytes) => Cow::Borrowed(bytes),
+                other => Cow::Owned(value_text(other).into_bytes()),
+            };
+            Masked::Masked(SqlValue::Text(masking.hash(&bytes)))
+        }
+        MaskAction::Partial { .. } => match value {
+            // BLOB没有可以部分保留的文本表示
+            ValueRef::Blob(_) => Masked::Masked(SqlValue::Null),
+            other => Masked::Masked(SqlValue::Text(partial(action, &value_text(other)))),
+        },
+        MaskAction::Replace { value } => {
+            Masked::Masked(value::from_json(value, false).unwrap_or(SqlValue::Null))
+        }
+    }
+}
+
+/// 用检测器检查文本值
+fn detect<'v>(masking: &Masking, value: ValueRef<'v>) -> Masked<'v> {
+    let ValueRef::Text(bytes) = value else {
+        return Masked::Unchanged(value);
+    };
+    let Ok(text) = std::str::from_utf8(bytes) else {
+        return Masked::Unchanged(value);
+    };
+
+    let mut masked = Cow::Borrowed(text);
+    for detector in &masking.detectors {
+        if !detector.pattern.is_match(&masked) {
+            continue;
+        }
+        if detector.action == MaskAction::Drop {
+            return Masked::Masked(SqlValue::Null);
+        }
+        let replaced = detector
+            .pattern
+            .replace_all(&masked, |caps: &regex::Captures<'_>| {
+                let matched = &caps[0];
+                match &detector.action {
+                    MaskAction::Hash => masking.hash(matched.as_bytes()),
+                    MaskAction::Partial { .. } => partial(&detector.action, matched),
+                    MaskAction::Replace {
+                        value: Value::String(s),
+                    } => s.clone(),
+                    MaskAction::Replace { value } => value.to_string(),
+                    MaskAction::Drop => String::new(),
+                }
+            })
+            .into_owned();
+        masked = Cow::Owned(replaced);
+    }
+
+    match masked {
+        Cow::Borrowed(_) => Masked::Unchanged(value),
+        Cow::Owned(text) => Masked::Masked(SqlValue::Text(text)),
+    }
+}
+
+/// 保留开头和结尾的字符，其余替换为掩码字符
+fn partial(action: &MaskAction, text: &str) -> String {
+    let MaskAction::Partial {
+        keep_start,
+        keep_end,
+        mask_char,
+    } = *action
+    else {
+        return text.to_string();
+    };
+    let chars: Vec<char> = text.chars().collect();
+    // 保留的字符不能覆盖整个值，否则等于没有脱敏
+    let keep_start = keep_start.min(chars.len() / 2);
+    let keep_end = keep_end.min(chars.len() - keep_start).min(chars.len() / 2);
+    chars
+        .iter()
+        .enumerate()
+        .map(|(i, &c)| {
+            if i < keep_start || i >= chars.len() - keep_end {
+                c
+            } else {
+                mask_char
+            }
+        })
+        .collect()
+}
+
+/// 非BLOB值的文本表示
+fn value_text(value: ValueRef<'_>) -> String {
+    match value {
+        ValueRef::Null => String::new(),
+        ValueRef::Integer(i) => i.to_string(),
+        ValueRef::Real(f) => f.to_string(),
+        ValueRef::Text(bytes) | ValueRef::Blob(bytes) => {
+            String::from_utf8_lossy(bytes).into_owned()
+        }
+    }
+}
+
+/// 返回语句每个结果列来源的表名和列名，由表达式计算的列为`None`
+///
+/// rusqlite没有公开语句的列元数据，因此在同一个连接上单独准备一次语句来读取。
+pub fn column_origins(
+    conn: &Connection,
+    sql: &str,
+) -> rusqlite::Result<Vec<Option<(String, String)>>> {
+    let sql = CString::new(sql)?;
+    // SAFETY: 连接句柄在`conn`的生命周期内有效；语句在返回前被释放，读取的字符串在释放前复制
+    unsafe {
+        let db = conn.handle();
+        let mut stmt = ptr::null_mut();
+        let rc = ffi::sqlite3_prepare_v2(db, sql.as_ptr(), -1, &mut stmt, ptr::null_mut());
+        if rc != ffi::SQLITE_OK {
+            let message = CStr::from_ptr(ffi::sqlite3_errmsg(db))
+                .to_string_lossy()
+                .into_owned();
+            return Err(rusqlite::Error::SqliteFailure(
+                ffi::Error::new(rc),
+                Some(message),
+            ));
+        }
+        if stmt.is_null() {
+            return Ok(Vec::new());
+        }
+
+        let count = ffi::sqlite3_column_count(stmt);
+        let origins = (0..count)
+            .map(|i| {
+                let table = ffi::sqlite3_column_table_name(stmt, i);
+                let column = ffi::sqlite3_column_origin_name(stmt, i);
+                (!table.is_null() && !column.is_null()).then(|| {
+                    (
+                        CStr::from_ptr(table).to_string_lossy().into_owned(),
+                        CStr::from_ptr(column).to_string_lossy().into_owned(),
+                    )
+                })
+            })
+            .collect();
+        ffi::sqlite3_finalize(stmt);
+        Ok(origins)
+    }
+}
@@ -0,0 +1,763 @@
+/*!
+ * # SQL访问策略
+ *
+ * 本模块通过SQLite的授权回调（authorizer）在语句准备阶段检查每个动作，
+ * 拒绝违反策略的语句。被拒绝时SQLite只会返回通用的"not authorized"错误，
+ * 因此回调会把被拒绝的动作记录到[`DenialSlot`]中，由路由器据此生成说明策略的错误信息。
+ *
+ * 支持两种策略，可以同时启用：
+ *
+ * - 只读模式：只允许读取数据，禁止写入、结构变更、`ATTACH`/`DETACH`以及修改设置的PRAGMA
+ * - 访问策略（[`Policy`]）：从JSON文件加载的允许/拒绝规则，按动作类别、表名和名称（PRAGMA、函数等）匹配
+ *
+ * # 访问策略
+ *
+ * 规则按顺序匹配，第一条匹配的规则决定是否允许该动作；没有规则匹配时使用`default`（默认为`deny`）。
+ * 每条规则包含：
+ *
+ * - `name`：规则名称，出现在拒绝的错误信息中
+ * - `effect`：`allow`或`deny`
+ * - `actions`：动作类别，`read`、`insert`、`update`、`delete`、`create`、`drop`、`alter`、`reindex`、
+ *   `analyze`、`pragma`、`function`、`attach`、`detach`，或者`*`表示所有类别
+ * - `tables`：（可选）表名模式，只匹配涉及这些表的动作；`create`/`drop`匹配所涉及的表或视图
+ * - `names`：（可选）名称模式，匹配PRAGMA名、函数名、索引/触发器名、`ATTACH`的文件名和`DETACH`的数据库名
+ *
+ * 模式不区分大小写，`*`匹配任意多个字符，`?`匹配单个字符。`SELECT`语句本身、递归查询、事务和保存点总是允许；
+ * 结构变更对`sqlite_master`的修改由结构变更动作本身决定，不单独检查。
+ *
+ * 直接修改`sqlite_master`可以绕过结构变更的检查，因此启用访问策略时`PRAGMA writable_schema`总是被拒绝，
+ * 连接还会开启SQLite的防御模式（`SQLITE_DBCONFIG_DEFENSIVE`），直接对结构表执行的`INSERT`、`UPDATE`和`DELETE`都会失败。
+ *
+ * ```json
+ * {
+ *     "default": "deny",
+ *     "rules": [
+ *         { "name": "no-ddl", "effect": "deny", "actions": ["drop", "alter"] },
+ *         { "name": "no-extensions", "effect": "deny", "actions": ["function"], "names": ["load_extension"] },
+ *         { "name": "read-anything", "effect": "allow", "actions": ["read", "function"] },
+ *         { "name": "write-staging", "effect": "allow", "actions": ["insert", "update"], "tables": ["staging_*"] }
+ *     ]
+ * }
+ * ```
+ */
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    path::Path,
+    sync::{Arc, Mutex},
+    thread::{self, ThreadId},
+};
+
+use rusqlite::{
+    config::DbConfig,
+    hooks::{AuthAction, AuthContext, Authorization},
+    Connection,
+};
+use serde::Deserialize;
+use tracing::warn;
+
+/// 只读模式下允许带参数调用的PRAGMA，它们的参数只是查询对象，不会修改数据库
+const READ_ONLY_PRAGMAS_WITH_ARGUMENT: &[&str] = &[
+    "foreign_key_check",
+    "foreign_key_list",
+    "index_info",
+    "index_list",
+    "index_xinfo",
+    "integrity_check",
+    "quick_check",
+    "table_info",
+    "table_list",
+    "table_xinfo",
+];
+
+/// 访问策略规则可以使用的动作类别
+const ACTIONS: &[&str] = &[
+    "read", "insert", "update", "delete", "create", "drop", "alter", "reindex", "analyze",
+    "pragma", "function", "attach", "detach",
+];
+
+/// 存储数据库结构的内部表，结构变更会修改它们
+const SCHEMA_TABLES: &[&str] = &[
+    "sqlite_master",
+    "sqlite_schema",
+    "sqlite_temp_master",
+    "sqlite_temp_schema",
+];
+
+/// 一次被策略拒绝的动作
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Denial {
+    /// 拒绝该动作的策略名称
+    pub policy: String,
+    /// 拒绝该动作的规则名称，`None`表示没有规则匹配或者策略本身没有规则
+    pub rule: Option<String>,
+    /// 被拒绝动作的描述
+    pub action: String,
+}
+
+impl std::fmt::Display for Denial {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.policy.as_str(), &self.rule) {
+            ("read-only", _) => write!(
+                f,
+                "Denied by read-only policy: {} is not allowed because the server is running in read-only mode",
+                self.action
+            ),
+            ("schema", _) => write!(
+                f,
+                "Denied by access policy: {} is never allowed because it can modify the schema without policy checks",
+                self.action
+            ),
+            ("database", Some(database)) => write!(
+                f,
+                "Denied by database scope: {} is outside database '{}'; qualify table names with the database name",
+                self.action, database
+            ),
+            (policy, Some(rule)) => write!(
+                f,
+                "Denied by {} policy rule '{}': {}",
+                policy, rule, self.action
+            ),
+            (policy, None) => write!(
+                f,
+                "Denied by {} policy: {} is not allowed by any rule",
+                policy, self.action
+            ),
+        }
+    }
+}
+
+/// 访问策略规则的效果
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Effect {
+    /// 允许动作
+    Allow,
+    /// 拒绝动作
+    Deny,
+}
+
+/// 访问策略中的一条规则
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    /// 规则名称
+    pub name: String,
+    /// 规则匹配时的效果
+    pub effect: Effect,
+    /// 规则适用的动作类别，`*`表示所有类别
+    pub actions: Vec<String>,
+    /// 表名模式，`None`表示不限制
+    #[serde(default)]
+    pub tables: Option<Vec<String>>,
+    /// PRAGMA名、函数名等名称的模式，`None`表示不限制
+    #[serde(default)]
+    pub names: Option<Vec<String>>,
+}
+
+impl Rule {
+    /// 判断规则是否匹配某个动作
+    fn matches(&self, subject: &Subject<'_>) -> bool {
+        self.actions
+            .iter()
+            .any(|action| action == "*" || action.eq_ignore_ascii_case(subject.action))
+            && matches_patterns(self.tables.as_deref(), subject.table)
+            && matches_patterns(self.names.as_deref(), subject.name)
+    }
+}
+
+/// 从配置文件加载的访问策略
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Policy {
+    /// 没有规则匹配时的效果
+    #[serde(default = "default_effect")]
+    pub default: Effect,
+    /// 按顺序匹配的规则
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+}
+
+fn default_effect() -> Effect {
+    Effect::Deny
+}
+
+/// 加载访问策略失败
+#[derive(Debug)]
+pub enum PolicyError {
+    /// 读取策略文件失败
+    Io(std::io::Error),
+    /// 策略内容无效
+    Invalid(String),
+}
+
+impl std::fmt::Display for PolicyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "Failed to read policy file: {}", e),
+            Self::Invalid(message) => write!(f, "Invalid policy: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for PolicyError {}
+
+impl Policy {
+    /// 从JSON文本解析访问策略
+    ///
+    /// # 示例
+    ///
+    /// ```
+    /// use mcp_sqlite::policy::Policy;
+    /// use rusqlite::hooks::AuthAction;
+    ///
+    /// let policy = Policy::from_json(r#"{
+    ///     "rules": [
+    ///         { "name": "no-ddl", "effect": "deny", "actions": ["drop", "alter"] },
+    ///         { "name": "write-staging", "effect": "allow", "actions": ["insert"], "tables": ["staging_*"] }
+    ///     ]
+    /// }"#).unwrap();
+    ///
+    /// assert!(policy.check(&AuthAction::Insert { table_name: "staging_orders" }).is_ok());
+    /// let denial = policy.check(&AuthAction::DropTable { table_name: "users" }).unwrap_err();
+    /// assert_eq!(denial.to_string(), "Denied by access policy rule 'no-ddl': DROP TABLE users");
+    /// let denial = policy.check(&AuthAction::Insert { table_name: "users" }).unwrap_err();
+    /// assert_eq!(denial.rule, None);
+    /// ```
+    pub fn from_json(json: &str) -> Result<Self, PolicyError> {
+        let policy: Self =
+            serde_json::from_str(json).map_err(|e| PolicyError::Invalid(e.to_string()))?;
+        for rule in &policy.rules {
+            if let Some(action) = rule.actions.iter().find(|action| {
+                *action != "*" && !ACTIONS.contains(&action.to_ascii_lowercase().as_str())
+            }) {
+                return Err(PolicyError::Invalid(format!(
+                    "Unknown action '{}' in rule '{}'; expected one of: *, {}",
+                    action,
+                    rule.name,
+                    ACTIONS.join(", ")
+                )));
+            }
+        }
+        Ok(policy)
+    }
+
+    /// 从JSON文件加载访问策略
+    pub fn load(path: &Path) -> Result<Self, PolicyError> {
+        let json = std::fs::read_to_string(path).map_err(PolicyError::Io)?;
+        Self::from_json(&json)
+    }
+
+    /// 判断策略是否对所有表都允许某类动作
+    ///
+    /// 只按规则本身判断：第一条可能匹配该类动作的拒绝规则，或者带有`tables`/`names`限制的规则之后
+    /// 没有无条件的允许规则时，都视为不是对所有表都允许
+    ///
+    /// # 示例
+    ///
+    /// ```
+    /// use mcp_sqlite::policy::Policy;
+    ///
+    /// let policy = Policy::from_json(r#"{
+    ///     "default": "allow",
+    ///     "rules": [{ "name": "keep-users", "effect": "deny", "actions": ["delete"], "tables": ["users"] }]
+    /// }"#).unwrap();
+    /// assert!(policy.allows_everywhere("insert"));
+    /// assert!(!policy.allows_everywhere("delete"));
+    /// ```
+    pub fn allows_everywhere(&self, action: &str) -> bool {
+        for rule in &self.rules {
+            if !rule
+                .actions
+                .iter()
+                .any(|candidate| candidate == "*" || candidate.eq_ignore_ascii_case(action))
+            {
+                continue;
+            }
+            match rule.effect {
+                Effect::Deny => return false,
+                Effect::Allow if rule.tables.is_none() && rule.names.is_none() => return true,
+                // 有限制的允许规则只覆盖部分表，继续看后面的规则
+                Effect::Allow => {}
+            }
+        }
+        self.default == Effect::Allow
+    }
+
+    /// 判断策略是否允许用备份替换整个数据库
+    ///
+    /// 恢复通过在线备份API直接覆盖数据库页，不经过授权回调，
+    /// 只有策略对所有表都允许`insert`、`delete`、`create`和`drop`时才不会借此绕过策略
+    pub fn allows_restore(&self) -> bool {
+        ["insert", "delete", "create", "drop"]
+            .iter()
+            .all(|action| self.allows_everywhere(action))
+    }
+
+    /// 检查策略是否允许某个动作，拒绝时返回说明规则的[`Denial`]
+    ///
+    /// `PRAGMA writable_schema`不论规则如何总是被拒绝
+    ///
+    /// # 示例
+    ///
+    /// ```
+    /// use mcp_sqlite::policy::Policy;
+    /// use rusqlite::hooks::AuthAction;
+    ///
+    /// let policy = Policy::from_json(r#"{ "default": "allow" }"#).unwrap();
+    /// let pragma = AuthAction::Pragma { pragma_name: "writable_schema", pragma_value: Some("ON") };
+    /// assert_eq!(policy.check(&pragma).unwrap_err().policy, "schema");
+    /// ```
+    pub fn check(&self, action: &AuthAction<'_>) -> Result<(), Denial> {
+        if matches!(action, AuthAction::Pragma { pragma_name, .. }
+            if pragma_name.eq_ignore_ascii_case("writable_schema"))
+        {
+            return Err(Denial {
+                policy: "schema".to_string(),
+                rule: None,
+                action: describe_action(action),
+            });
+        }
+        let Some(subject) = Subject::of(action) else {
+            return Ok(());
+        };
+        let rule = self.rules.iter().find(|rule| rule.matches(&subject));
+        let effect = rule.map_or(self.default, |rule| rule.effect);
+        match effect {
+            Effect::Allow => Ok(()),
+            Effect::Deny => Err(Denial {
+                policy: "access".to_string(),
+                rule: rule.map(|rule| rule.name.clone()),
+                action: describe_action(action),
+            }),
+        }
+    }
+}
+
+/// 访问策略匹配的动作属性
+struct Subject<'a> {
+    action: &'static str,
+    table: Option<&'a str>,
+    name: Option<&'a str>,
+}
+
+impl<'a> Subject<'a> {
+    /// 提取动作的类别、表名和名称，总是允许的动作返回`None`
+    fn of(action: &AuthAction<'a>) -> Option<Self> {
+        let (action, table, name) = match *action {
+            AuthAction::Select
+            | AuthAction::Recursive
+            | AuthAction::Transaction { .. }
+            | AuthAction::Savepoint { .. } => return None,
+            // 不打开writable_schema时只有CREATE、DROP和ALTER能修改结构表，由结构变更动作本身检查；
+            // 启用策略的连接拒绝writable_schema并开启防御模式，直接修改结构表的语句会被SQLite拒绝
+            AuthAction::Insert { table_name }
+            | AuthAction::Update { table_name, .. }
+            | AuthAction::Delete { table_name }
+                if SCHEMA_TABLES.contains(&table_name.to_ascii_lowercase().as_str()) =>
+            {
+                return None
+            }
+            AuthAction::Read { table_name, .. } => ("read", Some(table_name), None),
+            AuthAction::Insert { table_name } => ("insert", Some(table_name), None),
+            AuthAction::Update { table_name, .. } => ("update", Some(table_name), None),
+            AuthAction::Delete { table_name } => ("delete", Some(table_name), None),
+            AuthAction::CreateTable { table_name } | AuthAction::CreateTempTable { table_name } => {
+                ("create", Some(table_name), Some(table_name))
+            }
+            AuthAction::CreateView { view_name } | AuthAction::CreateTempView { view_name } => {
+                ("create", Some(view_name), Some(view_name))
+            }
+            AuthAction::CreateIndex {
+                index_name,
+                table_name,
+            }
+            | AuthAction::CreateTempIndex {
+                index_name,
+                table_name,
+            } => ("create", Some(table_name), Some(index_name)),
+            AuthAction::CreateTrigger {
+                trigger_name,
+                table_name,
+            }
+            | AuthAction::CreateTempTrigger {
+                trigger_name,
+                table_name,
+            } => ("create", Some(table_name), Some(trigger_name)),
+            AuthAction::CreateVtable {
+                table_name,
+                module_name,
+            } => ("create", Some(table_name), Some(module_name)),
+            AuthAction::DropTable { table_name } | AuthAction::DropTempTable { table_name } => {
+                ("drop", Some(table_name), Some(table_name))
+            }
+            AuthAction::DropView { view_name } | AuthAction::DropTempView { view_name } => {
+                ("drop", Some(view_name), Some(view_name))
+            }
+            AuthAction::DropIndex {
+                index_name,
+                table_name,
+            }
+            | AuthAction::DropTempIndex {
+                index_name,
+                table_name,
+            } => ("drop", Some(table_name), Some(index_name)),
+            AuthAction::DropTrigger {
+                trigger_name,
+                table_name,
+            }
+            | AuthAction::DropTempTrigger {
+                trigger_name,
+                table_name,
+            } => ("drop", Some(table_name), Some(trigger_name)),
+            AuthAction::DropVtable {
+                table_name,
+                module_name,
+            } => ("drop", Some(table_name), Some(module_name)),
+            AuthAction::AlterTable { table_name, .. } => ("alter", Some(table_name), None),
+            AuthAction::Reindex { index_name } => ("reindex", None, Some(index_name)),
+            AuthAction::Analyze { table_name } => ("analyze", Some(table_name), None),
+            AuthAction::Pragma { pragma_name, .. } => ("pragma", None, Some(pragma_name)),
+            AuthAction::Function { function_name } => ("function", None, Some(function_name)),
+            AuthAction::Attach { filename } => ("attach", None, Some(filename)),
+            AuthAction::Detach { database_name } => ("detach", None, Some(database_name)),
+            // 未知的动作只能被`*`规则或默认效果处理
+            _ => ("unknown", None, None),
+        };
+        Some(Self {
+            action,
+            table,
+            name,
+        })
+    }
+}
+
+/// 值是否匹配任意一个模式，`patterns`为`None`时总是匹配
+fn matches_patterns(patterns: Option<&[String]>, value: Option<&str>) -> bool {
+    match (patterns, value) {
+        (None, _) => true,
+        (Some(patterns), Some(value)) => patterns.iter().any(|pattern| glob_match(pattern, value)),
+        (Some(_), None) => false,
+    }
+}
+
+/// 不区分大小写的通配符匹配，`*`匹配任意多个字符，`?`匹配单个字符
+pub(crate) fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // 最近一个`*`的位置，以及它当前匹配到的文本位置，失配时从这里回溯
+    let mut star = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((star_p, star_t)) => {
+                    p = star_p + 1;
+                    t = star_t + 1;
+                    star = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// 记录最近一次被拒绝的动作，在授权回调和路由器之间共享
+///
+/// 授权回调在执行语句的线程上运行，每个线程分别记录，多个连接并发执行时互不影响
+#[derive(Debug, Clone, Default)]
+pub struct DenialSlot(Arc<Mutex<HashMap<ThreadId, Denial>>>);
+
+impl DenialSlot {
+    /// 记录当前线程上一次被拒绝的动作
+    fn record(&self, denial: Denial) {
+        if let Ok(mut slot) = self.0.lock() {
+            slot.insert(thread::current().id(), denial);
+        }
+    }
+
+    /// 取出并清除当前线程上最近一次被拒绝的动作
+    pub fn take(&self) -> Option<Denial> {
+        self.0
+            .lock()
+            .ok()
+            .and_then(|mut slot| slot.remove(&thread::current().id()))
+    }
+}
+
+thread_local! {
+    /// 当前线程上准备的`DELETE`是否需要逐行删除
+    static ROW_BY_ROW_DELETE: Cell<bool> = const { Cell::new(false) };
+    /// 当前线程上准备的语句中修改数据库结构的动作，`None`表示不记录
+    static SCHEMA_CHANGES: RefCell<Option<Vec<String>>> = const { RefCell::new(None) };
+    /// 当前线程上准备的语句只能访问的数据库，`None`表示不限制
+    static DATABASE_SCOPE: RefCell<Option<String>> = const { RefCell::new(None) };
+    /// 当前线程上准备的`VACUUM INTO`写入的备份文件，`None`表示不允许
+    static BACKUP_TARGET: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// 在`f`执行期间禁用当前线程上的截断优化，使不带`WHERE`的`DELETE`逐行删除
+///
+/// 截断优化直接清空表，不会调用更新回调。写入预览依赖更新回调记录被删除的行，
+/// 因此在预演期间准备的语句需要逐行删除。只对安装了[`install`]授权回调的连接有效。
+pub fn with_row_by_row_delete<T>(f: impl FnOnce() -> T) -> T {
+    let previous = ROW_BY_ROW_DELETE.with(|flag| flag.replace(true));
+    let result = f();
+    ROW_BY_ROW_DELETE.with(|flag| flag.set(previous));
+    result
+}
+
+/// 在`f`执行期间记录当前线程上准备的语句中修改数据库结构的动作，如`DROP TABLE users`
+///
+/// 临时对象的创建和删除不计入。与[`with_row_by_row_delete`]一样只对安装了[`install`]授权回调的连接有效
+pub fn record_schema_changes<T>(f: impl FnOnce() -> T) -> (T, Vec<String>) {
+    let previous = SCHEMA_CHANGES.with(|changes| changes.replace(Some(Vec::new())));
+    let result = f();
+    let changes = SCHEMA_CHANGES.with(|changes| changes.replace(previous));
+    (result, changes.unwrap_or_default())
+}
+
+/// 在`f`执行期间把当前线程上准备的语句限制在指定的数据库中
+///
+/// 读写其他数据库中的表或者修改其结构的语句被拒绝，临时数据库`temp`不受限制。
+/// `database`为`None`时不限制。与[`with_row_by_row_delete`]一样只对安装了[`install`]授权回调的连接有效
+pub fn with_database_scope<T>(database: Option<&str>, f: impl FnOnce() -> T) -> T {
+    let previous = DATABASE_SCOPE.with(|scope| scope.replace(database.map(str::to_string)));
+    let result = f();
+    DATABASE_SCOPE.with(|scope| scope.replace(previous));
+    result
+}
+
+/// 在`f`执行期间允许当前线程上准备的`VACUUM INTO`写入指定的备份文件
+///
+/// `VACUUM INTO`把目标文件附加为`vacuum_db`再写入，只读模式和禁止`ATTACH`的访问策略会拒绝它。
+/// 备份文件由服务器在备份目录中选定，因此只放行附加这一个文件和对它的写入，其他动作照常检查。
+/// 与[`with_row_by_row_delete`]一样只对安装了[`install`]授权回调的连接有效
+pub fn with_backup_target<T>(target: &str, f: impl FnOnce() -> T) -> T {
+    let previous = BACKUP_TARGET.with(|slot| slot.replace(Some(target.to_string())));
+    let result = f();
+    BACKUP_TARGET.with(|slot| slot.replace(previous));
+    result
+}
+
+/// 判断动作是否为`VACUUM INTO`附加或写入当前线程允许的备份文件
+fn is_backup_target(ctx: &AuthContext<'_>) -> bool {
+    BACKUP_TARGET.with(|slot| {
+        let slot = slot.borrow();
+        let Some(target) = slot.as_deref() else {
+            return false;
+        };
+        match ctx.action {
+            AuthAction::Attach { filename } => filename == target,
+            _ => ctx.database_name == Some("vacuum_db"),
+        }
+    })
+}
+
+/// 检查动作是否在当前线程的数据库范围之内
+fn check_database_scope(ctx: &AuthContext<'_>) -> Result<(), Denial> {
+    let Some(database) = ctx.database_name else {
+        return Ok(());
+    };
+    let scoped = Subject::of(&ctx.action).is_some_and(|subject| {
+        matches!(
+            subject.action,
+            "read" | "insert" | "update" | "delete" | "create" | "drop" | "alter"
+        )
+    });
+    if !scoped || database.eq_ignore_ascii_case("temp") {
+        return Ok(());
+    }
+    DATABASE_SCOPE.with(|scope| match scope.borrow().as_deref() {
+        Some(scope) if !scope.eq_ignore_ascii_case(database) => Err(Denial {
+            policy: "database".to_string(),
+            rule: Some(scope.to_string()),
+            action: format!("{} in database {}", describe_action(&ctx.action), database),
+        }),
+        _ => Ok(()),
+    })
+}
+
+/// 在连接上安装执行策略的授权回调
+///
+/// 两种策略都未启用时回调允许所有动作，只用于[`with_row_by_row_delete`]、[`with_database_scope`]等。
+/// 启用任意一种策略时连接开启防御模式，结构表只能通过结构变更语句修改
+///
+/// # 参数
+///
+/// * `conn` - SQLite数据库连接
+/// * `read_only` - 是否启用只读策略，先于访问策略检查
+/// * `policy` - 访问策略
+/// * `denials` - 被拒绝的动作会记录到这里
+pub fn install(
+    conn: &Connection,
+    read_only: bool,
+    policy: Option<Arc<Policy>>,
+    denials: DenialSlot,
+) {
+    if read_only || policy.is_some() {
+        // 防御模式是授权回调之外的第二道防线：即使writable_schema被打开也不能直接修改结构表
+        if let Err(e) = conn.set_db_config(DbConfig::SQLITE_DBCONFIG_DEFENSIVE, true) {
+            warn!("Failed to enable defensive mode: {}", e);
+        }
+    }
+    conn.authorizer(Some(move |ctx: AuthContext<'_>| {
+        let result = if is_backup_target(&ctx) {
+            Ok(())
+        } else if read_only && !read_only_allows(&ctx.action) {
+            Err(Denial {
+                policy: "read-only".to_string(),
+                rule: None,
+                action: describe_action(&ctx.action),
+            })
+        } else {
+            policy
+                .as_ref()
+                .map_or(Ok(()), |policy| policy.check(&ctx.action))
+        }
+        .and_then(|()| check_database_scope(&ctx));
+        if result.is_ok() && is_schema_change(&ctx.action) {
+            SCHEMA_CHANGES.with(|changes| {
+                if let Some(changes) = changes.borrow_mut().as_mut() {
+                    // 结构变化后重新准备的语句会再次触发回调
+                    let change = describe_action(&ctx.action);
+                    if !changes.contains(&change) {
+                        changes.push(change);
+                    }
+                }
+            });
+        }
+        match result {
+            // 授权回调对DELETE返回IGNORE时SQLite绕过截断优化，仍然删除所有匹配的行；
+            // 但对结构表返回IGNORE会使DROP静默失效
+            Ok(()) if ROW_BY_ROW_DELETE.with(Cell::get) && is_user_delete(&ctx.action) => {
+                Authorization::Ignore
+            }
+            Ok(()) => Authorization::Allow,
+            Err(denial) => {
+                denials.record(denial);
+                Authorization::Deny
+            }
+        }
+    }));
+}
+
+/// 判断动作是否修改持久的数据库结构
+fn is_schema_change(action: &AuthAction<'_>) -> bool {
+    let temporary = matches!(
+        action,
+        AuthAction::CreateTempIndex { .. }
+            | AuthAction::CreateTempTable { .. }
+            | AuthAction::CreateTempTrigger { .. }
+            | AuthAction::CreateTempView { .. }
+            | AuthAction::DropTempIndex { .. }
+            | AuthAction::DropTempTable { .. }
+            | AuthAction::DropTempTrigger { .. }
+            | AuthAction::DropTempView { .. }
+    );
+    !temporary
+        && Subject::of(action)
+            .is_some_and(|subject| matches!(subject.action, "create" | "drop" | "alter"))
+}
+
+/// 判断动作是否为删除用户表中的行
+fn is_user_delete(action: &AuthAction<'_>) -> bool {
+    matches!(action, AuthAction::Delete { table_name }
+        if !SCHEMA_TABLES.contains(&table_name.to_ascii_lowercase().as_str()))
+}
+
+/// 判断只读模式是否允许某个动作
+fn read_only_allows(action: &AuthAction<'_>) -> bool {
+    match action {
+        AuthAction::Select
+        | AuthAction::Read { .. }
+        | AuthAction::Function { .. }
+        | AuthAction::Recursive
+        | AuthAction::Transaction { .. }
+        | AuthAction::Savepoint { .. } => true,
+        AuthAction::Pragma {
+            pragma_name,
+            pragma_value,
+        } => {
+            let name = pragma_name.to_ascii_lowercase();
+            // writable_schema即使只是读取也不允许，避免绕过结构保护
+            name != "writable_schema"
+                && (pragma_value.is_none()
+                    || READ_ONLY_PRAGMAS_WITH_ARGUMENT.contains(&name.as_str()))
+        }
+        _ => false,
+    }
+}
+
+/// 生成动作的可读描述，用于错误信息
+pub fn describe_action(action: &AuthAction<'_>) -> String {
+    match action {
+        AuthAction::CreateIndex { index_name, .. }
+        | AuthAction::CreateTempIndex { index_name, .. } => {
+            format!("CREATE INDEX {}", index_name)
+        }
+        AuthAction::CreateTable { table_name } | AuthAction::CreateTempTable { table_name } => {
+            format!("CREATE TABLE {}", table_name)
+        }
+        AuthAction::CreateTrigger { trigger_name, .. }
+        | AuthAction::CreateTempTrigger { trigger_name, .. } => {
+            format!("CREATE TRIGGER {}", trigger_name)
+        }
+        AuthAction::CreateView { view_name } | AuthAction::CreateTempView { view_name } => {
+            format!("CREATE VIEW {}", view_name)
+        }
+        AuthAction::CreateVtable { table_name, .. } => {
+            format!("CREATE VIRTUAL TABLE {}", table_name)
+        }
+        AuthAction::Delete { table_name } => format!("DELETE on {}", table_name),
+        AuthAction::DropIndex { index_name, .. } | AuthAction::DropTempIndex { index_name, .. } => {
+            format!("DROP INDEX {}", index_name)
+        }
+        AuthAction::DropTable { table_name } | AuthAction::DropTempTable { table_name } => {
+            format!("DROP TABLE {}", table_name)
+        }
+        AuthAction::DropTrigger { trigger_name, .. }
+        | AuthAction::DropTempTrigger { trigger_name, .. } => {
+            format!("DROP TRIGGER {}", trigger_name)
+        }
+        AuthAction::DropView { view_name } | AuthAction::DropTempView { view_name } => {
+            format!("DROP VIEW {}", view_name)
+        }
+        AuthAction::DropVtable { table_name, .. } => format!("DROP VIRTUAL TABLE {}", table_name),
+        AuthAction::Insert { table_name } => format!("INSERT on {}", table_name),
+        AuthAction::Update {
+            table_name,
+            column_name,
+        } => format!("UPDATE on {}.{}", table_name, column_name),
+        AuthAction::Read {
+            table_name,
+            column_name,
+        } => format!("read of {}.{}", table_name, column_name),
+        AuthAction::Pragma {
+            pragma_name,
+            pragma_value: Some(value),
+        } => format!("PRAGMA {} = {}", pragma_name, value),
+        AuthAction::Pragma { pragma_name, .. } => format!("PRAGMA {}", pragma_name),
+        AuthAction::Attach { filename } => format!("ATTACH '{}'", filename),
+        AuthAction::Detach { database_name } => format!("DETACH {}", database_name),
+        AuthAction::AlterTable { table_name, .. } => format!("ALTER TABLE {}", table_name),
+        AuthAction::Reindex { index_name } => format!("REINDEX {}", index_name),
+        AuthAction::Analyze { table_name } => format!("ANALYZE {}", table_name),
+        AuthAction::Function { function_name } => format!("function {}()", function_name),
+        AuthAction::Transaction { operation } => format!("transaction {:?}", operation),
+        AuthAction::Savepoint { savepoint_name, .. } => format!("SAVEPOINT {}", savepoint_name),
+        AuthAction::Select => "SELECT".to_string(),
+        AuthAction::Recursive => "recursive query".to_string(),
+        other => format!("{:?}", other),
+    }
+}
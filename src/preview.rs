@@ -0,0 +1,339 @@
+/*!
+ * # 写入预览
+ *
+ * `execute`、`executemany`和`executescript`的`dry_run`参数为`true`时，语句在保存点中正常执行，
+ * 执行完毕后回滚到保存点，工具返回修改的预览，数据库保持不变。
+ *
+ * 执行期间通过SQLite的更新回调记录被插入、更新和删除的行，包括触发器和外键级联修改的行。
+ * 回滚之前读取这些行的新值（`after`），回滚之后读取旧值（`before`）。预览按表汇总：
+ *
+ * ```json
+ * {
+ *     "schema": "main",
+ *     "table": "users",
+ *     "inserted": 0,
+ *     "updated": 0,
+ *     "deleted": 120,
+ *     "rows": [
+ *         { "rowid": 7, "action": "delete", "before": { "id": 7, "name": "Bob" }, "after": null }
+ *     ],
+ *     "omitted_rows": 100
+ * }
+ * ```
+ *
+ * `inserted`、`updated`和`deleted`是更新回调报告的操作次数。`rows`中的每一行只出现一次，
+ * `action`由前后两个值推断，数量超过上限的行不再列出，只计入`omitted_rows`。
+ * 脱敏配置同样作用于`before`和`after`。
+ *
+ * 限制：
+ *
+ * - 更新回调不报告`WITHOUT ROWID`表的修改，这些修改只体现在`rowcount`中
+ * - 结构变更同样会被回滚，只在`schema_changes`中列出（如`DROP TABLE users`），不会出现在行级预览中；
+ *   在预演中创建的表没有`before`，删除的表没有`after`
+ * - 预演不能包含`BEGIN`、`COMMIT`、`ROLLBACK`、`SAVEPOINT`、`RELEASE`等事务控制语句。
+ *   预演期间还安装了提交回调，即使有语句试图提交，修改也会被回滚
+ */
+
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+};
+
+use rusqlite::{hooks::Action, Connection};
+use serde_json::{json, Value};
+
+use crate::{
+    masking::{MaskPlan, Masking},
+    policy,
+    schema::quote_identifier,
+    server::extract_row_values,
+};
+
+/// 每张表默认最多列出的行数
+pub const DEFAULT_PREVIEW_ROWS: usize = 20;
+
+/// 预演使用的保存点
+const SAVEPOINT: &str = "mcp_dry_run";
+
+/// 预演中不允许的事务控制语句
+const TRANSACTION_KEYWORDS: &[&str] =
+    &["BEGIN", "COMMIT", "END", "ROLLBACK", "SAVEPOINT", "RELEASE"];
+
+/// 如果语句是事务控制语句，返回其关键字
+///
+/// 跳过开头的空白和注释，不区分大小写
+///
+/// # 示例
+///
+/// ```
+/// use mcp_sqlite::preview::transaction_keyword;
+///
+/// assert_eq!(transaction_keyword("  -- done\n commit;"), Some("COMMIT"));
+/// assert_eq!(transaction_keyword("/* x */ Rollback TO s"), Some("ROLLBACK"));
+/// assert_eq!(transaction_keyword("DELETE FROM t"), None);
+/// ```
+pub fn transaction_keyword(sql: &str) -> Option<&'static str> {
+    let mut rest = sql;
+    loop {
+        rest = rest.trim_start();
+        if let Some(comment) = rest.strip_prefix("--") {
+            rest = comment.split_once('\n').map_or("", |(_, tail)| tail);
+        } else if let Some(comment) = rest.strip_prefix("/*") {
+            rest = comment.split_once("*/").map_or("", |(_, tail)| tail);
+        } else {
+            break;
+        }
+    }
+    let word: String = rest
+        .chars()
+        .take_while(|c| c.is_ascii_alphabetic())
+        .collect();
+    TRANSACTION_KEYWORDS
+        .iter()
+        .copied()
+        .find(|keyword| keyword.eq_ignore_ascii_case(&word))
+}
+
+/// 预演的结果
+#[derive(Debug, Clone, PartialEq)]
+pub struct Preview {
+    /// 每张被修改的表一项，格式见模块文档
+    pub changes: Vec<Value>,
+    /// 修改数据库结构的动作，如`DROP TABLE users`
+    pub schema_changes: Vec<String>,
+}
+
+impl Preview {
+    /// 所有表上插入、更新和删除的总次数
+    pub fn changed_rows(&self) -> u64 {
+        self.changes
+            .iter()
+            .map(|table| {
+                ["inserted", "updated", "deleted"]
+                    .iter()
+                    .filter_map(|key| table[*key].as_u64())
+                    .sum::<u64>()
+            })
+            .sum()
+    }
+}
+
+/// 在保存点中执行`f`并回滚，返回`f`的结果和修改的预览
+///
+/// `f`返回后回滚到保存点；无论`f`是否成功，数据库都保持不变。
+/// 连接需要安装[`policy::install`]的授权回调，否则不带`WHERE`的`DELETE`删除的行不会出现在预览中，
+/// 结构变更也不会被记录
+///
+/// # 参数
+///
+/// * `conn` - 数据库连接
+/// * `max_rows` - 每张表最多列出的行数
+/// * `masking` - 应用于行值的脱敏配置
+/// * `f` - 执行修改的操作
+///
+/// # 返回值
+///
+/// `f`的结果和[`Preview`]；只有保存点本身失败时返回错误
+///
+/// # 示例
+///
+/// ```
+/// use mcp_sqlite::preview::dry_run;
+/// use rusqlite::Connection;
+///
+/// let conn = Connection::open_in_memory().unwrap();
+/// conn.execute_batch("CREATE TABLE t (x); INSERT INTO t VALUES (1), (2);").unwrap();
+///
+/// let (changed, preview) = dry_run(&conn, 10, None, || conn.execute("UPDATE t SET x = x * 10", [])).unwrap();
+/// assert_eq!(changed.unwrap(), 2);
+/// assert_eq!(preview.changed_rows(), 2);
+/// assert_eq!(preview.changes[0]["updated"], 2);
+/// assert_eq!(preview.changes[0]["rows"][0]["before"]["x"], 1);
+/// assert_eq!(preview.changes[0]["rows"][0]["after"]["x"], 10);
+///
+/// let x: i64 = conn.query_row("SELECT sum(x) FROM t", [], |row| row.get(0)).unwrap();
+/// assert_eq!(x, 3);
+/// ```
+pub fn dry_run<T, F>(
+    conn: &Connection,
+    max_rows: usize,
+    masking: Option<&Masking>,
+    f: F,
+) -> rusqlite::Result<(T, Preview)>
+where
+    F: FnOnce() -> T,
+{
+    conn.execute_batch(&format!("SAVEPOINT {}", SAVEPOINT))?;
+
+    let recorder = Arc::new(Mutex::new(Vec::<TableChanges>::new()));
+    let hook_recorder = recorder.clone();
+    conn.update_hook(Some(
+        move |action: Action, schema: &str, table: &str, rowid: i64| {
+            if let Ok(mut tables) = hook_recorder.lock() {
+                record_change(&mut tables, max_rows, action, schema, table, rowid);
+            }
+        },
+    ));
+    // 有语句试图提交时改为回滚
+    conn.commit_hook(Some(|| true));
+
+    let (result, schema_changes) =
+        policy::with_row_by_row_delete(|| policy::record_schema_changes(f));
+
+    conn.update_hook(None::<fn(Action, &str, &str, i64)>);
+    let mut tables = std::mem::take(&mut *recorder.lock().unwrap_or_else(|e| e.into_inner()));
+    for table in &mut tables {
+        table.after = read_rows(conn, table, masking);
+    }
+
+    // 释放最外层的保存点也是一次提交，回滚之前先移除提交回调
+    conn.commit_hook(None::<fn() -> bool>);
+    let rolled_back = conn.execute_batch(&format!("ROLLBACK TO {0}; RELEASE {0}", SAVEPOINT));
+    // 语句自己结束了事务时保存点已经不存在，修改也已被提交回调回滚
+    if let Err(e) = rolled_back {
+        if !conn.is_autocommit() {
+            return Err(e);
+        }
+    }
+
+    for table in &mut tables {
+        table.before = read_rows(conn, table, masking);
+    }
+
+    let preview = Preview {
+        changes: tables.iter().map(TableChanges::to_json).collect(),
+        schema_changes,
+    };
+    Ok((result, preview))
+}
+
+/// 一张表上的修改
+struct TableChanges {
+    schema: String,
+    table: String,
+    inserted: u64,
+    updated: u64,
+    deleted: u64,
+    /// 列出的行，按第一次修改的顺序
+    rowids: Vec<i64>,
+    seen: HashSet<i64>,
+    /// 超过上限而没有列出的行数
+    omitted: u64,
+    before: Vec<Option<Value>>,
+    after: Vec<Option<Value>>,
+}
+
+impl TableChanges {
+    fn to_json(&self) -> Value {
+        let rows: Vec<Value> = self
+            .rowids
+            .iter()
+            .enumerate()
+            .map(|(i, rowid)| {
+                let before = self.before.get(i).cloned().flatten();
+                let after = self.after.get(i).cloned().flatten();
+                let action = match (&before, &after) {
+                    (None, Some(_)) => "insert",
+                    (Some(_), Some(_)) => "update",
+                    (Some(_), None) => "delete",
+                    // 在预演中插入后又被删除的行
+                    (None, None) => "transient",
+                };
+                json!({
+                    "rowid": rowid,
+                    "action": action,
+                    "before": before,
+                    "after": after,
+                })
+            })
+            .collect();
+        json!({
+            "schema": self.schema,
+            "table": self.table,
+            "inserted": self.inserted,
+            "updated": self.updated,
+            "deleted": self.deleted,
+            "rows": rows,
+            "omitted_rows": self.omitted,
+        })
+    }
+}
+
+fn record_change(
+    tables: &mut Vec<TableChanges>,
+    max_rows: usize,
+    action: Action,
+    schema: &str,
+    table: &str,
+    rowid: i64,
+) {
+    let index = match tables
+        .iter()
+        .position(|t| t.schema == schema && t.table == table)
+    {
+        Some(index) => index,
+        None => {
+            tables.push(TableChanges {
+                schema: schema.to_string(),
+                table: table.to_string(),
+                inserted: 0,
+                updated: 0,
+                deleted: 0,
+                rowids: Vec::new(),
+                seen: HashSet::new(),
+                omitted: 0,
+                before: Vec::new(),
+                after: Vec::new(),
+            });
+            tables.len() - 1
+        }
+    };
+    let changes = &mut tables[index];
+    match action {
+        Action::SQLITE_INSERT => changes.inserted += 1,
+        Action::SQLITE_UPDATE => changes.updated += 1,
+        Action::SQLITE_DELETE => changes.deleted += 1,
+        _ => {}
+    }
+    if changes.seen.contains(&rowid) {
+        return;
+    }
+    if changes.rowids.len() < max_rows {
+        changes.rowids.push(rowid);
+        changes.seen.insert(rowid);
+    } else {
+        // 超过上限后不再记录行号，同一行的多次修改可能被重复计数
+        changes.omitted += 1;
+    }
+}
+
+/// 读取列出的行的当前值，行不存在、表不存在或无权读取时为`None`
+fn read_rows(
+    conn: &Connection,
+    table: &TableChanges,
+    masking: Option<&Masking>,
+) -> Vec<Option<Value>> {
+    let sql = format!(
+        "SELECT * FROM {}.{} WHERE rowid = ?",
+        quote_identifier(&table.schema),
+        quote_identifier(&table.table)
+    );
+    let (mut stmt, plan) = match conn
+        .prepare(&sql)
+        .and_then(|stmt| Ok((stmt, MaskPlan::for_query(masking, conn, &sql)?)))
+    {
+        Ok(prepared) => prepared,
+        Err(_) => return vec![None; table.rowids.len()],
+    };
+    let column_names: Vec<String> = stmt.column_names().iter().map(|s| s.to_string()).collect();
+    table
+        .rowids
+        .iter()
+        .map(|rowid| {
+            stmt.query_row([rowid], |row| {
+                Ok(extract_row_values(row, &column_names, &plan))
+            })
+            .ok()
+        })
+        .collect()
+}
@@ -0,0 +1,235 @@
+/*!
+ * # 数据库注册表
+ *
+ * `open_database`工具在运行时打开启动时还不存在的数据库文件，并以名称登记在注册表中，
+ * 之后的工具调用通过`database`参数选择它；`close_database`关闭并注销数据库。
+ *
+ * 为了防止模型打开任意文件，只能打开允许的目录树（见[`crate::server::RouterOptions::open_roots`]）中的文件：
+ *
+ * - 相对路径相对于第一个允许的目录
+ * - 路径中的`..`和符号链接先被解析，解析后的路径必须仍然位于某个允许的目录之下
+ * - 不存在的文件只有在调用方要求创建时才会被创建，且其父目录必须已经存在
+ *
+ * 每个打开的数据库拥有独立的连接和工作线程（见[`crate::worker`]），一个数据库上的长查询
+ * 不会阻塞主数据库或其他打开的数据库。打开的数据库由所有会话共享，关闭后正在执行的调用仍会完成。
+ */
+
+use std::{
+    collections::BTreeMap,
+    io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use crate::worker::DbWorker;
+
+/// 注册表操作错误
+#[derive(Debug)]
+pub enum RegistryError {
+    /// 没有配置允许的目录
+    Disabled,
+    /// 路径不在允许的目录中
+    NotAllowed(String),
+    /// 名称或路径无效，例如名称已被使用
+    Invalid(String),
+    /// 无法访问路径
+    Io(String, io::Error),
+}
+
+impl std::fmt::Display for RegistryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Disabled => write!(f, "Opening databases is disabled: no directory is allowed"),
+            Self::NotAllowed(path) => {
+                write!(f, "Path is outside the allowed directories: {}", path)
+            }
+            Self::Invalid(message) => write!(f, "{}", message),
+            Self::Io(path, e) => write!(f, "Cannot access {}: {}", path, e),
+        }
+    }
+}
+
+impl std::error::Error for RegistryError {}
+
+/// 运行时打开的数据库
+#[derive(Debug, Clone)]
+pub struct OpenedDatabase {
+    /// 数据库名称
+    pub name: String,
+    /// 解析后的数据库文件路径
+    pub path: PathBuf,
+    /// 是否以只读方式打开
+    pub read_only: bool,
+    /// 持有该数据库连接的工作线程
+    pub worker: DbWorker,
+}
+
+/// 运行时打开的数据库的注册表
+///
+/// 克隆得到的注册表共享同样的数据库
+#[derive(Debug, Clone, Default)]
+pub struct DatabaseRegistry {
+    roots: Arc<Vec<PathBuf>>,
+    reserved: Arc<Vec<String>>,
+    databases: Arc<Mutex<BTreeMap<String, OpenedDatabase>>>,
+}
+
+impl DatabaseRegistry {
+    /// 创建注册表
+    ///
+    /// # 参数
+    ///
+    /// * `roots` - 允许打开的目录，为空时不能打开任何数据库；不存在的目录不匹配任何路径
+    /// * `reserved` - 不能使用的名称，如启动时附加的数据库，`main`和`temp`总是保留
+    pub fn new(roots: Vec<PathBuf>, reserved: Vec<String>) -> Self {
+        Self {
+            roots: Arc::new(roots),
+            reserved: Arc::new(reserved),
+            databases: Arc::default(),
+        }
+    }
+
+    /// 是否允许打开数据库
+    pub fn is_enabled(&self) -> bool {
+        !self.roots.is_empty()
+    }
+
+    /// 允许的目录
+    pub fn roots(&self) -> &[PathBuf] {
+        &self.roots
+    }
+
+    /// 检查名称能否用于新打开的数据库
+    ///
+    /// 名称必须是以字母或下划线开头的标识符，不能是保留名称，也不能与已经打开的数据库重名（不区分大小写）
+    pub fn check_name(&self, name: &str) -> Result<(), RegistryError> {
+        let is_identifier = name
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !is_identifier {
+            return Err(RegistryError::Invalid(format!(
+                "Invalid database name: {}; use letters, digits and underscores",
+                name
+            )));
+        }
+        let reserved = ["main", "temp"]
+            .into_iter()
+            .chain(self.reserved.iter().map(String::as_str))
+            .any(|reserved| reserved.eq_ignore_ascii_case(name));
+        if reserved || self.get(name).is_some() {
+            return Err(RegistryError::Invalid(format!(
+                "Database name is already in use: {}",
+                name
+            )));
+        }
+        Ok(())
+    }
+
+    /// 把请求的路径解析为允许的目录中的文件
+    ///
+    /// # 参数
+    ///
+    /// * `path` - 请求的路径，相对路径相对于第一个允许的目录
+    /// * `create` - 文件不存在时是否允许创建；为`false`时文件必须已经存在
+    ///
+    /// # 示例
+    ///
+    /// ```
+    /// use mcp_sqlite::registry::DatabaseRegistry;
+    ///
+    /// let root = std::env::temp_dir().join(format!("mcp-sqlite-registry-{}", std::process::id()));
+    /// std::fs::create_dir_all(&root).unwrap();
+    /// let registry = DatabaseRegistry::new(vec![root.clone()], vec![]);
+    ///
+    /// let path = registry.resolve("new.db", true).unwrap();
+    /// assert!(path.starts_with(root.canonicalize().unwrap()));
+    /// assert!(registry.resolve("new.db", false).is_err());
+    /// assert!(registry.resolve("../outside.db", true).is_err());
+    /// # std::fs::remove_dir_all(&root).unwrap();
+    /// ```
+    pub fn resolve(&self, path: &str, create: bool) -> Result<PathBuf, RegistryError> {
+        resolve_path(&self.roots, path, create)
+    }
+
+    /// 登记打开的数据库，名称已被使用时返回错误
+    pub fn register(&self, database: OpenedDatabase) -> Result<(), RegistryError> {
+        let mut databases = self.lock();
+        let key = database.name.to_ascii_lowercase();
+        if databases.contains_key(&key) {
+            return Err(RegistryError::Invalid(format!(
+                "Database name is already in use: {}",
+                database.name
+            )));
+        }
+        databases.insert(key, database);
+        Ok(())
+    }
+
+    /// 按名称获取打开的数据库，不区分大小写
+    pub fn get(&self, name: &str) -> Option<OpenedDatabase> {
+        self.lock().get(&name.to_ascii_lowercase()).cloned()
+    }
+
+    /// 注销打开的数据库，最后一个工作线程句柄被释放后连接关闭
+    pub fn remove(&self, name: &str) -> Option<OpenedDatabase> {
+        self.lock().remove(&name.to_ascii_lowercase())
+    }
+
+    /// 按名称排列的所有打开的数据库
+    pub fn list(&self) -> Vec<OpenedDatabase> {
+        self.lock().values().cloned().collect()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BTreeMap<String, OpenedDatabase>> {
+        self.databases
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// 把请求的路径解析为允许的目录树中的文件
+///
+/// 规则与[`DatabaseRegistry::resolve`]相同，`import_csv`读取的文件同样用它检查；`roots`为空时返回[`RegistryError::Disabled`]
+pub fn resolve_path(roots: &[PathBuf], path: &str, create: bool) -> Result<PathBuf, RegistryError> {
+    let first = roots.first().ok_or(RegistryError::Disabled)?;
+    // 每次重新解析允许的目录，目录本身也可能是符号链接
+    let root = first
+        .canonicalize()
+        .map_err(|e| RegistryError::Io(first.display().to_string(), e))?;
+    let roots: Vec<PathBuf> = roots
+        .iter()
+        .filter_map(|root| root.canonicalize().ok())
+        .collect();
+    let requested = Path::new(path);
+    let joined = if requested.is_absolute() {
+        requested.to_path_buf()
+    } else {
+        root.join(requested)
+    };
+
+    let resolved = if create && !joined.exists() {
+        // 文件不存在，解析父目录后再拼上文件名；以`..`结尾的路径没有文件名
+        let file_name = joined
+            .file_name()
+            .ok_or_else(|| RegistryError::Invalid(format!("Not a file path: {}", path)))?;
+        let parent = joined.parent().unwrap_or(&root);
+        parent
+            .canonicalize()
+            .map_err(|e| RegistryError::Io(parent.display().to_string(), e))?
+            .join(file_name)
+    } else {
+        joined
+            .canonicalize()
+            .map_err(|e| RegistryError::Io(path.to_string(), e))?
+    };
+
+    if !roots.iter().any(|root| resolved.starts_with(root)) {
+        return Err(RegistryError::NotAllowed(path.to_string()));
+    }
+    if resolved.is_dir() {
+        return Err(RegistryError::Invalid(format!("Not a file: {}", path)));
+    }
+    Ok(resolved)
+}
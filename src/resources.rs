@@ -0,0 +1,369 @@
+/*!
+ * # 数据库结构资源
+ *
+ * 本模块将数据库结构以MCP资源的形式暴露给客户端，客户端可以直接把这些资源附加到上下文中，
+ * 而不必消耗工具调用：
+ *
+ * - `sqlite://schema`：所有表、视图、索引和触发器的定义
+ * - `sqlite://table/{name}/schema`：表的列、索引和外键
+ * - `sqlite://table/{name}/sample`：表的前几行数据
+ * - `sqlite://view/{name}/definition`：视图的定义语句
+ *
+ * 以上URI指向`main`数据库。启动时附加的数据库使用带`db/{database}/`前缀的URI，
+ * 如`sqlite://db/analytics/schema`和`sqlite://db/analytics/table/events/sample`。
+ *
+ * 资源列表在每次调用时从`sqlite_master`重新计算，新建的表会立即出现。
+ * URI中的数据库名、表名和视图名经过百分号编码。
+ */
+
+use mcp_core_fishcode2025::{handler::ResourceError, Resource};
+use rusqlite::Connection;
+use serde_json::{json, Value};
+
+use crate::masking::{MaskPlan, Masking};
+use crate::schema::{self, quote_identifier};
+use crate::server::extract_row_values;
+
+/// 资源URI的前缀
+const URI_PREFIX: &str = "sqlite://";
+
+/// 不带数据库前缀的资源URI所属的数据库
+const MAIN_DATABASE: &str = "main";
+
+/// `sqlite://table/{name}/sample`资源返回的最大行数
+pub const DEFAULT_SAMPLE_ROWS: usize = 10;
+
+/// 数据库结构资源
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum SchemaResource {
+    /// 整个数据库的结构
+    Schema,
+    /// 表的列、索引和外键
+    TableSchema(String),
+    /// 表的前几行数据
+    TableSample(String),
+    /// 视图的定义语句
+    ViewDefinition(String),
+}
+
+/// 属于某个数据库的结构资源
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct DatabaseResource {
+    /// 资源所在的数据库
+    database: String,
+    resource: SchemaResource,
+}
+
+impl DatabaseResource {
+    /// 解析资源URI，不带`db/{database}/`前缀的URI属于`main`数据库
+    fn parse(uri: &str) -> Option<Self> {
+        let path = uri.strip_prefix(URI_PREFIX)?;
+        let (database, path) = match path.strip_prefix("db/") {
+            Some(rest) => {
+                let (database, path) = rest.split_once('/')?;
+                (decode_component(database)?, path)
+            }
+            None => (MAIN_DATABASE.to_string(), path),
+        };
+        if path == "schema" {
+            return Some(Self::new(database, SchemaResource::Schema));
+        }
+
+        let segments: Vec<&str> = path.split('/').collect();
+        let resource = match segments.as_slice() {
+            ["table", name, "schema"] => SchemaResource::TableSchema(decode_component(name)?),
+            ["table", name, "sample"] => SchemaResource::TableSample(decode_component(name)?),
+            ["view", name, "definition"] => SchemaResource::ViewDefinition(decode_component(name)?),
+            _ => return None,
+        };
+        Some(Self::new(database, resource))
+    }
+
+    fn new(database: String, resource: SchemaResource) -> Self {
+        Self { database, resource }
+    }
+
+    fn uri(&self) -> String {
+        let prefix = if self.database == MAIN_DATABASE {
+            URI_PREFIX.to_string()
+        } else {
+            format!("{}db/{}/", URI_PREFIX, encode_component(&self.database))
+        };
+        match &self.resource {
+            SchemaResource::Schema => format!("{}schema", prefix),
+            SchemaResource::TableSchema(name) => {
+                format!("{}table/{}/schema", prefix, encode_component(name))
+            }
+            SchemaResource::TableSample(name) => {
+                format!("{}table/{}/sample", prefix, encode_component(name))
+            }
+            SchemaResource::ViewDefinition(name) => {
+                format!("{}view/{}/definition", prefix, encode_component(name))
+            }
+        }
+    }
+
+    fn to_resource(&self) -> Option<Resource> {
+        // 附加数据库中的对象名带上数据库名，与main数据库中的同名对象区分
+        let qualified = |name: &str| {
+            if self.database == MAIN_DATABASE {
+                name.to_string()
+            } else {
+                format!("{}.{}", self.database, name)
+            }
+        };
+        let (name, description) = match &self.resource {
+            SchemaResource::Schema if self.database == MAIN_DATABASE => {
+                ("schema".to_string(), "数据库中所有对象的定义".to_string())
+            }
+            SchemaResource::Schema => (
+                format!("{} schema", self.database),
+                format!("数据库{}中所有对象的定义", self.database),
+            ),
+            SchemaResource::TableSchema(name) => (
+                format!("{} schema", qualified(name)),
+                format!("表{}的列、索引和外键", qualified(name)),
+            ),
+            SchemaResource::TableSample(name) => (
+                format!("{} sample", qualified(name)),
+                format!("表{}的前{}行数据", qualified(name), DEFAULT_SAMPLE_ROWS),
+            ),
+            SchemaResource::ViewDefinition(name) => (
+                format!("{} definition", qualified(name)),
+                format!("视图{}的定义语句", qualified(name)),
+            ),
+        };
+
+        let mut resource =
+            Resource::with_uri(self.uri(), name, 0.0, Some("text".to_string())).ok()?;
+        resource.description = Some(description);
+        Some(resource)
+    }
+}
+
+/// 根据`sqlite_master`列出当前可用的资源
+///
+/// # 参数
+///
+/// * `conn` - SQLite数据库连接
+///
+/// # 返回值
+///
+/// 为`main`和每个附加的数据库返回整个数据库的结构资源，以及每个表的结构和样本资源、每个视图的定义资源；
+/// 临时数据库属于单个连接，不列出
+pub fn list_resources(conn: &Connection) -> rusqlite::Result<Vec<Resource>> {
+    let mut stmt =
+        conn.prepare("SELECT name FROM pragma_database_list WHERE name <> 'temp' ORDER BY seq")?;
+    let databases = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let mut resources = Vec::new();
+    for database in databases {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT type, name FROM {}.sqlite_master \
+             WHERE type IN ('table', 'view') AND name NOT LIKE 'sqlite\\_%' ESCAPE '\\' \
+             ORDER BY type, name",
+            quote_identifier(&database)
+        ))?;
+        let objects = stmt
+            .query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        resources.push(DatabaseResource::new(
+            database.clone(),
+            SchemaResource::Schema,
+        ));
+        for (kind, name) in objects {
+            if kind == "view" {
+                resources.push(DatabaseResource::new(
+                    database.clone(),
+                    SchemaResource::ViewDefinition(name),
+                ));
+            } else {
+                resources.push(DatabaseResource::new(
+                    database.clone(),
+                    SchemaResource::TableSchema(name.clone()),
+                ));
+                resources.push(DatabaseResource::new(
+                    database.clone(),
+                    SchemaResource::TableSample(name),
+                ));
+            }
+        }
+    }
+
+    Ok(resources
+        .iter()
+        .filter_map(DatabaseResource::to_resource)
+        .collect())
+}
+
+/// 读取资源内容
+///
+/// # 参数
+///
+/// * `conn` - SQLite数据库连接
+/// * `uri` - 资源URI
+/// * `masking` - 应用于表样本数据的脱敏配置
+///
+/// # 返回值
+///
+/// 成功时返回资源内容的JSON文本；URI无法识别或对象不存在时返回`ResourceError::NotFound`
+pub fn read_resource(
+    conn: &Connection,
+    uri: &str,
+    masking: Option<&Masking>,
+) -> Result<String, ResourceError> {
+    let DatabaseResource { database, resource } = DatabaseResource::parse(uri)
+        .ok_or_else(|| ResourceError::NotFound(format!("Unknown resource: {}", uri)))?;
+
+    let database = database.as_str();
+    let content = match &resource {
+        SchemaResource::Schema => read_schema(conn, database),
+        SchemaResource::TableSchema(name) => read_table_schema(conn, database, name),
+        SchemaResource::TableSample(name) => read_table_sample(conn, database, name, masking),
+        SchemaResource::ViewDefinition(name) => read_view_definition(conn, database, name),
+    }
+    .map_err(|e| ResourceError::ExecutionError(format!("Failed to read resource: {}", e)))?
+    .ok_or_else(|| ResourceError::NotFound(format!("Resource not found: {}", uri)))?;
+
+    Ok(serde_json::to_string(&content).unwrap_or_default())
+}
+
+fn read_schema(conn: &Connection, database: &str) -> rusqlite::Result<Option<Value>> {
+    let exists: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM pragma_database_list WHERE name = ?1 COLLATE NOCASE)",
+        [database],
+        |row| row.get(0),
+    )?;
+    if !exists {
+        return Ok(None);
+    }
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT type, name, tbl_name, sql FROM {}.sqlite_master \
+         WHERE sql IS NOT NULL AND name NOT LIKE 'sqlite\\_%' ESCAPE '\\' \
+         ORDER BY CASE type WHEN 'table' THEN 0 WHEN 'view' THEN 1 WHEN 'index' THEN 2 ELSE 3 END, name",
+        quote_identifier(database)
+    ))?;
+    let objects = stmt
+        .query_map([], |row| {
+            Ok(json!({
+                "type": row.get::<_, String>(0)?,
+                "name": row.get::<_, String>(1)?,
+                "table": row.get::<_, String>(2)?,
+                "sql": row.get::<_, String>(3)?,
+            }))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    Ok(Some(json!({ "objects": objects })))
+}
+
+fn read_table_schema(
+    conn: &Connection,
+    database: &str,
+    name: &str,
+) -> rusqlite::Result<Option<Value>> {
+    let mut table = match schema::describe_table(conn, Some(database), name)? {
+        Some(table) => table,
+        None => return Ok(None),
+    };
+    if table["type"] == "view" {
+        return Ok(None);
+    }
+
+    if let Some(indexes) = schema::list_indexes(conn, Some(database), name)? {
+        table["indexes"] = indexes["indexes"].clone();
+    }
+    if let Some(foreign_keys) = schema::list_foreign_keys(conn, Some(database), name)? {
+        table["foreign_keys"] = foreign_keys["foreign_keys"].clone();
+    }
+    Ok(Some(table))
+}
+
+fn read_table_sample(
+    conn: &Connection,
+    database: &str,
+    name: &str,
+    masking: Option<&Masking>,
+) -> rusqlite::Result<Option<Value>> {
+    let table = match schema::describe_table(conn, Some(database), name)? {
+        Some(table) if table["type"] != "view" => table,
+        _ => return Ok(None),
+    };
+    let table_name = table["name"].as_str().unwrap_or(name);
+
+    let sql = format!(
+        "SELECT * FROM {}.{} LIMIT {}",
+        quote_identifier(table["schema"].as_str().unwrap_or(database)),
+        quote_identifier(table_name),
+        DEFAULT_SAMPLE_ROWS
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let plan = MaskPlan::for_query(masking, conn, &sql)?;
+    let column_names: Vec<String> = stmt.column_names().iter().map(|s| s.to_string()).collect();
+
+    let mut rows = stmt.query([])?;
+    let mut result_rows = Vec::new();
+    while let Some(row) = rows.next()? {
+        result_rows.push(extract_row_values(row, &column_names, &plan));
+    }
+
+    Ok(Some(json!({
+        "table": table_name,
+        "columns": plan.columns(column_names),
+        "rows": result_rows,
+    })))
+}
+
+fn read_view_definition(
+    conn: &Connection,
+    database: &str,
+    name: &str,
+) -> rusqlite::Result<Option<Value>> {
+    let table = match schema::describe_table(conn, Some(database), name)? {
+        Some(table) if table["type"] == "view" => table,
+        _ => return Ok(None),
+    };
+
+    Ok(Some(json!({
+        "view": table["name"],
+        "sql": table["sql"],
+        "columns": table["columns"],
+    })))
+}
+
+/// 对URI路径段进行百分号编码，只保留RFC 3986中的非保留字符
+fn encode_component(s: &str) -> String {
+    let mut encoded = String::with_capacity(s.len());
+    for b in s.bytes() {
+        if b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_' | b'~') {
+            encoded.push(b as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", b));
+        }
+    }
+    encoded
+}
+
+/// 解码百分号编码的URI路径段
+fn decode_component(s: &str) -> Option<String> {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = s.get(i + 1..i + 3)?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
@@ -0,0 +1,471 @@
+/*!
+ * # 数据库结构内省
+ *
+ * 本模块基于SQLite的PRAGMA表值函数读取数据库结构，并将结果整理为结构化的JSON：
+ *
+ * - `pragma_database_list`：连接上打开的数据库，包括附加的数据库
+ * - `pragma_table_list`：表、视图、虚拟表及其所属的schema
+ * - `pragma_table_xinfo`：列定义，包括隐藏列和生成列
+ * - `pragma_index_list` / `pragma_index_xinfo`：索引及其列、唯一性、部分索引条件
+ * - `pragma_foreign_key_list`：外键及其`ON UPDATE`/`ON DELETE`动作
+ *
+ * 所有表名和schema名都以绑定参数的形式传入，不会拼接进SQL文本。
+ */
+
+use rusqlite::{Connection, OptionalExtension};
+use serde_json::{json, Value};
+
+/// 表的基本信息，对应`PRAGMA table_list`的一行
+struct TableEntry {
+    schema: String,
+    name: String,
+    kind: String,
+    ncol: i64,
+    without_rowid: bool,
+    strict: bool,
+}
+
+impl TableEntry {
+    fn to_json(&self) -> Value {
+        json!({
+            "schema": self.schema,
+            "name": self.name,
+            "type": self.kind,
+            "column_count": self.ncol,
+            "without_rowid": self.without_rowid,
+            "strict": self.strict,
+        })
+    }
+}
+
+/// 列出连接上打开的数据库
+///
+/// # 返回值
+///
+/// 返回`{"databases": [...]}`，每一项包含`name`、数据库文件路径`file`（内存和临时数据库为`null`）
+/// 以及不含内部表的`tables`数量
+///
+/// # 示例
+///
+/// ```
+/// use mcp_sqlite::schema::list_databases;
+/// use rusqlite::Connection;
+///
+/// let conn = Connection::open_in_memory().unwrap();
+/// conn.execute_batch("ATTACH ':memory:' AS analytics; CREATE TABLE analytics.events (id);")
+///     .unwrap();
+///
+/// let result = list_databases(&conn).unwrap();
+/// assert_eq!(result["databases"][0]["name"], "main");
+/// assert_eq!(result["databases"][1]["name"], "analytics");
+/// assert_eq!(result["databases"][1]["tables"], 1);
+/// ```
+pub fn list_databases(conn: &Connection) -> rusqlite::Result<Value> {
+    let mut stmt = conn.prepare(
+        "SELECT d.name, nullif(d.file, ''), \
+         (SELECT count(*) FROM pragma_table_list AS t \
+          WHERE t.schema = d.name AND t.name NOT LIKE 'sqlite\\_%' ESCAPE '\\') \
+         FROM pragma_database_list AS d ORDER BY d.seq",
+    )?;
+    let databases = stmt
+        .query_map([], |row| {
+            Ok(json!({
+                "name": row.get::<_, String>(0)?,
+                "file": row.get::<_, Option<String>>(1)?,
+                "tables": row.get::<_, i64>(2)?,
+            }))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    Ok(json!({ "databases": databases }))
+}
+
+/// 列出数据库中的表、视图和虚拟表
+///
+/// # 参数
+///
+/// * `conn` - SQLite数据库连接
+/// * `schema` - 只列出指定schema（如`main`、`temp`或附加数据库名）中的对象，`None`表示全部
+/// * `include_system` - 是否包含`sqlite_`开头的内部表
+///
+/// # 返回值
+///
+/// 返回`{"tables": [...]}`，每一项包含`schema`、`name`、`type`、`column_count`、
+/// `without_rowid`和`strict`字段
+pub fn list_tables(
+    conn: &Connection,
+    schema: Option<&str>,
+    include_system: bool,
+) -> rusqlite::Result<Value> {
+    let mut stmt = conn.prepare(
+        "SELECT schema, name, type, ncol, wr, strict FROM pragma_table_list \
+         WHERE (?1 IS NULL OR schema = ?1 COLLATE NOCASE) \
+         AND (?2 OR name NOT LIKE 'sqlite\\_%' ESCAPE '\\') \
+         ORDER BY schema, name",
+    )?;
+    let tables = stmt
+        .query_map((schema, include_system), table_entry_from_row)?
+        .map(|entry| entry.map(|e| e.to_json()))
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    Ok(json!({ "tables": tables }))
+}
+
+/// 描述一个表、视图或虚拟表的列定义
+///
+/// # 参数
+///
+/// * `conn` - SQLite数据库连接
+/// * `schema` - 表所在的schema，`None`表示按SQLite的默认查找顺序
+/// * `table` - 表名
+///
+/// # 返回值
+///
+/// 表不存在时返回`Ok(None)`，否则返回包含`columns`、`primary_key`和建表`sql`的JSON对象。
+/// 每个列包含类型、是否可空、默认值、主键序号以及隐藏列/生成列标记。
+pub fn describe_table(
+    conn: &Connection,
+    schema: Option<&str>,
+    table: &str,
+) -> rusqlite::Result<Option<Value>> {
+    let entry = match find_table(conn, schema, table)? {
+        Some(entry) => entry,
+        None => return Ok(None),
+    };
+
+    let mut stmt = conn.prepare(
+        "SELECT cid, name, type, \"notnull\", dflt_value, pk, hidden \
+         FROM pragma_table_xinfo(?1, ?2) ORDER BY cid",
+    )?;
+    let mut primary_key: Vec<(i64, String)> = Vec::new();
+    let columns = stmt
+        .query_map((&entry.name, &entry.schema), |row| {
+            let name: String = row.get(1)?;
+            let pk: i64 = row.get(5)?;
+            let hidden: i64 = row.get(6)?;
+            Ok((
+                name.clone(),
+                pk,
+                json!({
+                    "cid": row.get::<_, i64>(0)?,
+                    "name": name,
+                    "type": row.get::<_, String>(2)?,
+                    "nullable": row.get::<_, i64>(3)? == 0,
+                    "default": row.get::<_, Option<String>>(4)?,
+                    "pk": pk,
+                    "hidden": hidden == 1,
+                    "generated": match hidden {
+                        2 => Value::from("virtual"),
+                        3 => Value::from("stored"),
+                        _ => Value::Null,
+                    },
+                }),
+            ))
+        })?
+        .map(|col| {
+            col.map(|(name, pk, value)| {
+                if pk > 0 {
+                    primary_key.push((pk, name));
+                }
+                value
+            })
+        })
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    // 按主键序号排列复合主键的列
+    primary_key.sort();
+    let primary_key: Vec<String> = primary_key.into_iter().map(|(_, name)| name).collect();
+
+    let mut result = entry.to_json();
+    result["columns"] = Value::Array(columns);
+    result["primary_key"] = json!(primary_key);
+    result["sql"] = json!(object_sql(conn, &entry.schema, &entry.name)?);
+
+    Ok(Some(result))
+}
+
+/// 列出一个表上的索引
+///
+/// # 参数
+///
+/// * `conn` - SQLite数据库连接
+/// * `schema` - 表所在的schema，`None`表示按SQLite的默认查找顺序
+/// * `table` - 表名
+///
+/// # 返回值
+///
+/// 表不存在时返回`Ok(None)`，否则返回`{"indexes": [...]}`。每个索引包含唯一性、
+/// 来源（`c`为CREATE INDEX，`u`为UNIQUE约束，`pk`为主键）、部分索引条件以及键列。
+pub fn list_indexes(
+    conn: &Connection,
+    schema: Option<&str>,
+    table: &str,
+) -> rusqlite::Result<Option<Value>> {
+    let entry = match find_table(conn, schema, table)? {
+        Some(entry) => entry,
+        None => return Ok(None),
+    };
+
+    let mut stmt = conn.prepare(
+        "SELECT name, \"unique\", origin, partial FROM pragma_index_list(?1, ?2) ORDER BY seq",
+    )?;
+    let indexes = stmt
+        .query_map((&entry.name, &entry.schema), |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, i64>(1)? != 0,
+                row.get::<_, String>(2)?,
+                row.get::<_, i64>(3)? != 0,
+            ))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let mut result = Vec::with_capacity(indexes.len());
+    for (name, unique, origin, partial) in indexes {
+        let sql = object_sql(conn, &entry.schema, &name)?;
+        let predicate = if partial {
+            sql.as_deref().and_then(partial_index_predicate)
+        } else {
+            None
+        };
+
+        result.push(json!({
+            "name": name,
+            "unique": unique,
+            "origin": origin,
+            "partial": partial,
+            "where": predicate,
+            "columns": index_columns(conn, &entry.schema, &name)?,
+            "sql": sql,
+        }));
+    }
+
+    Ok(Some(json!({
+        "schema": entry.schema,
+        "table": entry.name,
+        "indexes": result,
+    })))
+}
+
+/// 列出一个表上的外键
+///
+/// # 参数
+///
+/// * `conn` - SQLite数据库连接
+/// * `schema` - 表所在的schema，`None`表示按SQLite的默认查找顺序
+/// * `table` - 表名
+///
+/// # 返回值
+///
+/// 表不存在时返回`Ok(None)`，否则返回`{"foreign_keys": [...]}`。复合外键的多个列
+/// 会合并为同一项，`from`与`to`按列顺序一一对应。
+pub fn list_foreign_keys(
+    conn: &Connection,
+    schema: Option<&str>,
+    table: &str,
+) -> rusqlite::Result<Option<Value>> {
+    let entry = match find_table(conn, schema, table)? {
+        Some(entry) => entry,
+        None => return Ok(None),
+    };
+
+    let mut stmt = conn.prepare(
+        "SELECT id, \"table\", \"from\", \"to\", on_update, on_delete, \"match\" \
+         FROM pragma_foreign_key_list(?1, ?2) ORDER BY id, seq",
+    )?;
+    let mut rows = stmt.query((&entry.name, &entry.schema))?;
+
+    let mut foreign_keys: Vec<Value> = Vec::new();
+    let mut current_id: Option<i64> = None;
+    while let Some(row) = rows.next()? {
+        let id: i64 = row.get(0)?;
+        let from: String = row.get(2)?;
+        // 引用目标表主键时`to`为NULL
+        let to: Option<String> = row.get(3)?;
+
+        if current_id != Some(id) {
+            current_id = Some(id);
+            foreign_keys.push(json!({
+                "id": id,
+                "table": row.get::<_, String>(1)?,
+                "from": [],
+                "to": [],
+                "on_update": row.get::<_, String>(4)?,
+                "on_delete": row.get::<_, String>(5)?,
+                "match": row.get::<_, String>(6)?,
+            }));
+        }
+
+        if let Some(fk) = foreign_keys.last_mut() {
+            if let Some(list) = fk["from"].as_array_mut() {
+                list.push(Value::String(from));
+            }
+            if let Some(list) = fk["to"].as_array_mut() {
+                list.push(to.map(Value::String).unwrap_or(Value::Null));
+            }
+        }
+    }
+
+    Ok(Some(json!({
+        "schema": entry.schema,
+        "table": entry.name,
+        "foreign_keys": foreign_keys,
+    })))
+}
+
+/// 按名称查找表，返回其所在schema等基本信息
+fn find_table(
+    conn: &Connection,
+    schema: Option<&str>,
+    table: &str,
+) -> rusqlite::Result<Option<TableEntry>> {
+    // temp优先，其次main，然后是按附加顺序排列的数据库，与SQLite解析未限定表名的顺序一致
+    conn.query_row(
+        "SELECT t.schema, t.name, t.type, t.ncol, t.wr, t.strict \
+         FROM pragma_table_list AS t JOIN pragma_database_list AS d ON d.name = t.schema \
+         WHERE t.name = ?1 COLLATE NOCASE AND (?2 IS NULL OR t.schema = ?2 COLLATE NOCASE) \
+         ORDER BY CASE t.schema WHEN 'temp' THEN -1 ELSE d.seq END LIMIT 1",
+        (table, schema),
+        table_entry_from_row,
+    )
+    .optional()
+}
+
+fn table_entry_from_row(row: &rusqlite::Row) -> rusqlite::Result<TableEntry> {
+    Ok(TableEntry {
+        schema: row.get(0)?,
+        name: row.get(1)?,
+        kind: row.get(2)?,
+        ncol: row.get(3)?,
+        without_rowid: row.get::<_, i64>(4)? != 0,
+        strict: row.get::<_, i64>(5)? != 0,
+    })
+}
+
+/// 读取索引的列，只保留键列（`key = 1`），表达式索引的列名为NULL
+fn index_columns(conn: &Connection, schema: &str, index: &str) -> rusqlite::Result<Vec<Value>> {
+    let mut stmt = conn.prepare(
+        "SELECT seqno, cid, name, \"desc\", coll FROM pragma_index_xinfo(?1, ?2) \
+         WHERE key = 1 ORDER BY seqno",
+    )?;
+    let columns = stmt
+        .query_map((index, schema), |row| {
+            let cid: i64 = row.get(1)?;
+            Ok(json!({
+                "seqno": row.get::<_, i64>(0)?,
+                "cid": cid,
+                "name": row.get::<_, Option<String>>(2)?,
+                "expression": cid == -2,
+                "desc": row.get::<_, i64>(3)? != 0,
+                "collation": row.get::<_, Option<String>>(4)?,
+            }))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(columns)
+}
+
+/// 读取对象在`sqlite_master`中的建表/建索引语句，自动创建的索引没有SQL
+pub(crate) fn object_sql(
+    conn: &Connection,
+    schema: &str,
+    name: &str,
+) -> rusqlite::Result<Option<String>> {
+    let sql = format!(
+        "SELECT sql FROM {}.sqlite_master WHERE name = ?1 COLLATE NOCASE",
+        quote_identifier(schema)
+    );
+    Ok(conn
+        .query_row(&sql, [name], |row| row.get::<_, Option<String>>(0))
+        .optional()?
+        .flatten())
+}
+
+/// 从`CREATE INDEX ... WHERE ...`语句中截取部分索引的条件
+fn partial_index_predicate(sql: &str) -> Option<String> {
+    let bytes = sql.as_bytes();
+    let mut depth = 0i32;
+    let mut i = 0;
+    // 跳过括号、引号和注释中的内容，避免把表达式索引或字符串中的WHERE误判为索引条件
+    while i < bytes.len() {
+        let end = match bytes[i] {
+            quote @ (b'\'' | b'"' | b'`' | b'[') => {
+                let close = if quote == b'[' { b']' } else { quote };
+                // 重复的引号是转义，相当于紧接着的另一段引号内容
+                find_byte(bytes, i + 1, close).map_or(bytes.len(), |j| j + 1)
+            }
+            b'-' if bytes[i + 1..].starts_with(b"-") => {
+                find_byte(bytes, i + 2, b'\n').map_or(bytes.len(), |j| j + 1)
+            }
+            b'/' if bytes[i + 1..].starts_with(b"*") => bytes[i + 2..]
+                .windows(2)
+                .position(|w| w == b"*/")
+                .map_or(bytes.len(), |j| i + 2 + j + 2),
+            b'(' => {
+                depth += 1;
+                i + 1
+            }
+            b')' => {
+                depth -= 1;
+                i + 1
+            }
+            _ if depth == 0 && is_keyword_at(bytes, i, b"WHERE") => {
+                return Some(sql[i + b"WHERE".len()..].trim().to_string());
+            }
+            _ => i + 1,
+        };
+        i = end;
+    }
+    None
+}
+
+/// `from`之后第一个`byte`的位置
+fn find_byte(bytes: &[u8], from: usize, byte: u8) -> Option<usize> {
+    bytes[from..]
+        .iter()
+        .position(|&b| b == byte)
+        .map(|j| from + j)
+}
+
+/// `i`处是否为独立的关键字`keyword`（大写），而不是标识符的一部分
+fn is_keyword_at(bytes: &[u8], i: usize, keyword: &[u8]) -> bool {
+    let is_identifier =
+        |b: &u8| b.is_ascii_alphanumeric() || *b == b'_' || *b == b'$' || *b >= 0x80;
+    bytes.len() >= i + keyword.len()
+        && bytes[i..i + keyword.len()].eq_ignore_ascii_case(keyword)
+        && !i.checked_sub(1).is_some_and(|j| is_identifier(&bytes[j]))
+        && !bytes.get(i + keyword.len()).is_some_and(is_identifier)
+}
+
+/// 用双引号转义SQL标识符
+pub(crate) fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::partial_index_predicate;
+
+    #[test]
+    fn partial_index_predicate_skips_quotes_and_parentheses() {
+        assert_eq!(
+            partial_index_predicate("CREATE INDEX \"索引\" ON t(x) WHERE x > 0").as_deref(),
+            Some("x > 0")
+        );
+        assert_eq!(
+            partial_index_predicate(
+                "CREATE INDEX \"where (\" ON t(lower(x)) WHERE name <> 'a) WHERE (' AND é = 1"
+            )
+            .as_deref(),
+            Some("name <> 'a) WHERE (' AND é = 1")
+        );
+        assert_eq!(
+            partial_index_predicate("CREATE INDEX i ON t([where]) /* WHERE */ -- WHERE\nWHERE y")
+                .as_deref(),
+            Some("y")
+        );
+        assert_eq!(
+            partial_index_predicate("CREATE INDEX i ON t(x, \"索引\", nowhere)"),
+            None
+        );
+    }
+}
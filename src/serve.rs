@@ -0,0 +1,335 @@
+/*!
+ * # 并发服务循环
+ *
+ * `mcp-server`提供的`Server::run`逐个处理请求并忽略所有通知，长时间运行的语句会阻塞后续请求，
+ * 客户端发送的`notifications/cancelled`也无法送达。本模块提供按行读取JSON-RPC消息的服务循环：
+ *
+ * - 每个请求在独立的任务中处理，响应按完成顺序写回
+ * - 收到`notifications/cancelled`时取消对应请求的[`CancelToken`]，正在执行的语句会被中断，
+ *   按照MCP规范，被取消的请求不再发送响应
+ *
+ * 消息的分发由[`Session`]完成，其他传输（如[`crate::sse`]）为每个客户端会话创建一个`Session`，
+ * 把收到的消息交给它，并从提供的通道中读取要发送的回复。
+ *
+ * # 示例
+ *
+ * ```no_run
+ * use mcp_sqlite::{serve::serve, SQLiteRouter};
+ * use tokio::io::{stdin, stdout};
+ *
+ * # async fn run() -> std::io::Result<()> {
+ * let router = SQLiteRouter::new(":memory:").unwrap();
+ * serve(router, stdin(), stdout()).await
+ * # }
+ * ```
+ */
+
+use std::{
+    collections::{hash_map::RandomState, HashMap},
+    future::Future,
+    hash::{BuildHasher, Hasher},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use mcp_core_fishcode2025::protocol::{
+    ErrorData, JsonRpcError, JsonRpcMessage, JsonRpcNotification, JsonRpcRequest, JsonRpcResponse,
+    INTERNAL_ERROR, PARSE_ERROR,
+};
+use mcp_server_fishcode2025::{router::RouterService, Router};
+use serde_json::Value;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader},
+    sync::mpsc,
+};
+use tower_service::Service;
+use tracing::{debug, info, warn};
+
+use crate::interrupt::{with_cancel_token, CancelToken};
+
+/// 读取缓冲区的容量，与`ByteTransport`保持一致，以便接收很大的请求
+const READ_BUFFER_CAPACITY: usize = 2 * 1024 * 1024;
+
+/// 正在处理的请求及其取消令牌
+type InFlight = Arc<Mutex<HashMap<u64, CancelToken>>>;
+
+/// 一个客户端会话
+///
+/// 会话解析收到的JSON-RPC消息并在独立的任务中处理请求，`notifications/cancelled`只能取消同一会话中的请求
+#[derive(Clone)]
+pub struct Session<T> {
+    router: T,
+    in_flight: InFlight,
+}
+
+impl<T> Session<T>
+where
+    T: Router + Clone + Send + Sync + 'static,
+{
+    /// 创建会话
+    pub fn new(router: T) -> Self {
+        Self {
+            router,
+            in_flight: InFlight::default(),
+        }
+    }
+
+    /// 处理客户端发送的一条JSON-RPC消息
+    ///
+    /// # 参数
+    ///
+    /// * `message` - JSON文本
+    /// * `tx` - 请求的响应和解析错误写入这个通道，处理完毕后任务释放各自持有的发送端
+    ///
+    /// # 返回值
+    ///
+    /// 消息是请求或者无法解析时返回`true`，即客户端会收到回复（请求被取消时除外）
+    pub fn handle(&self, message: &str, tx: &mpsc::UnboundedSender<JsonRpcMessage>) -> bool {
+        debug!(json = %message, "incoming message");
+
+        match serde_json::from_str(message) {
+            Ok(value) => self.handle_value(value, tx),
+            Err(e) => {
+                let error = parse_error(format!("Invalid JSON: {}", e));
+                send_error(tx, error);
+                true
+            }
+        }
+    }
+
+    /// 处理已经解析为JSON的消息，参数和返回值与[`Session::handle`]相同
+    pub fn handle_value(&self, value: Value, tx: &mpsc::UnboundedSender<JsonRpcMessage>) -> bool {
+        match parse_message(value) {
+            Ok(JsonRpcMessage::Request(request)) => {
+                spawn_request(
+                    self.router.clone(),
+                    request,
+                    self.in_flight.clone(),
+                    tx.clone(),
+                );
+                true
+            }
+            Ok(JsonRpcMessage::Notification(notification)) => {
+                handle_notification(&notification, &self.in_flight);
+                false
+            }
+            Ok(_) => {
+                // 服务器不发送请求，忽略响应和其他消息
+                false
+            }
+            Err(error) => {
+                send_error(tx, error);
+                true
+            }
+        }
+    }
+
+    /// 取消会话中所有正在处理的请求，用于客户端断开连接时
+    pub fn cancel_all(&self) {
+        for token in lock(&self.in_flight).values() {
+            token.cancel();
+        }
+    }
+}
+
+/// 在按行分隔的字节流上运行MCP服务器，直到输入结束
+///
+/// # 参数
+///
+/// * `router` - 处理请求的路由器
+/// * `reader` - 读取JSON-RPC消息的输入流
+/// * `writer` - 写入JSON-RPC消息的输出流
+///
+/// # 返回值
+///
+/// 输入结束且所有请求处理完毕后返回`Ok(())`；读写失败时返回I/O错误
+pub async fn serve<T, R, W>(router: T, reader: R, writer: W) -> std::io::Result<()>
+where
+    T: Router + Clone + Send + Sync + 'static,
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin + Send + 'static,
+{
+    serve_until(router, reader, writer, std::future::pending()).await
+}
+
+/// 与[`serve`]相同，但在`shutdown`完成时停止读取新的消息
+///
+/// 停止读取后，已经开始处理的请求会继续执行，它们的响应写出后才返回，用于平滑关闭。
+///
+/// # 参数
+///
+/// * `router` - 处理请求的路由器
+/// * `reader` - 读取JSON-RPC消息的输入流
+/// * `writer` - 写入JSON-RPC消息的输出流
+/// * `shutdown` - 完成时开始关闭
+pub async fn serve_until<T, R, W, S>(
+    router: T,
+    reader: R,
+    writer: W,
+    shutdown: S,
+) -> std::io::Result<()>
+where
+    T: Router + Clone + Send + Sync + 'static,
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin + Send + 'static,
+    S: Future<Output = ()>,
+{
+    let (tx, rx) = mpsc::unbounded_channel();
+    let writer_task = tokio::spawn(write_messages(writer, rx));
+    let session = Session::new(router);
+
+    info!("Server started");
+    let mut lines = BufReader::with_capacity(READ_BUFFER_CAPACITY, reader).lines();
+    tokio::pin!(shutdown);
+    loop {
+        let line = tokio::select! {
+            line = lines.next_line() => match line? {
+                Some(line) => line,
+                None => break,
+            },
+            () = &mut shutdown => {
+                info!("Shutting down, waiting for in-flight requests");
+                break;
+            }
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        session.handle(&line, &tx);
+    }
+
+    // 输入结束后等待所有请求处理完毕并写出响应
+    drop(tx);
+    writer_task.await.map_err(std::io::Error::other)?
+}
+
+/// 在独立的任务中处理请求，并把响应发送给写入任务
+fn spawn_request<T>(
+    router: T,
+    request: JsonRpcRequest,
+    in_flight: InFlight,
+    tx: mpsc::UnboundedSender<JsonRpcMessage>,
+) where
+    T: Router + Clone + Send + Sync + 'static,
+{
+    let id = request.id;
+    let token = CancelToken::default();
+    if let Some(id) = id {
+        lock(&in_flight).insert(id, token.clone());
+    }
+
+    tokio::spawn(async move {
+        let mut service = RouterService(router);
+        let response = match with_cancel_token(token.clone(), service.call(request)).await {
+            Ok(response) => response,
+            Err(e) => JsonRpcResponse {
+                jsonrpc: "2.0".to_string(),
+                id,
+                result: None,
+                error: Some(ErrorData {
+                    code: INTERNAL_ERROR,
+                    message: e.to_string(),
+                    data: None,
+                }),
+            },
+        };
+
+        if let Some(id) = id {
+            lock(&in_flight).remove(&id);
+        }
+        if token.is_cancelled() {
+            info!(request_id = ?id, "Request cancelled, dropping response");
+            return;
+        }
+        let _ = tx.send(JsonRpcMessage::Response(response));
+    });
+}
+
+/// 处理通知，目前只处理`notifications/cancelled`
+fn handle_notification(notification: &JsonRpcNotification, in_flight: &InFlight) {
+    if notification.method != "notifications/cancelled" {
+        return;
+    }
+
+    let request_id = notification
+        .params
+        .as_ref()
+        .and_then(|params| params.get("requestId"))
+        .and_then(Value::as_u64);
+    match request_id.and_then(|id| lock(in_flight).get(&id).cloned()) {
+        Some(token) => {
+            info!(request_id = ?request_id, "Cancelling request");
+            token.cancel();
+        }
+        None => debug!(request_id = ?request_id, "Cancellation for unknown request"),
+    }
+}
+
+/// 依次写出消息，每条消息占一行
+async fn write_messages<W>(
+    mut writer: W,
+    mut rx: mpsc::UnboundedReceiver<JsonRpcMessage>,
+) -> std::io::Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    while let Some(message) = rx.recv().await {
+        let json = serde_json::to_string(&message)?;
+        debug!(json = %json, "outgoing message");
+        writer.write_all(json.as_bytes()).await?;
+        writer.write_all(b"\n").await?;
+        writer.flush().await?;
+    }
+    Ok(())
+}
+
+/// 解析并验证JSON-RPC消息
+fn parse_message(value: Value) -> Result<JsonRpcMessage, ErrorData> {
+    if !value.is_object() {
+        return Err(parse_error("Message must be a JSON object".into()));
+    }
+    if value.get("jsonrpc") != Some(&Value::from("2.0")) {
+        return Err(parse_error("Missing or invalid jsonrpc version".into()));
+    }
+    serde_json::from_value(value).map_err(|e| parse_error(format!("Invalid message: {}", e)))
+}
+
+fn parse_error(message: String) -> ErrorData {
+    warn!(error = %message, "Invalid message");
+    ErrorData {
+        code: PARSE_ERROR,
+        message,
+        data: None,
+    }
+}
+
+fn send_error(tx: &mpsc::UnboundedSender<JsonRpcMessage>, error: ErrorData) {
+    let _ = tx.send(JsonRpcMessage::Error(JsonRpcError {
+        jsonrpc: "2.0".to_string(),
+        id: None,
+        error,
+    }));
+}
+
+/// 生成难以猜测的会话ID
+///
+/// `RandomState`的密钥来自操作系统的随机数，每次创建都不相同，结合计数器生成128位的ID
+pub(crate) fn new_session_id() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+    let mut id = String::with_capacity(32);
+    for _ in 0..2 {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u64(count);
+        id.push_str(&format!("{:016x}", hasher.finish()));
+    }
+    id
+}
+
+fn lock(in_flight: &InFlight) -> std::sync::MutexGuard<'_, HashMap<u64, CancelToken>> {
+    in_flight
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
@@ -4,7 +4,7 @@
  * 本模块实现了SQLite MCP服务器的核心功能，包括：
  *
  * - SQLite连接管理
- * - MCP方法实现（query, execute, executemany, executescript）
+ * - MCP方法实现（query, execute, executemany, executescript以及结构内省工具）
  * - 参数处理和结果格式化
  *
  * ## 主要组件
@@ -20,12 +20,38 @@
  * #### 查询参数
  *
  * - `query`：要执行的SQL查询
- * - `params`：（可选）绑定到查询的参数
+ * - `params`：（可选）绑定到查询的参数，可以是按位置绑定的数组，也可以是按名称绑定到`:name`、`@name`或`$name`占位符的对象
+ * - `limit`：（可选）最多返回的行数
+ * - `offset`：（可选）跳过的行数
+ * - `cursor`：（可选）为`true`时启用游标模式，只返回第一页（默认100行）
+ * - `format`：（可选）结果格式，可以是`objects`（默认）、`arrays`、`csv`、`tsv`或`markdown`，详见[`crate::format`]
+ * - `typed`：（可选）为`true`时每个值带有SQLite存储类型，`params`也按类型化表示解析，详见[`crate::value`]
  *
  * #### 查询返回值
  *
  * - `columns`：列名
- * - `rows`：查询返回的行
+ * - `rows`：查询返回的行（`objects`和`arrays`格式）
+ * - `text`：格式化的结果文本（`csv`、`tsv`和`markdown`格式）
+ * - `has_more`：是否还有剩余结果
+ * - `rows_fetched`：到本页为止读取的总行数（包括跳过的行）
+ * - `cursor_id`：游标模式下还有剩余结果时返回游标ID，否则为`null`
+ *
+ * ### `fetch`
+ *
+ * 从游标读取下一页结果，参数为`cursor_id`以及可选的`limit`和`format`，返回值与`query`相同。
+ * 结果读完后游标自动关闭，空闲超过[`RouterOptions::cursor_timeout`]的游标会失效。
+ * 游标只能用于只读语句，在自己的只读连接上保持执行中的语句，详见[`crate::cursor`]。
+ *
+ * ### `explain`
+ *
+ * 返回语句的查询计划树，参数为`query`、可选的`params`、`typed`和`bytecode`，语句本身不会执行。
+ * 计划中的全表扫描和临时B树排序被标记并汇总在`warnings`中，详见[`crate::explain`]。
+ *
+ * ### `suggest_indexes`
+ *
+ * 为`queries`中的一组查询推荐索引：在只包含结构和`sqlite_stat1`统计信息的内存副本中创建候选索引，
+ * 返回查询优化器会使用的`CREATE INDEX`语句和每个查询计划的变化。`test`为`true`时单独测试每个建议，
+ * 返回修改前后的计划树。数据库本身不会被修改，详见[`crate::advisor`]。
  *
  * ### `execute`
  *
@@ -34,7 +60,9 @@
  * #### 执行参数
  *
  * - `statement`：要执行的SQL语句
- * - `params`：（可选）绑定到语句的参数
+ * - `params`：（可选）绑定到语句的参数，数组或对象，与`query`相同
+ * - `typed`：（可选）为`true`时按类型化表示解析`params`
+ * - `dry_run`：（可选）为`true`时只预演，见“预演”
  *
  * #### 执行返回值
  *
@@ -48,7 +76,10 @@
  * #### 批量执行参数
  *
  * - `statement`：要执行的SQL语句
- * - `params_list`：绑定到语句的参数列表
+ * - `params_list`：绑定到语句的参数列表，每一项都是数组或对象
+ * - `atomic`：（可选）为`true`时在保存点中执行，任何一组参数失败都会撤销之前的写入
+ * - `typed`：（可选）为`true`时按类型化表示解析`params_list`
+ * - `dry_run`：（可选）为`true`时只预演，见“预演”
  *
  * #### 批量执行返回值
  *
@@ -61,15 +92,156 @@
  * #### 脚本参数
  *
  * - `script`：要执行的SQL脚本
+ * - `dry_run`：（可选）为`true`时只预演，见“预演”
  *
  * #### 脚本返回值
  *
  * - `rowcount`：受影响的行数
+ *
+ * ### `import_csv`
+ *
+ * 把CSV数据批量插入表中，所有行在一个保存点中插入。
+ *
+ * #### 导入参数
+ *
+ * - `table`：目标表，不存在时按推断的列类型创建
+ * - `csv`或`path`：CSV文本，或者`--import-dir`目录中的CSV文件
+ * - `delimiter`、`header`、`null_tokens`：（可选）分隔符（默认为`,`）、首行是否为表头（默认为`true`）和空值标记（默认为`[""]`）
+ * - `on_conflict`：（可选）`fail`（默认，撤销整个导入）、`ignore`或`replace`
+ * - `database`、`transaction_id`、`dry_run`：与`execute`相同
+ *
+ * #### 导入返回值
+ *
+ * - `rows_read`、`rowcount`、`ignored`、`rejected`：读取、插入、因冲突跳过和被拒绝的行数
+ * - `rejected_lines`：被拒绝的行号和原因，最多20行
+ *
+ * ### 预演
+ *
+ * `execute`、`executemany`和`executescript`接受`dry_run`参数。为`true`时语句在保存点中执行后回滚，
+ * 数据库保持不变，返回值在原有字段之外包含`"dry_run": true`、`changes`和`schema_changes`：每张被修改的表的插入、
+ * 更新和删除次数，被修改的行修改前后的值（每张表最多`preview_rows`行），以及修改数据库结构的动作。
+ * 详见[`crate::preview`]。
+ *
+ * ### 审批
+ *
+ * 通过[`RouterOptions::approval`]启用审批后，写入工具先预演写入。修改数据库结构、删除表中所有行
+ * 或修改的行数超过阈值的写入不会执行，而是返回`"approval_required": true`、`approval_token`、
+ * 需要审批的原因`reasons`和预览；用`confirm`工具提交`approval_token`后按原来的参数执行。
+ * 令牌只属于当前会话，只能使用一次并且会过期。启用审批时`query`不能执行修改数据库的语句。
+ * 详见[`crate::approval`]。
+ *
+ * ### `confirm`
+ *
+ * 确认需要审批的写入，参数为`approval_token`，返回值与被确认的写入工具相同。
+ *
+ * ### 超时与取消
+ *
+ * `query`、`fetch`、`explain`、`suggest_indexes`、`execute`、`executemany`和`executescript`接受可选的`timeout_ms`参数，
+ * 未提供时使用[`RouterOptions::statement_timeout`]。超时的语句被中断，调用返回
+ * `Statement timed out after N ms`错误；客户端通过`notifications/cancelled`取消的请求返回
+ * `Statement was cancelled by the client`错误。详见[`crate::interrupt`]。
+ *
+ * 所有SQL都在[`crate::worker`]提供的数据库工作线程上执行，不会阻塞异步运行时。
+ * 不携带`transaction_id`的只读`query`、`fetch`、`explain`、`suggest_indexes`和结构内省调用在只读连接池上并发执行，
+ * 其余调用在唯一的可写连接上执行，详见[`RouterOptions::readers`]。
+ *
+ * ### 事务
+ *
+ * `begin_transaction`开始一个跨调用保持打开的事务并返回`transaction_id`；`commit`和`rollback`结束事务，
+ * `savepoint`和`release`管理事务中的保存点，`rollback`也可以通过`savepoint`参数回滚到保存点。
+ * 事务打开期间，`execute`、`executemany`和`executescript`必须携带该事务的`transaction_id`。
+ * 事务空闲超过[`RouterOptions::transaction_timeout`]后会被自动回滚。
+ *
+ * ### `list_tables`
+ *
+ * 列出表、视图和虚拟表（包括附加数据库中的对象）。
+ *
+ * #### 列表参数
+ *
+ * - `database`：（可选）只列出指定数据库中的对象，`schema`是同义参数
+ * - `include_system`：（可选）是否包含`sqlite_`开头的内部表，默认为`false`
+ *
+ * ### `describe_table`
+ *
+ * 返回表的列定义：类型、是否可空、默认值、主键序号、隐藏列和生成列。
+ *
+ * ### `list_indexes`
+ *
+ * 返回表上的索引：唯一性、来源、部分索引条件和键列。
+ *
+ * ### `list_foreign_keys`
+ *
+ * 返回表上的外键：引用表、列对应关系以及`ON UPDATE`/`ON DELETE`动作。
+ *
+ * `describe_table`、`list_indexes`和`list_foreign_keys`都接受`table`参数和可选的`database`参数。
+ *
+ * ### `list_databases`
+ *
+ * 列出打开的数据库：名称、文件路径和表的数量。
+ *
+ * ## 多个数据库
+ *
+ * [`RouterOptions::databases`]中的数据库在启动时附加到所有连接上，SQL中用数据库名限定表名。
+ * 执行SQL的工具接受可选的`database`参数，语句只能读写该数据库（和临时数据库）中的表，
+ * 其他数据库中的表被授权回调拒绝，错误信息以`Denied by database scope`开头。
+ *
+ * 配置[`RouterOptions::open_roots`]后，`open_database`可以在运行时打开允许的目录中的数据库文件，
+ * `database`参数同样可以选择这些数据库，调用在该数据库自己的连接上执行，见[`crate::registry`]。
+ *
+ * ## 备份与恢复
+ *
+ * 配置[`RouterOptions::backup_dir`]后提供以下工具，备份文件只能位于该目录中，详见[`crate::backup`]：
+ *
+ * - `backup`：把数据库复制为目录中的文件，`method`为`backup`（在线备份API，默认）或`vacuum`（`VACUUM INTO`）
+ * - `restore`：用目录中的备份替换数据库的内容；启用审批时总是需要`confirm`确认。
+ *   访问策略没有对所有表允许`insert`、`delete`、`create`和`drop`时被隐藏并拒绝
+ * - `list_backups`：列出目录中的备份及其大小和修改时间
+ *
+ * 两者都接受可选的`database`参数，可以是附加的数据库或`open_database`打开的数据库。
+ * 有打开的事务时不能备份或恢复。
+ *
+ * ## 只读模式
+ *
+ * 通过[`RouterOptions::read_only`]启用只读模式后，数据库以只读方式打开，写入工具从工具列表中隐藏，
+ * 并且授权回调会拒绝`query`中的写入语句、`ATTACH`和修改设置的PRAGMA。违反策略的调用会返回
+ * 以`Denied by read-only policy`开头的错误信息。
+ *
+ * ## 访问策略
+ *
+ * 通过[`RouterOptions::policy`]可以指定更细粒度的允许/拒绝规则，例如只允许写入`staging_*`表、
+ * 禁止`DROP`和`ALTER`以及`load_extension`等函数，规则格式见[`crate::policy`]。违反规则的调用返回
+ * 以`Denied by access policy`开头的错误信息，其中包含拒绝该动作的规则名称。
+ *
+ * ## 审计日志
+ *
+ * 通过[`RouterOptions::audit`]可以记录每一次工具调用的SQL、参数、行数、耗时、错误和会话ID，
+ * 记录之间以哈希链相连，删除或修改记录都可以被检测出来，详见[`crate::audit`]。
+ *
+ * ## 资源
+ *
+ * 数据库结构同时以MCP资源的形式提供，详见[`crate::resources`]：
+ *
+ * - `sqlite://schema`
+ * - `sqlite://table/{name}/schema`
+ * - `sqlite://table/{name}/sample`
+ * - `sqlite://view/{name}/definition`
+ *
+ * 附加数据库的资源URI带有`db/{database}/`前缀。
  */
 
-use std::{future::Future, pin::Pin, sync::Arc};
+use std::{
+    collections::HashSet,
+    future::Future,
+    path::PathBuf,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc, Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
 
-use base64::{engine::general_purpose::STANDARD, Engine};
 use mcp_core_fishcode2025::{
     handler::{PromptError, ResourceError, ToolError},
     prompt::Prompt,
@@ -77,17 +249,280 @@ use mcp_core_fishcode2025::{
     Content, Resource, Tool,
 };
 use mcp_server_fishcode2025::router::CapabilitiesBuilder;
-use rusqlite::{Connection, Row, ToSql};
+use rusqlite::{
+    params, params_from_iter,
+    types::{Value as SqlValue, ValueRef},
+    Batch, Connection, DatabaseName, OpenFlags, Row, Statement,
+};
 use serde_json::{json, Value};
-use tokio::sync::Mutex;
-use tracing::debug;
+use tokio::runtime::RuntimeFlavor;
+use tracing::{debug, error};
+
+use crate::{
+    advisor::IndexAdvisor,
+    approval::{self, ApprovalRules, Approvals, PendingApproval},
+    audit::{AuditEvent, AuditLog},
+    backup::{self, BackupDirectory, BackupError, BackupMethod},
+    cursor::{self, Cursors, PageRequest, QueryPage},
+    explain,
+    format::OutputFormat,
+    import::{self, Conflict, RejectedLine},
+    interrupt::{self, CancelToken, WatchGuard, Watchdog},
+    masking::{MaskPlan, Masked, Masking},
+    policy::{self, DenialSlot, Policy},
+    preview::{self, transaction_keyword},
+    registry::{self, DatabaseRegistry, OpenedDatabase, RegistryError},
+    resources, schema,
+    transaction::{self, TransactionError, TransactionMode, Transactions},
+    value,
+    worker::DbWorker,
+};
+
+/// 修改数据库的工具，只读模式下不可用
+const WRITE_TOOLS: &[&str] = &[
+    "execute",
+    "executemany",
+    "executescript",
+    "confirm",
+    "restore",
+    "import_csv",
+];
+
+/// 只读连接池的默认大小
+pub const DEFAULT_READERS: usize = 4;
+
+/// 只读连接可以执行的工具，调用携带`transaction_id`时仍然使用写连接
+const READ_TOOLS: &[&str] = &[
+    "query",
+    "fetch",
+    "explain",
+    "suggest_indexes",
+    "list_tables",
+    "describe_table",
+    "list_indexes",
+    "list_foreign_keys",
+    "list_databases",
+];
+
+/// 管理运行时打开的数据库的工具，没有允许的目录时隐藏
+const REGISTRY_TOOLS: &[&str] = &["open_database", "close_database", "list_open_databases"];
+
+/// 备份与恢复工具，没有备份目录时隐藏
+const BACKUP_TOOLS: &[&str] = &["backup", "restore", "list_backups"];
+
+/// 可以在运行时打开的数据库上执行的工具，`restore`单独选择数据库的连接
+const OPENED_DATABASE_TOOLS: &[&str] = &[
+    "query",
+    "fetch",
+    "explain",
+    "suggest_indexes",
+    "execute",
+    "executemany",
+    "executescript",
+    "confirm",
+    "list_tables",
+    "describe_table",
+    "list_indexes",
+    "list_foreign_keys",
+    "backup",
+    "import_csv",
+];
+
+/// 用于生成共享内存数据库名称的计数器
+static MEMORY_DATABASES: AtomicU64 = AtomicU64::new(1);
+
+/// 用于生成会话ID的计数器
+static SESSIONS: AtomicU64 = AtomicU64::new(1);
+
+/// SQLite路由器的配置选项
+#[derive(Debug, Clone)]
+pub struct RouterOptions {
+    /// 只读模式
+    ///
+    /// 启用后以`SQLITE_OPEN_READ_ONLY`打开数据库，隐藏`execute`、`executemany`和`executescript`工具，
+    /// 并安装授权回调，禁止`query`执行写入语句、`ATTACH`和修改设置的PRAGMA
+    pub read_only: bool,
+    /// 显式事务的空闲超时时间，超时后事务被自动回滚
+    pub transaction_timeout: Duration,
+    /// 查询游标的空闲超时时间，超时后游标失效
+    pub cursor_timeout: Duration,
+    /// 执行SQL的工具的默认超时时间，`None`表示不限制，可以被`timeout_ms`参数覆盖
+    pub statement_timeout: Option<Duration>,
+    /// 只读连接池的大小，`0`表示所有调用都使用写连接
+    ///
+    /// 不属于显式事务的只读查询、结构内省和资源读取在只读连接上并发执行
+    pub readers: usize,
+    /// 是否为数据库文件自动启用WAL日志模式，使读取不会被写入阻塞
+    ///
+    /// WAL模式会持久地记录在数据库文件中；只读模式和内存数据库不受影响
+    pub wal: bool,
+    /// 访问策略，由授权回调在所有连接上执行，见[`crate::policy`]
+    pub policy: Option<Arc<Policy>>,
+    /// 数据脱敏配置，应用于查询结果和资源中的表数据，见[`crate::masking`]
+    pub masking: Option<Arc<Masking>>,
+    /// 审计日志，记录每一次工具调用，见[`crate::audit`]
+    pub audit: Option<Arc<AuditLog>>,
+    /// 写入审批规则，`None`表示不审批，见[`crate::approval`]
+    ///
+    /// 启用后破坏性的写入需要通过`confirm`工具确认，`query`不能执行修改数据库的语句
+    pub approval: Option<ApprovalRules>,
+    /// 启动时附加到所有连接上的数据库，工具通过`database`参数按名称选择
+    pub databases: Vec<AttachedDatabase>,
+    /// `open_database`工具可以打开的目录树，为空时隐藏`open_database`等工具，见[`crate::registry`]
+    pub open_roots: Vec<PathBuf>,
+    /// `backup`和`restore`工具使用的备份目录，`None`时隐藏这些工具，见[`crate::backup`]
+    pub backup_dir: Option<PathBuf>,
+    /// `import_csv`工具可以读取的目录树，为空时只能导入`csv`参数中的数据，见[`crate::import`]
+    pub import_roots: Vec<PathBuf>,
+}
+
+/// 启动时附加的命名数据库
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttachedDatabase {
+    /// 数据库名称，SQL中用它限定表名，如`analytics.events`
+    pub name: String,
+    /// 数据库文件路径，使用":memory:"表示内存数据库
+    pub path: String,
+}
+
+impl Default for RouterOptions {
+    fn default() -> Self {
+        Self {
+            read_only: false,
+            transaction_timeout: transaction::DEFAULT_IDLE_TIMEOUT,
+            cursor_timeout: cursor::DEFAULT_IDLE_TIMEOUT,
+            statement_timeout: None,
+            readers: DEFAULT_READERS,
+            wal: true,
+            policy: None,
+            masking: None,
+            audit: None,
+            approval: None,
+            databases: Vec::new(),
+            open_roots: Vec::new(),
+            backup_dir: None,
+            import_roots: Vec::new(),
+        }
+    }
+}
 
 /// SQLite MCP服务器路由器
 ///
 /// 负责处理MCP客户端请求，执行SQL操作，并返回结果
 pub struct SQLiteRouter {
-    /// SQLite数据库连接
-    conn: Arc<Mutex<Connection>>,
+    /// 持有可写连接的工作线程，修改数据库的调用和显式事务都在这里执行
+    writer: DbWorker,
+    /// 只读连接池，`None`表示所有调用都使用写连接
+    readers: Option<DbWorker>,
+    /// 路由器配置
+    options: Arc<RouterOptions>,
+    /// 被访问策略拒绝的最近一次动作
+    denials: DenialSlot,
+    /// 跨调用保持打开的显式事务
+    transactions: Transactions,
+    /// 分页读取查询结果的游标
+    cursors: Cursors,
+    /// 游标打开自己的只读连接时使用的数据库，`None`表示数据库是连接私有的内存数据库，不能打开游标
+    read_source: Option<Arc<ReadSource>>,
+    /// 等待确认的写入
+    approvals: Approvals,
+    /// 中断超时或被取消的语句
+    watchdog: Watchdog,
+    /// 会话的生命周期
+    scope: Arc<SessionScope>,
+    /// 运行时打开的数据库，所有会话共享
+    registry: DatabaseRegistry,
+    /// 在打开的数据库的连接上执行调用时为该数据库的名称
+    opened: Option<String>,
+}
+
+/// 只读连接上的工具调用结果
+enum ReadOutcome {
+    /// 调用已经执行
+    Done(Result<Value, ToolError>),
+    /// 调用需要在写连接上执行，交回调用参数
+    NeedsWriter(Value),
+}
+
+/// 游标线程执行的查询
+struct CursorStatement {
+    query: String,
+    bind_params: Value,
+    typed: bool,
+    /// 第一页之前跳过的行数
+    offset: u64,
+    database: Option<String>,
+}
+
+/// 在游标线程上从执行中的语句逐页读取结果
+struct CursorReader<'a, 'stmt> {
+    router: &'a SQLiteRouter,
+    rows: &'a mut rusqlite::Rows<'stmt>,
+    columns: Vec<String>,
+    column_count: usize,
+    typed: bool,
+    plan: &'a MaskPlan<'a>,
+    /// 为判断是否还有剩余结果而多读的一行，属于下一页
+    pending: Option<Vec<Value>>,
+}
+
+impl CursorReader<'_, '_> {
+    /// 读取下一行
+    fn next_row(&mut self) -> Result<Option<Vec<Value>>, ToolError> {
+        if let Some(row) = self.pending.take() {
+            return Ok(Some(row));
+        }
+        match self.rows.next() {
+            Ok(Some(row)) => Ok(Some(extract_row_cells(
+                row,
+                self.column_count,
+                self.typed,
+                self.plan,
+            ))),
+            Ok(None) => Ok(None),
+            Err(e) => Err(self.router.sql_error("Failed to fetch rows", e)),
+        }
+    }
+
+    /// 跳过`count`行
+    fn skip(&mut self, count: u64) -> Result<(), ToolError> {
+        for _ in 0..count {
+            if self.next_row()?.is_none() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// 读取最多`limit`行，再多读一行以判断是否还有剩余结果
+    fn next_page(&mut self, limit: u64) -> Result<QueryPage, ToolError> {
+        let mut rows = Vec::new();
+        while let Some(row) = self.next_row()? {
+            if rows.len() as u64 >= limit {
+                self.pending = Some(row);
+                break;
+            }
+            rows.push(row);
+        }
+        Ok(QueryPage {
+            columns: self.columns.clone(),
+            rows,
+            has_more: self.pending.is_some(),
+        })
+    }
+}
+
+/// 游标在自己的连接上读取的数据库
+#[derive(Debug)]
+enum ReadSource {
+    /// 启动时指定的数据库及其附加数据库
+    Main {
+        db_path: String,
+        databases: Vec<AttachedDatabase>,
+        shared_memory: bool,
+    },
+    /// 运行时打开的数据库
+    Opened { path: PathBuf, read_only: bool },
 }
 
 impl SQLiteRouter {
@@ -109,12 +544,251 @@ impl SQLiteRouter {
     /// let router = SQLiteRouter::new(":memory:").expect("创建路由器失败");
     /// ```
     pub fn new(db_path: &str) -> Result<Self, rusqlite::Error> {
-        let conn = Connection::open(db_path)?;
+        Self::with_options(db_path, RouterOptions::default())
+    }
+
+    /// 使用指定的配置创建SQLite MCP服务器路由器
+    ///
+    /// # 参数
+    ///
+    /// * `db_path` - SQLite数据库文件路径，使用":memory:"表示内存数据库
+    /// * `options` - 路由器配置
+    ///
+    /// # 返回值
+    ///
+    /// 成功时返回`SQLiteRouter`实例，失败时返回SQLite错误
+    ///
+    /// # 示例
+    ///
+    /// ```
+    /// use mcp_sqlite::server::{RouterOptions, SQLiteRouter};
+    ///
+    /// let options = RouterOptions {
+    ///     read_only: true,
+    ///     ..Default::default()
+    /// };
+    /// let router = SQLiteRouter::with_options(":memory:", options).expect("创建路由器失败");
+    /// ```
+    pub fn with_options(db_path: &str, options: RouterOptions) -> Result<Self, rusqlite::Error> {
+        let denials = DenialSlot::default();
+        let watchdog = Watchdog::default();
+
+        // 空路径表示每个连接私有的临时数据库，无法在连接之间共享
+        let reader_count = if db_path.is_empty() {
+            0
+        } else {
+            options.readers
+        };
+
+        // 连接池中的每个连接都要看到同一个内存数据库，因此使用命名的共享缓存内存数据库
+        let shared_memory = db_path == ":memory:" && reader_count > 0;
+        let db_path = if shared_memory {
+            shared_memory_uri()
+        } else {
+            db_path.to_string()
+        };
+        // 附加的内存数据库同样需要在连接之间共享
+        let databases: Vec<AttachedDatabase> = options
+            .databases
+            .iter()
+            .map(|database| AttachedDatabase {
+                name: database.name.clone(),
+                path: if reader_count > 0 && matches!(database.path.as_str(), "" | ":memory:") {
+                    shared_memory_uri()
+                } else {
+                    database.path.clone()
+                },
+            })
+            .collect();
+
+        // 附加数据库在安装授权回调之前完成，只读模式和访问策略可能禁止ATTACH
+        let conn = if options.read_only {
+            let conn = open_read_only(&db_path)?;
+            attach_databases(&conn, &databases)?;
+            conn
+        } else {
+            let conn = Connection::open(&db_path)?;
+            attach_databases(&conn, &databases)?;
+            // 内存数据库不支持WAL，SQLite会保持原来的日志模式
+            if options.wal {
+                let mode: String =
+                    conn.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get(0))?;
+                debug!("Journal mode: {}", mode);
+                for database in &databases {
+                    let mode: String = conn.pragma_update_and_check(
+                        Some(DatabaseName::Attached(&database.name)),
+                        "journal_mode",
+                        "WAL",
+                        |row| row.get(0),
+                    )?;
+                    debug!("Journal mode of {}: {}", database.name, mode);
+                }
+            }
+            conn
+        };
+        policy::install(
+            &conn,
+            options.read_only,
+            options.policy.clone(),
+            denials.clone(),
+        );
+        watchdog.install(&conn);
+
+        // 私有的内存数据库只能通过写连接访问，游标无法打开自己的连接
+        let private = |path: &str| matches!(path, "" | ":memory:");
+        let read_source = (!private(&db_path)
+            && !databases.iter().any(|database| private(&database.path)))
+        .then(|| {
+            Arc::new(ReadSource::Main {
+                db_path: db_path.clone(),
+                databases: databases.clone(),
+                shared_memory,
+            })
+        });
+        let readers = match &read_source {
+            Some(source) if reader_count > 0 => (0..reader_count)
+                .map(|_| open_reader(source, &options, &denials, &watchdog))
+                .collect::<Result<Vec<_>, rusqlite::Error>>()?,
+            _ => Vec::new(),
+        };
+
+        let transactions = Transactions::new(options.transaction_timeout);
+        let cursors = Cursors::new(options.cursor_timeout);
+        let approvals = Approvals::new(approval_timeout(&options));
+        let registry = DatabaseRegistry::new(
+            options.open_roots.clone(),
+            databases
+                .iter()
+                .map(|database| database.name.clone())
+                .collect(),
+        );
+
+        let writer = DbWorker::spawn("mcp-sqlite-writer", conn);
         Ok(Self {
-            conn: Arc::new(Mutex::new(conn)),
+            readers: (!readers.is_empty())
+                .then(|| DbWorker::spawn_pool("mcp-sqlite-reader", readers)),
+            options: Arc::new(options),
+            denials,
+            scope: Arc::new(SessionScope {
+                id: SESSIONS.fetch_add(1, Ordering::Relaxed),
+                transactions: transactions.clone(),
+                writer: writer.clone(),
+            }),
+            writer,
+            transactions,
+            cursors,
+            read_source,
+            approvals,
+            watchdog,
+            registry,
+            opened: None,
+        })
+    }
+
+    /// 为新的客户端会话创建路由器
+    ///
+    /// 返回的路由器与原路由器共享数据库连接和配置，但拥有独立的显式事务、游标和审批令牌：
+    /// 会话只能使用和结束自己开始的事务，其他会话在该事务结束前不能使用写连接。
+    /// 会话的所有路由器副本都被释放后，会话仍然打开的事务会被回滚。
+    ///
+    /// # 示例
+    ///
+    /// ```
+    /// use mcp_sqlite::server::SQLiteRouter;
+    ///
+    /// let router = SQLiteRouter::new(":memory:").expect("创建路由器失败");
+    /// let first = router.session();
+    /// let second = router.session();
+    /// ```
+    pub fn session(&self) -> Self {
+        let transactions = self.transactions.for_session();
+        Self {
+            writer: self.writer.clone(),
+            readers: self.readers.clone(),
+            options: Arc::clone(&self.options),
+            denials: self.denials.clone(),
+            cursors: Cursors::new(self.options.cursor_timeout),
+            read_source: self.read_source.clone(),
+            approvals: Approvals::new(approval_timeout(&self.options)),
+            watchdog: self.watchdog.clone(),
+            scope: Arc::new(SessionScope {
+                id: SESSIONS.fetch_add(1, Ordering::Relaxed),
+                transactions: transactions.clone(),
+                writer: self.writer.clone(),
+            }),
+            transactions,
+            registry: self.registry.clone(),
+            opened: None,
+        }
+    }
+
+    /// 会话ID，在进程内唯一，审计日志用它区分客户端
+    ///
+    /// # 示例
+    ///
+    /// ```
+    /// use mcp_sqlite::server::SQLiteRouter;
+    ///
+    /// let router = SQLiteRouter::new(":memory:").expect("创建路由器失败");
+    /// assert_eq!(router.clone().session_id(), router.session_id());
+    /// assert_ne!(router.session().session_id(), router.session_id());
+    /// ```
+    pub fn session_id(&self) -> u64 {
+        self.scope.id
+    }
+
+    /// 将SQLite错误转换为工具错误
+    ///
+    /// 如果错误是由访问策略拒绝、超时或取消引起的，返回说明原因的错误信息
+    fn sql_error(&self, context: &str, e: rusqlite::Error) -> ToolError {
+        if let Some(interruption) = self.watchdog.interruption(&e) {
+            return ToolError::ExecutionError(interruption.to_string());
+        }
+        match self.denials.take() {
+            Some(denial) => ToolError::ExecutionError(denial.to_string()),
+            None => ToolError::ExecutionError(format!("{}: {}", context, e)),
+        }
+    }
+
+    /// 开始监视执行SQL的调用，超过`timeout_ms`参数或默认超时时间、或者请求被取消时中断语句
+    fn watch(&self, params: &Value) -> Result<WatchGuard<'_>, ToolError> {
+        Ok(self
+            .watchdog
+            .watch(self.statement_timeout(params)?, CancelToken::current()))
+    }
+
+    /// 调用的语句超时时间，`timeout_ms`为0或者未配置默认超时时为`None`
+    fn statement_timeout(&self, params: &Value) -> Result<Option<Duration>, ToolError> {
+        Ok(match optional_u64_param(params, "timeout_ms")? {
+            Some(0) => None,
+            Some(ms) => Some(Duration::from_millis(ms)),
+            None => self.options.statement_timeout,
         })
     }
 
+    /// 将事务错误转换为工具错误
+    fn transaction_error(&self, context: &str, e: TransactionError) -> ToolError {
+        match e {
+            TransactionError::Invalid(message) => ToolError::InvalidParameters(message),
+            TransactionError::Sql(e) => self.sql_error(context, e),
+        }
+    }
+
+    /// 检查调用能否在当前事务状态下访问数据库
+    ///
+    /// 有打开的事务时，修改数据库的调用必须通过`transaction_id`参数携带该事务的句柄
+    fn check_transaction(
+        &self,
+        conn: &Connection,
+        params: &Value,
+        write: bool,
+    ) -> Result<(), ToolError> {
+        let transaction_id = optional_str_param(params, "transaction_id")?;
+        self.transactions
+            .check_access(conn, transaction_id, write)
+            .map_err(|e| self.transaction_error("Transaction check failed", e))
+    }
+
     /// 执行SQL查询并返回结果
     ///
     /// # 参数
@@ -124,7 +798,23 @@ impl SQLiteRouter {
     /// # 返回值
     ///
     /// 成功时返回包含查询结果的JSON对象，失败时返回工具错误
-    async fn query(&self, params: Value) -> Result<Value, ToolError> {
+    fn query(&self, conn: &Connection, params: Value) -> Result<Value, ToolError> {
+        self.check_transaction(conn, &params, false)?;
+        // 启用审批时写入必须经过execute等工具的审批
+        if self.options.approval.is_some() {
+            if let Some(query) = params.get("query").and_then(Value::as_str) {
+                if !is_read_only_sql(conn, query) {
+                    return Err(ToolError::InvalidParameters(
+                        "query cannot modify the database when approval is required; use execute instead".into(),
+                    ));
+                }
+            }
+        }
+        self.read_query(conn, params)
+    }
+
+    /// 执行SQL查询但不检查事务状态，只读连接上的查询直接调用
+    fn read_query(&self, conn: &Connection, params: Value) -> Result<Value, ToolError> {
         // 获取查询参数
         let query = match params.get("query") {
             Some(Value::String(q)) => q,
@@ -138,121 +828,511 @@ impl SQLiteRouter {
         // 获取绑定参数
         let params_json = json!([]);
         let bind_params = params.get("params").unwrap_or(&params_json);
-        let bind_params = match bind_params {
-            Value::Array(arr) => arr,
-            _ => {
+        if !matches!(bind_params, Value::Array(_) | Value::Object(_)) {
+            return Err(ToolError::InvalidParameters(
+                "params must be an array or an object".into(),
+            ));
+        }
+
+        // 获取分页参数，游标模式下未指定limit时使用默认页大小
+        let use_cursor = optional_bool_param(&params, "cursor")?;
+        let offset = optional_u64_param(&params, "offset")?.unwrap_or(0);
+        let limit = match optional_u64_param(&params, "limit")? {
+            Some(0) => {
                 return Err(ToolError::InvalidParameters(
-                    "params must be an array".into(),
+                    "limit must be a positive integer".into(),
                 ))
             }
+            None if use_cursor => Some(cursor::DEFAULT_PAGE_SIZE),
+            limit => limit,
         };
 
-        // 执行查询
-        let conn = self.conn.lock().await;
+        let format = output_format_param(&params)?.unwrap_or_default();
+        let typed = typed_param(&params, format)?;
 
-        let mut stmt = match conn.prepare(query) {
-            Ok(stmt) => stmt,
-            Err(e) => {
-                return Err(ToolError::ExecutionError(format!(
-                    "Failed to prepare query: {}",
-                    e
-                )))
-            }
-        };
+        if use_cursor {
+            return self.open_cursor(conn, &params, query, bind_params, format, typed, offset);
+        }
 
-        // 将JSON参数转换为SQLite参数
-        let sql_params: Vec<Box<dyn ToSql>> =
-            bind_params.iter().map(|v| json_value_to_sql(v)).collect();
+        // 执行查询
+        let _watch = self.watch(&params)?;
 
-        let sql_params_refs: Vec<&dyn ToSql> = sql_params.iter().map(|p| p.as_ref()).collect();
+        let page = self.query_page(conn, query, bind_params, typed, offset, limit)?;
+        let rows_fetched = offset + page.rows.len() as u64;
+        Ok(page.to_json(format, rows_fetched))
+    }
 
-        // 先获取列名，避免借用冲突
-        let column_names: Vec<String> = {
-            let names = stmt.column_names();
-            names.iter().map(|s| s.to_string()).collect()
+    /// 以游标模式执行查询并返回第一页
+    ///
+    /// 查询在游标线程自己的只读连接上执行，结果还没有读完时线程保持执行中的语句，等待`fetch`的请求
+    #[allow(clippy::too_many_arguments)]
+    fn open_cursor(
+        &self,
+        conn: &Connection,
+        params: &Value,
+        query: &str,
+        bind_params: &Value,
+        format: OutputFormat,
+        typed: bool,
+        offset: u64,
+    ) -> Result<Value, ToolError> {
+        // 游标只能读取：写入语句会在游标线程上执行，而且只读连接无法执行
+        let read_only = conn
+            .prepare(query)
+            .map_err(|e| self.sql_error("Failed to prepare query", e))?
+            .readonly();
+        if !read_only {
+            return Err(ToolError::InvalidParameters(
+                "cursor can only be used with read-only queries".into(),
+            ));
+        }
+        if optional_str_param(params, "transaction_id")?.is_some() {
+            return Err(ToolError::InvalidParameters(
+                "cursor cannot be used inside a transaction because it reads on its own connection; use limit and offset instead".into(),
+            ));
+        }
+        let Some(source) = self.read_source.clone() else {
+            return Err(ToolError::InvalidParameters(
+                "cursor is not available for a private in-memory database; use limit and offset instead".into(),
+            ));
         };
+        if !self.cursors.has_capacity() {
+            return Err(ToolError::InvalidParameters(format!(
+                "Too many open cursors (at most {}); read existing cursors to the end or let them expire",
+                cursor::MAX_OPEN_CURSORS
+            )));
+        }
+        let limit = optional_u64_param(params, "limit")?.unwrap_or(cursor::DEFAULT_PAGE_SIZE);
+        let database = self
+            .local_database(database_param(params)?)
+            .map(str::to_string);
 
-        // 执行查询
-        let mut rows = match stmt.query(sql_params_refs.as_slice()) {
-            Ok(rows) => rows,
-            Err(e) => {
-                return Err(ToolError::ExecutionError(format!(
-                    "Failed to execute query: {}",
-                    e
-                )))
-            }
+        let cursor_conn = open_reader(&source, &self.options, &self.denials, &self.watchdog)
+            .map_err(|e| self.sql_error("Failed to open cursor connection", e))?;
+        let (reply, response) = mpsc::sync_channel(1);
+        let first = PageRequest {
+            limit,
+            timeout: self.statement_timeout(params)?,
+            token: CancelToken::current(),
+            reply,
         };
+        let (pages, requests) = mpsc::channel();
+        let router = self.clone();
+        let statement = CursorStatement {
+            query: query.to_string(),
+            bind_params: bind_params.clone(),
+            typed,
+            offset,
+            database: database.clone(),
+        };
+        thread::Builder::new()
+            .name("mcp-sqlite-cursor".to_string())
+            .spawn(move || router.serve_cursor(cursor_conn, statement, first, requests))
+            .map_err(|e| ToolError::ExecutionError(format!("Failed to start cursor: {}", e)))?;
 
-        // 获取结果行
-        let mut result_rows = Vec::new();
-        while let Ok(Some(row)) = rows.next() {
-            let row_values = extract_row_values(row, &column_names);
-            result_rows.push(row_values);
-        }
+        let page = response
+            .recv()
+            .map_err(|_| ToolError::ExecutionError("Cursor stopped unexpectedly".into()))??;
+        let rows_fetched = offset + page.rows.len() as u64;
 
-        Ok(json!({
-            "columns": column_names,
-            "rows": result_rows,
-        }))
+        // 结果已经读完时不再登记游标，线程随之结束
+        let cursor_id = page.has_more.then(|| {
+            self.cursors
+                .open(query, rows_fetched, format, typed, database, pages)
+        });
+
+        let mut result = page.to_json(format, rows_fetched);
+        result["cursor_id"] = json!(cursor_id);
+        Ok(result)
     }
 
-    /// 执行SQL语句
-    async fn execute(&self, params: Value) -> Result<Value, ToolError> {
-        // 获取语句参数
-        let statement = match params.get("statement") {
-            Some(Value::String(s)) => s,
+    /// 游标线程：执行查询，之后按请求逐页读取，直到结果读完、游标被关闭或者空闲超时
+    fn serve_cursor(
+        &self,
+        conn: Connection,
+        statement: CursorStatement,
+        first: PageRequest,
+        requests: mpsc::Receiver<PageRequest>,
+    ) {
+        // 语句可能在执行中被重新预编译，授权回调因此在整个游标期间按数据库范围检查
+        policy::with_database_scope(statement.database.as_deref(), || {
+            let prepared = {
+                let _watch = self.watchdog.watch(first.timeout, first.token.clone());
+                self.prepare_query(
+                    &conn,
+                    &statement.query,
+                    &statement.bind_params,
+                    statement.typed,
+                )
+                .and_then(|(stmt, sql_params)| {
+                    let plan = MaskPlan::for_query(
+                        self.options.masking.as_deref(),
+                        &conn,
+                        &statement.query,
+                    )
+                    .map_err(|e| self.sql_error("Failed to prepare query", e))?;
+                    Ok((stmt, sql_params, plan))
+                })
+            };
+            let (mut stmt, sql_params, plan) = match prepared {
+                Ok(prepared) => prepared,
+                Err(e) => {
+                    let _ = first.reply.send(Err(e));
+                    return;
+                }
+            };
+            let column_count = stmt.column_count();
+            let columns = plan.columns(stmt.column_names().iter().map(|s| s.to_string()).collect());
+            let mut rows = match stmt.query(params_from_iter(&sql_params)) {
+                Ok(rows) => rows,
+                Err(e) => {
+                    let _ = first
+                        .reply
+                        .send(Err(self.sql_error("Failed to execute query", e)));
+                    return;
+                }
+            };
+
+            let mut reader = CursorReader {
+                router: self,
+                rows: &mut rows,
+                columns,
+                column_count,
+                typed: statement.typed,
+                plan: &plan,
+                pending: None,
+            };
+            let mut request = first;
+            let mut skip = statement.offset;
+            loop {
+                let result = interrupt::with_thread_cancel_token(request.token.clone(), || {
+                    let _watch = self.watchdog.watch(request.timeout, request.token.clone());
+                    reader.skip(std::mem::take(&mut skip))?;
+                    reader.next_page(request.limit)
+                });
+                let done = !matches!(&result, Ok(page) if page.has_more);
+                let _ = request.reply.send(result);
+                if done {
+                    return;
+                }
+                request = match requests.recv_timeout(self.cursors.idle_timeout()) {
+                    Ok(request) => request,
+                    Err(_) => return,
+                };
+            }
+        });
+    }
+
+    /// 从游标读取下一页结果
+    fn fetch(&self, conn: &Connection, params: Value) -> Result<Value, ToolError> {
+        self.check_transaction(conn, &params, false)?;
+        self.read_fetch(conn, params)
+    }
+
+    /// 从游标读取下一页结果但不检查事务状态，只读连接上的读取直接调用
+    fn read_fetch(&self, _conn: &Connection, params: Value) -> Result<Value, ToolError> {
+        let cursor_id = match params.get("cursor_id") {
+            Some(Value::String(id)) => id,
             _ => {
                 return Err(ToolError::InvalidParameters(
-                    "Missing required parameter: statement".into(),
+                    "Missing required parameter: cursor_id".into(),
                 ))
             }
         };
-
-        // 获取绑定参数
-        let params_json = json!([]);
-        let bind_params = params.get("params").unwrap_or(&params_json);
-        let bind_params = match bind_params {
-            Value::Array(arr) => arr,
-            _ => {
+        let limit = match optional_u64_param(&params, "limit")? {
+            Some(0) => {
                 return Err(ToolError::InvalidParameters(
-                    "params must be an array".into(),
+                    "limit must be a positive integer".into(),
                 ))
             }
+            limit => limit.unwrap_or(cursor::DEFAULT_PAGE_SIZE),
         };
 
-        // 执行语句
-        let conn = self.conn.lock().await;
+        let expired =
+            || ToolError::InvalidParameters(format!("Unknown or expired cursor: {}", cursor_id));
+        let cursor = self.cursors.get(cursor_id).ok_or_else(expired)?;
+        let format = output_format_param(&params)?.unwrap_or(cursor.format);
+        if cursor.typed && format.is_text() {
+            return Err(typed_text_format_error());
+        }
 
-        // 将JSON参数转换为SQLite参数
-        let sql_params: Vec<Box<dyn ToSql>> =
-            bind_params.iter().map(|v| json_value_to_sql(v)).collect();
+        // 游标线程已经因空闲超时或出错结束时，游标同样视为过期
+        let (reply, response) = mpsc::sync_channel(1);
+        let request = PageRequest {
+            limit,
+            timeout: self.statement_timeout(&params)?,
+            token: CancelToken::current(),
+            reply,
+        };
+        let page = match cursor
+            .pages
+            .send(request)
+            .ok()
+            .and_then(|_| response.recv().ok())
+        {
+            Some(Ok(page)) => page,
+            Some(Err(e)) => {
+                self.cursors.close(cursor_id);
+                return Err(e);
+            }
+            None => {
+                self.cursors.close(cursor_id);
+                return Err(expired());
+            }
+        };
+        let rows_fetched = cursor.position + page.rows.len() as u64;
+
+        if page.has_more {
+            self.cursors.advance(cursor_id, rows_fetched);
+        } else {
+            self.cursors.close(cursor_id);
+        }
 
-        let sql_params_refs: Vec<&dyn ToSql> = sql_params.iter().map(|p| p.as_ref()).collect();
+        let mut result = page.to_json(format, rows_fetched);
+        result["cursor_id"] = json!(cursor_id);
 
-        // 执行语句
-        let result = conn.execute(statement, sql_params_refs.as_slice());
+        Ok(result)
+    }
 
-        match result {
-            Ok(rows_affected) => {
-                // 获取最后插入的行ID
-                let last_insert_id = conn.last_insert_rowid();
+    /// 预编译查询并把JSON参数转换为SQLite参数
+    fn prepare_query<'c>(
+        &self,
+        conn: &'c Connection,
+        query: &str,
+        bind_params: &Value,
+        typed: bool,
+    ) -> Result<(Statement<'c>, Vec<SqlValue>), ToolError> {
+        let stmt = match conn.prepare(query) {
+            Ok(stmt) => stmt,
+            Err(e) => return Err(self.sql_error("Failed to prepare query", e)),
+        };
 
-                Ok(json!({
-                    "rowcount": rows_affected,
-                    "lastrowid": last_insert_id,
-                }))
-            }
-            Err(e) => Err(ToolError::ExecutionError(format!(
-                "Failed to execute statement: {}",
-                e
-            ))),
-        }
+        // 将JSON参数转换为SQLite参数
+        let sql_params = resolve_params(&stmt, bind_params, typed, "params")?;
+        Ok((stmt, sql_params))
     }
 
-    /// 执行多个SQL语句
-    async fn executemany(&self, params: Value) -> Result<Value, ToolError> {
-        // 获取语句参数
-        let statement = match params.get("statement") {
+    /// 返回查询计划，`bytecode`参数为`true`时同时返回虚拟机指令
+    ///
+    /// 与`query`一样预编译和绑定参数，但语句本身不会执行
+    fn explain(&self, conn: &Connection, params: Value) -> Result<Value, ToolError> {
+        let query = match params.get("query") {
+            Some(Value::String(q)) => q,
+            _ => {
+                return Err(ToolError::InvalidParameters(
+                    "Missing required parameter: query".into(),
+                ))
+            }
+        };
+
+        let params_json = json!([]);
+        let bind_params = params.get("params").unwrap_or(&params_json);
+        if !matches!(bind_params, Value::Array(_) | Value::Object(_)) {
+            return Err(ToolError::InvalidParameters(
+                "params must be an array or an object".into(),
+            ));
+        }
+        let typed = optional_bool_param(&params, "typed")?;
+        let bytecode = optional_bool_param(&params, "bytecode")?;
+
+        let _watch = self.watch(&params)?;
+
+        let (mut stmt, sql_params) = self.prepare_query(
+            conn,
+            &format!("EXPLAIN QUERY PLAN {}", query),
+            bind_params,
+            typed,
+        )?;
+        let steps = stmt
+            .query(params_from_iter(&sql_params))
+            .and_then(explain::read_plan)
+            .map_err(|e| self.sql_error("Failed to explain query", e))?;
+
+        let mut result = json!({
+            "plan": explain::plan_tree(&steps),
+            "warnings": explain::plan_warnings(&steps),
+        });
+        if bytecode {
+            let (mut stmt, sql_params) =
+                self.prepare_query(conn, &format!("EXPLAIN {}", query), bind_params, typed)?;
+            let instructions = stmt
+                .query(params_from_iter(&sql_params))
+                .and_then(explain::read_bytecode)
+                .map_err(|e| self.sql_error("Failed to explain query", e))?;
+            result["bytecode"] = json!(instructions);
+        }
+        Ok(result)
+    }
+
+    /// 根据查询计划推荐索引
+    ///
+    /// 查询先在当前连接上预编译，使访问策略同样作用于被分析的查询，之后的分析在内存中的结构副本上进行
+    fn suggest_indexes(&self, conn: &Connection, params: Value) -> Result<Value, ToolError> {
+        let queries: Vec<&str> = match params.get("queries") {
+            Some(Value::Array(queries)) if !queries.is_empty() => queries
+                .iter()
+                .map(|query| {
+                    query.as_str().ok_or_else(|| {
+                        ToolError::InvalidParameters("queries must contain strings".into())
+                    })
+                })
+                .collect::<Result<_, _>>()?,
+            _ => {
+                return Err(ToolError::InvalidParameters(
+                    "Missing required parameter: queries".into(),
+                ))
+            }
+        };
+        let test = optional_bool_param(&params, "test")?;
+
+        let _watch = self.watch(&params)?;
+
+        for (i, query) in queries.iter().enumerate() {
+            if let Err(e) = conn.prepare(&format!("EXPLAIN QUERY PLAN {}", query)) {
+                return Err(self.sql_error(&format!("Failed to prepare queries[{}]", i), e));
+            }
+        }
+        // 读取结构的内部查询不受数据库范围限制，查询已经在范围内预编译过
+        let database = self
+            .local_database(database_param(&params)?)
+            .unwrap_or("main");
+        policy::with_database_scope(None, || IndexAdvisor::for_database(conn, database))
+            .and_then(|advisor| advisor.suggest(&queries, test))
+            .map_err(|e| self.sql_error("Failed to suggest indexes", e))
+    }
+
+    /// 执行查询并读取一页结果
+    ///
+    /// 跳过前`offset`行后最多读取`limit`行，再多读一行以判断是否还有剩余结果。
+    /// `limit`为`None`时读取所有剩余行。
+    fn query_page(
+        &self,
+        conn: &Connection,
+        query: &str,
+        bind_params: &Value,
+        typed: bool,
+        offset: u64,
+        limit: Option<u64>,
+    ) -> Result<QueryPage, ToolError> {
+        let (mut stmt, sql_params) = self.prepare_query(conn, query, bind_params, typed)?;
+
+        let plan = MaskPlan::for_query(self.options.masking.as_deref(), conn, query)
+            .map_err(|e| self.sql_error("Failed to prepare query", e))?;
+
+        // 先获取列名，避免借用冲突
+        let column_count = stmt.column_count();
+        let column_names: Vec<String> = {
+            let names = stmt.column_names();
+            plan.columns(names.iter().map(|s| s.to_string()).collect())
+        };
+
+        // 执行查询
+        let mut rows = match stmt.query(params_from_iter(&sql_params)) {
+            Ok(rows) => rows,
+            Err(e) => return Err(self.sql_error("Failed to execute query", e)),
+        };
+
+        // 跳过偏移量之前的行
+        let mut skipped = 0;
+        while skipped < offset {
+            match rows.next() {
+                Ok(Some(_)) => skipped += 1,
+                Ok(None) => break,
+                Err(e) => return Err(self.sql_error("Failed to fetch rows", e)),
+            }
+        }
+
+        // 获取结果行
+        let mut result_rows = Vec::new();
+        let mut has_more = false;
+        loop {
+            let row = match rows.next() {
+                Ok(Some(row)) => row,
+                Ok(None) => break,
+                Err(e) => return Err(self.sql_error("Failed to fetch rows", e)),
+            };
+            if limit.is_some_and(|limit| result_rows.len() as u64 >= limit) {
+                has_more = true;
+                break;
+            }
+            result_rows.push(extract_row_cells(row, column_count, typed, &plan));
+        }
+
+        Ok(QueryPage {
+            columns: column_names,
+            rows: result_rows,
+            has_more,
+        })
+    }
+
+    /// 执行SQL语句，`approved`为`true`时表示写入已经确认，不再审批
+    fn execute(
+        &self,
+        conn: &Connection,
+        params: Value,
+        approved: bool,
+    ) -> Result<Value, ToolError> {
+        // 获取语句参数
+        let statement = match params.get("statement") {
+            Some(Value::String(s)) => s,
+            _ => {
+                return Err(ToolError::InvalidParameters(
+                    "Missing required parameter: statement".into(),
+                ))
+            }
+        };
+
+        // 获取绑定参数
+        let params_json = json!([]);
+        let bind_params = params.get("params").unwrap_or(&params_json);
+        if !matches!(bind_params, Value::Array(_) | Value::Object(_)) {
+            return Err(ToolError::InvalidParameters(
+                "params must be an array or an object".into(),
+            ));
+        }
+
+        let typed = optional_bool_param(&params, "typed")?;
+        self.check_preview_statement(&params, approved, statement)?;
+
+        // 执行语句
+        self.check_transaction(conn, &params, true)?;
+        self.with_preview(conn, "execute", &params, approved, |_| {
+            let _watch = self.watch(&params)?;
+
+            let mut stmt = match conn.prepare(statement) {
+                Ok(stmt) => stmt,
+                Err(e) => return Err(self.sql_error("Failed to prepare statement", e)),
+            };
+
+            // 将JSON参数转换为SQLite参数
+            let sql_params = resolve_params(&stmt, bind_params, typed, "params")?;
+
+            let result = stmt.execute(params_from_iter(&sql_params));
+
+            match result {
+                Ok(rows_affected) => {
+                    // 获取最后插入的行ID
+                    let last_insert_id = conn.last_insert_rowid();
+
+                    Ok(json!({
+                        "rowcount": rows_affected,
+                        "lastrowid": last_insert_id,
+                    }))
+                }
+                Err(e) => Err(self.sql_error("Failed to execute statement", e)),
+            }
+        })
+    }
+
+    /// 执行多个SQL语句，`approved`为`true`时表示写入已经确认，不再审批
+    fn executemany(
+        &self,
+        conn: &Connection,
+        params: Value,
+        approved: bool,
+    ) -> Result<Value, ToolError> {
+        // 获取语句参数
+        let statement = match params.get("statement") {
             Some(Value::String(s)) => s,
             _ => {
                 return Err(ToolError::InvalidParameters(
@@ -271,168 +1351,1951 @@ impl SQLiteRouter {
             }
         };
 
+        let atomic = optional_bool_param(&params, "atomic")?;
+        let typed = optional_bool_param(&params, "typed")?;
+        self.check_preview_statement(&params, approved, statement)?;
+
         // 执行语句
-        let conn = self.conn.lock().await;
+        self.check_transaction(conn, &params, true)?;
+        self.with_preview(conn, "executemany", &params, approved, |_| {
+            let _watch = self.watch(&params)?;
+            self.execute_many_params(conn, statement, params_list, atomic, typed)
+        })
+    }
 
-        let mut stmt = match conn.prepare(statement) {
-            Ok(stmt) => stmt,
+    /// 执行`executemany`，`atomic`为`true`时在保存点中执行
+    fn execute_many_params(
+        &self,
+        conn: &Connection,
+        statement: &str,
+        params_list: &[Value],
+        atomic: bool,
+        typed: bool,
+    ) -> Result<Value, ToolError> {
+        if !atomic {
+            let rows_affected = self.execute_many(conn, statement, params_list, typed)?;
+            return Ok(json!({
+                "rowcount": rows_affected,
+            }));
+        }
+
+        // 原子模式：在保存点中执行，任何一组参数失败都会撤销之前的写入
+        if let Err(e) = conn.execute_batch("SAVEPOINT mcp_executemany") {
+            return Err(self.sql_error("Failed to start savepoint", e));
+        }
+        match self.execute_many(conn, statement, params_list, typed) {
+            Ok(rows_affected) => {
+                if let Err(e) = conn.execute_batch("RELEASE mcp_executemany") {
+                    return Err(self.sql_error("Failed to release savepoint", e));
+                }
+                Ok(json!({
+                    "rowcount": rows_affected,
+                }))
+            }
             Err(e) => {
-                return Err(ToolError::ExecutionError(format!(
-                    "Failed to prepare statement: {}",
-                    e
-                )))
+                if let Err(rollback_error) =
+                    conn.execute_batch("ROLLBACK TO mcp_executemany; RELEASE mcp_executemany")
+                {
+                    error!("Failed to roll back executemany: {}", rollback_error);
+                }
+                Err(e)
             }
+        }
+    }
+
+    /// 使用参数列表逐组执行语句，返回受影响的总行数
+    fn execute_many(
+        &self,
+        conn: &Connection,
+        statement: &str,
+        params_list: &[Value],
+        typed: bool,
+    ) -> Result<usize, ToolError> {
+        let mut stmt = match conn.prepare(statement) {
+            Ok(stmt) => stmt,
+            Err(e) => return Err(self.sql_error("Failed to prepare statement", e)),
         };
 
         let mut rows_affected = 0;
 
-        for params_item in params_list {
+        for (i, params_item) in params_list.iter().enumerate() {
             match params_item {
-                Value::Array(params) => {
+                Value::Array(_) | Value::Object(_) => {
                     // 将JSON参数转换为SQLite参数
-                    let sql_params: Vec<Box<dyn ToSql>> =
-                        params.iter().map(|v| json_value_to_sql(v)).collect();
-
-                    let sql_params_refs: Vec<&dyn ToSql> =
-                        sql_params.iter().map(|p| p.as_ref()).collect();
+                    let sql_params =
+                        resolve_params(&stmt, params_item, typed, &format!("params_list[{}]", i))?;
 
-                    match stmt.execute(sql_params_refs.as_slice()) {
+                    match stmt.execute(params_from_iter(&sql_params)) {
                         Ok(count) => rows_affected += count,
-                        Err(e) => {
-                            return Err(ToolError::ExecutionError(format!(
-                                "Failed to execute statement: {}",
-                                e
-                            )))
+                        Err(e) => return Err(self.sql_error("Failed to execute statement", e)),
+                    }
+                }
+                _ => {
+                    return Err(ToolError::InvalidParameters(
+                        "params_list must contain arrays or objects".into(),
+                    ))
+                }
+            }
+        }
+
+        Ok(rows_affected)
+    }
+
+    /// 执行SQL脚本，`approved`为`true`时表示写入已经确认，不再审批
+    fn executescript(
+        &self,
+        conn: &Connection,
+        params: Value,
+        approved: bool,
+    ) -> Result<Value, ToolError> {
+        // 获取脚本参数
+        let script = match params.get("script") {
+            Some(Value::String(s)) => s,
+            _ => {
+                return Err(ToolError::InvalidParameters(
+                    "Missing required parameter: script".into(),
+                ))
+            }
+        };
+
+        // 执行脚本
+        self.check_transaction(conn, &params, true)?;
+        let dry_run = optional_bool_param(&params, "dry_run")?;
+        self.with_preview(conn, "executescript", &params, approved, |previewing| {
+            let _watch = self.watch(&params)?;
+
+            let result = if previewing {
+                self.execute_script_checked(conn, script, dry_run)
+            } else {
+                conn.execute_batch(script)
+                    .map_err(|e| self.sql_error("Failed to execute script", e))
+            };
+            // 由于execute_batch不返回受影响的行数，我们返回0
+            result.map(|()| {
+                json!({
+                    "rowcount": 0,
+                })
+            })
+        })
+    }
+
+    /// 把CSV数据导入表中，`approved`为`true`时表示写入已经确认，不再审批
+    ///
+    /// 表不存在时按推断的列类型创建；所有行在一个保存点中插入，`on_conflict`为`fail`时任何一行失败都会撤销整个导入
+    fn import_csv(
+        &self,
+        conn: &Connection,
+        params: Value,
+        approved: bool,
+    ) -> Result<Value, ToolError> {
+        let table = match params.get("table") {
+            Some(Value::String(table)) => table,
+            _ => {
+                return Err(ToolError::InvalidParameters(
+                    "Missing required parameter: table".into(),
+                ))
+            }
+        };
+        let text = self.csv_text(&params)?;
+        let delimiter = delimiter_param(&params)?;
+        let header = match params.get("header") {
+            None | Some(Value::Null) => true,
+            Some(_) => optional_bool_param(&params, "header")?,
+        };
+        let null_tokens = null_tokens_param(&params)?;
+        let conflict = match optional_str_param(&params, "on_conflict")? {
+            None => Conflict::default(),
+            Some(conflict) => Conflict::parse(conflict).ok_or_else(|| {
+                ToolError::InvalidParameters(
+                    "on_conflict must be one of: fail, ignore, replace".into(),
+                )
+            })?,
+        };
+        let database = self
+            .local_database(database_param(&params)?)
+            .unwrap_or("main");
+
+        let parsed = import::parse(&text, delimiter);
+        let mut rejected = parsed.rejected;
+        let mut records = parsed.records.into_iter();
+        let header_fields =
+            if header {
+                let first = records.next();
+                // 表头之前被拒绝的行说明表头本身格式错误，不能用之后的记录代替
+                if let Some(bad) = rejected
+                    .first()
+                    .filter(|bad| first.as_ref().is_none_or(|h| bad.line < h.line))
+                {
+                    return Err(ToolError::InvalidParameters(format!(
+                        "CSV header at line {} is malformed: {}",
+                        bad.line, bad.reason
+                    )));
+                }
+                Some(first.ok_or_else(|| {
+                    ToolError::InvalidParameters("CSV data has no header row".into())
+                })?)
+            } else {
+                None
+            };
+        let records: Vec<_> = records.collect();
+        let count = match (&header_fields, records.first()) {
+            (Some(header), _) => header.fields.len(),
+            (None, Some(record)) => record.fields.len(),
+            (None, None) => {
+                return Err(ToolError::InvalidParameters(
+                    "CSV data contains no rows".into(),
+                ))
+            }
+        };
+
+        // pragma_table_info的读取不属于目标数据库，不受数据库范围限制
+        let existing: Vec<String> = policy::with_database_scope(None, || {
+            conn.prepare("SELECT name FROM pragma_table_info(?1, ?2) ORDER BY cid")
+                .and_then(|mut stmt| {
+                    stmt.query_map((table, database), |row| row.get(0))?
+                        .collect::<Result<_, _>>()
+                })
+        })
+        .map_err(|e| self.sql_error("Failed to read table columns", e))?;
+        let columns = import_columns(
+            table,
+            &existing,
+            header_fields.as_ref().map(|h| &h.fields[..]),
+            count,
+        )?;
+        let types = import::infer_types(&records, count, &null_tokens);
+
+        let rows_read = records.len() + rejected.len();
+        rejected.extend(
+            records
+                .iter()
+                .filter(|record| record.fields.len() != count)
+                .map(|record| RejectedLine {
+                    line: record.line,
+                    reason: format!("Expected {} fields, found {}", count, record.fields.len()),
+                }),
+        );
+        let target = format!(
+            "{}.{}",
+            schema::quote_identifier(database),
+            schema::quote_identifier(table)
+        );
+        let column_list = columns
+            .iter()
+            .map(|column| schema::quote_identifier(column))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        self.check_transaction(conn, &params, true)?;
+        self.with_preview(conn, "import_csv", &params, approved, |_| {
+            let _watch = self.watch(&params)?;
+            let mut rejected = rejected.clone();
+
+            conn.execute_batch("SAVEPOINT mcp_import_csv")
+                .map_err(|e| self.sql_error("Failed to start savepoint", e))?;
+            let result = (|| {
+                let created = existing.is_empty();
+                if created {
+                    let definitions = columns
+                        .iter()
+                        .zip(&types)
+                        .map(|(column, column_type)| {
+                            format!(
+                                "{} {}",
+                                schema::quote_identifier(column),
+                                column_type.as_sql()
+                            )
+                        })
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    conn.execute_batch(&format!("CREATE TABLE {} ({})", target, definitions))
+                        .map_err(|e| self.sql_error("Failed to create table", e))?;
+                }
+
+                let placeholders = vec!["?"; count].join(", ");
+                let mut stmt = conn
+                    .prepare(&format!(
+                        "{} {} ({}) VALUES ({})",
+                        conflict.insert_verb(),
+                        target,
+                        column_list,
+                        placeholders
+                    ))
+                    .map_err(|e| self.sql_error("Failed to prepare statement", e))?;
+                let mut inserted = 0;
+                let mut ignored = 0;
+                for record in records.iter().filter(|record| record.fields.len() == count) {
+                    let values = record
+                        .fields
+                        .iter()
+                        .zip(&types)
+                        .map(|(field, column_type)| {
+                            import::to_value(field, *column_type, &null_tokens)
+                        });
+                    match stmt.execute(params_from_iter(values)) {
+                        Ok(0) => ignored += 1,
+                        Ok(n) => inserted += n,
+                        // 只有fail策略在出错时中止，其他策略把无法插入的行列为被拒绝的行
+                        Err(e) if conflict == Conflict::Fail => {
+                            return Err(self
+                                .sql_error(&format!("Failed to import line {}", record.line), e))
+                        }
+                        Err(e) => rejected.push(RejectedLine {
+                            line: record.line,
+                            reason: e.to_string(),
+                        }),
+                    }
+                }
+                Ok((created, inserted, ignored))
+            })();
+
+            let (created, inserted, ignored) = match result {
+                Ok(counts) => {
+                    conn.execute_batch("RELEASE mcp_import_csv")
+                        .map_err(|e| self.sql_error("Failed to release savepoint", e))?;
+                    counts
+                }
+                Err(e) => {
+                    if let Err(rollback_error) =
+                        conn.execute_batch("ROLLBACK TO mcp_import_csv; RELEASE mcp_import_csv")
+                    {
+                        error!("Failed to roll back import_csv: {}", rollback_error);
+                    }
+                    return Err(e);
+                }
+            };
+
+            rejected.sort_by_key(|line| line.line);
+            let column_info: Vec<Value> = columns
+                .iter()
+                .zip(&types)
+                .map(|(name, column_type)| json!({"name": name, "type": column_type.as_sql()}))
+                .collect();
+            Ok(json!({
+                "table": table,
+                "created": created,
+                "columns": column_info,
+                "rows_read": rows_read,
+                "rowcount": inserted,
+                "ignored": ignored,
+                "rejected": rejected.len(),
+                "rejected_lines": rejected
+                    .iter()
+                    .take(import::MAX_REJECTED_LINES)
+                    .collect::<Vec<_>>(),
+            }))
+        })
+    }
+
+    /// 读取`import_csv`的CSV数据：`csv`参数中的文本，或者导入目录中`path`指定的文件
+    fn csv_text(&self, params: &Value) -> Result<String, ToolError> {
+        match (
+            optional_str_param(params, "path")?,
+            optional_str_param(params, "csv")?,
+        ) {
+            (Some(_), Some(_)) => Err(ToolError::InvalidParameters(
+                "Pass either path or csv, not both".into(),
+            )),
+            (None, None) => Err(ToolError::InvalidParameters(
+                "Missing required parameter: path or csv".into(),
+            )),
+            (None, Some(csv)) => Ok(csv.to_string()),
+            (Some(_), None) if self.options.import_roots.is_empty() => {
+                Err(ToolError::InvalidParameters(
+                    "Importing files is disabled: no import directory is configured; pass the data in csv instead".into(),
+                ))
+            }
+            (Some(path), None) => {
+                let resolved = registry::resolve_path(&self.options.import_roots, path, false)
+                    .map_err(registry_error)?;
+                std::fs::read_to_string(&resolved).map_err(|e| match e.kind() {
+                    std::io::ErrorKind::InvalidData => {
+                        ToolError::InvalidParameters(format!("{} is not valid UTF-8", path))
+                    }
+                    _ => ToolError::ExecutionError(format!("Failed to read {}: {}", path, e)),
+                })
+            }
+        }
+    }
+
+    /// 逐条执行脚本中的语句，遇到事务控制语句时报错，用于预演
+    fn execute_script_checked(
+        &self,
+        conn: &Connection,
+        script: &str,
+        dry_run: bool,
+    ) -> Result<(), ToolError> {
+        let mut batch = Batch::new(conn, script);
+        loop {
+            let mut stmt = match batch.next() {
+                Ok(Some(stmt)) => stmt,
+                Ok(None) => return Ok(()),
+                Err(e) => return Err(self.sql_error("Failed to execute script", e)),
+            };
+            if let Some(keyword) = stmt.expanded_sql().as_deref().and_then(transaction_keyword) {
+                return Err(preview_transaction_error(dry_run, keyword));
+            }
+            // 与execute_batch一样，每条语句只执行一步
+            let mut rows = stmt.raw_query();
+            if let Err(e) = rows.next() {
+                return Err(self.sql_error("Failed to execute script", e));
+            }
+        }
+    }
+
+    /// 预演或审批写入
+    ///
+    /// `dry_run`参数为`true`时在保存点中执行`f`后回滚，在结果中附加修改的预览，预览的格式见[`crate::preview`]。
+    /// 启用审批且写入尚未确认时同样先预演，写入需要审批时返回审批令牌，否则再次调用`f`执行写入。
+    /// `f`的参数表示本次调用是否为预演
+    fn with_preview<F>(
+        &self,
+        conn: &Connection,
+        tool_name: &str,
+        params: &Value,
+        approved: bool,
+        f: F,
+    ) -> Result<Value, ToolError>
+    where
+        F: Fn(bool) -> Result<Value, ToolError>,
+    {
+        let dry_run = optional_bool_param(params, "dry_run")?;
+        let rules = self.options.approval.as_ref().filter(|_| !approved);
+        if !dry_run && rules.is_none() {
+            return f(false);
+        }
+        let max_rows = optional_u64_param(params, "preview_rows")?
+            .map_or(preview::DEFAULT_PREVIEW_ROWS, |n| n as usize);
+
+        let (result, preview) =
+            preview::dry_run(conn, max_rows, self.options.masking.as_deref(), || f(true))
+                .map_err(|e| self.sql_error("Failed to roll back dry run", e))?;
+        let reasons = match rules {
+            Some(rules) if !dry_run => rules.assess(conn, &preview),
+            _ => Vec::new(),
+        };
+        // 读取预览时被访问策略拒绝的动作不应出现在之后的错误信息中
+        let _ = self.denials.take();
+
+        let mut result = result?;
+        if dry_run {
+            result["dry_run"] = json!(true);
+            result["changes"] = json!(preview.changes);
+            result["schema_changes"] = json!(preview.schema_changes);
+            return Ok(result);
+        }
+        if reasons.is_empty() {
+            return f(false);
+        }
+
+        let token = self
+            .approvals
+            .request(tool_name, params.clone(), reasons.clone());
+        Ok(json!({
+            "approval_required": true,
+            "approval_token": token,
+            "expires_in_ms": self.approvals.timeout().as_millis() as u64,
+            "reasons": reasons,
+            "rowcount": result.get("rowcount"),
+            "changes": preview.changes,
+            "schema_changes": preview.schema_changes,
+        }))
+    }
+
+    /// 预演的语句不能是事务控制语句
+    fn check_preview_statement(
+        &self,
+        params: &Value,
+        approved: bool,
+        statement: &str,
+    ) -> Result<(), ToolError> {
+        let dry_run = optional_bool_param(params, "dry_run")?;
+        if !dry_run && (approved || self.options.approval.is_none()) {
+            return Ok(());
+        }
+        match transaction_keyword(statement) {
+            Some(keyword) => Err(preview_transaction_error(dry_run, keyword)),
+            None => Ok(()),
+        }
+    }
+
+    /// 确认等待审批的写入并执行
+    fn confirm(&self, conn: &Connection, params: Value) -> Result<Value, ToolError> {
+        let pending = self.take_approval(&params)?;
+        let database = self.database_scope(conn, &pending.tool, &pending.arguments)?;
+        policy::with_database_scope(database.as_deref(), || match pending.tool.as_str() {
+            "execute" => self.execute(conn, pending.arguments, true),
+            "executemany" => self.executemany(conn, pending.arguments, true),
+            "executescript" => self.executescript(conn, pending.arguments, true),
+            "import_csv" => self.import_csv(conn, pending.arguments, true),
+            tool => Err(ToolError::ExecutionError(format!(
+                "Cannot confirm tool: {}",
+                tool
+            ))),
+        })
+    }
+
+    /// 取出`confirm`参数中的审批令牌对应的写入，令牌随之失效
+    fn take_approval(&self, params: &Value) -> Result<PendingApproval, ToolError> {
+        let token = match params.get("approval_token") {
+            Some(Value::String(token)) => token,
+            _ => {
+                return Err(ToolError::InvalidParameters(
+                    "Missing required parameter: approval_token".into(),
+                ))
+            }
+        };
+        self.approvals.take(token).ok_or_else(|| {
+            ToolError::InvalidParameters(format!(
+                "Unknown, used or expired approval token: {}",
+                token
+            ))
+        })
+    }
+
+    /// `confirm`参数中的审批令牌对应的写入，不使令牌失效
+    fn pending_approval(&self, params: &Value) -> Option<PendingApproval> {
+        params
+            .get("approval_token")
+            .and_then(Value::as_str)
+            .and_then(|token| self.approvals.get(token))
+    }
+
+    /// `confirm`确认的工具名称
+    fn pending_tool(&self, params: &Value) -> Option<String> {
+        self.pending_approval(params).map(|pending| pending.tool)
+    }
+
+    /// 执行只读操作的工作线程，没有只读连接池时使用写连接
+    fn read_worker(&self) -> &DbWorker {
+        self.readers.as_ref().unwrap_or(&self.writer)
+    }
+
+    /// 在写连接上执行操作并等待结果
+    async fn run<T, F>(&self, f: F) -> Result<T, ToolError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&SQLiteRouter, &Connection) -> Result<T, ToolError> + Send + 'static,
+    {
+        self.run_on(&self.writer, f).await
+    }
+
+    /// 在指定的工作线程上执行操作并等待结果
+    async fn run_on<T, F>(&self, worker: &DbWorker, f: F) -> Result<T, ToolError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&SQLiteRouter, &Connection) -> Result<T, ToolError> + Send + 'static,
+    {
+        let router = self.clone();
+        worker
+            .run(move |conn| f(&router, conn))
+            .await
+            .map_err(|e| ToolError::ExecutionError(e.to_string()))?
+    }
+
+    /// 在指定的工作线程上执行需要可变连接的操作并等待结果
+    async fn run_mut_on<T, F>(&self, worker: &DbWorker, f: F) -> Result<T, ToolError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&SQLiteRouter, &mut Connection) -> Result<T, ToolError> + Send + 'static,
+    {
+        let router = self.clone();
+        worker
+            .run_mut(move |conn| f(&router, conn))
+            .await
+            .map_err(|e| ToolError::ExecutionError(e.to_string()))?
+    }
+
+    /// 执行工具调用，只读模式下拒绝写入工具
+    async fn call(&self, tool_name: String, arguments: Value) -> Result<Value, ToolError> {
+        if self.options.read_only && WRITE_TOOLS.contains(&tool_name.as_str()) {
+            return Err(ToolError::ExecutionError(format!(
+                "Denied by read-only policy: tool {} is not available because the server is running in read-only mode",
+                tool_name
+            )));
+        }
+
+        // 恢复需要可变的连接，在选择打开的数据库之前单独处理
+        if tool_name == "restore" {
+            return self.restore(arguments, false).await;
+        }
+        if tool_name == "confirm" && self.pending_tool(&arguments).as_deref() == Some("restore") {
+            let pending = self.take_approval(&arguments)?;
+            return self.restore(pending.arguments, true).await;
+        }
+
+        if let Some(database) = self.opened_database(&tool_name, &arguments) {
+            return self.call_opened(database, tool_name, arguments).await;
+        }
+
+        match tool_name.as_str() {
+            "begin_transaction" => self.begin_transaction(arguments).await,
+            "list_backups" => self.list_backups(),
+            "open_database" => self.open_database(arguments).await,
+            "close_database" => self.close_database(arguments),
+            "list_open_databases" => Ok(self.list_open_databases()),
+            _ => self.dispatch(tool_name, arguments).await,
+        }
+    }
+
+    /// 调用选择的运行时打开的数据库
+    ///
+    /// `fetch`沿用打开游标时的数据库，`confirm`沿用等待确认的写入的数据库，其他工具由`database`参数选择
+    fn opened_database(&self, tool_name: &str, arguments: &Value) -> Option<OpenedDatabase> {
+        let database = match tool_name {
+            "fetch" => arguments
+                .get("cursor_id")
+                .and_then(Value::as_str)
+                .and_then(|id| self.cursors.get(id))
+                .and_then(|cursor| cursor.database),
+            "confirm" => self.pending_approval(arguments).and_then(|pending| {
+                pending
+                    .arguments
+                    .get("database")
+                    .and_then(Value::as_str)
+                    .map(str::to_string)
+            }),
+            _ => arguments
+                .get("database")
+                .and_then(Value::as_str)
+                .map(str::to_string),
+        }?;
+        self.registry.get(&database)
+    }
+
+    /// 在运行时打开的数据库的连接上执行工具调用
+    ///
+    /// 打开的数据库不支持显式事务，调用看到的是一个没有打开事务的独立事务状态
+    async fn call_opened(
+        &self,
+        database: OpenedDatabase,
+        tool_name: String,
+        arguments: Value,
+    ) -> Result<Value, ToolError> {
+        if !OPENED_DATABASE_TOOLS.contains(&tool_name.as_str()) {
+            return Err(ToolError::InvalidParameters(format!(
+                "{} cannot be used with opened database {}",
+                tool_name, database.name
+            )));
+        }
+        if optional_str_param(&arguments, "transaction_id")?.is_some() {
+            return Err(ToolError::InvalidParameters(format!(
+                "Explicit transactions are not supported on opened database {}",
+                database.name
+            )));
+        }
+        if database.read_only && WRITE_TOOLS.contains(&tool_name.as_str()) {
+            return Err(ToolError::InvalidParameters(format!(
+                "Database {} is opened read-only",
+                database.name
+            )));
+        }
+
+        self.for_opened(&database)
+            .run_on(&database.worker, move |router, conn| {
+                router.call_tool_blocking(conn, &tool_name, arguments)
+            })
+            .await
+    }
+
+    /// 在运行时打开的数据库的连接上执行调用的路由器，事务状态独立于主数据库
+    fn for_opened(&self, database: &OpenedDatabase) -> Self {
+        Self {
+            transactions: Transactions::new(self.options.transaction_timeout),
+            read_source: Some(Arc::new(ReadSource::Opened {
+                path: database.path.clone(),
+                read_only: database.read_only,
+            })),
+            opened: Some(database.name.clone()),
+            ..self.clone()
+        }
+    }
+
+    /// 在允许的目录中打开数据库文件并以名称登记
+    async fn open_database(&self, params: Value) -> Result<Value, ToolError> {
+        let (Some(name), Some(path)) = (
+            optional_str_param(&params, "name")?,
+            optional_str_param(&params, "path")?,
+        ) else {
+            return Err(ToolError::InvalidParameters(
+                "Missing required parameters: name and path".into(),
+            ));
+        };
+        let create = optional_bool_param(&params, "create")?;
+        let read_only = self.options.read_only || optional_bool_param(&params, "read_only")?;
+        if create && read_only {
+            return Err(ToolError::InvalidParameters(
+                "Cannot create a database that is opened read-only".into(),
+            ));
+        }
+
+        self.registry.check_name(name).map_err(registry_error)?;
+        let path = self
+            .registry
+            .resolve(path, create)
+            .map_err(registry_error)?;
+
+        let mut flags = OpenFlags::SQLITE_OPEN_NO_MUTEX;
+        flags |= if read_only {
+            OpenFlags::SQLITE_OPEN_READ_ONLY
+        } else if create {
+            OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE
+        } else {
+            OpenFlags::SQLITE_OPEN_READ_WRITE
+        };
+        let conn = Connection::open_with_flags(&path, flags)
+            .map_err(|e| self.sql_error("Failed to open database", e))?;
+        policy::install(
+            &conn,
+            read_only,
+            self.options.policy.clone(),
+            self.denials.clone(),
+        );
+        self.watchdog.install(&conn);
+        let worker = DbWorker::spawn(&format!("mcp-sqlite-{}", name), conn);
+
+        // SQLite在第一次读取结构时才检查文件头，不是数据库的文件在这里报错
+        let tables = self
+            .run_on(&worker, |router, conn| {
+                conn.query_row(
+                    "SELECT count(*) FROM sqlite_master \
+                     WHERE type = 'table' AND name NOT LIKE 'sqlite\\_%' ESCAPE '\\'",
+                    [],
+                    |row| row.get::<_, i64>(0),
+                )
+                .map_err(|e| router.sql_error("Failed to open database", e))
+            })
+            .await?;
+
+        self.registry
+            .register(OpenedDatabase {
+                name: name.to_string(),
+                path: path.clone(),
+                read_only,
+                worker,
+            })
+            .map_err(registry_error)?;
+
+        Ok(json!({
+            "name": name,
+            "path": path.display().to_string(),
+            "read_only": read_only,
+            "tables": tables,
+        }))
+    }
+
+    /// 关闭运行时打开的数据库
+    fn close_database(&self, params: Value) -> Result<Value, ToolError> {
+        let name = match params.get("name") {
+            Some(Value::String(name)) => name,
+            _ => {
+                return Err(ToolError::InvalidParameters(
+                    "Missing required parameter: name".into(),
+                ))
+            }
+        };
+        let database = self.registry.remove(name).ok_or_else(|| {
+            ToolError::InvalidParameters(format!("Unknown opened database: {}", name))
+        })?;
+
+        Ok(json!({
+            "name": database.name,
+            "closed": true,
+        }))
+    }
+
+    /// 列出运行时打开的数据库和允许打开的目录
+    fn list_open_databases(&self) -> Value {
+        let databases: Vec<Value> = self
+            .registry
+            .list()
+            .into_iter()
+            .map(|database| {
+                json!({
+                    "name": database.name,
+                    "path": database.path.display().to_string(),
+                    "read_only": database.read_only,
+                })
+            })
+            .collect();
+        let roots: Vec<String> = self
+            .registry
+            .roots()
+            .iter()
+            .map(|root| root.display().to_string())
+            .collect();
+
+        json!({
+            "databases": databases,
+            "allowed_directories": roots,
+        })
+    }
+
+    /// 访问策略是否允许`restore`：恢复不经过授权回调，可能绕过策略保护的表
+    fn restore_allowed(&self) -> bool {
+        self.options
+            .policy
+            .as_ref()
+            .is_none_or(|policy| policy.allows_restore())
+    }
+
+    /// 备份目录，没有配置时返回错误
+    fn backups(&self) -> Result<BackupDirectory, ToolError> {
+        self.options
+            .backup_dir
+            .as_ref()
+            .map(BackupDirectory::new)
+            .ok_or_else(|| {
+                ToolError::InvalidParameters(
+                    "Backups are disabled: no backup directory is configured".into(),
+                )
+            })
+    }
+
+    /// 将备份错误转换为工具错误
+    fn backup_error(&self, context: &str, e: BackupError) -> ToolError {
+        match e {
+            BackupError::Sql(e) => self.sql_error(context, e),
+            BackupError::Io(_) | BackupError::Busy => ToolError::ExecutionError(e.to_string()),
+            _ => ToolError::InvalidParameters(e.to_string()),
+        }
+    }
+
+    /// 备份和恢复不能在打开的事务中执行，否则会复制或覆盖未提交的数据
+    fn check_no_transaction(&self, conn: &Connection, action: &str) -> Result<(), ToolError> {
+        // 其他会话的事务返回等待它结束的错误
+        self.check_transaction(conn, &Value::Null, false)?;
+        if conn.is_autocommit() {
+            Ok(())
+        } else {
+            Err(ToolError::InvalidParameters(format!(
+                "Cannot {} while a transaction is open; commit or roll it back first",
+                action
+            )))
+        }
+    }
+
+    /// 把数据库复制为备份目录中的文件
+    fn backup(&self, conn: &Connection, params: Value) -> Result<Value, ToolError> {
+        let backups = self.backups()?;
+        let method = match optional_str_param(&params, "method")? {
+            None => BackupMethod::default(),
+            Some(method) => BackupMethod::parse(method).ok_or_else(|| {
+                ToolError::InvalidParameters("method must be one of: backup, vacuum".into())
+            })?,
+        };
+        let pages_per_step = pages_per_step_param(&params)?;
+        let requested = database_param(&params)?.unwrap_or("main");
+        let database = self.local_database(Some(requested)).unwrap_or("main");
+        self.check_database(conn, Some(database))?;
+        self.check_no_transaction(conn, "back up")?;
+
+        let name = match optional_str_param(&params, "name")? {
+            Some(name) => name.to_string(),
+            None => backups.default_name(requested),
+        };
+        let path = backups
+            .path(&name)
+            .map_err(|e| self.backup_error("Backup failed", e))?;
+
+        let started = Instant::now();
+        let progress = match method {
+            BackupMethod::Online => Some(
+                backup::backup(conn, database, &path, pages_per_step)
+                    .map_err(|e| self.backup_error("Backup failed", e))?,
+            ),
+            BackupMethod::Vacuum => {
+                backup::vacuum_into(conn, database, &path)
+                    .map_err(|e| self.backup_error("Backup failed", e))?;
+                None
+            }
+        };
+        let size = std::fs::metadata(&path)
+            .map_err(|e| self.backup_error("Backup failed", e.into()))?
+            .len();
+
+        let mut result = json!({
+            "name": name,
+            "database": requested,
+            "method": method.as_str(),
+            "size": size,
+            "elapsed_ms": started.elapsed().as_millis() as u64,
+        });
+        if let Some(progress) = progress {
+            result["pages"] = json!(progress.pages);
+            result["steps"] = json!(progress.steps);
+        }
+        Ok(result)
+    }
+
+    /// 用备份目录中的文件替换数据库的内容
+    ///
+    /// 启用审批时未经确认的恢复只返回审批令牌，`approved`为`true`表示已经通过`confirm`确认
+    async fn restore(&self, params: Value, approved: bool) -> Result<Value, ToolError> {
+        if !self.restore_allowed() {
+            return Err(ToolError::ExecutionError(
+                "Denied by access policy: tool restore is not available because the policy does not allow insert, delete, create and drop on every table".into(),
+            ));
+        }
+        let backups = self.backups()?;
+        let name = match params.get("name") {
+            Some(Value::String(name)) => name.clone(),
+            _ => {
+                return Err(ToolError::InvalidParameters(
+                    "Missing required parameter: name".into(),
+                ))
+            }
+        };
+        let path = backups
+            .path(&name)
+            .map_err(|e| self.backup_error("Restore failed", e))?;
+        if !path.is_file() {
+            return Err(self.backup_error("Restore failed", BackupError::NotFound(name)));
+        }
+        let pages_per_step = pages_per_step_param(&params)?;
+        let requested = database_param(&params)?.unwrap_or("main").to_string();
+
+        let (router, worker) = match self.registry.get(&requested) {
+            Some(opened) if opened.read_only => {
+                return Err(ToolError::InvalidParameters(format!(
+                    "Database {} is opened read-only",
+                    opened.name
+                )))
+            }
+            Some(opened) => (self.for_opened(&opened), opened.worker),
+            None => (self.clone(), self.writer.clone()),
+        };
+        router
+            .run_mut_on(&worker, move |router, conn| {
+                let database = router
+                    .local_database(Some(&requested))
+                    .unwrap_or("main")
+                    .to_string();
+                router.check_database(conn, Some(&database))?;
+                router.check_no_transaction(conn, "restore")?;
+
+                if router.options.approval.is_some() && !approved {
+                    let reasons = vec![format!(
+                        "Replaces all contents of database {} with backup {}",
+                        requested, name
+                    )];
+                    let token = router.approvals.request("restore", params, reasons.clone());
+                    return Ok(json!({
+                        "approval_required": true,
+                        "approval_token": token,
+                        "expires_in_ms": router.approvals.timeout().as_millis() as u64,
+                        "reasons": reasons,
+                    }));
+                }
+
+                let started = Instant::now();
+                let progress = backup::restore(conn, &database, &path, pages_per_step)
+                    .map_err(|e| router.backup_error("Restore failed", e))?;
+                Ok(json!({
+                    "name": name,
+                    "database": requested,
+                    "pages": progress.pages,
+                    "steps": progress.steps,
+                    "elapsed_ms": started.elapsed().as_millis() as u64,
+                }))
+            })
+            .await
+    }
+
+    /// 列出备份目录中的备份
+    fn list_backups(&self) -> Result<Value, ToolError> {
+        let backups = self.backups()?;
+        let files: Vec<Value> = backups
+            .list()
+            .map_err(|e| self.backup_error("Failed to list backups", e))?
+            .into_iter()
+            .map(|file| {
+                json!({
+                    "name": file.name,
+                    "size": file.size,
+                    "modified": file.modified_rfc3339(),
+                })
+            })
+            .collect();
+
+        Ok(json!({
+            "directory": backups.root().display().to_string(),
+            "backups": files,
+        }))
+    }
+
+    /// 根据调用参数生成审计信息，`fetch`记录游标对应的查询，`confirm`记录确认的写入
+    fn audit_event(&self, tool_name: &str, arguments: &Value) -> AuditEvent {
+        if tool_name == "confirm" {
+            if let Some(pending) = self.pending_approval(arguments) {
+                return AuditEvent {
+                    tool: tool_name.to_string(),
+                    ..self.audit_event(&pending.tool, &pending.arguments)
+                };
+            }
+        }
+        let sql = match tool_name {
+            "query" | "explain" => arguments
+                .get("query")
+                .and_then(Value::as_str)
+                .map(str::to_string),
+            "execute" | "executemany" => arguments
+                .get("statement")
+                .and_then(Value::as_str)
+                .map(str::to_string),
+            "executescript" => arguments
+                .get("script")
+                .and_then(Value::as_str)
+                .map(str::to_string),
+            "suggest_indexes" => {
+                arguments
+                    .get("queries")
+                    .and_then(Value::as_array)
+                    .map(|queries| {
+                        queries
+                            .iter()
+                            .filter_map(Value::as_str)
+                            .collect::<Vec<_>>()
+                            .join(";\n")
+                    })
+            }
+            "fetch" => arguments
+                .get("cursor_id")
+                .and_then(Value::as_str)
+                .and_then(|id| self.cursors.get(id))
+                .map(|cursor| cursor.sql),
+            _ => None,
+        };
+        AuditEvent {
+            session: self.session_id(),
+            tool: tool_name.to_string(),
+            sql,
+            params: arguments
+                .get("params")
+                .or_else(|| arguments.get("params_list"))
+                .cloned(),
+            ..Default::default()
+        }
+    }
+
+    /// 执行除`begin_transaction`以外的工具调用
+    ///
+    /// 不属于显式事务的只读调用交给只读连接池，其余调用以及会修改数据库的查询在写连接上执行
+    async fn dispatch(&self, tool_name: String, arguments: Value) -> Result<Value, ToolError> {
+        let mut arguments = arguments;
+        if let Some(readers) = &self.readers {
+            if READ_TOOLS.contains(&tool_name.as_str())
+                && optional_str_param(&arguments, "transaction_id")?.is_none()
+            {
+                let name = tool_name.clone();
+                let outcome = self
+                    .run_on(readers, move |router, conn| {
+                        Ok(router.call_read_tool(conn, &name, arguments))
+                    })
+                    .await?;
+                match outcome {
+                    ReadOutcome::Done(result) => return result,
+                    ReadOutcome::NeedsWriter(returned) => arguments = returned,
+                }
+            }
+        }
+
+        self.run(move |router, conn| router.call_tool_blocking(conn, &tool_name, arguments))
+            .await
+    }
+
+    /// 在只读连接上执行工具调用
+    ///
+    /// `query`的语句会修改数据库时不执行，交回参数由写连接处理；游标只读，`fetch`总是在这里执行
+    fn call_read_tool(&self, conn: &Connection, tool_name: &str, arguments: Value) -> ReadOutcome {
+        let sql = match tool_name {
+            "query" => arguments.get("query").and_then(Value::as_str),
+            _ => None,
+        };
+        if sql.is_some_and(|sql| !is_read_only_sql(conn, sql)) {
+            return ReadOutcome::NeedsWriter(arguments);
+        }
+        let database = match self.database_scope(conn, tool_name, &arguments) {
+            Ok(database) => database,
+            Err(e) => return ReadOutcome::Done(Err(e)),
+        };
+
+        ReadOutcome::Done(policy::with_database_scope(
+            database.as_deref(),
+            || match tool_name {
+                "query" => self.read_query(conn, arguments),
+                "fetch" => self.read_fetch(conn, arguments),
+                "explain" => self.explain(conn, arguments),
+                "suggest_indexes" => self.suggest_indexes(conn, arguments),
+                "list_tables" => self.list_tables(conn, arguments),
+                "describe_table" => self.describe_table(conn, arguments),
+                "list_indexes" => self.list_indexes(conn, arguments),
+                "list_foreign_keys" => self.list_foreign_keys(conn, arguments),
+                "list_databases" => self.list_databases(conn),
+                _ => Err(ToolError::NotFound(format!("Unknown tool: {}", tool_name))),
+            },
+        ))
+    }
+
+    /// 在写连接上执行工具调用
+    ///
+    /// `begin_transaction`需要在异步上下文中启动回滚任务，由[`Router::call_tool`]单独处理
+    fn call_tool_blocking(
+        &self,
+        conn: &Connection,
+        tool_name: &str,
+        arguments: Value,
+    ) -> Result<Value, ToolError> {
+        let database = self.database_scope(conn, tool_name, &arguments)?;
+        policy::with_database_scope(database.as_deref(), || match tool_name {
+            "query" => self.query(conn, arguments),
+            "fetch" => self.fetch(conn, arguments),
+            "explain" => {
+                self.check_transaction(conn, &arguments, false)?;
+                self.explain(conn, arguments)
+            }
+            "suggest_indexes" => {
+                self.check_transaction(conn, &arguments, false)?;
+                self.suggest_indexes(conn, arguments)
+            }
+            "execute" => self.execute(conn, arguments, false),
+            "executemany" => self.executemany(conn, arguments, false),
+            "executescript" => self.executescript(conn, arguments, false),
+            "import_csv" => self.import_csv(conn, arguments, false),
+            "confirm" => self.confirm(conn, arguments),
+            "commit" => self.commit(conn, arguments),
+            "rollback" => self.rollback(conn, arguments),
+            "savepoint" => self.savepoint(conn, arguments),
+            "release" => self.release(conn, arguments),
+            "list_tables" => self.list_tables(conn, arguments),
+            "describe_table" => self.describe_table(conn, arguments),
+            "list_indexes" => self.list_indexes(conn, arguments),
+            "list_foreign_keys" => self.list_foreign_keys(conn, arguments),
+            "list_databases" => self.list_databases(conn),
+            "backup" => self.backup(conn, arguments),
+            _ => Err(ToolError::NotFound(format!("Unknown tool: {}", tool_name))),
+        })
+    }
+
+    /// 工具调用限定的数据库，`None`表示不限制
+    ///
+    /// 执行SQL的工具按`database`参数限定，`fetch`沿用打开游标时的数据库。
+    /// 内省工具把`database`当作schema，由各自的查询限定
+    fn database_scope(
+        &self,
+        conn: &Connection,
+        tool_name: &str,
+        arguments: &Value,
+    ) -> Result<Option<String>, ToolError> {
+        let database = match tool_name {
+            "query" | "explain" | "suggest_indexes" | "execute" | "executemany"
+            | "executescript" | "import_csv" => database_param(arguments)?.map(str::to_string),
+            "fetch" => arguments
+                .get("cursor_id")
+                .and_then(Value::as_str)
+                .and_then(|id| self.cursors.get(id))
+                .and_then(|cursor| cursor.database),
+            _ => None,
+        };
+        let database = self.local_database(database.as_deref()).map(str::to_string);
+        self.check_database(conn, database.as_deref())?;
+        Ok(database)
+    }
+
+    /// 在当前连接上选择的数据库
+    ///
+    /// 在运行时打开的数据库的连接上，指向该数据库的名称表示连接自己的数据库，不再限定
+    fn local_database<'a>(&self, database: Option<&'a str>) -> Option<&'a str> {
+        database.filter(|database| {
+            !self
+                .opened
+                .as_deref()
+                .is_some_and(|opened| opened.eq_ignore_ascii_case(database))
+        })
+    }
+
+    /// 检查数据库名称是否属于连接上打开的数据库
+    fn check_database(&self, conn: &Connection, database: Option<&str>) -> Result<(), ToolError> {
+        let Some(database) = database else {
+            return Ok(());
+        };
+        let exists: bool = conn
+            .query_row(
+                "SELECT EXISTS (SELECT 1 FROM pragma_database_list WHERE name = ?1 COLLATE NOCASE)",
+                [database],
+                |row| row.get(0),
+            )
+            .map_err(|e| self.sql_error("Failed to check database", e))?;
+        if exists {
+            Ok(())
+        } else {
+            Err(ToolError::InvalidParameters(format!(
+                "Unknown database: {}",
+                database
+            )))
+        }
+    }
+
+    /// 开始一个跨调用保持打开的事务
+    async fn begin_transaction(&self, params: Value) -> Result<Value, ToolError> {
+        let mode = match optional_str_param(&params, "mode")? {
+            None => TransactionMode::default(),
+            Some(mode) => TransactionMode::parse(mode).ok_or_else(|| {
+                ToolError::InvalidParameters(
+                    "mode must be one of: deferred, immediate, exclusive".into(),
+                )
+            })?,
+        };
+
+        let transaction_id = self
+            .run(move |router, conn| {
+                router
+                    .transactions
+                    .begin(conn, mode)
+                    .map_err(|e| router.transaction_error("Failed to begin transaction", e))
+            })
+            .await?;
+        self.spawn_transaction_reaper(transaction_id.clone());
+
+        Ok(json!({
+            "transaction_id": transaction_id,
+            "idle_timeout_ms": self.transactions.idle_timeout().as_millis() as u64,
+        }))
+    }
+
+    /// 提交事务
+    fn commit(&self, conn: &Connection, params: Value) -> Result<Value, ToolError> {
+        let transaction_id = required_transaction_id(&params)?;
+
+        self.transactions
+            .commit(conn, transaction_id)
+            .map_err(|e| self.transaction_error("Failed to commit transaction", e))?;
+
+        Ok(json!({
+            "transaction_id": transaction_id,
+            "committed": true,
+        }))
+    }
+
+    /// 回滚事务，或者回滚到指定的保存点
+    fn rollback(&self, conn: &Connection, params: Value) -> Result<Value, ToolError> {
+        let transaction_id = required_transaction_id(&params)?;
+        let savepoint = optional_str_param(&params, "savepoint")?;
+
+        self.transactions
+            .rollback(conn, transaction_id, savepoint)
+            .map_err(|e| self.transaction_error("Failed to roll back transaction", e))?;
+
+        Ok(json!({
+            "transaction_id": transaction_id,
+            "rolled_back": true,
+            "savepoint": savepoint,
+            "savepoints": self.transactions.savepoints(conn, transaction_id),
+        }))
+    }
+
+    /// 在事务中建立保存点
+    fn savepoint(&self, conn: &Connection, params: Value) -> Result<Value, ToolError> {
+        let (transaction_id, name) = savepoint_params(&params)?;
+
+        self.transactions
+            .savepoint(conn, transaction_id, name)
+            .map_err(|e| self.transaction_error("Failed to create savepoint", e))?;
+
+        Ok(json!({
+            "transaction_id": transaction_id,
+            "savepoint": name,
+            "savepoints": self.transactions.savepoints(conn, transaction_id),
+        }))
+    }
+
+    /// 释放保存点
+    fn release(&self, conn: &Connection, params: Value) -> Result<Value, ToolError> {
+        let (transaction_id, name) = savepoint_params(&params)?;
+
+        self.transactions
+            .release(conn, transaction_id, name)
+            .map_err(|e| self.transaction_error("Failed to release savepoint", e))?;
+
+        Ok(json!({
+            "transaction_id": transaction_id,
+            "released": name,
+            "savepoints": self.transactions.savepoints(conn, transaction_id),
+        }))
+    }
+
+    /// 启动后台任务，在事务空闲超时后自动回滚
+    fn spawn_transaction_reaper(&self, transaction_id: String) {
+        // 不持有路由器，以免会话结束后事务仍要等到超时才被回滚
+        let transactions = self.transactions.clone();
+        let writer = self.writer.clone();
+
+        tokio::spawn(async move {
+            while let Some(deadline) = transactions.deadline(&transaction_id) {
+                tokio::time::sleep_until(deadline.into()).await;
+
+                let transactions = transactions.clone();
+                let id = transaction_id.clone();
+                let expired = writer
+                    .run(move |conn| transactions.expire(conn, &id).is_none())
+                    .await;
+                if expired.unwrap_or(true) {
+                    break;
+                }
+            }
+        });
+    }
+
+    /// 列出数据库中的表、视图和虚拟表
+    fn list_tables(&self, conn: &Connection, params: Value) -> Result<Value, ToolError> {
+        let schema_name = self.local_database(database_param(&params)?);
+        let include_system = optional_bool_param(&params, "include_system")?;
+        self.check_database(conn, schema_name)?;
+
+        schema::list_tables(conn, schema_name, include_system)
+            .map_err(|e| self.sql_error("Failed to list tables", e))
+    }
+
+    /// 描述表的列定义
+    fn describe_table(&self, conn: &Connection, params: Value) -> Result<Value, ToolError> {
+        let (schema_name, table) = table_params(&params)?;
+        let schema_name = self.local_database(schema_name);
+        self.check_database(conn, schema_name)?;
+
+        match schema::describe_table(conn, schema_name, table) {
+            Ok(Some(result)) => Ok(result),
+            Ok(None) => Err(table_not_found(table)),
+            Err(e) => Err(self.sql_error("Failed to describe table", e)),
+        }
+    }
+
+    /// 列出表上的索引
+    fn list_indexes(&self, conn: &Connection, params: Value) -> Result<Value, ToolError> {
+        let (schema_name, table) = table_params(&params)?;
+        let schema_name = self.local_database(schema_name);
+        self.check_database(conn, schema_name)?;
+
+        match schema::list_indexes(conn, schema_name, table) {
+            Ok(Some(result)) => Ok(result),
+            Ok(None) => Err(table_not_found(table)),
+            Err(e) => Err(self.sql_error("Failed to list indexes", e)),
+        }
+    }
+
+    /// 列出表上的外键
+    fn list_foreign_keys(&self, conn: &Connection, params: Value) -> Result<Value, ToolError> {
+        let (schema_name, table) = table_params(&params)?;
+        let schema_name = self.local_database(schema_name);
+        self.check_database(conn, schema_name)?;
+
+        match schema::list_foreign_keys(conn, schema_name, table) {
+            Ok(Some(result)) => Ok(result),
+            Ok(None) => Err(table_not_found(table)),
+            Err(e) => Err(self.sql_error("Failed to list foreign keys", e)),
+        }
+    }
+
+    /// 列出连接上打开的数据库
+    fn list_databases(&self, conn: &Connection) -> Result<Value, ToolError> {
+        schema::list_databases(conn).map_err(|e| self.sql_error("Failed to list databases", e))
+    }
+}
+
+impl mcp_server_fishcode2025::Router for SQLiteRouter {
+    fn name(&self) -> String {
+        "sqlite".to_string()
+    }
+
+    fn instructions(&self) -> String {
+        "SQLite数据库访问服务，提供执行SQL查询和语句的能力。".to_string()
+    }
+
+    fn capabilities(&self) -> ServerCapabilities {
+        CapabilitiesBuilder::new()
+            .with_tools(true)
+            .with_resources(false, false)
+            .build()
+    }
+
+    fn list_tools(&self) -> Vec<Tool> {
+        let tools = vec![
+            Tool::new(
+                "query".to_string(),
+                "执行SQL查询并返回结果".to_string(),
+                json!({
+                    "type": "object",
+                    "required": ["query"],
+                    "properties": {
+                        "query": {
+                            "type": "string",
+                            "description": "要执行的SQL查询"
+                        },
+                        "params": {
+                            "type": ["array", "object"],
+                            "description": "绑定到查询的参数：按位置绑定的数组，或按名称绑定到:name、@name、$name占位符的对象（键可以省略前缀）"
+                        },
+                        "limit": {
+                            "type": "integer",
+                            "minimum": 1,
+                            "description": "（可选）最多返回的行数"
+                        },
+                        "offset": {
+                            "type": "integer",
+                            "minimum": 0,
+                            "description": "（可选）跳过的行数，默认为0"
+                        },
+                        "cursor": {
+                            "type": "boolean",
+                            "description": "为true时只返回第一页，并在还有剩余结果时返回cursor_id，通过fetch读取后续页面"
+                        },
+                        "format": {
+                            "type": "string",
+                            "enum": ["objects", "arrays", "csv", "tsv", "markdown"],
+                            "description": "结果格式：objects（默认，以列名为键的对象）、arrays（按列顺序的数组，保留重复列名）、csv、tsv或markdown"
+                        },
+                        "typed": {
+                            "type": "boolean",
+                            "description": "为true时每个值带有SQLite存储类型，例如{\"type\": \"blob\", \"base64\": \"...\"}，params也按同样的表示解析"
+                        },
+                        "database": {
+                            "type": "string",
+                            "description": "（可选）把语句限定在该数据库（如main或附加数据库名）中，读写其他数据库的表会被拒绝；也可以是open_database打开的数据库名"
+                        },
+                        "transaction_id": {
+                            "type": "string",
+                            "description": "（可选）在其中执行查询的事务句柄"
+                        },
+                        "timeout_ms": {
+                            "type": "integer",
+                            "minimum": 0,
+                            "description": "（可选）超时毫秒数，超时后语句被中断；0表示不限制，默认使用服务器的--statement-timeout-ms"
+                        }
+                    }
+                }),
+            ),
+            Tool::new(
+                "fetch".to_string(),
+                "从query返回的游标读取下一页结果，读完后游标自动关闭".to_string(),
+                json!({
+                    "type": "object",
+                    "required": ["cursor_id"],
+                    "properties": {
+                        "cursor_id": {
+                            "type": "string",
+                            "description": "query返回的游标ID"
+                        },
+                        "limit": {
+                            "type": "integer",
+                            "minimum": 1,
+                            "description": "（可选）本页最多返回的行数，默认为100"
+                        },
+                        "format": {
+                            "type": "string",
+                            "enum": ["objects", "arrays", "csv", "tsv", "markdown"],
+                            "description": "（可选）结果格式，默认沿用query中指定的格式"
+                        },
+                        "timeout_ms": {
+                            "type": "integer",
+                            "minimum": 0,
+                            "description": "（可选）超时毫秒数，超时后语句被中断；0表示不限制，默认使用服务器的--statement-timeout-ms"
+                        }
+                    }
+                }),
+            ),
+            Tool::new(
+                "explain".to_string(),
+                "返回查询计划树（EXPLAIN QUERY PLAN），标记全表扫描和临时B树排序，语句不会执行".to_string(),
+                json!({
+                    "type": "object",
+                    "required": ["query"],
+                    "properties": {
+                        "query": {
+                            "type": "string",
+                            "description": "要分析的SQL语句"
+                        },
+                        "params": {
+                            "type": ["array", "object"],
+                            "description": "绑定到语句的参数，与query相同"
+                        },
+                        "typed": {
+                            "type": "boolean",
+                            "description": "为true时按类型化表示解析参数"
+                        },
+                        "bytecode": {
+                            "type": "boolean",
+                            "description": "为true时同时返回EXPLAIN输出的虚拟机指令"
+                        },
+                        "database": {
+                            "type": "string",
+                            "description": "（可选）把语句限定在该数据库（如main或附加数据库名）中，读写其他数据库的表会被拒绝；也可以是open_database打开的数据库名"
+                        },
+                        "transaction_id": {
+                            "type": "string",
+                            "description": "（可选）在其中分析语句的事务句柄"
+                        },
+                        "timeout_ms": {
+                            "type": "integer",
+                            "minimum": 0,
+                            "description": "（可选）超时毫秒数；0表示不限制，默认使用服务器的--statement-timeout-ms"
+                        }
+                    }
+                }),
+            ),
+            Tool::new(
+                "suggest_indexes".to_string(),
+                "根据查询计划和sqlite_stat1统计信息为一组查询推荐CREATE INDEX语句，在内存中的结构副本上测试候选索引，不修改数据库".to_string(),
+                json!({
+                    "type": "object",
+                    "required": ["queries"],
+                    "properties": {
+                        "queries": {
+                            "type": "array",
+                            "items": { "type": "string" },
+                            "minItems": 1,
+                            "description": "要分析的SQL查询，可以包含未绑定的参数"
+                        },
+                        "test": {
+                            "type": "boolean",
+                            "description": "为true时单独测试每个建议的索引，返回受益查询修改前后的计划树"
+                        },
+                        "database": {
+                            "type": "string",
+                            "description": "（可选）分析该数据库（如main、附加数据库名或open_database打开的数据库名）中的表，查询应当使用不带数据库名的表名"
+                        },
+                        "transaction_id": {
+                            "type": "string",
+                            "description": "（可选）在其中分析查询的事务句柄"
+                        },
+                        "timeout_ms": {
+                            "type": "integer",
+                            "minimum": 0,
+                            "description": "（可选）超时毫秒数；0表示不限制，默认使用服务器的--statement-timeout-ms"
+                        }
+                    }
+                }),
+            ),
+            Tool::new(
+                "execute".to_string(),
+                "执行SQL语句".to_string(),
+                json!({
+                    "type": "object",
+                    "required": ["statement"],
+                    "properties": {
+                        "statement": {
+                            "type": "string",
+                            "description": "要执行的SQL语句"
+                        },
+                        "params": {
+                            "type": ["array", "object"],
+                            "description": "绑定到语句的参数：按位置绑定的数组，或按名称绑定的对象"
+                        },
+                        "typed": {
+                            "type": "boolean",
+                            "description": "为true时按类型化表示解析参数，例如{\"type\": \"blob\", \"base64\": \"...\"}"
+                        },
+                        "database": {
+                            "type": "string",
+                            "description": "（可选）把语句限定在该数据库（如main或附加数据库名）中，读写其他数据库的表会被拒绝；也可以是open_database打开的数据库名"
+                        },
+                        "transaction_id": {
+                            "type": "string",
+                            "description": "有打开的事务时必须提供该事务的句柄"
+                        },
+                        "dry_run": {
+                            "type": "boolean",
+                            "description": "为true时只预演：执行后回滚，返回受影响的行数以及被修改的行修改前后的值"
+                        },
+                        "preview_rows": {
+                            "type": "integer",
+                            "minimum": 0,
+                            "description": "（可选）预演或审批时每张表最多列出的行数，默认为20，其余的行只计数"
+                        },
+                        "timeout_ms": {
+                            "type": "integer",
+                            "minimum": 0,
+                            "description": "（可选）超时毫秒数，超时后语句被中断；0表示不限制，默认使用服务器的--statement-timeout-ms"
+                        }
+                    }
+                }),
+            ),
+            Tool::new(
+                "executemany".to_string(),
+                "使用不同参数多次执行SQL语句".to_string(),
+                json!({
+                    "type": "object",
+                    "required": ["statement", "params_list"],
+                    "properties": {
+                        "statement": {
+                            "type": "string",
+                            "description": "要执行的SQL语句"
+                        },
+                        "params_list": {
+                            "type": "array",
+                            "description": "绑定到语句的参数列表，每一项都是按位置绑定的数组或按名称绑定的对象"
+                        },
+                        "atomic": {
+                            "type": "boolean",
+                            "description": "为true时在保存点中执行，任何一组参数失败都会撤销之前的写入"
+                        },
+                        "typed": {
+                            "type": "boolean",
+                            "description": "为true时按类型化表示解析参数，例如{\"type\": \"blob\", \"base64\": \"...\"}"
+                        },
+                        "database": {
+                            "type": "string",
+                            "description": "（可选）把语句限定在该数据库（如main或附加数据库名）中，读写其他数据库的表会被拒绝；也可以是open_database打开的数据库名"
+                        },
+                        "transaction_id": {
+                            "type": "string",
+                            "description": "有打开的事务时必须提供该事务的句柄"
+                        },
+                        "dry_run": {
+                            "type": "boolean",
+                            "description": "为true时只预演：执行后回滚，返回受影响的行数以及被修改的行修改前后的值"
+                        },
+                        "preview_rows": {
+                            "type": "integer",
+                            "minimum": 0,
+                            "description": "（可选）预演或审批时每张表最多列出的行数，默认为20，其余的行只计数"
+                        },
+                        "timeout_ms": {
+                            "type": "integer",
+                            "minimum": 0,
+                            "description": "（可选）超时毫秒数，超时后语句被中断；0表示不限制，默认使用服务器的--statement-timeout-ms"
+                        }
+                    }
+                }),
+            ),
+            Tool::new(
+                "executescript".to_string(),
+                "执行SQL脚本".to_string(),
+                json!({
+                    "type": "object",
+                    "required": ["script"],
+                    "properties": {
+                        "script": {
+                            "type": "string",
+                            "description": "要执行的SQL脚本"
+                        },
+                        "database": {
+                            "type": "string",
+                            "description": "（可选）把语句限定在该数据库（如main或附加数据库名）中，读写其他数据库的表会被拒绝；也可以是open_database打开的数据库名"
+                        },
+                        "transaction_id": {
+                            "type": "string",
+                            "description": "有打开的事务时必须提供该事务的句柄"
+                        },
+                        "dry_run": {
+                            "type": "boolean",
+                            "description": "为true时只预演：执行后回滚，返回受影响的行数以及被修改的行修改前后的值"
+                        },
+                        "preview_rows": {
+                            "type": "integer",
+                            "minimum": 0,
+                            "description": "（可选）预演或审批时每张表最多列出的行数，默认为20，其余的行只计数"
+                        },
+                        "timeout_ms": {
+                            "type": "integer",
+                            "minimum": 0,
+                            "description": "（可选）超时毫秒数，超时后语句被中断；0表示不限制，默认使用服务器的--statement-timeout-ms"
+                        }
+                    }
+                }),
+            ),
+            Tool::new(
+                "confirm".to_string(),
+                "确认需要审批的写入并执行；execute、executemany或executescript返回approval_required时使用".to_string(),
+                json!({
+                    "type": "object",
+                    "required": ["approval_token"],
+                    "properties": {
+                        "approval_token": {
+                            "type": "string",
+                            "description": "写入工具返回的审批令牌，只能使用一次，过期后失效"
+                        }
+                    }
+                }),
+            ),
+            Tool::new(
+                "import_csv".to_string(),
+                "把CSV数据批量导入表中：推断列类型，表不存在时创建，并在一个事务中插入所有行".to_string(),
+                json!({
+                    "type": "object",
+                    "required": ["table"],
+                    "properties": {
+                        "table": {
+                            "type": "string",
+                            "description": "目标表名，不存在时按推断的列类型创建"
+                        },
+                        "csv": {
+                            "type": "string",
+                            "description": "CSV文本，与path二选一"
+                        },
+                        "path": {
+                            "type": "string",
+                            "description": "导入目录中的CSV文件路径（UTF-8），与csv二选一；相对路径相对于第一个导入目录"
+                        },
+                        "database": {
+                            "type": "string",
+                            "description": "（可选）目标表所在的数据库，如main、附加数据库名或open_database打开的数据库名，默认为main"
+                        },
+                        "delimiter": {
+                            "type": "string",
+                            "description": "（可选）字段分隔符，单个字符，默认为逗号；制表符分隔的数据使用\t"
+                        },
+                        "header": {
+                            "type": "boolean",
+                            "description": "（可选）第一行是否为列名，默认为true；为false时列名为column1、column2……，已有的表按顺序使用其列"
+                        },
+                        "null_tokens": {
+                            "type": "array",
+                            "items": {"type": "string"},
+                            "description": "（可选）表示NULL的字段值，只匹配没有引号的字段，默认为[\"\"]，即空字段为NULL"
+                        },
+                        "on_conflict": {
+                            "type": "string",
+                            "enum": ["fail", "ignore", "replace"],
+                            "description": "（可选）与已有的行冲突时：fail中止并撤销整个导入（默认），ignore跳过该行，replace替换已有的行"
+                        },
+                        "transaction_id": {
+                            "type": "string",
+                            "description": "有打开的事务时必须提供该事务的句柄"
+                        },
+                        "dry_run": {
+                            "type": "boolean",
+                            "description": "为true时只预演：导入后回滚，返回行数以及将插入的行"
+                        },
+                        "preview_rows": {
+                            "type": "integer",
+                            "minimum": 0,
+                            "description": "（可选）预演或审批时每张表最多列出的行数，默认为20，其余的行只计数"
+                        },
+                        "timeout_ms": {
+                            "type": "integer",
+                            "minimum": 0,
+                            "description": "（可选）超时毫秒数，超时后导入被中断并撤销；0表示不限制，默认使用服务器的--statement-timeout-ms"
+                        }
+                    }
+                }),
+            ),
+            Tool::new(
+                "begin_transaction".to_string(),
+                "开始一个跨调用保持打开的事务，返回事务句柄；事务空闲超时后自动回滚".to_string(),
+                json!({
+                    "type": "object",
+                    "properties": {
+                        "mode": {
+                            "type": "string",
+                            "enum": ["deferred", "immediate", "exclusive"],
+                            "description": "事务模式，默认为deferred"
+                        }
+                    }
+                }),
+            ),
+            Tool::new(
+                "commit".to_string(),
+                "提交事务".to_string(),
+                json!({
+                    "type": "object",
+                    "required": ["transaction_id"],
+                    "properties": {
+                        "transaction_id": {
+                            "type": "string",
+                            "description": "begin_transaction返回的事务句柄"
                         }
                     }
-                }
-                _ => {
-                    return Err(ToolError::InvalidParameters(
-                        "params_list must contain arrays".into(),
-                    ))
-                }
-            }
-        }
-
-        Ok(json!({
-            "rowcount": rows_affected,
-        }))
-    }
-
-    /// 执行SQL脚本
-    async fn executescript(&self, params: Value) -> Result<Value, ToolError> {
-        // 获取脚本参数
-        let script = match params.get("script") {
-            Some(Value::String(s)) => s,
-            _ => {
-                return Err(ToolError::InvalidParameters(
-                    "Missing required parameter: script".into(),
-                ))
-            }
-        };
-
-        // 执行脚本
-        let conn = self.conn.lock().await;
-
-        match conn.execute_batch(script) {
-            Ok(_) => {
-                // 由于execute_batch不返回受影响的行数，我们返回0
-                Ok(json!({
-                    "rowcount": 0,
-                }))
-            }
-            Err(e) => Err(ToolError::ExecutionError(format!(
-                "Failed to execute script: {}",
-                e
-            ))),
-        }
-    }
-}
-
-impl mcp_server_fishcode2025::Router for SQLiteRouter {
-    fn name(&self) -> String {
-        "sqlite".to_string()
-    }
-
-    fn instructions(&self) -> String {
-        "SQLite数据库访问服务，提供执行SQL查询和语句的能力。".to_string()
-    }
-
-    fn capabilities(&self) -> ServerCapabilities {
-        CapabilitiesBuilder::new().with_tools(true).build()
-    }
-
-    fn list_tools(&self) -> Vec<Tool> {
-        vec![
+                }),
+            ),
             Tool::new(
-                "query".to_string(),
-                "执行SQL查询并返回结果".to_string(),
+                "rollback".to_string(),
+                "回滚事务，或者回滚到指定的保存点".to_string(),
                 json!({
                     "type": "object",
-                    "required": ["query"],
+                    "required": ["transaction_id"],
                     "properties": {
-                        "query": {
+                        "transaction_id": {
                             "type": "string",
-                            "description": "要执行的SQL查询"
+                            "description": "begin_transaction返回的事务句柄"
                         },
-                        "params": {
-                            "type": "array",
-                            "description": "绑定到查询的参数"
+                        "savepoint": {
+                            "type": "string",
+                            "description": "（可选）回滚到该保存点，事务保持打开"
                         }
                     }
                 }),
             ),
             Tool::new(
-                "execute".to_string(),
-                "执行SQL语句".to_string(),
+                "savepoint".to_string(),
+                "在事务中建立保存点".to_string(),
+                savepoint_schema(),
+            ),
+            Tool::new(
+                "release".to_string(),
+                "释放保存点".to_string(),
+                savepoint_schema(),
+            ),
+            Tool::new(
+                "list_tables".to_string(),
+                "列出数据库中的表、视图和虚拟表".to_string(),
                 json!({
                     "type": "object",
-                    "required": ["statement"],
                     "properties": {
-                        "statement": {
+                        "database": {
                             "type": "string",
-                            "description": "要执行的SQL语句"
+                            "description": "只列出指定数据库（如main、temp或附加数据库名）中的对象；open_database打开的数据库名表示该数据库的连接"
                         },
-                        "params": {
-                            "type": "array",
-                            "description": "绑定到语句的参数"
+                        "schema": {
+                            "type": "string",
+                            "description": "database的同义参数"
+                        },
+                        "include_system": {
+                            "type": "boolean",
+                            "description": "是否包含sqlite_开头的内部表"
                         }
                     }
                 }),
             ),
             Tool::new(
-                "executemany".to_string(),
-                "使用不同参数多次执行SQL语句".to_string(),
+                "describe_table".to_string(),
+                "返回表的列定义，包括类型、是否可空、默认值、主键序号、隐藏列和生成列".to_string(),
+                table_schema("要描述的表、视图或虚拟表名"),
+            ),
+            Tool::new(
+                "list_indexes".to_string(),
+                "列出表上的索引，包括唯一性、部分索引条件和键列".to_string(),
+                table_schema("要列出索引的表名"),
+            ),
+            Tool::new(
+                "list_foreign_keys".to_string(),
+                "列出表上的外键，包括引用的表和列以及ON UPDATE/ON DELETE动作".to_string(),
+                table_schema("要列出外键的表名"),
+            ),
+            Tool::new(
+                "list_databases".to_string(),
+                "列出打开的数据库，包括main、temp和启动时附加的数据库及其文件路径".to_string(),
                 json!({
                     "type": "object",
-                    "required": ["statement", "params_list"],
+                    "properties": {}
+                }),
+            ),
+            Tool::new(
+                "open_database".to_string(),
+                "在允许的目录中打开数据库文件并命名，之后通过其他工具的database参数使用它".to_string(),
+                json!({
+                    "type": "object",
+                    "required": ["name", "path"],
                     "properties": {
-                        "statement": {
+                        "name": {
                             "type": "string",
-                            "description": "要执行的SQL语句"
+                            "description": "数据库名称，由字母、数字和下划线组成，不能与已有的数据库重名"
                         },
-                        "params_list": {
-                            "type": "array",
-                            "description": "绑定到语句的参数列表"
+                        "path": {
+                            "type": "string",
+                            "description": "数据库文件路径，必须位于允许的目录中；相对路径相对于第一个允许的目录"
+                        },
+                        "read_only": {
+                            "type": "boolean",
+                            "description": "为true时以只读方式打开"
+                        },
+                        "create": {
+                            "type": "boolean",
+                            "description": "为true时文件不存在则创建"
                         }
                     }
                 }),
             ),
             Tool::new(
-                "executescript".to_string(),
-                "执行SQL脚本".to_string(),
+                "close_database".to_string(),
+                "关闭open_database打开的数据库".to_string(),
                 json!({
                     "type": "object",
-                    "required": ["script"],
+                    "required": ["name"],
                     "properties": {
-                        "script": {
+                        "name": {
                             "type": "string",
-                            "description": "要执行的SQL脚本"
+                            "description": "open_database使用的数据库名称"
                         }
                     }
                 }),
             ),
-        ]
+            Tool::new(
+                "list_open_databases".to_string(),
+                "列出open_database打开的数据库以及允许打开的目录".to_string(),
+                json!({
+                    "type": "object",
+                    "properties": {}
+                }),
+            ),
+            Tool::new(
+                "backup".to_string(),
+                "把数据库备份为备份目录中的文件，在风险操作之前保存快照".to_string(),
+                json!({
+                    "type": "object",
+                    "properties": {
+                        "name": {
+                            "type": "string",
+                            "description": "（可选）备份文件名，不能包含目录，文件不能已经存在；默认由数据库名和时间生成"
+                        },
+                        "database": {
+                            "type": "string",
+                            "description": "（可选）要备份的数据库，如main、附加数据库名或open_database打开的数据库名，默认为main"
+                        },
+                        "method": {
+                            "type": "string",
+                            "enum": ["backup", "vacuum"],
+                            "description": "（可选）backup使用在线备份API逐批复制（默认），vacuum使用VACUUM INTO生成紧凑的副本"
+                        },
+                        "pages_per_step": {
+                            "type": "integer",
+                            "description": "（可选）在线备份每一批复制的页数，默认为100"
+                        }
+                    }
+                }),
+            ),
+            Tool::new(
+                "restore".to_string(),
+                "用备份目录中的备份替换数据库的全部内容".to_string(),
+                json!({
+                    "type": "object",
+                    "required": ["name"],
+                    "properties": {
+                        "name": {
+                            "type": "string",
+                            "description": "备份文件名，见list_backups"
+                        },
+                        "database": {
+                            "type": "string",
+                            "description": "（可选）要恢复的数据库，如main、附加数据库名或open_database打开的数据库名，默认为main"
+                        },
+                        "pages_per_step": {
+                            "type": "integer",
+                            "description": "（可选）每一批复制的页数，默认为100"
+                        }
+                    }
+                }),
+            ),
+            Tool::new(
+                "list_backups".to_string(),
+                "列出备份目录中的备份，包括大小和修改时间，最新的在前".to_string(),
+                json!({
+                    "type": "object",
+                    "properties": {}
+                }),
+            ),
+        ];
+
+        tools
+            .into_iter()
+            .filter(|tool| !(self.options.read_only && WRITE_TOOLS.contains(&tool.name.as_str())))
+            .filter(|tool| self.options.approval.is_some() || tool.name != "confirm")
+            .filter(|tool| {
+                self.registry.is_enabled() || !REGISTRY_TOOLS.contains(&tool.name.as_str())
+            })
+            .filter(|tool| {
+                self.options.backup_dir.is_some() || !BACKUP_TOOLS.contains(&tool.name.as_str())
+            })
+            .filter(|tool| self.restore_allowed() || tool.name != "restore")
+            .collect()
     }
 
     fn call_tool(
@@ -446,12 +3309,20 @@ impl mcp_server_fishcode2025::Router for SQLiteRouter {
         Box::pin(async move {
             debug!("Calling tool: {}", tool_name);
 
-            let result = match tool_name.as_str() {
-                "query" => self_clone.query(arguments).await?,
-                "execute" => self_clone.execute(arguments).await?,
-                "executemany" => self_clone.executemany(arguments).await?,
-                "executescript" => self_clone.executescript(arguments).await?,
-                _ => return Err(ToolError::NotFound(format!("Unknown tool: {}", tool_name))),
+            let result = match self_clone.options.audit.clone() {
+                Some(audit) => {
+                    let mut event = self_clone.audit_event(&tool_name, &arguments);
+                    let started = Instant::now();
+                    let result = self_clone.call(tool_name, arguments).await;
+                    event.duration = started.elapsed();
+                    match &result {
+                        Ok(result) => event.row_count = row_count(result),
+                        Err(e) => event.error = Some(e.to_string()),
+                    }
+                    audit.record(event);
+                    result?
+                }
+                None => self_clone.call(tool_name, arguments).await?,
             };
 
             // 使用Content::text方法将JSON转换为字符串
@@ -461,14 +3332,45 @@ impl mcp_server_fishcode2025::Router for SQLiteRouter {
     }
 
     fn list_resources(&self) -> Vec<Resource> {
-        vec![]
+        // Router接口在这里是同步的，只能阻塞等待工作线程；在多线程运行时中先让出当前工作线程，
+        // 避免慢查询拖住同一线程上的其他任务
+        let list = || self.read_worker().run_blocking(resources::list_resources);
+        let listed = match tokio::runtime::Handle::try_current() {
+            Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => {
+                tokio::task::block_in_place(list)
+            }
+            _ => list(),
+        };
+        match listed {
+            Ok(Ok(resources)) => resources,
+            Ok(Err(e)) => {
+                error!("Failed to list resources: {}", e);
+                vec![]
+            }
+            Err(e) => {
+                error!("Failed to list resources: {}", e);
+                vec![]
+            }
+        }
     }
 
     fn read_resource(
         &self,
-        _uri: &str,
+        uri: &str,
     ) -> Pin<Box<dyn Future<Output = Result<String, ResourceError>> + Send + 'static>> {
-        Box::pin(async { Err(ResourceError::NotFound("Resource not found".into())) })
+        let self_clone = self.clone();
+        let uri = uri.to_string();
+        let masking = self.options.masking.clone();
+
+        Box::pin(async move {
+            debug!("Reading resource: {}", uri);
+
+            self_clone
+                .read_worker()
+                .run(move |conn| resources::read_resource(conn, &uri, masking.as_deref()))
+                .await
+                .map_err(|e| ResourceError::ExecutionError(e.to_string()))?
+        })
     }
 
     fn list_prompts(&self) -> Vec<Prompt> {
@@ -486,55 +3388,516 @@ impl mcp_server_fishcode2025::Router for SQLiteRouter {
 impl Clone for SQLiteRouter {
     fn clone(&self) -> Self {
         Self {
-            conn: Arc::clone(&self.conn),
+            writer: self.writer.clone(),
+            readers: self.readers.clone(),
+            options: Arc::clone(&self.options),
+            denials: self.denials.clone(),
+            transactions: self.transactions.clone(),
+            cursors: self.cursors.clone(),
+            read_source: self.read_source.clone(),
+            approvals: self.approvals.clone(),
+            watchdog: self.watchdog.clone(),
+            scope: Arc::clone(&self.scope),
+            registry: self.registry.clone(),
+            opened: self.opened.clone(),
         }
     }
 }
 
-/// 将JSON值转换为SQLite参数
-fn json_value_to_sql(value: &Value) -> Box<dyn ToSql> {
-    match value {
-        Value::Null => Box::new(Option::<String>::None),
-        Value::Bool(b) => Box::new(*b),
-        Value::Number(n) => {
-            if n.is_i64() {
-                Box::new(n.as_i64().unwrap())
-            } else if n.is_u64() {
-                Box::new(n.as_u64().unwrap() as i64)
-            } else {
-                Box::new(n.as_f64().unwrap())
+/// 会话的生命周期，会话的所有路由器副本都被释放后回滚会话仍然打开的事务
+struct SessionScope {
+    id: u64,
+    transactions: Transactions,
+    writer: DbWorker,
+}
+
+impl Drop for SessionScope {
+    fn drop(&mut self) {
+        let transactions = self.transactions.clone();
+        let _ = self
+            .writer
+            .run_detached(move |conn| transactions.abandon(conn));
+    }
+}
+
+/// 预演中出现事务控制语句的错误，`dry_run`为`false`时表示审批前的预演
+fn preview_transaction_error(dry_run: bool, keyword: &str) -> ToolError {
+    if dry_run {
+        ToolError::InvalidParameters(format!(
+            "dry_run cannot contain transaction control statements: {}",
+            keyword
+        ))
+    } else {
+        ToolError::InvalidParameters(format!(
+            "Writes that require approval cannot contain transaction control statements: {}; use begin_transaction and commit instead",
+            keyword
+        ))
+    }
+}
+
+/// 审批令牌的有效期，未启用审批时使用默认值
+fn approval_timeout(options: &RouterOptions) -> Duration {
+    options
+        .approval
+        .as_ref()
+        .map_or(approval::DEFAULT_TOKEN_TIMEOUT, |rules| rules.token_timeout)
+}
+
+/// 工具返回值中的行数：写入工具受影响的行数，或者查询返回的行数
+fn row_count(result: &Value) -> Option<u64> {
+    result.get("rowcount").and_then(Value::as_u64).or_else(|| {
+        result
+            .get("rows")
+            .and_then(Value::as_array)
+            .map(|rows| rows.len() as u64)
+    })
+}
+
+/// 生成进程内唯一的共享缓存内存数据库URI
+fn shared_memory_uri() -> String {
+    format!(
+        "file:mcp-sqlite-{}-{}?mode=memory&cache=shared",
+        std::process::id(),
+        MEMORY_DATABASES.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+/// 在连接上附加命名数据库，只读连接附加的数据库同样只读
+fn attach_databases(
+    conn: &Connection,
+    databases: &[AttachedDatabase],
+) -> Result<(), rusqlite::Error> {
+    for database in databases {
+        conn.execute(
+            "ATTACH DATABASE ?1 AS ?2",
+            params![database.path, database.name],
+        )?;
+    }
+    Ok(())
+}
+
+/// 以只读方式打开数据库连接
+fn open_read_only(db_path: &str) -> Result<Connection, rusqlite::Error> {
+    Connection::open_with_flags(
+        db_path,
+        OpenFlags::SQLITE_OPEN_READ_ONLY
+            | OpenFlags::SQLITE_OPEN_URI
+            | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )
+}
+
+/// 打开只读连接，安装与写连接相同的访问策略和语句监视
+fn open_reader(
+    source: &ReadSource,
+    options: &RouterOptions,
+    denials: &DenialSlot,
+    watchdog: &Watchdog,
+) -> Result<Connection, rusqlite::Error> {
+    let (conn, read_only) = match source {
+        ReadSource::Main {
+            db_path,
+            databases,
+            shared_memory,
+        } => {
+            let conn = open_read_only(db_path)?;
+            attach_databases(&conn, databases)?;
+            // 共享缓存中的读取会被写连接未提交事务的表锁阻塞，内存数据库的只读连接因此读取未提交的数据
+            if *shared_memory {
+                conn.pragma_update(None, "read_uncommitted", true)?;
             }
+            (conn, options.read_only)
         }
-        Value::String(s) => Box::new(s.clone()),
-        Value::Array(_) => Box::new(value.to_string()),
-        Value::Object(_) => Box::new(value.to_string()),
+        ReadSource::Opened { path, read_only } => (
+            Connection::open_with_flags(
+                path,
+                OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+            )?,
+            *read_only,
+        ),
+    };
+    policy::install(&conn, read_only, options.policy.clone(), denials.clone());
+    watchdog.install(&conn);
+    Ok(conn)
+}
+
+/// 语句是否只读，无法预编译的语句视为只读，由只读连接报告错误
+fn is_read_only_sql(conn: &Connection, sql: &str) -> bool {
+    conn.prepare(sql).map_or(true, |stmt| stmt.readonly())
+}
+
+/// 读取可选的字符串参数
+fn optional_str_param<'a>(params: &'a Value, name: &str) -> Result<Option<&'a str>, ToolError> {
+    match params.get(name) {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::String(s)) => Ok(Some(s)),
+        _ => Err(ToolError::InvalidParameters(format!(
+            "{} must be a string",
+            name
+        ))),
     }
 }
 
-/// 从SQLite行中提取值
-fn extract_row_values(row: &Row, column_names: &[String]) -> Value {
-    let mut values = serde_json::Map::new();
+/// 读取可选的`format`参数
+fn output_format_param(params: &Value) -> Result<Option<OutputFormat>, ToolError> {
+    match optional_str_param(params, "format")? {
+        None => Ok(None),
+        Some(format) => OutputFormat::parse(format).map(Some).ok_or_else(|| {
+            ToolError::InvalidParameters(format!(
+                "format must be one of: {}",
+                OutputFormat::NAMES.join(", ")
+            ))
+        }),
+    }
+}
 
-    for (i, name) in column_names.iter().enumerate() {
-        let value = match row.get_ref(i) {
-            Ok(rusqlite::types::ValueRef::Null) => Value::Null,
-            Ok(rusqlite::types::ValueRef::Integer(i)) => Value::Number(i.into()),
-            Ok(rusqlite::types::ValueRef::Real(f)) => {
-                if let Some(n) = serde_json::Number::from_f64(f) {
-                    Value::Number(n)
-                } else {
-                    Value::Null
-                }
+/// 读取可选的`typed`参数，类型化结果只能使用JSON格式输出
+fn typed_param(params: &Value, format: OutputFormat) -> Result<bool, ToolError> {
+    let typed = optional_bool_param(params, "typed")?;
+    if typed && format.is_text() {
+        return Err(typed_text_format_error());
+    }
+    Ok(typed)
+}
+
+fn typed_text_format_error() -> ToolError {
+    ToolError::InvalidParameters(
+        "typed results are only supported with the objects and arrays formats".into(),
+    )
+}
+
+/// 读取可选的非负整数参数
+fn optional_u64_param(params: &Value, name: &str) -> Result<Option<u64>, ToolError> {
+    match params.get(name) {
+        None | Some(Value::Null) => Ok(None),
+        Some(value) => value.as_u64().map(Some).ok_or_else(|| {
+            ToolError::InvalidParameters(format!("{} must be a non-negative integer", name))
+        }),
+    }
+}
+
+/// 读取可选的布尔参数，缺省为`false`
+fn optional_bool_param(params: &Value, name: &str) -> Result<bool, ToolError> {
+    match params.get(name) {
+        None | Some(Value::Null) => Ok(false),
+        Some(Value::Bool(b)) => Ok(*b),
+        _ => Err(ToolError::InvalidParameters(format!(
+            "{} must be a boolean",
+            name
+        ))),
+    }
+}
+
+/// 读取事务工具必需的`transaction_id`参数
+fn required_transaction_id(params: &Value) -> Result<&str, ToolError> {
+    match params.get("transaction_id") {
+        Some(Value::String(id)) => Ok(id),
+        _ => Err(ToolError::InvalidParameters(
+            "Missing required parameter: transaction_id".into(),
+        )),
+    }
+}
+
+/// 读取保存点工具共用的`transaction_id`和`name`参数
+fn savepoint_params(params: &Value) -> Result<(&str, &str), ToolError> {
+    let name = match params.get("name") {
+        Some(Value::String(name)) => name,
+        _ => {
+            return Err(ToolError::InvalidParameters(
+                "Missing required parameter: name".into(),
+            ))
+        }
+    };
+    Ok((required_transaction_id(params)?, name))
+}
+
+/// 保存点工具共用的输入模式
+fn savepoint_schema() -> Value {
+    json!({
+        "type": "object",
+        "required": ["transaction_id", "name"],
+        "properties": {
+            "transaction_id": {
+                "type": "string",
+                "description": "begin_transaction返回的事务句柄"
+            },
+            "name": {
+                "type": "string",
+                "description": "保存点名称"
+            }
+        }
+    })
+}
+
+/// 读取`pages_per_step`参数，必须为正数
+fn pages_per_step_param(params: &Value) -> Result<i32, ToolError> {
+    match optional_u64_param(params, "pages_per_step")? {
+        None => Ok(backup::DEFAULT_PAGES_PER_STEP),
+        Some(pages) => i32::try_from(pages)
+            .ok()
+            .filter(|pages| *pages > 0)
+            .ok_or_else(|| {
+                ToolError::InvalidParameters("pages_per_step must be a positive integer".into())
+            }),
+    }
+}
+
+/// 读取`import_csv`的`delimiter`参数，必须是单个字符，默认为逗号
+fn delimiter_param(params: &Value) -> Result<char, ToolError> {
+    let Some(delimiter) = optional_str_param(params, "delimiter")? else {
+        return Ok(',');
+    };
+    let mut chars = delimiter.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if !matches!(c, '"' | '\n' | '\r') => Ok(c),
+        _ => Err(ToolError::InvalidParameters(
+            "delimiter must be a single character other than a quote or a line break".into(),
+        )),
+    }
+}
+
+/// 读取`import_csv`的`null_tokens`参数，默认只有空字段表示`NULL`
+fn null_tokens_param(params: &Value) -> Result<Vec<String>, ToolError> {
+    match params.get("null_tokens") {
+        None | Some(Value::Null) => Ok(vec![String::new()]),
+        Some(Value::Array(tokens)) => tokens
+            .iter()
+            .map(|token| token.as_str().map(str::to_string))
+            .collect::<Option<_>>()
+            .ok_or_else(|| {
+                ToolError::InvalidParameters("null_tokens must be an array of strings".into())
+            }),
+        Some(_) => Err(ToolError::InvalidParameters(
+            "null_tokens must be an array of strings".into(),
+        )),
+    }
+}
+
+/// `import_csv`插入的列
+///
+/// 新表使用表头中的名称或生成的名称；已有的表按表头中的名称匹配其列（不区分大小写），
+/// 没有表头时按顺序使用表的前几列
+fn import_columns(
+    table: &str,
+    existing: &[String],
+    header: Option<&[import::CsvField]>,
+    count: usize,
+) -> Result<Vec<String>, ToolError> {
+    if existing.is_empty() || header.is_some() {
+        let names = import::column_names(header, count).map_err(ToolError::InvalidParameters)?;
+        if existing.is_empty() {
+            return Ok(names);
+        }
+        return names
+            .iter()
+            .map(|name| {
+                existing
+                    .iter()
+                    .find(|column| column.eq_ignore_ascii_case(name))
+                    .cloned()
+                    .ok_or_else(|| {
+                        ToolError::InvalidParameters(format!(
+                            "Column {} does not exist in table {}",
+                            name, table
+                        ))
+                    })
+            })
+            .collect();
+    }
+    if count > existing.len() {
+        return Err(ToolError::InvalidParameters(format!(
+            "CSV rows have {} fields but table {} has {} columns",
+            count,
+            table,
+            existing.len()
+        )));
+    }
+    Ok(existing[..count].to_vec())
+}
+
+/// 把注册表错误转换为工具错误
+fn registry_error(e: RegistryError) -> ToolError {
+    ToolError::InvalidParameters(e.to_string())
+}
+
+/// 读取内省工具共用的`database`（或`schema`）和`table`参数
+fn table_params(params: &Value) -> Result<(Option<&str>, &str), ToolError> {
+    let table = match params.get("table") {
+        Some(Value::String(t)) => t,
+        _ => {
+            return Err(ToolError::InvalidParameters(
+                "Missing required parameter: table".into(),
+            ))
+        }
+    };
+    Ok((database_param(params)?, table))
+}
+
+/// 读取`database`参数，同时接受同义的`schema`参数
+fn database_param(params: &Value) -> Result<Option<&str>, ToolError> {
+    let database = optional_str_param(params, "database")?;
+    let schema = optional_str_param(params, "schema")?;
+    match (database, schema) {
+        (Some(database), Some(schema)) if !database.eq_ignore_ascii_case(schema) => Err(
+            ToolError::InvalidParameters("database and schema must name the same database".into()),
+        ),
+        _ => Ok(database.or(schema)),
+    }
+}
+
+fn table_not_found(table: &str) -> ToolError {
+    ToolError::InvalidParameters(format!("Table not found: {}", table))
+}
+
+/// 内省工具共用的输入模式
+fn table_schema(table_description: &str) -> Value {
+    json!({
+        "type": "object",
+        "required": ["table"],
+        "properties": {
+            "table": {
+                "type": "string",
+                "description": table_description
+            },
+            "database": {
+                "type": "string",
+                "description": "表所在的数据库（如main、temp、附加数据库名或open_database打开的数据库名），默认按SQLite的查找顺序"
+            },
+            "schema": {
+                "type": "string",
+                "description": "database的同义参数"
             }
-            Ok(rusqlite::types::ValueRef::Text(t)) => {
-                Value::String(String::from_utf8_lossy(t).to_string())
+        }
+    })
+}
+
+/// 按语句的占位符将绑定参数转换为SQLite值
+///
+/// 数组按位置绑定；对象按名称绑定到`:name`、`@name`或`$name`占位符，键可以带或不带前缀。
+/// `label`用于在错误信息中指出出错的参数。
+fn resolve_params(
+    stmt: &Statement<'_>,
+    params: &Value,
+    typed: bool,
+    label: &str,
+) -> Result<Vec<SqlValue>, ToolError> {
+    match params {
+        Value::Array(values) => bind_values(values, typed, label),
+        Value::Object(map) => bind_named_values(stmt, map, typed, label),
+        _ => Err(ToolError::InvalidParameters(format!(
+            "{} must be an array or an object",
+            label
+        ))),
+    }
+}
+
+/// 按占位符名称将参数对象转换为按位置排列的SQLite值
+fn bind_named_values(
+    stmt: &Statement<'_>,
+    map: &serde_json::Map<String, Value>,
+    typed: bool,
+    label: &str,
+) -> Result<Vec<SqlValue>, ToolError> {
+    let mut values = Vec::with_capacity(stmt.parameter_count());
+    let mut used = HashSet::new();
+    let mut missing = Vec::new();
+
+    for i in 1..=stmt.parameter_count() {
+        // 匿名的`?`和编号的`?NNN`占位符只能按位置绑定
+        let name = match stmt.parameter_name(i) {
+            Some(name) if !name.starts_with('?') => name,
+            _ => {
+                return Err(ToolError::InvalidParameters(format!(
+                    "{} is an object, but the statement uses positional placeholders; pass an array instead",
+                    label
+                )))
             }
-            Ok(rusqlite::types::ValueRef::Blob(b)) => Value::String(STANDARD.encode(b)),
-            Err(_) => Value::Null,
         };
 
-        values.insert(name.clone(), value);
+        // 优先匹配带前缀的键，其次匹配去掉前缀的键
+        let key = if map.contains_key(name) {
+            name
+        } else {
+            &name[1..]
+        };
+        match map.get(key) {
+            Some(v) => {
+                used.insert(key);
+                values.push(value::from_json(v, typed).map_err(|e| {
+                    ToolError::InvalidParameters(format!("Invalid {}.{}: {}", label, key, e))
+                })?);
+            }
+            None => missing.push(name),
+        }
+    }
+
+    if !missing.is_empty() {
+        return Err(ToolError::InvalidParameters(format!(
+            "Missing named parameters in {}: {}",
+            label,
+            missing.join(", ")
+        )));
+    }
+
+    let unexpected: Vec<&str> = map
+        .keys()
+        .map(String::as_str)
+        .filter(|key| !used.contains(key))
+        .collect();
+    if !unexpected.is_empty() {
+        return Err(ToolError::InvalidParameters(format!(
+            "Unexpected named parameters in {}: {}",
+            label,
+            unexpected.join(", ")
+        )));
+    }
+
+    Ok(values)
+}
+
+/// 将JSON参数列表转换为SQLite值，`label`用于在错误信息中指出出错的参数
+fn bind_values(values: &[Value], typed: bool, label: &str) -> Result<Vec<SqlValue>, ToolError> {
+    values
+        .iter()
+        .enumerate()
+        .map(|(i, v)| {
+            value::from_json(v, typed).map_err(|e| {
+                ToolError::InvalidParameters(format!("Invalid {}[{}]: {}", label, i, e))
+            })
+        })
+        .collect()
+}
+
+/// 从SQLite行中提取值并按计划脱敏，返回以列名为键的对象
+pub(crate) fn extract_row_values(row: &Row, column_names: &[String], plan: &MaskPlan) -> Value {
+    let mut values = serde_json::Map::new();
+
+    for (i, name) in column_names.iter().enumerate() {
+        if let Some(value) = extract_cell(row, i, false, plan) {
+            values.insert(name.clone(), value);
+        }
     }
 
     Value::Object(values)
 }
+
+/// 从SQLite行中按列顺序提取值并按计划脱敏，`typed`为`true`时每个值带有存储类型
+pub(crate) fn extract_row_cells(
+    row: &Row,
+    column_count: usize,
+    typed: bool,
+    plan: &MaskPlan,
+) -> Vec<Value> {
+    (0..column_count)
+        .filter_map(|i| extract_cell(row, i, typed, plan))
+        .collect()
+}
+
+/// 提取一个值并按计划脱敏，列被删除时返回`None`
+fn extract_cell(row: &Row, index: usize, typed: bool, plan: &MaskPlan) -> Option<Value> {
+    let value = row.get_ref(index).unwrap_or(ValueRef::Null);
+    match plan.apply(index, value) {
+        Masked::Dropped => None,
+        Masked::Unchanged(value) => Some(value::to_json(value, typed)),
+        Masked::Masked(value) => Some(value::to_json(ValueRef::from(&value), typed)),
+    }
+}
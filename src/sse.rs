@@ -0,0 +1,244 @@
+/*!
+ * # HTTP/SSE传输
+ *
+ * 实现MCP的HTTP with SSE传输（协议版本2024-11-05），多个客户端可以共享同一个长期运行的服务器：
+ *
+ * 1. 客户端`GET /sse`打开事件流，服务器创建会话并发送`endpoint`事件，
+ *    其数据是该会话的消息地址，如`/message?sessionId=...`
+ * 2. 客户端向消息地址`POST`JSON-RPC消息，服务器立即返回`202 Accepted`
+ * 3. 响应以`message`事件的形式通过事件流发送给客户端
+ *
+ * 每个事件流对应一个[`Session`]，请求的并发处理和取消与stdio传输相同。
+ * 每个会话使用自己的路由器，显式事务、游标和审批令牌不会被其他会话使用。
+ * 事件流关闭后会话被移除，会话中正在处理的请求被取消。
+ *
+ * 为防止DNS重绑定攻击，带有非本地`Origin`头的请求被拒绝。服务器没有身份验证，默认只应监听本地地址。
+ *
+ * # 示例
+ *
+ * ```no_run
+ * use mcp_sqlite::{sse::serve_sse, SQLiteRouter};
+ * use tokio::net::TcpListener;
+ *
+ * # async fn run() -> std::io::Result<()> {
+ * let router = SQLiteRouter::new(":memory:").unwrap();
+ * let listener = TcpListener::bind("127.0.0.1:8080").await?;
+ * serve_sse(move || router.session(), listener).await
+ * # }
+ * ```
+ */
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use mcp_core_fishcode2025::protocol::JsonRpcMessage;
+use mcp_server_fishcode2025::Router;
+use tokio::{
+    io::{AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader},
+    net::{tcp::OwnedWriteHalf, TcpListener, TcpStream},
+    sync::mpsc,
+};
+use tracing::{debug, info, warn};
+
+use crate::{
+    http::{self, is_local_origin, Request, RequestError, Response},
+    serve::{self, Session},
+};
+
+/// 打开事件流的路径
+pub const SSE_PATH: &str = "/sse";
+
+/// 接收客户端消息的路径
+pub const MESSAGE_PATH: &str = "/message";
+
+/// 事件流上发送保活注释的间隔，用于及时发现已经断开的客户端
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// 会话ID到会话及其事件流发送端的映射
+type Sessions<T> = Arc<Mutex<HashMap<String, SseSession<T>>>>;
+
+/// 一个事件流对应的会话
+#[derive(Clone)]
+struct SseSession<T> {
+    session: Session<T>,
+    tx: mpsc::UnboundedSender<JsonRpcMessage>,
+}
+
+/// 在TCP监听器上运行HTTP/SSE传输的MCP服务器
+///
+/// # 参数
+///
+/// * `new_router` - 为每个新会话创建路由器
+/// * `listener` - 已经绑定的TCP监听器
+///
+/// # 返回值
+///
+/// 只在接受连接失败时返回错误
+pub async fn serve_sse<T, F>(new_router: F, listener: TcpListener) -> std::io::Result<()>
+where
+    T: Router + Clone + Send + Sync + 'static,
+    F: Fn() -> T + Send + Sync + 'static,
+{
+    let new_router = Arc::new(new_router);
+    let sessions: Sessions<T> = Arc::default();
+    info!(address = ?listener.local_addr()?, "SSE server started");
+
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        debug!(peer = %peer, "Accepted connection");
+        tokio::spawn(handle_connection(
+            new_router.clone(),
+            sessions.clone(),
+            stream,
+        ));
+    }
+}
+
+/// 处理一个HTTP连接上的请求
+async fn handle_connection<T, F>(new_router: Arc<F>, sessions: Sessions<T>, stream: TcpStream)
+where
+    T: Router + Clone + Send + Sync + 'static,
+    F: Fn() -> T + Send + Sync + 'static,
+{
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+
+    loop {
+        let request = match http::read_request(&mut reader).await {
+            Ok(Some(request)) => request,
+            Ok(None) | Err(RequestError::Io(_)) => return,
+            Err(RequestError::Invalid(status, message)) => {
+                let _ = Response::text(status, message)
+                    .header("Connection", "close")
+                    .write_to(&mut writer)
+                    .await;
+                return;
+            }
+        };
+        if let Some(origin) = request.header("origin") {
+            if !is_local_origin(origin) {
+                warn!(origin = %origin, "Rejected request from non-local origin");
+                let _ = Response::text(403, "Forbidden origin")
+                    .header("Connection", "close")
+                    .write_to(&mut writer)
+                    .await;
+                return;
+            }
+        }
+
+        let response = match (request.method.as_str(), request.path.as_str()) {
+            ("GET", SSE_PATH) => {
+                stream_events(new_router(), sessions, reader, writer).await;
+                return;
+            }
+            ("POST", MESSAGE_PATH) => post_message(&sessions, &request),
+            (_, SSE_PATH) => Response::text(405, "Method not allowed").header("Allow", "GET"),
+            (_, MESSAGE_PATH) => Response::text(405, "Method not allowed").header("Allow", "POST"),
+            _ => Response::text(404, "Not found"),
+        };
+        if response.write_to(&mut writer).await.is_err() || !request.keep_alive() {
+            return;
+        }
+    }
+}
+
+/// 创建会话并通过事件流发送消息，直到客户端断开连接
+async fn stream_events<T, R>(
+    router: T,
+    sessions: Sessions<T>,
+    mut reader: R,
+    mut writer: OwnedWriteHalf,
+) where
+    T: Router + Clone + Send + Sync + 'static,
+    R: tokio::io::AsyncRead + Unpin,
+{
+    let session_id = serve::new_session_id();
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let session = Session::new(router);
+    lock(&sessions).insert(
+        session_id.clone(),
+        SseSession {
+            session: session.clone(),
+            tx,
+        },
+    );
+    info!(session_id = %session_id, "SSE session opened");
+
+    let head = Response::new(200)
+        .header("Content-Type", "text/event-stream")
+        .header("Cache-Control", "no-cache");
+    let endpoint = format!("{}?sessionId={}", MESSAGE_PATH, session_id);
+    let mut result = head.write_stream_head(&mut writer).await;
+    if result.is_ok() {
+        result = send(&mut writer, &http::sse_event(Some("endpoint"), &endpoint)).await;
+    }
+
+    let mut keep_alive = tokio::time::interval(KEEP_ALIVE_INTERVAL);
+    keep_alive.tick().await;
+    let mut buf = [0u8; 256];
+    while result.is_ok() {
+        tokio::select! {
+            message = rx.recv() => match message {
+                Some(message) => {
+                    let json = match serde_json::to_string(&message) {
+                        Ok(json) => json,
+                        Err(e) => {
+                            warn!(error = %e, "Failed to serialize message");
+                            continue;
+                        }
+                    };
+                    debug!(session_id = %session_id, json = %json, "outgoing message");
+                    result = send(&mut writer, &http::sse_event(Some("message"), &json)).await;
+                }
+                None => break,
+            },
+            // 客户端不会在事件流上发送数据，读到连接结束说明客户端已经断开
+            read = reader.read(&mut buf) => {
+                if !matches!(read, Ok(n) if n > 0) {
+                    break;
+                }
+            }
+            _ = keep_alive.tick() => {
+                result = send(&mut writer, ": keep-alive\n\n").await;
+            }
+        }
+    }
+
+    lock(&sessions).remove(&session_id);
+    session.cancel_all();
+    info!(session_id = %session_id, "SSE session closed");
+}
+
+/// 把客户端消息交给对应的会话
+fn post_message<T>(sessions: &Sessions<T>, request: &Request) -> Response
+where
+    T: Router + Clone + Send + Sync + 'static,
+{
+    let session = match request.query_param("sessionId") {
+        Some(id) => lock(sessions).get(id).cloned(),
+        None => return Response::text(400, "Missing sessionId query parameter"),
+    };
+    let Some(session) = session else {
+        return Response::text(404, "Unknown or closed session");
+    };
+    let Ok(message) = std::str::from_utf8(&request.body) else {
+        return Response::text(400, "Request body must be UTF-8 encoded JSON");
+    };
+
+    session.session.handle(message, &session.tx);
+    Response::text(202, "Accepted")
+}
+
+async fn send<W: AsyncWrite + Unpin>(writer: &mut W, data: &str) -> std::io::Result<()> {
+    writer.write_all(data.as_bytes()).await?;
+    writer.flush().await
+}
+
+fn lock<T>(sessions: &Sessions<T>) -> std::sync::MutexGuard<'_, HashMap<String, SseSession<T>>> {
+    sessions
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
@@ -0,0 +1,347 @@
+/*!
+ * # Streamable HTTP传输
+ *
+ * 实现MCP的Streamable HTTP传输（协议版本2025-03-26）。服务器只有一个端点[`MCP_PATH`]：
+ *
+ * - `POST`：请求体是一条JSON-RPC消息或消息数组。只包含通知时返回`202 Accepted`；
+ *   包含请求时，客户端接受`text/event-stream`则以事件流逐个返回响应并在全部完成后关闭，
+ *   否则等待全部完成后以`application/json`返回
+ * - `DELETE`：结束`Mcp-Session-Id`指定的会话
+ * - `GET`：服务器不会主动发送消息，返回`405 Method Not Allowed`
+ *
+ * `initialize`请求会创建新的会话，会话ID通过响应头`Mcp-Session-Id`返回，之后的请求都必须携带它：
+ * 缺少会话ID返回`400`，会话不存在或已过期返回`404`。每个会话使用工厂函数创建的路由器，
+ * 对于[`SQLiteRouter`](crate::SQLiteRouter)，应使用[`SQLiteRouter::session`](crate::SQLiteRouter::session)
+ * 让会话共享数据库，同时拥有独立的事务和游标。
+ *
+ * 为防止DNS重绑定攻击，带有非本地`Origin`头的请求被拒绝。服务器没有身份验证，默认只应监听本地地址。
+ *
+ * # 示例
+ *
+ * ```no_run
+ * use mcp_sqlite::{streamable::serve_streamable_http, SQLiteRouter};
+ * use tokio::net::TcpListener;
+ *
+ * # async fn run() -> std::io::Result<()> {
+ * let router = SQLiteRouter::new(":memory:").unwrap();
+ * let listener = TcpListener::bind("127.0.0.1:8080").await?;
+ * serve_streamable_http(move || router.session(), listener).await
+ * # }
+ * ```
+ */
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use mcp_core_fishcode2025::protocol::{JsonRpcMessage, PARSE_ERROR};
+use mcp_server_fishcode2025::Router;
+use serde_json::{json, Value};
+use tokio::{
+    io::{AsyncWrite, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+    sync::mpsc,
+};
+use tracing::{debug, info, warn};
+
+use crate::{
+    http::{self, is_local_origin, Request, RequestError, Response},
+    serve::{self, Session},
+};
+
+/// MCP端点的路径
+pub const MCP_PATH: &str = "/mcp";
+
+/// 携带会话ID的请求头和响应头
+pub const SESSION_HEADER: &str = "Mcp-Session-Id";
+
+/// 会话空闲多久后过期
+pub const SESSION_IDLE_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+/// 一个HTTP会话
+struct HttpSession<T> {
+    session: Session<T>,
+    last_activity: Instant,
+}
+
+/// 所有连接共享的服务器状态
+struct State<T, F> {
+    sessions: Mutex<HashMap<String, HttpSession<T>>>,
+    new_router: F,
+}
+
+impl<T, F> State<T, F>
+where
+    T: Router + Clone + Send + Sync + 'static,
+{
+    /// 获取会话表，同时移除已经过期的会话并取消其中正在处理的请求
+    fn sessions(&self) -> std::sync::MutexGuard<'_, HashMap<String, HttpSession<T>>> {
+        let mut sessions = self
+            .sessions
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let expired: Vec<String> = sessions
+            .iter()
+            .filter(|(_, entry)| entry.last_activity.elapsed() >= SESSION_IDLE_TIMEOUT)
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in expired {
+            if let Some(entry) = sessions.remove(&id) {
+                entry.session.cancel_all();
+                info!(session_id = %id, "HTTP session expired");
+            }
+        }
+        sessions
+    }
+}
+
+/// 在TCP监听器上运行Streamable HTTP传输的MCP服务器
+///
+/// # 参数
+///
+/// * `new_router` - 为每个新会话创建路由器
+/// * `listener` - 已经绑定的TCP监听器
+///
+/// # 返回值
+///
+/// 只在接受连接失败时返回错误
+pub async fn serve_streamable_http<T, F>(
+    new_router: F,
+    listener: TcpListener,
+) -> std::io::Result<()>
+where
+    T: Router + Clone + Send + Sync + 'static,
+    F: Fn() -> T + Send + Sync + 'static,
+{
+    let state = Arc::new(State {
+        sessions: Mutex::new(HashMap::new()),
+        new_router,
+    });
+    info!(address = ?listener.local_addr()?, "Streamable HTTP server started");
+
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        debug!(peer = %peer, "Accepted connection");
+        tokio::spawn(handle_connection(state.clone(), stream));
+    }
+}
+
+/// 处理一个HTTP连接上的请求
+async fn handle_connection<T, F>(state: Arc<State<T, F>>, stream: TcpStream)
+where
+    T: Router + Clone + Send + Sync + 'static,
+    F: Fn() -> T + Send + Sync + 'static,
+{
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+
+    loop {
+        let request = match http::read_request(&mut reader).await {
+            Ok(Some(request)) => request,
+            Ok(None) | Err(RequestError::Io(_)) => return,
+            Err(RequestError::Invalid(status, message)) => {
+                let _ = Response::text(status, message)
+                    .header("Connection", "close")
+                    .write_to(&mut writer)
+                    .await;
+                return;
+            }
+        };
+
+        if request.path != MCP_PATH {
+            let response = Response::text(404, "Not found");
+            if response.write_to(&mut writer).await.is_err() || !request.keep_alive() {
+                return;
+            }
+            continue;
+        }
+        if let Some(origin) = request.header("origin") {
+            if !is_local_origin(origin) {
+                warn!(origin = %origin, "Rejected request from non-local origin");
+                let _ = Response::text(403, "Forbidden origin")
+                    .header("Connection", "close")
+                    .write_to(&mut writer)
+                    .await;
+                return;
+            }
+        }
+
+        let response = match request.method.as_str() {
+            "POST" => match post(&state, &request) {
+                Ok(Reply::Stream(head, rx)) => {
+                    stream_responses(&mut writer, head, rx).await;
+                    return;
+                }
+                Ok(Reply::Json(head, rx, batch)) => collect_responses(head, rx, batch).await,
+                Err(response) => response,
+            },
+            "DELETE" => delete(&state, &request),
+            _ => Response::text(405, "Method not allowed").header("Allow", "POST, DELETE"),
+        };
+        if response.write_to(&mut writer).await.is_err() || !request.keep_alive() {
+            return;
+        }
+    }
+}
+
+/// 请求被会话接受后的回复方式
+enum Reply {
+    /// 以事件流逐个发送响应
+    Stream(Response, mpsc::UnboundedReceiver<JsonRpcMessage>),
+    /// 等待全部响应后以JSON发送，`bool`表示请求体是否为数组
+    Json(Response, mpsc::UnboundedReceiver<JsonRpcMessage>, bool),
+}
+
+/// 处理`POST`请求，把消息交给会话
+fn post<T, F>(state: &State<T, F>, request: &Request) -> Result<Reply, Response>
+where
+    T: Router + Clone + Send + Sync + 'static,
+    F: Fn() -> T + Send + Sync + 'static,
+{
+    let accept = request.header("accept").unwrap_or("*/*");
+    let accepts_stream = accept.contains("text/event-stream");
+    if !accepts_stream && !accept.contains("application/json") && !accept.contains("*/*") {
+        return Err(Response::text(
+            406,
+            "Accept must include application/json or text/event-stream",
+        ));
+    }
+    if let Some(content_type) = request.header("content-type") {
+        if !content_type.starts_with("application/json") {
+            return Err(Response::text(415, "Content-Type must be application/json"));
+        }
+    }
+
+    let body: Value = serde_json::from_slice(&request.body).map_err(|e| {
+        Response::json(
+            400,
+            &json!({
+                "jsonrpc": "2.0",
+                "id": null,
+                "error": { "code": PARSE_ERROR, "message": format!("Invalid JSON: {}", e) }
+            }),
+        )
+    })?;
+    let batch = body.is_array();
+    let messages = match body {
+        Value::Array(messages) if messages.is_empty() => {
+            return Err(Response::text(400, "Empty batch"));
+        }
+        Value::Array(messages) => messages,
+        message => vec![message],
+    };
+
+    // initialize请求开始新的会话，其他请求必须属于已有的会话
+    let initialize = messages
+        .iter()
+        .any(|message| message.get("method") == Some(&json!("initialize")));
+    let (session_id, session) = if initialize {
+        let session_id = serve::new_session_id();
+        let session = Session::new((state.new_router)());
+        state.sessions().insert(
+            session_id.clone(),
+            HttpSession {
+                session: session.clone(),
+                last_activity: Instant::now(),
+            },
+        );
+        info!(session_id = %session_id, "HTTP session opened");
+        (session_id, session)
+    } else {
+        let session_id = request
+            .header(SESSION_HEADER)
+            .ok_or_else(|| Response::text(400, "Missing Mcp-Session-Id header"))?;
+        let mut sessions = state.sessions();
+        let entry = sessions
+            .get_mut(session_id)
+            .ok_or_else(|| Response::text(404, "Unknown or expired session"))?;
+        entry.last_activity = Instant::now();
+        (session_id.to_string(), entry.session.clone())
+    };
+
+    let (tx, rx) = mpsc::unbounded_channel();
+    let mut expects_reply = false;
+    for message in messages {
+        expects_reply |= session.handle_value(message, &tx);
+    }
+    // 请求任务持有发送端的副本，全部完成后接收端随之结束
+    drop(tx);
+
+    if !expects_reply {
+        return Err(Response::new(202).header(SESSION_HEADER, session_id));
+    }
+    if accepts_stream {
+        let head = Response::new(200)
+            .header("Content-Type", "text/event-stream")
+            .header("Cache-Control", "no-cache")
+            .header(SESSION_HEADER, session_id);
+        Ok(Reply::Stream(head, rx))
+    } else {
+        let head = Response::new(200).header(SESSION_HEADER, session_id);
+        Ok(Reply::Json(head, rx, batch))
+    }
+}
+
+/// 以事件流发送响应，所有请求处理完毕后关闭连接
+async fn stream_responses<W>(
+    writer: &mut W,
+    head: Response,
+    mut rx: mpsc::UnboundedReceiver<JsonRpcMessage>,
+) where
+    W: AsyncWrite + Unpin,
+{
+    if head.write_stream_head(writer).await.is_err() {
+        return;
+    }
+    while let Some(message) = rx.recv().await {
+        let Ok(json) = serde_json::to_string(&message) else {
+            continue;
+        };
+        debug!(json = %json, "outgoing message");
+        let event = http::sse_event(Some("message"), &json);
+        if writer.write_all(event.as_bytes()).await.is_err() || writer.flush().await.is_err() {
+            // 按照规范，断开连接不代表客户端取消请求，请求继续执行
+            return;
+        }
+    }
+}
+
+/// 等待所有响应并以JSON返回
+async fn collect_responses(
+    head: Response,
+    mut rx: mpsc::UnboundedReceiver<JsonRpcMessage>,
+    batch: bool,
+) -> Response {
+    let mut responses = Vec::new();
+    while let Some(message) = rx.recv().await {
+        responses.push(message);
+    }
+    let body = match (batch, responses.len()) {
+        (false, 1) => serde_json::to_vec(&responses[0]),
+        _ => serde_json::to_vec(&responses),
+    };
+    match body {
+        Ok(body) => head.header("Content-Type", "application/json").body(body),
+        Err(e) => Response::text(500, format!("Failed to serialize response: {}", e)),
+    }
+}
+
+/// 处理`DELETE`请求，结束会话并取消其中正在处理的请求
+fn delete<T, F>(state: &State<T, F>, request: &Request) -> Response
+where
+    T: Router + Clone + Send + Sync + 'static,
+{
+    let Some(session_id) = request.header(SESSION_HEADER) else {
+        return Response::text(400, "Missing Mcp-Session-Id header");
+    };
+    match state.sessions().remove(session_id) {
+        Some(entry) => {
+            entry.session.cancel_all();
+            info!(session_id = %session_id, "HTTP session closed");
+            Response::new(204)
+        }
+        None => Response::text(404, "Unknown or expired session"),
+    }
+}
@@ -0,0 +1,369 @@
+/*!
+ * # 显式事务
+ *
+ * 本模块管理跨多次工具调用保持打开的事务。事务直接在共享的数据库连接上执行
+ * `BEGIN`/`COMMIT`/`ROLLBACK`/`SAVEPOINT`/`RELEASE`，并以事务句柄（`transaction_id`）标识：
+ *
+ * - 同一时刻最多只有一个打开的事务
+ * - 事务打开期间，修改数据库的调用必须携带该事务的句柄，避免无关的写入被卷入事务
+ * - 事务空闲超过指定时间后会被自动回滚，防止被遗弃的事务一直持有写锁
+ * - 多个客户端会话共享连接时，事务属于开始它的会话（见[`Transactions::for_session`]），
+ *   其他会话在事务结束前不能访问该连接
+ *
+ * 脚本中直接执行的`COMMIT`或`ROLLBACK`也会被识别：每次访问前都会根据连接的自动提交状态同步事务状态。
+ */
+
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use rusqlite::Connection;
+
+use crate::schema::quote_identifier;
+
+/// 事务默认的空闲超时时间
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// 事务模式，对应`BEGIN DEFERRED`/`IMMEDIATE`/`EXCLUSIVE`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TransactionMode {
+    /// 第一次读写时才获取锁
+    #[default]
+    Deferred,
+    /// 立即获取写锁
+    Immediate,
+    /// 立即获取排他锁
+    Exclusive,
+}
+
+impl TransactionMode {
+    /// 从字符串解析事务模式，不区分大小写
+    pub fn parse(mode: &str) -> Option<Self> {
+        match mode.to_ascii_lowercase().as_str() {
+            "deferred" => Some(Self::Deferred),
+            "immediate" => Some(Self::Immediate),
+            "exclusive" => Some(Self::Exclusive),
+            _ => None,
+        }
+    }
+
+    fn begin_sql(self) -> &'static str {
+        match self {
+            Self::Deferred => "BEGIN DEFERRED",
+            Self::Immediate => "BEGIN IMMEDIATE",
+            Self::Exclusive => "BEGIN EXCLUSIVE",
+        }
+    }
+}
+
+/// 事务操作错误
+#[derive(Debug)]
+pub enum TransactionError {
+    /// 调用方使用事务的方式不正确，例如句柄不匹配或保存点不存在
+    Invalid(String),
+    /// SQLite执行失败
+    Sql(rusqlite::Error),
+}
+
+impl From<rusqlite::Error> for TransactionError {
+    fn from(e: rusqlite::Error) -> Self {
+        Self::Sql(e)
+    }
+}
+
+/// 当前打开的事务
+#[derive(Debug)]
+struct OpenTransaction {
+    id: String,
+    session: u64,
+    savepoints: Vec<String>,
+    last_activity: Instant,
+}
+
+/// 事务管理器
+///
+/// 只记录事务状态，所有方法都要求调用方已经持有数据库连接的锁，从而保证状态与连接一致
+#[derive(Debug, Clone)]
+pub struct Transactions {
+    current: Arc<Mutex<Option<OpenTransaction>>>,
+    next_id: Arc<AtomicU64>,
+    next_session: Arc<AtomicU64>,
+    session: u64,
+    idle_timeout: Duration,
+}
+
+impl Transactions {
+    /// 创建事务管理器
+    ///
+    /// # 参数
+    ///
+    /// * `idle_timeout` - 事务空闲多久后自动回滚
+    pub fn new(idle_timeout: Duration) -> Self {
+        Self {
+            current: Arc::new(Mutex::new(None)),
+            next_id: Arc::new(AtomicU64::new(1)),
+            next_session: Arc::new(AtomicU64::new(1)),
+            session: 0,
+            idle_timeout,
+        }
+    }
+
+    /// 为新的会话创建事务管理器，与原管理器共享同一个连接上的事务状态
+    ///
+    /// 会话只能使用和结束自己开始的事务；其他会话的事务打开期间，任何访问检查都会失败
+    pub fn for_session(&self) -> Self {
+        Self {
+            current: self.current.clone(),
+            next_id: self.next_id.clone(),
+            next_session: self.next_session.clone(),
+            session: self.next_session.fetch_add(1, Ordering::Relaxed),
+            idle_timeout: self.idle_timeout,
+        }
+    }
+
+    /// 事务的空闲超时时间
+    pub fn idle_timeout(&self) -> Duration {
+        self.idle_timeout
+    }
+
+    /// 检查调用方能否在当前事务状态下访问数据库
+    ///
+    /// 没有打开的事务时，调用方不能携带句柄；有打开的事务时，`require_handle`为`true`
+    /// 的调用（即修改数据库的调用）必须携带匹配的句柄。检查通过后会刷新事务的活动时间。
+    pub fn check_access(
+        &self,
+        conn: &Connection,
+        transaction_id: Option<&str>,
+        require_handle: bool,
+    ) -> Result<(), TransactionError> {
+        let mut current = self.lock(conn);
+        match (current.as_mut(), transaction_id) {
+            (Some(tx), _) if tx.session != self.session => Err(TransactionError::Invalid(format!(
+                "Transaction {} of another session is in progress; try again after it ends",
+                tx.id
+            ))),
+            (None, None) => Ok(()),
+            (None, Some(id)) => Err(unknown_transaction(id)),
+            (Some(tx), Some(id)) if tx.id == id => {
+                tx.last_activity = Instant::now();
+                Ok(())
+            }
+            (Some(_), Some(id)) => Err(unknown_transaction(id)),
+            (Some(tx), None) if require_handle => Err(TransactionError::Invalid(format!(
+                "Transaction {} is in progress; pass its transaction_id to modify the database",
+                tx.id
+            ))),
+            (Some(_), None) => Ok(()),
+        }
+    }
+
+    /// 开始一个新事务并返回其句柄
+    pub fn begin(
+        &self,
+        conn: &Connection,
+        mode: TransactionMode,
+    ) -> Result<String, TransactionError> {
+        let mut current = self.lock(conn);
+        if let Some(tx) = current.as_ref() {
+            let owner = if tx.session == self.session {
+                ""
+            } else {
+                " of another session"
+            };
+            return Err(TransactionError::Invalid(format!(
+                "Transaction {}{} is already in progress",
+                tx.id, owner
+            )));
+        }
+        if !conn.is_autocommit() {
+            return Err(TransactionError::Invalid(
+                "A transaction started by a SQL statement is already in progress".into(),
+            ));
+        }
+
+        conn.execute_batch(mode.begin_sql())?;
+
+        let id = format!("tx-{}", self.next_id.fetch_add(1, Ordering::Relaxed));
+        *current = Some(OpenTransaction {
+            id: id.clone(),
+            session: self.session,
+            savepoints: Vec::new(),
+            last_activity: Instant::now(),
+        });
+        Ok(id)
+    }
+
+    /// 提交事务
+    pub fn commit(&self, conn: &Connection, transaction_id: &str) -> Result<(), TransactionError> {
+        let mut current = self.lock(conn);
+        self.check_id(current.as_ref(), transaction_id)?;
+
+        conn.execute_batch("COMMIT")?;
+        *current = None;
+        Ok(())
+    }
+
+    /// 回滚整个事务，或者回滚到指定的保存点
+    ///
+    /// 回滚到保存点后事务仍然打开，该保存点之后建立的保存点被丢弃
+    pub fn rollback(
+        &self,
+        conn: &Connection,
+        transaction_id: &str,
+        savepoint: Option<&str>,
+    ) -> Result<(), TransactionError> {
+        let mut current = self.lock(conn);
+        let tx = self.check_id(current.as_mut(), transaction_id)?;
+
+        match savepoint {
+            Some(name) => {
+                let pos = find_savepoint(tx, name)?;
+                conn.execute_batch(&format!("ROLLBACK TO {}", quote_identifier(name)))?;
+                tx.savepoints.truncate(pos + 1);
+                tx.last_activity = Instant::now();
+            }
+            None => {
+                conn.execute_batch("ROLLBACK")?;
+                *current = None;
+            }
+        }
+        Ok(())
+    }
+
+    /// 在事务中建立保存点
+    pub fn savepoint(
+        &self,
+        conn: &Connection,
+        transaction_id: &str,
+        name: &str,
+    ) -> Result<(), TransactionError> {
+        let mut current = self.lock(conn);
+        let tx = self.check_id(current.as_mut(), transaction_id)?;
+
+        conn.execute_batch(&format!("SAVEPOINT {}", quote_identifier(name)))?;
+        tx.savepoints.push(name.to_string());
+        tx.last_activity = Instant::now();
+        Ok(())
+    }
+
+    /// 释放保存点，该保存点之后建立的保存点也一并释放
+    pub fn release(
+        &self,
+        conn: &Connection,
+        transaction_id: &str,
+        name: &str,
+    ) -> Result<(), TransactionError> {
+        let mut current = self.lock(conn);
+        let tx = self.check_id(current.as_mut(), transaction_id)?;
+
+        let pos = find_savepoint(tx, name)?;
+        conn.execute_batch(&format!("RELEASE {}", quote_identifier(name)))?;
+        tx.savepoints.truncate(pos);
+        tx.last_activity = Instant::now();
+        Ok(())
+    }
+
+    /// 返回事务的保存点列表，事务不存在时返回`None`
+    pub fn savepoints(&self, conn: &Connection, transaction_id: &str) -> Option<Vec<String>> {
+        let current = self.lock(conn);
+        current
+            .as_ref()
+            .filter(|tx| tx.id == transaction_id)
+            .map(|tx| tx.savepoints.clone())
+    }
+
+    /// 事务的空闲截止时间，事务已结束时返回`None`
+    pub fn deadline(&self, transaction_id: &str) -> Option<Instant> {
+        let current = self.current.lock().ok()?;
+        current
+            .as_ref()
+            .filter(|tx| tx.id == transaction_id)
+            .map(|tx| tx.last_activity + self.idle_timeout)
+    }
+
+    /// 如果事务已经空闲超时则将其回滚
+    ///
+    /// # 返回值
+    ///
+    /// 事务仍然打开时返回新的截止时间；事务已结束或刚被回滚时返回`None`
+    pub fn expire(&self, conn: &Connection, transaction_id: &str) -> Option<Instant> {
+        let mut current = self.lock(conn);
+        let tx = current.as_ref().filter(|tx| tx.id == transaction_id)?;
+
+        let deadline = tx.last_activity + self.idle_timeout;
+        if Instant::now() < deadline {
+            return Some(deadline);
+        }
+
+        if let Err(e) = conn.execute_batch("ROLLBACK") {
+            tracing::error!(
+                "Failed to roll back idle transaction {}: {}",
+                transaction_id,
+                e
+            );
+        }
+        *current = None;
+        None
+    }
+
+    /// 回滚本会话仍然打开的事务，用于会话结束时
+    pub fn abandon(&self, conn: &Connection) {
+        let mut current = self.lock(conn);
+        let Some(tx) = current.as_ref().filter(|tx| tx.session == self.session) else {
+            return;
+        };
+
+        if let Err(e) = conn.execute_batch("ROLLBACK") {
+            tracing::error!("Failed to roll back abandoned transaction {}: {}", tx.id, e);
+        }
+        *current = None;
+    }
+
+    /// 检查句柄是否对应本会话当前打开的事务
+    fn check_id<T: std::ops::Deref<Target = OpenTransaction>>(
+        &self,
+        tx: Option<T>,
+        transaction_id: &str,
+    ) -> Result<T, TransactionError> {
+        match tx {
+            Some(tx) if tx.id == transaction_id && tx.session == self.session => Ok(tx),
+            _ => Err(unknown_transaction(transaction_id)),
+        }
+    }
+
+    /// 获取事务状态，并根据连接的自动提交状态丢弃已被SQL语句结束的事务
+    fn lock(&self, conn: &Connection) -> std::sync::MutexGuard<'_, Option<OpenTransaction>> {
+        let mut current = self
+            .current
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if current.is_some() && conn.is_autocommit() {
+            *current = None;
+        }
+        current
+    }
+}
+
+impl Default for Transactions {
+    fn default() -> Self {
+        Self::new(DEFAULT_IDLE_TIMEOUT)
+    }
+}
+
+fn find_savepoint(tx: &OpenTransaction, name: &str) -> Result<usize, TransactionError> {
+    tx.savepoints
+        .iter()
+        .rposition(|s| s.eq_ignore_ascii_case(name))
+        .ok_or_else(|| TransactionError::Invalid(format!("Unknown savepoint: {}", name)))
+}
+
+fn unknown_transaction(transaction_id: &str) -> TransactionError {
+    TransactionError::Invalid(format!(
+        "Unknown or expired transaction: {}",
+        transaction_id
+    ))
+}
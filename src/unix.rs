@@ -0,0 +1,181 @@
+/*!
+ * # Unix域套接字传输
+ *
+ * 在Unix域套接字上接受多个客户端，适合与其他进程部署在同一台机器上的场景。每个连接上的消息格式与stdio传输相同
+ * （按行分隔的JSON-RPC消息），由[`serve_until`]处理，路由器由工厂函数为每个连接单独创建。
+ * 对于[`SQLiteRouter`](crate::SQLiteRouter)，应使用[`SQLiteRouter::session`](crate::SQLiteRouter::session)，
+ * 连接之间共享数据库，同时拥有独立的事务和游标，连接关闭时回滚其中未结束的事务。
+ *
+ * `shutdown`完成后服务器停止接受新连接，所有连接停止读取新的消息，正在执行的语句执行完毕并写出响应后才返回。
+ *
+ * # 示例
+ *
+ * ```no_run
+ * use mcp_sqlite::{unix::{bind, serve_unix}, SQLiteRouter};
+ *
+ * # async fn run() -> std::io::Result<()> {
+ * let router = SQLiteRouter::new(":memory:").unwrap();
+ * let listener = bind("/run/mcp-sqlite.sock".as_ref(), 0o660)?;
+ * serve_unix(move || router.session(), listener, async {
+ *     let _ = tokio::signal::ctrl_c().await;
+ * })
+ * .await
+ * # }
+ * ```
+ */
+
+use std::{
+    fs::{self, Permissions},
+    future::Future,
+    io,
+    os::unix::fs::{FileTypeExt, PermissionsExt},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use mcp_server_fishcode2025::Router;
+use tokio::{net::UnixListener, sync::watch, task::JoinSet};
+use tracing::{debug, info, warn};
+
+use crate::serve::serve_until;
+
+/// 接受连接失败（如文件描述符耗尽）后重试前的等待时间
+const ACCEPT_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// 绑定Unix域套接字并设置文件权限
+///
+/// 路径上已经存在的套接字文件如果没有进程在监听，会被视为上次运行遗留的文件并删除。
+/// 套接字文件在创建时就具有`mode`指定的权限，其他用户没有机会在设置权限之前连接。
+///
+/// # 参数
+///
+/// * `path` - 套接字文件路径
+/// * `mode` - 套接字文件的权限，如`0o660`
+pub fn bind(path: &Path, mode: u32) -> io::Result<UnixListener> {
+    if let Ok(metadata) = fs::symlink_metadata(path) {
+        if !metadata.file_type().is_socket() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} exists and is not a socket", path.display()),
+            ));
+        }
+        if std::os::unix::net::UnixStream::connect(path).is_ok() {
+            return Err(io::Error::new(
+                io::ErrorKind::AddrInUse,
+                format!("Another server is listening on {}", path.display()),
+            ));
+        }
+        fs::remove_file(path)?;
+    }
+
+    let listener = {
+        let _umask = Umask::set(!mode & 0o777);
+        UnixListener::bind(path)?
+    };
+    fs::set_permissions(path, Permissions::from_mode(mode))?;
+    Ok(listener)
+}
+
+/// 在作用域内设置进程的文件创建掩码，离开作用域时恢复
+///
+/// 掩码属于整个进程，因此只在创建套接字文件的短暂期间设置；期间其他线程创建的文件权限只会更严格
+struct Umask(libc::mode_t);
+
+impl Umask {
+    fn set(mask: u32) -> Self {
+        // SAFETY: umask只修改进程的文件创建掩码并返回原来的值，不会失败
+        Self(unsafe { libc::umask(mask as libc::mode_t) })
+    }
+}
+
+impl Drop for Umask {
+    fn drop(&mut self) {
+        // SAFETY: 同上
+        unsafe {
+            libc::umask(self.0);
+        }
+    }
+}
+
+/// 在Unix域套接字上运行MCP服务器，直到`shutdown`完成且所有连接处理完正在执行的请求
+///
+/// 返回前删除套接字文件。
+///
+/// # 参数
+///
+/// * `new_router` - 为每个连接创建路由器
+/// * `listener` - 已经绑定的监听器，见[`bind`]
+/// * `shutdown` - 完成时开始平滑关闭
+///
+/// # 返回值
+///
+/// 只在读取监听地址失败时返回错误；接受连接失败时记录日志，稍后继续接受
+pub async fn serve_unix<T, F, S>(
+    new_router: F,
+    listener: UnixListener,
+    shutdown: S,
+) -> io::Result<()>
+where
+    T: Router + Clone + Send + Sync + 'static,
+    F: Fn() -> T,
+    S: Future<Output = ()>,
+{
+    let _socket_file = listener
+        .local_addr()?
+        .as_pathname()
+        .map(|path| SocketFile(path.to_path_buf()));
+    info!(address = ?listener.local_addr()?, "Unix socket server started");
+
+    let (stop_tx, stop_rx) = watch::channel(false);
+    let mut connections = JoinSet::new();
+    tokio::pin!(shutdown);
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                // 文件描述符耗尽等错误是暂时的，返回会中断所有正在处理的连接
+                let stream = match accepted {
+                    Ok((stream, _)) => stream,
+                    Err(e) => {
+                        warn!(error = %e, "Failed to accept connection");
+                        tokio::time::sleep(ACCEPT_RETRY_DELAY).await;
+                        continue;
+                    }
+                };
+                debug!("Accepted connection");
+                let router = new_router();
+                let mut stop = stop_rx.clone();
+                connections.spawn(async move {
+                    let (reader, writer) = stream.into_split();
+                    let stopped = async move {
+                        let _ = stop.wait_for(|stopped| *stopped).await;
+                    };
+                    if let Err(e) = serve_until(router, reader, writer, stopped).await {
+                        warn!(error = %e, "Connection closed with error");
+                    }
+                });
+            }
+            // 及时回收已经结束的连接
+            Some(_) = connections.join_next(), if !connections.is_empty() => {}
+            () = &mut shutdown => break,
+        }
+    }
+
+    drop(listener);
+    info!(
+        connections = connections.len(),
+        "Shutting down, waiting for in-flight requests"
+    );
+    let _ = stop_tx.send(true);
+    while connections.join_next().await.is_some() {}
+    info!("Unix socket server stopped");
+    Ok(())
+}
+
+/// 离开作用域时删除套接字文件
+struct SocketFile(PathBuf);
+
+impl Drop for SocketFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
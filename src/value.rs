@@ -0,0 +1,193 @@
+/*!
+ * # SQLite值与JSON之间的转换
+ *
+ * 默认情况下查询结果中的值直接转换为JSON：整数和实数转换为数字，文本转换为字符串，
+ * BLOB转换为base64字符串，非有限的实数（`NaN`、`Infinity`）转换为`null`。
+ *
+ * 启用类型化模式（`typed: true`）后，每个值都带有SQLite存储类型，不会丢失信息：
+ *
+ * | 存储类型 | JSON表示 |
+ * | --- | --- |
+ * | NULL | `{"type": "null"}` |
+ * | INTEGER | `{"type": "integer", "value": 42}` |
+ * | REAL | `{"type": "real", "value": 1.5}`，非有限值为`"NaN"`、`"Infinity"`或`"-Infinity"` |
+ * | TEXT | `{"type": "text", "value": "abc"}` |
+ * | BLOB | `{"type": "blob", "base64": "AAEC"}` |
+ *
+ * 类型化模式下绑定参数也使用同样的表示，`integer`的`value`可以是数字或十进制字符串。
+ * 普通JSON标量在两种模式下都可以作为参数；超出64位有符号整数范围的整数会被拒绝，而不是被截断。
+ */
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use rusqlite::types::{Value as SqlValue, ValueRef};
+use serde_json::{json, Value};
+
+/// 将SQLite值转换为JSON
+///
+/// # 参数
+///
+/// * `value` - SQLite值
+/// * `typed` - 是否输出带存储类型的表示
+///
+/// # 示例
+///
+/// ```
+/// use mcp_sqlite::value::to_json;
+/// use rusqlite::types::ValueRef;
+/// use serde_json::json;
+///
+/// assert_eq!(to_json(ValueRef::Blob(&[0, 1, 2]), false), json!("AAEC"));
+/// assert_eq!(
+///     to_json(ValueRef::Blob(&[0, 1, 2]), true),
+///     json!({"type": "blob", "base64": "AAEC"})
+/// );
+/// assert_eq!(
+///     to_json(ValueRef::Real(f64::INFINITY), true),
+///     json!({"type": "real", "value": "Infinity"})
+/// );
+/// ```
+pub fn to_json(value: ValueRef<'_>, typed: bool) -> Value {
+    if !typed {
+        return match value {
+            ValueRef::Null => Value::Null,
+            ValueRef::Integer(i) => Value::Number(i.into()),
+            ValueRef::Real(f) => serde_json::Number::from_f64(f)
+                .map(Value::Number)
+                .unwrap_or(Value::Null),
+            ValueRef::Text(t) => Value::String(String::from_utf8_lossy(t).to_string()),
+            ValueRef::Blob(b) => Value::String(STANDARD.encode(b)),
+        };
+    }
+
+    match value {
+        ValueRef::Null => json!({ "type": "null" }),
+        ValueRef::Integer(i) => json!({ "type": "integer", "value": i }),
+        ValueRef::Real(f) => {
+            let value = match serde_json::Number::from_f64(f) {
+                Some(n) => Value::Number(n),
+                None if f.is_nan() => json!("NaN"),
+                None if f > 0.0 => json!("Infinity"),
+                None => json!("-Infinity"),
+            };
+            json!({ "type": "real", "value": value })
+        }
+        ValueRef::Text(t) => json!({ "type": "text", "value": String::from_utf8_lossy(t) }),
+        ValueRef::Blob(b) => json!({ "type": "blob", "base64": STANDARD.encode(b) }),
+    }
+}
+
+/// 将JSON参数转换为SQLite值
+///
+/// 布尔值绑定为`0`或`1`，数组和对象绑定为JSON文本。类型化模式下对象必须是带存储类型的表示。
+///
+/// # 参数
+///
+/// * `value` - JSON参数
+/// * `typed` - 是否按带存储类型的表示解析对象
+///
+/// # 返回值
+///
+/// 参数无法无损转换时返回错误说明
+///
+/// # 示例
+///
+/// ```
+/// use mcp_sqlite::value::from_json;
+/// use rusqlite::types::Value;
+/// use serde_json::json;
+///
+/// assert_eq!(
+///     from_json(&json!({"type": "blob", "base64": "AAEC"}), true),
+///     Ok(Value::Blob(vec![0, 1, 2]))
+/// );
+/// assert_eq!(
+///     from_json(&json!({"type": "real", "value": 1}), true),
+///     Ok(Value::Real(1.0))
+/// );
+/// assert!(from_json(&json!(u64::MAX), false).is_err());
+/// ```
+pub fn from_json(value: &Value, typed: bool) -> Result<SqlValue, String> {
+    match value {
+        Value::Null => Ok(SqlValue::Null),
+        Value::Bool(b) => Ok(SqlValue::Integer(*b as i64)),
+        Value::Number(n) => {
+            if let Some(i) = n.as_i64() {
+                Ok(SqlValue::Integer(i))
+            } else if n.is_u64() {
+                Err(out_of_range(n))
+            } else {
+                Ok(SqlValue::Real(n.as_f64().unwrap_or(f64::NAN)))
+            }
+        }
+        Value::String(s) => Ok(SqlValue::Text(s.clone())),
+        Value::Object(_) if typed => from_typed_json(value),
+        Value::Array(_) | Value::Object(_) => Ok(SqlValue::Text(value.to_string())),
+    }
+}
+
+/// 解析带存储类型的参数
+fn from_typed_json(value: &Value) -> Result<SqlValue, String> {
+    let kind = match value.get("type") {
+        Some(Value::String(kind)) => kind.as_str(),
+        _ => return Err("typed value must have a string \"type\" field".into()),
+    };
+    let field = |name: &str| {
+        value
+            .get(name)
+            .ok_or_else(|| format!("typed {} value must have a \"{}\" field", kind, name))
+    };
+
+    match kind {
+        "null" => Ok(SqlValue::Null),
+        "integer" => match field("value")? {
+            Value::Number(n) => match n.as_i64() {
+                Some(i) => Ok(SqlValue::Integer(i)),
+                None if n.is_u64() => Err(out_of_range(n)),
+                None => Err(format!("{} is not an integer", n)),
+            },
+            Value::String(s) => s.trim().parse::<i64>().map(SqlValue::Integer).map_err(|_| {
+                format!(
+                    "{} is not an integer in the range of a 64-bit signed integer",
+                    s
+                )
+            }),
+            _ => Err("integer value must be a number or a decimal string".into()),
+        },
+        "real" => match field("value")? {
+            Value::Number(n) => Ok(SqlValue::Real(n.as_f64().unwrap_or(f64::NAN))),
+            Value::String(s) => match s.as_str() {
+                "NaN" => Ok(SqlValue::Real(f64::NAN)),
+                "Infinity" => Ok(SqlValue::Real(f64::INFINITY)),
+                "-Infinity" => Ok(SqlValue::Real(f64::NEG_INFINITY)),
+                _ => Err(format!(
+                    "{} is not a real number; use a number, \"NaN\", \"Infinity\" or \"-Infinity\"",
+                    s
+                )),
+            },
+            _ => Err("real value must be a number or a string".into()),
+        },
+        "text" => match field("value")? {
+            Value::String(s) => Ok(SqlValue::Text(s.clone())),
+            _ => Err("text value must be a string".into()),
+        },
+        "blob" => match field("base64")? {
+            Value::String(s) => STANDARD
+                .decode(s)
+                .map(SqlValue::Blob)
+                .map_err(|e| format!("invalid base64 in blob value: {}", e)),
+            _ => Err("blob base64 must be a string".into()),
+        },
+        other => Err(format!(
+            "unknown value type: {}; expected null, integer, real, text or blob",
+            other
+        )),
+    }
+}
+
+fn out_of_range(n: &serde_json::Number) -> String {
+    format!(
+        "integer {} is out of range; SQLite integers are 64-bit signed (max {})",
+        n,
+        i64::MAX
+    )
+}
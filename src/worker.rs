@@ -0,0 +1,199 @@
+/*!
+ * # 数据库工作线程
+ *
+ * rusqlite的调用都是阻塞的。如果直接在异步任务中执行，长时间运行的语句会占住运行时的工作线程，
+ * 导致服务器无法及时响应其他请求。本模块把数据库连接交给专用的线程，
+ * 调用方通过通道提交操作，并异步等待结果。
+ *
+ * 一个工作线程上的操作按提交顺序逐个执行。[`DbWorker::spawn_pool`]启动共享同一个队列的多个线程，
+ * 每个线程持有自己的连接，操作由空闲的线程取走，用于并发读取。
+ *
+ * 提交时所在任务的[`CancelToken`]会随操作一起传到工作线程，因此排队中或正在执行的操作同样可以被取消。
+ */
+
+use std::{
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::{mpsc, Arc, Mutex},
+    thread,
+};
+
+use rusqlite::Connection;
+use tokio::sync::oneshot;
+use tracing::error;
+
+use crate::interrupt::{self, CancelToken};
+
+/// 提交给工作线程的操作
+type Job = Box<dyn FnOnce(&mut Connection) + Send>;
+
+/// 工作线程已经停止
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WorkerStopped;
+
+impl std::fmt::Display for WorkerStopped {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Database worker is not running")
+    }
+}
+
+impl std::error::Error for WorkerStopped {}
+
+/// 持有数据库连接的工作线程
+///
+/// 克隆得到的句柄共享同样的线程，最后一个句柄被释放后线程退出并关闭连接
+#[derive(Debug, Clone)]
+pub struct DbWorker {
+    jobs: mpsc::Sender<Job>,
+}
+
+impl DbWorker {
+    /// 启动工作线程，连接的所有权转移给该线程
+    ///
+    /// # 参数
+    ///
+    /// * `name` - 线程名称
+    /// * `conn` - 数据库连接
+    ///
+    /// # Panics
+    ///
+    /// 与[`std::thread::spawn`]一样，操作系统无法创建线程时panic
+    pub fn spawn(name: &str, conn: Connection) -> Self {
+        let (jobs, rx) = mpsc::channel::<Job>();
+        let rx = Arc::new(Mutex::new(rx));
+        spawn_thread(name.to_string(), conn, rx);
+        Self { jobs }
+    }
+
+    /// 启动共享同一个操作队列的一组工作线程，每个线程持有一个连接
+    ///
+    /// 线程依次命名为`{name}-0`、`{name}-1`……
+    ///
+    /// # 参数
+    ///
+    /// * `name` - 线程名称前缀
+    /// * `conns` - 数据库连接，每个连接对应一个线程
+    ///
+    /// # Panics
+    ///
+    /// `conns`为空，或者操作系统无法创建线程时panic
+    ///
+    /// # 示例
+    ///
+    /// ```
+    /// use mcp_sqlite::worker::DbWorker;
+    /// use rusqlite::Connection;
+    ///
+    /// let conns = (0..2).map(|_| Connection::open_in_memory().unwrap()).collect();
+    /// let pool = DbWorker::spawn_pool("reader", conns);
+    /// let name = pool
+    ///     .run_blocking(|_| std::thread::current().name().map(str::to_string))
+    ///     .unwrap();
+    /// assert!(name.unwrap().starts_with("reader-"));
+    /// ```
+    pub fn spawn_pool(name: &str, conns: Vec<Connection>) -> Self {
+        assert!(
+            !conns.is_empty(),
+            "a worker pool needs at least one connection"
+        );
+        let (jobs, rx) = mpsc::channel::<Job>();
+        let rx = Arc::new(Mutex::new(rx));
+        for (i, conn) in conns.into_iter().enumerate() {
+            spawn_thread(format!("{}-{}", name, i), conn, rx.clone());
+        }
+        Self { jobs }
+    }
+
+    /// 在工作线程上执行操作并异步等待结果
+    ///
+    /// # 示例
+    ///
+    /// ```
+    /// use mcp_sqlite::worker::DbWorker;
+    /// use rusqlite::Connection;
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let worker = DbWorker::spawn("db", Connection::open_in_memory().unwrap());
+    /// let n: i64 = worker
+    ///     .run(|conn| conn.query_row("SELECT 40 + 2", [], |row| row.get(0)))
+    ///     .await
+    ///     .unwrap()
+    ///     .unwrap();
+    /// assert_eq!(n, 42);
+    /// # });
+    /// ```
+    pub async fn run<T, F>(&self, f: F) -> Result<T, WorkerStopped>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Connection) -> T + Send + 'static,
+    {
+        self.run_mut(move |conn| f(conn)).await
+    }
+
+    /// 在工作线程上执行需要可变连接的操作并异步等待结果，如以连接为目标的备份恢复
+    pub async fn run_mut<T, F>(&self, f: F) -> Result<T, WorkerStopped>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> T + Send + 'static,
+    {
+        let (tx, rx) = oneshot::channel();
+        self.submit(f, move |result| {
+            let _ = tx.send(result);
+        })?;
+        rx.await.map_err(|_| WorkerStopped)
+    }
+
+    /// 在工作线程上执行操作并阻塞等待结果，供同步代码使用
+    pub fn run_blocking<T, F>(&self, f: F) -> Result<T, WorkerStopped>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Connection) -> T + Send + 'static,
+    {
+        let (tx, rx) = mpsc::sync_channel(1);
+        self.submit(
+            move |conn| f(conn),
+            move |result| {
+                let _ = tx.send(result);
+            },
+        )?;
+        rx.recv().map_err(|_| WorkerStopped)
+    }
+
+    /// 提交操作但不等待结果，用于无法等待的场合（如`Drop`）
+    pub fn run_detached<F>(&self, f: F) -> Result<(), WorkerStopped>
+    where
+        F: FnOnce(&Connection) + Send + 'static,
+    {
+        self.submit(move |conn| f(conn), |_| ())
+    }
+
+    fn submit<T, F, R>(&self, f: F, reply: R) -> Result<(), WorkerStopped>
+    where
+        F: FnOnce(&mut Connection) -> T + Send + 'static,
+        R: FnOnce(T) + Send + 'static,
+    {
+        let token = CancelToken::current();
+        let job: Job = Box::new(move |conn| {
+            reply(interrupt::with_thread_cancel_token(token, || f(conn)));
+        });
+        self.jobs.send(job).map_err(|_| WorkerStopped)
+    }
+}
+
+/// 启动一个从队列中取出操作并在连接上执行的线程
+fn spawn_thread(name: String, mut conn: Connection, rx: Arc<Mutex<mpsc::Receiver<Job>>>) {
+    thread::Builder::new()
+        .name(name)
+        .spawn(move || loop {
+            // 只在取操作时持有队列锁，执行操作期间其他线程可以继续取操作
+            let job = match rx.lock() {
+                Ok(rx) => rx.recv(),
+                Err(_) => break,
+            };
+            let Ok(job) = job else { break };
+            // 操作中的panic只影响这一次调用，调用方会收到WorkerStopped
+            if catch_unwind(AssertUnwindSafe(|| job(&mut conn))).is_err() {
+                error!("Database operation panicked");
+            }
+        })
+        .expect("failed to spawn database worker thread");
+}
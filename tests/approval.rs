@@ -0,0 +1,181 @@
+//! 写入审批的集成测试
+
+mod common;
+
+use std::time::Duration;
+
+use common::{call, call_err};
+use mcp_sqlite::{approval::ApprovalRules, RouterOptions, SQLiteRouter};
+use serde_json::{json, Value};
+
+/// 创建启用审批的路由器，修改超过3行的写入需要确认
+async fn router(token_timeout: Duration) -> SQLiteRouter {
+    let options = RouterOptions {
+        approval: Some(ApprovalRules {
+            row_threshold: Some(3),
+            token_timeout,
+        }),
+        ..Default::default()
+    };
+    let router = SQLiteRouter::with_options(":memory:", options).unwrap();
+    // 建表是结构变更，需要确认
+    let result = call(
+        &router,
+        "executescript",
+        json!({
+            "script": "
+                CREATE TABLE items (id INTEGER PRIMARY KEY, name TEXT);
+                INSERT INTO items (name) VALUES ('a'), ('b'), ('c'), ('d'), ('e');
+            "
+        }),
+    )
+    .await;
+    confirm(&router, &result).await;
+    router
+}
+
+/// 确认需要审批的写入
+async fn confirm(router: &SQLiteRouter, result: &Value) -> Value {
+    assert_eq!(result["approval_required"], true, "{}", result);
+    let token = result["approval_token"].as_str().unwrap();
+    call(router, "confirm", json!({ "approval_token": token })).await
+}
+
+/// 读取表中的行数
+async fn count(router: &SQLiteRouter) -> Value {
+    let result = call(
+        router,
+        "query",
+        json!({ "query": "SELECT count(*) AS n FROM items" }),
+    )
+    .await;
+    result["rows"][0]["n"].clone()
+}
+
+#[tokio::test]
+async fn destructive_writes_wait_for_confirmation() {
+    let router = router(Duration::from_secs(60)).await;
+
+    // 不具有破坏性的写入直接执行
+    let result = call(
+        &router,
+        "execute",
+        json!({ "statement": "DELETE FROM items WHERE id >= 3" }),
+    )
+    .await;
+    assert_eq!(result["rowcount"], 3);
+
+    let result = call(
+        &router,
+        "execute",
+        json!({ "statement": "DELETE FROM items" }),
+    )
+    .await;
+    assert_eq!(
+        result["reasons"],
+        json!(["Deletes all 2 rows of main.items"])
+    );
+    assert_eq!(result["rowcount"], 2);
+    assert_eq!(result["expires_in_ms"], 60000);
+    assert_eq!(count(&router).await, 2);
+
+    let confirmed = confirm(&router, &result).await;
+    assert_eq!(confirmed["rowcount"], 2);
+    assert_eq!(count(&router).await, 0);
+
+    let result = call(
+        &router,
+        "executemany",
+        json!({
+            "statement": "INSERT INTO items (name) VALUES (?)",
+            "params_list": [["w"], ["x"], ["y"], ["z"]]
+        }),
+    )
+    .await;
+    assert_eq!(
+        result["reasons"],
+        json!(["Modifies 4 rows, more than the approval threshold of 3"])
+    );
+    assert_eq!(count(&router).await, 0);
+}
+
+#[tokio::test]
+async fn tokens_are_single_use_and_session_bound() {
+    let router = router(Duration::from_secs(60)).await;
+
+    let result = call(
+        &router,
+        "execute",
+        json!({ "statement": "DROP TABLE items" }),
+    )
+    .await;
+    assert_eq!(
+        result["reasons"],
+        json!(["Schema change: DROP TABLE items"])
+    );
+    let token = result["approval_token"].as_str().unwrap();
+
+    // 其他会话不能使用这个令牌
+    let other = router.session();
+    let error = call_err(&other, "confirm", json!({ "approval_token": token })).await;
+    assert!(
+        error.contains("Unknown, used or expired approval token"),
+        "{}",
+        error
+    );
+
+    call(&router, "confirm", json!({ "approval_token": token })).await;
+    let error = call_err(&router, "confirm", json!({ "approval_token": token })).await;
+    assert!(
+        error.contains("Unknown, used or expired approval token"),
+        "{}",
+        error
+    );
+
+    let error = call_err(&router, "confirm", json!({})).await;
+    assert!(
+        error.contains("Missing required parameter: approval_token"),
+        "{}",
+        error
+    );
+}
+
+#[tokio::test]
+async fn tokens_expire() {
+    let router = router(Duration::from_millis(200)).await;
+
+    let result = call(
+        &router,
+        "execute",
+        json!({ "statement": "DELETE FROM items" }),
+    )
+    .await;
+    let token = result["approval_token"].as_str().unwrap();
+    tokio::time::sleep(Duration::from_millis(400)).await;
+
+    let error = call_err(&router, "confirm", json!({ "approval_token": token })).await;
+    assert!(
+        error.contains("Unknown, used or expired approval token"),
+        "{}",
+        error
+    );
+    assert_eq!(count(&router).await, 5);
+}
+
+#[tokio::test]
+async fn query_cannot_bypass_approval() {
+    let router = router(Duration::from_secs(60)).await;
+
+    let error = call_err(
+        &router,
+        "query",
+        json!({ "query": "DELETE FROM items RETURNING id" }),
+    )
+    .await;
+    assert!(
+        error.contains("query cannot modify the database when approval is required"),
+        "{}",
+        error
+    );
+    assert_eq!(count(&router).await, 5);
+}
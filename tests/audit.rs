@@ -0,0 +1,147 @@
+//! 审计日志的集成测试
+
+mod common;
+
+use std::sync::Arc;
+
+use common::{call, call_err, TempDir};
+use mcp_sqlite::{
+    audit::{verify, AuditDestination, AuditError, AuditLog, GENESIS_HASH, REDACTED},
+    RouterOptions, SQLiteRouter,
+};
+use rusqlite::Connection;
+use serde_json::{json, Value};
+
+/// 通过记录审计日志的路由器执行几次调用，返回后所有记录已经写入
+async fn record_calls(destination: &AuditDestination, redact_params: bool) {
+    let audit = Arc::new(AuditLog::open(destination.clone(), redact_params).unwrap());
+    let options = RouterOptions {
+        audit: Some(audit),
+        ..Default::default()
+    };
+    let router = SQLiteRouter::with_options(":memory:", options).unwrap();
+
+    call(
+        &router,
+        "execute",
+        json!({ "statement": "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT)" }),
+    )
+    .await;
+    call(
+        &router,
+        "executemany",
+        json!({
+            "statement": "INSERT INTO users (name) VALUES (?)",
+            "params_list": [["Alice"], ["Bob"]]
+        }),
+    )
+    .await;
+    call(
+        &router,
+        "query",
+        json!({ "query": "SELECT * FROM users WHERE name <> ?", "params": ["Carol"] }),
+    )
+    .await;
+    call_err(
+        &router,
+        "execute",
+        json!({ "statement": "DELETE FROM missing" }),
+    )
+    .await;
+
+    // 最后一个引用被释放时等待记录写入完毕
+    drop(router);
+}
+
+/// 读取日志文件中的记录
+fn read_records(path: &str) -> Vec<Value> {
+    std::fs::read_to_string(path)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn file_log_records_calls_in_a_hash_chain() {
+    let dir = TempDir::new("audit-file");
+    let path = dir.file("audit.jsonl");
+    let destination = AuditDestination::File(path.clone().into());
+    record_calls(&destination, false).await;
+
+    let records = read_records(&path);
+    assert_eq!(records.len(), 4);
+    assert_eq!(records[0]["seq"], 1);
+    assert_eq!(records[0]["prev_hash"], GENESIS_HASH);
+    assert_eq!(records[1]["prev_hash"], records[0]["hash"]);
+    assert_eq!(records[1]["tool"], "executemany");
+    assert_eq!(records[1]["params"], json!([["Alice"], ["Bob"]]));
+    assert_eq!(records[1]["row_count"], 2);
+    assert_eq!(records[2]["tool"], "query");
+    assert_eq!(records[2]["row_count"], 2);
+    assert_eq!(records[3]["sql"], "DELETE FROM missing");
+    assert!(records[3]["error"]
+        .as_str()
+        .unwrap()
+        .contains("no such table"));
+
+    let head = verify(&destination).unwrap();
+    assert_eq!(head.records, 4);
+    assert_eq!(head.last_hash, records[3]["hash"].as_str().unwrap());
+
+    // 重新打开后从最后一条记录继续哈希链
+    record_calls(&destination, false).await;
+    assert_eq!(verify(&destination).unwrap().records, 8);
+}
+
+#[tokio::test]
+async fn tampered_file_records_are_detected() {
+    let dir = TempDir::new("audit-tamper-file");
+    let path = dir.file("audit.jsonl");
+    let destination = AuditDestination::File(path.clone().into());
+    record_calls(&destination, false).await;
+    let original = std::fs::read_to_string(&path).unwrap();
+
+    let modified = original.replace("\"row_count\":2", "\"row_count\":0");
+    std::fs::write(&path, &modified).unwrap();
+    let error = verify(&destination).unwrap_err();
+    assert!(matches!(error, AuditError::Broken(_)), "{}", error);
+    assert!(error.to_string().contains("line 2"), "{}", error);
+
+    // 删除一条记录会使序号和prev_hash不再连续
+    let mut lines: Vec<&str> = original.lines().collect();
+    lines.remove(1);
+    std::fs::write(&path, lines.join("\n")).unwrap();
+    let error = verify(&destination).unwrap_err();
+    assert!(matches!(error, AuditError::Broken(_)), "{}", error);
+
+    std::fs::write(&path, "not json\n").unwrap();
+    let error = verify(&destination).unwrap_err();
+    assert!(matches!(error, AuditError::Invalid(_)), "{}", error);
+}
+
+#[tokio::test]
+async fn tampered_database_records_are_detected() {
+    let dir = TempDir::new("audit-tamper-db");
+    let path = dir.file("audit.db");
+    let destination = AuditDestination::Database(path.clone().into());
+    record_calls(&destination, true).await;
+    assert_eq!(verify(&destination).unwrap().records, 4);
+
+    let conn = Connection::open(&path).unwrap();
+    let params: String = conn
+        .query_row("SELECT params FROM audit_log WHERE seq = 2", [], |row| {
+            row.get(0)
+        })
+        .unwrap();
+    assert_eq!(
+        serde_json::from_str::<Value>(&params).unwrap(),
+        json!([[REDACTED], [REDACTED]])
+    );
+
+    conn.execute("UPDATE audit_log SET sql = 'SELECT 1' WHERE seq = 3", [])
+        .unwrap();
+    let error = verify(&destination).unwrap_err();
+    assert!(matches!(error, AuditError::Broken(_)), "{}", error);
+    assert!(error.to_string().contains("record 3"), "{}", error);
+}
@@ -0,0 +1,218 @@
+//! 备份与恢复的集成测试
+
+mod common;
+
+use std::sync::Arc;
+
+use common::{call, call_err, TempDir};
+use mcp_server_fishcode2025::Router;
+use mcp_sqlite::{approval::ApprovalRules, policy::Policy, RouterOptions, SQLiteRouter};
+use serde_json::{json, Value};
+
+/// 创建使用`backups`作为备份目录的路由器，数据库中有两行数据
+async fn router(dir: &TempDir, backups: &TempDir, approval: bool) -> SQLiteRouter {
+    let options = RouterOptions {
+        backup_dir: Some(backups.path().to_path_buf()),
+        approval: approval.then(ApprovalRules::default),
+        ..Default::default()
+    };
+    let router = SQLiteRouter::with_options(&dir.file("app.db"), options).unwrap();
+    let script = "CREATE TABLE items (name TEXT); INSERT INTO items VALUES ('a'), ('b');";
+    let result = call(&router, "executescript", json!({ "script": script })).await;
+    if approval {
+        let token = result["approval_token"].as_str().unwrap();
+        call(&router, "confirm", json!({ "approval_token": token })).await;
+    }
+    router
+}
+
+/// 读取表中的名称
+async fn names(router: &SQLiteRouter) -> Value {
+    let result = call(
+        router,
+        "query",
+        json!({ "query": "SELECT name FROM items ORDER BY name", "format": "arrays" }),
+    )
+    .await;
+    result["rows"].clone()
+}
+
+#[tokio::test]
+async fn backups_restore_the_saved_contents() {
+    let dir = TempDir::new("backup-roundtrip");
+    let backups = TempDir::new("backup-roundtrip-dir");
+    let router = router(&dir, &backups, false).await;
+
+    let result = call(
+        &router,
+        "backup",
+        json!({ "name": "online.db", "pages_per_step": 1 }),
+    )
+    .await;
+    assert_eq!(result["method"], "backup");
+    assert!(result["pages"].as_u64().unwrap() >= 2);
+    assert_eq!(result["steps"], result["pages"]);
+    let result = call(
+        &router,
+        "backup",
+        json!({ "name": "vacuum.db", "method": "vacuum" }),
+    )
+    .await;
+    assert_eq!(result["method"], "vacuum");
+
+    let result = call(&router, "list_backups", json!({})).await;
+    let mut listed: Vec<&str> = result["backups"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|backup| backup["name"].as_str().unwrap())
+        .collect();
+    listed.sort();
+    assert_eq!(listed, ["online.db", "vacuum.db"]);
+
+    for backup in ["online.db", "vacuum.db"] {
+        call(
+            &router,
+            "execute",
+            json!({ "statement": "DELETE FROM items" }),
+        )
+        .await;
+        assert_eq!(names(&router).await, json!([]));
+        call(&router, "restore", json!({ "name": backup })).await;
+        assert_eq!(names(&router).await, json!([["a"], ["b"]]));
+    }
+}
+
+#[tokio::test]
+async fn invalid_backup_requests_are_rejected() {
+    let dir = TempDir::new("backup-invalid");
+    let backups = TempDir::new("backup-invalid-dir");
+    let router = router(&dir, &backups, false).await;
+
+    call(&router, "backup", json!({ "name": "saved.db" })).await;
+    let error = call_err(&router, "backup", json!({ "name": "saved.db" })).await;
+    assert!(
+        error.contains("Backup already exists: saved.db"),
+        "{}",
+        error
+    );
+
+    let error = call_err(&router, "backup", json!({ "name": "../escape.db" })).await;
+    assert!(error.contains("Invalid backup name"), "{}", error);
+    let error = call_err(&router, "restore", json!({ "name": "missing.db" })).await;
+    assert!(error.contains("Backup not found: missing.db"), "{}", error);
+    let error = call_err(&router, "backup", json!({ "method": "copy" })).await;
+    assert!(
+        error.contains("method must be one of: backup, vacuum"),
+        "{}",
+        error
+    );
+
+    // 有打开的事务时不能备份或恢复
+    let begin = call(&router, "begin_transaction", json!({})).await;
+    let id = begin["transaction_id"].as_str().unwrap();
+    let error = call_err(&router, "backup", json!({ "name": "during.db" })).await;
+    assert!(
+        error.contains("Cannot back up while a transaction is open"),
+        "{}",
+        error
+    );
+    let error = call_err(&router, "restore", json!({ "name": "saved.db" })).await;
+    assert!(
+        error.contains("Cannot restore while a transaction is open"),
+        "{}",
+        error
+    );
+    call(&router, "rollback", json!({ "transaction_id": id })).await;
+}
+
+#[tokio::test]
+async fn restore_requires_confirmation_when_approval_is_enabled() {
+    let dir = TempDir::new("backup-approval");
+    let backups = TempDir::new("backup-approval-dir");
+    let router = router(&dir, &backups, true).await;
+
+    call(&router, "backup", json!({ "name": "saved.db" })).await;
+    call(
+        &router,
+        "execute",
+        json!({ "statement": "INSERT INTO items VALUES ('c')" }),
+    )
+    .await;
+
+    let result = call(&router, "restore", json!({ "name": "saved.db" })).await;
+    assert_eq!(result["approval_required"], true);
+    assert_eq!(names(&router).await, json!([["a"], ["b"], ["c"]]));
+
+    let token = result["approval_token"].as_str().unwrap();
+    call(&router, "confirm", json!({ "approval_token": token })).await;
+    assert_eq!(names(&router).await, json!([["a"], ["b"]]));
+}
+
+#[tokio::test]
+async fn restore_is_unavailable_when_the_policy_protects_tables() {
+    let dir = TempDir::new("backup-policy");
+    let backups = TempDir::new("backup-policy-dir");
+    let router = router(&dir, &backups, false).await;
+    call(
+        &router,
+        "executescript",
+        json!({ "script": "DELETE FROM items" }),
+    )
+    .await;
+    call(&router, "backup", json!({ "name": "empty.db" })).await;
+    drop(router);
+
+    let open_with_policy = |json: &str| {
+        let router = SQLiteRouter::with_options(
+            &dir.file("app.db"),
+            RouterOptions {
+                backup_dir: Some(backups.path().to_path_buf()),
+                policy: Some(Arc::new(Policy::from_json(json).unwrap())),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let restore = router
+            .list_tools()
+            .iter()
+            .any(|tool| tool.name == "restore");
+        (router, restore)
+    };
+
+    let (router, listed) = open_with_policy(
+        r#"{
+            "default": "allow",
+            "rules": [{ "name": "keep-items", "effect": "deny", "actions": ["delete"], "tables": ["items"] }]
+        }"#,
+    );
+    assert!(!listed);
+    call(
+        &router,
+        "execute",
+        json!({ "statement": "INSERT INTO items VALUES ('a')" }),
+    )
+    .await;
+    let error = call_err(
+        &router,
+        "execute",
+        json!({ "statement": "DELETE FROM items" }),
+    )
+    .await;
+    assert!(error.contains("rule 'keep-items'"), "{}", error);
+
+    // 恢复空的备份会绕过keep-items规则删除所有行
+    let error = call_err(&router, "restore", json!({ "name": "empty.db" })).await;
+    assert!(
+        error.contains("Denied by access policy: tool restore is not available"),
+        "{}",
+        error
+    );
+    assert_eq!(names(&router).await, json!([["a"]]));
+    drop(router);
+
+    let (router, listed) = open_with_policy(r#"{ "default": "allow" }"#);
+    assert!(listed);
+    call(&router, "restore", json!({ "name": "empty.db" })).await;
+    assert_eq!(names(&router).await, json!([]));
+}
@@ -0,0 +1,70 @@
+//! 集成测试共用的辅助函数
+
+#![allow(dead_code)]
+
+use std::{
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use mcp_server_fishcode2025::Router;
+use mcp_sqlite::SQLiteRouter;
+use serde_json::Value;
+
+/// 用于生成临时目录名的计数器
+static DIRS: AtomicU64 = AtomicU64::new(0);
+
+/// 调用工具并把返回的文本解析为JSON，调用失败时panic
+pub async fn call(router: &SQLiteRouter, tool: &str, arguments: Value) -> Value {
+    let content = router
+        .call_tool(tool, arguments)
+        .await
+        .unwrap_or_else(|e| panic!("{} failed: {}", tool, e));
+    serde_json::from_str(content[0].as_text().unwrap()).unwrap()
+}
+
+/// 调用预期失败的工具并返回错误信息，调用成功时panic
+pub async fn call_err(router: &SQLiteRouter, tool: &str, arguments: Value) -> String {
+    match router.call_tool(tool, arguments).await {
+        Ok(content) => panic!(
+            "{} unexpectedly succeeded: {}",
+            tool,
+            content[0].as_text().unwrap_or_default()
+        ),
+        Err(e) => e.to_string(),
+    }
+}
+
+/// 测试结束时删除的临时目录
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// 在系统临时目录下创建以`name`开头的空目录
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!(
+            "mcp-sqlite-{}-{}-{}",
+            name,
+            std::process::id(),
+            DIRS.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+
+    /// 目录的路径
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    /// 目录中文件的路径，以字符串返回
+    pub fn file(&self, name: &str) -> String {
+        self.0.join(name).to_string_lossy().into_owned()
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
@@ -0,0 +1,191 @@
+//! 查询游标的集成测试
+
+mod common;
+
+use std::time::Duration;
+
+use common::{call, call_err, TempDir};
+use mcp_sqlite::{RouterOptions, SQLiteRouter};
+use serde_json::{json, Value};
+
+/// 在数据库文件中创建25行测试数据
+async fn router(dir: &TempDir, options: RouterOptions) -> SQLiteRouter {
+    let router = SQLiteRouter::with_options(&dir.file("cursors.db"), options).unwrap();
+    call(
+        &router,
+        "executescript",
+        json!({
+            "script": "
+                CREATE TABLE numbers (n INTEGER PRIMARY KEY);
+                WITH RECURSIVE seq(n) AS (SELECT 1 UNION ALL SELECT n + 1 FROM seq WHERE n < 25)
+                INSERT INTO numbers SELECT n FROM seq;
+            "
+        }),
+    )
+    .await;
+    router
+}
+
+/// 取出一页中的数字
+fn numbers(page: &Value) -> Vec<i64> {
+    page["rows"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|row| row["n"].as_i64().unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn pages_continue_from_a_snapshot() {
+    let dir = TempDir::new("cursor-pages");
+    let router = router(&dir, RouterOptions::default()).await;
+
+    let first = call(
+        &router,
+        "query",
+        json!({ "query": "SELECT n FROM numbers ORDER BY n", "cursor": true, "limit": 10 }),
+    )
+    .await;
+    assert_eq!(numbers(&first), (1..=10).collect::<Vec<_>>());
+    assert_eq!(first["has_more"], true);
+    assert_eq!(first["rows_fetched"], 10);
+    let cursor_id = first["cursor_id"].as_str().unwrap().to_string();
+
+    // 之后的写入不影响游标的快照
+    call(
+        &router,
+        "execute",
+        json!({ "statement": "DELETE FROM numbers WHERE n <= 15" }),
+    )
+    .await;
+    call(
+        &router,
+        "execute",
+        json!({ "statement": "INSERT INTO numbers VALUES (100)" }),
+    )
+    .await;
+
+    let second = call(
+        &router,
+        "fetch",
+        json!({ "cursor_id": cursor_id, "limit": 10 }),
+    )
+    .await;
+    assert_eq!(numbers(&second), (11..=20).collect::<Vec<_>>());
+    assert_eq!(second["rows_fetched"], 20);
+
+    let last = call(&router, "fetch", json!({ "cursor_id": cursor_id })).await;
+    assert_eq!(numbers(&last), (21..=25).collect::<Vec<_>>());
+    assert_eq!(last["has_more"], false);
+
+    // 读完后游标自动关闭
+    let error = call_err(&router, "fetch", json!({ "cursor_id": cursor_id })).await;
+    assert!(error.contains("Unknown or expired cursor"), "{}", error);
+}
+
+#[tokio::test]
+async fn cursor_starts_at_offset_and_skips_registration_when_done() {
+    let dir = TempDir::new("cursor-offset");
+    let router = router(&dir, RouterOptions::default()).await;
+
+    let page = call(
+        &router,
+        "query",
+        json!({ "query": "SELECT n FROM numbers ORDER BY n", "cursor": true, "offset": 20, "limit": 10 }),
+    )
+    .await;
+    assert_eq!(numbers(&page), (21..=25).collect::<Vec<_>>());
+    assert_eq!(page["has_more"], false);
+    assert_eq!(page["rows_fetched"], 25);
+    assert_eq!(page["cursor_id"], Value::Null);
+}
+
+#[tokio::test]
+async fn cursors_reject_writes_transactions_and_private_memory() {
+    let dir = TempDir::new("cursor-reject");
+    let router = router(&dir, RouterOptions::default()).await;
+
+    let error = call_err(
+        &router,
+        "query",
+        json!({ "query": "DELETE FROM numbers WHERE n = 1 RETURNING n", "cursor": true }),
+    )
+    .await;
+    assert!(
+        error.contains("cursor can only be used with read-only queries"),
+        "{}",
+        error
+    );
+    let count = call(
+        &router,
+        "query",
+        json!({ "query": "SELECT count(*) AS n FROM numbers" }),
+    )
+    .await;
+    assert_eq!(count["rows"][0]["n"], 25);
+
+    let begin = call(&router, "begin_transaction", json!({})).await;
+    let id = begin["transaction_id"].as_str().unwrap();
+    let error = call_err(
+        &router,
+        "query",
+        json!({ "query": "SELECT n FROM numbers", "cursor": true, "transaction_id": id }),
+    )
+    .await;
+    assert!(
+        error.contains("cursor cannot be used inside a transaction"),
+        "{}",
+        error
+    );
+    call(&router, "rollback", json!({ "transaction_id": id })).await;
+
+    let options = RouterOptions {
+        readers: 0,
+        ..Default::default()
+    };
+    let private = SQLiteRouter::with_options(":memory:", options).unwrap();
+    let error = call_err(
+        &private,
+        "query",
+        json!({ "query": "SELECT 1", "cursor": true }),
+    )
+    .await;
+    assert!(error.contains("private in-memory database"), "{}", error);
+}
+
+#[tokio::test]
+async fn idle_cursors_expire() {
+    let dir = TempDir::new("cursor-expire");
+    let options = RouterOptions {
+        cursor_timeout: Duration::from_millis(200),
+        ..Default::default()
+    };
+    let router = router(&dir, options).await;
+
+    let first = call(
+        &router,
+        "query",
+        json!({ "query": "SELECT n FROM numbers", "cursor": true, "limit": 5 }),
+    )
+    .await;
+    let cursor_id = first["cursor_id"].as_str().unwrap().to_string();
+
+    tokio::time::sleep(Duration::from_millis(600)).await;
+
+    let error = call_err(&router, "fetch", json!({ "cursor_id": cursor_id })).await;
+    assert!(
+        error.contains(&format!("Unknown or expired cursor: {}", cursor_id)),
+        "{}",
+        error
+    );
+
+    let error = call_err(&router, "fetch", json!({ "cursor_id": "cursor-nope" })).await;
+    assert!(error.contains("Unknown or expired cursor"), "{}", error);
+    let error = call_err(&router, "fetch", json!({})).await;
+    assert!(
+        error.contains("Missing required parameter: cursor_id"),
+        "{}",
+        error
+    );
+}
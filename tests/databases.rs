@@ -0,0 +1,143 @@
+//! 多数据库支持的集成测试
+
+mod common;
+
+use common::{call, call_err, TempDir};
+use mcp_server_fishcode2025::Router;
+use mcp_sqlite::{server::AttachedDatabase, RouterOptions, SQLiteRouter};
+use rusqlite::Connection;
+use serde_json::{json, Value};
+
+/// 创建附加了`analytics`数据库的路由器
+async fn router(dir: &TempDir, read_only: bool) -> SQLiteRouter {
+    let main = dir.file("app.db");
+    let analytics = dir.file("stats.db");
+    Connection::open(&main)
+        .unwrap()
+        .execute_batch(
+            "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT);
+             INSERT INTO users VALUES (1, 'Alice'), (2, 'Bob');",
+        )
+        .unwrap();
+    Connection::open(&analytics)
+        .unwrap()
+        .execute_batch(
+            "CREATE TABLE events (user_id INTEGER, kind TEXT);
+             INSERT INTO events VALUES (1, 'login'), (1, 'logout'), (2, 'login');",
+        )
+        .unwrap();
+
+    let options = RouterOptions {
+        read_only,
+        databases: vec![AttachedDatabase {
+            name: "analytics".into(),
+            path: analytics,
+        }],
+        ..Default::default()
+    };
+    SQLiteRouter::with_options(&main, options).unwrap()
+}
+
+/// 表名列表
+fn table_names(result: &Value) -> Vec<&str> {
+    result["tables"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|table| table["name"].as_str().unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn attached_databases_are_listed_and_joinable() {
+    let dir = TempDir::new("databases-list");
+    let router = router(&dir, false).await;
+
+    let result = call(&router, "list_databases", json!({})).await;
+    let databases = result["databases"].as_array().unwrap();
+    let analytics = databases
+        .iter()
+        .find(|database| database["name"] == "analytics")
+        .unwrap();
+    assert!(analytics["file"].as_str().unwrap().ends_with("stats.db"));
+    assert_eq!(analytics["tables"], 1);
+
+    let result = call(
+        &router,
+        "query",
+        json!({
+            "query": "SELECT u.name, count(*) AS logins FROM users u JOIN analytics.events e ON e.user_id = u.id WHERE e.kind = 'login' GROUP BY u.id ORDER BY u.id",
+            "format": "arrays"
+        }),
+    )
+    .await;
+    assert_eq!(result["rows"], json!([["Alice", 1], ["Bob", 1]]));
+}
+
+#[tokio::test]
+async fn database_parameter_scopes_tools_and_resources() {
+    let dir = TempDir::new("databases-scope");
+    let router = router(&dir, false).await;
+
+    let result = call(&router, "list_tables", json!({ "database": "analytics" })).await;
+    assert_eq!(table_names(&result), ["events"]);
+    let result = call(
+        &router,
+        "describe_table",
+        json!({ "table": "events", "database": "analytics" }),
+    )
+    .await;
+    assert_eq!(result["schema"], "analytics");
+
+    call(
+        &router,
+        "execute",
+        json!({ "statement": "INSERT INTO events VALUES (2, 'logout')", "database": "analytics" }),
+    )
+    .await;
+    let error = call_err(
+        &router,
+        "execute",
+        json!({ "statement": "DELETE FROM users", "database": "analytics" }),
+    )
+    .await;
+    assert!(error.contains("Denied by database scope"), "{}", error);
+
+    let uris: Vec<String> = router
+        .list_resources()
+        .into_iter()
+        .map(|resource| resource.uri)
+        .collect();
+    assert!(uris.contains(&"sqlite://db/analytics/schema".to_string()));
+    let schema = router
+        .read_resource("sqlite://db/analytics/schema")
+        .await
+        .unwrap();
+    assert!(schema.contains("events"));
+    assert!(!schema.contains("users"));
+}
+
+#[tokio::test]
+async fn unknown_and_read_only_databases_are_rejected() {
+    let dir = TempDir::new("databases-reject");
+    let router = router(&dir, true).await;
+
+    let error = call_err(&router, "list_tables", json!({ "database": "archive" })).await;
+    assert!(error.contains("Unknown database: archive"), "{}", error);
+    let error = call_err(
+        &router,
+        "query",
+        json!({ "query": "SELECT 1", "database": "archive" }),
+    )
+    .await;
+    assert!(error.contains("Unknown database: archive"), "{}", error);
+
+    // 只读模式下附加的数据库同样只读
+    let error = call_err(
+        &router,
+        "query",
+        json!({ "query": "DELETE FROM analytics.events RETURNING kind" }),
+    )
+    .await;
+    assert!(error.contains("Denied by read-only policy"), "{}", error);
+}
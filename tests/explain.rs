@@ -0,0 +1,142 @@
+//! 查询计划工具的集成测试
+
+mod common;
+
+use common::{call, call_err};
+use mcp_sqlite::SQLiteRouter;
+use serde_json::{json, Value};
+
+/// 创建带有索引的表
+async fn router() -> SQLiteRouter {
+    let router = SQLiteRouter::new(":memory:").unwrap();
+    call(
+        &router,
+        "executescript",
+        json!({
+            "script": "
+                CREATE TABLE users (id INTEGER PRIMARY KEY, email TEXT, name TEXT);
+                CREATE INDEX users_email ON users(email);
+                CREATE TABLE orders (id INTEGER PRIMARY KEY, user_id INTEGER, total REAL);
+                INSERT INTO users VALUES (1, 'a@example.com', 'Alice');
+            "
+        }),
+    )
+    .await;
+    router
+}
+
+/// 按深度优先的顺序收集计划中的所有步骤
+fn steps(nodes: &Value) -> Vec<Value> {
+    let mut all = Vec::new();
+    for node in nodes.as_array().unwrap() {
+        all.push(node.clone());
+        all.extend(steps(&node["children"]));
+    }
+    all
+}
+
+#[tokio::test]
+async fn plans_flag_full_scans_and_temp_btrees() {
+    let router = router().await;
+
+    let result = call(
+        &router,
+        "explain",
+        json!({ "query": "SELECT * FROM users ORDER BY name" }),
+    )
+    .await;
+    let plan = steps(&result["plan"]);
+    let scan = plan
+        .iter()
+        .find(|step| step["detail"] == "SCAN users")
+        .unwrap();
+    assert_eq!(scan["full_scan"], true);
+    assert!(plan.iter().any(|step| step["temp_btree"] == true));
+    assert_eq!(
+        result["warnings"],
+        json!([
+            "Full table scan: SCAN users",
+            "Temporary B-tree: USE TEMP B-TREE FOR ORDER BY"
+        ])
+    );
+    assert!(result.get("bytecode").is_none());
+
+    let result = call(
+        &router,
+        "explain",
+        json!({ "query": "SELECT name FROM users WHERE email = ?", "params": ["a@example.com"] }),
+    )
+    .await;
+    let plan = steps(&result["plan"]);
+    assert!(plan[0]["detail"]
+        .as_str()
+        .unwrap()
+        .starts_with("SEARCH users USING INDEX users_email"));
+    assert_eq!(result["warnings"], json!([]));
+}
+
+#[tokio::test]
+async fn subqueries_nest_and_bytecode_is_optional() {
+    let router = router().await;
+
+    let result = call(
+        &router,
+        "explain",
+        json!({
+            "query": "SELECT * FROM users WHERE id IN (SELECT user_id FROM orders WHERE total > 10)",
+            "bytecode": true
+        }),
+    )
+    .await;
+    let nested = result["plan"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|node| !node["children"].as_array().unwrap().is_empty());
+    assert!(nested, "{}", result["plan"]);
+
+    let bytecode = result["bytecode"].as_array().unwrap();
+    assert_eq!(bytecode[0]["addr"], 0);
+    assert!(bytecode.iter().any(|op| op["opcode"] == "Halt"));
+}
+
+#[tokio::test]
+async fn explain_does_not_run_the_statement() {
+    let router = router().await;
+
+    call(&router, "explain", json!({ "query": "DELETE FROM users" })).await;
+    let count = call(
+        &router,
+        "query",
+        json!({ "query": "SELECT count(*) AS n FROM users" }),
+    )
+    .await;
+    assert_eq!(count["rows"][0]["n"], 1);
+
+    let error = call_err(&router, "explain", json!({})).await;
+    assert!(
+        error.contains("Missing required parameter: query"),
+        "{}",
+        error
+    );
+
+    let error = call_err(
+        &router,
+        "explain",
+        json!({ "query": "SELECT * FROM missing" }),
+    )
+    .await;
+    assert!(error.contains("no such table: missing"), "{}", error);
+
+    let error = call_err(
+        &router,
+        "explain",
+        json!({ "query": "SELECT * FROM users WHERE id = ?", "params": "1" }),
+    )
+    .await;
+    assert!(
+        error.contains("params must be an array or an object"),
+        "{}",
+        error
+    );
+}
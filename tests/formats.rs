@@ -0,0 +1,84 @@
+//! 查询结果格式的集成测试
+
+mod common;
+
+use common::{call, call_err};
+use mcp_sqlite::SQLiteRouter;
+use serde_json::{json, Value};
+
+const JOIN: &str = "SELECT a.id, b.id, b.note FROM a JOIN b ON b.a_id = a.id ORDER BY b.id";
+
+/// 创建两张都有`id`列的表
+async fn router() -> SQLiteRouter {
+    let router = SQLiteRouter::new(":memory:").unwrap();
+    call(
+        &router,
+        "executescript",
+        json!({
+            "script": "
+                CREATE TABLE a (id INTEGER PRIMARY KEY);
+                CREATE TABLE b (id INTEGER PRIMARY KEY, a_id INTEGER, note TEXT);
+                INSERT INTO a VALUES (1);
+                INSERT INTO b VALUES (10, 1, 'x, \"y\"'), (11, 1, NULL);
+            "
+        }),
+    )
+    .await;
+    router
+}
+
+/// 按指定格式执行连接查询
+async fn query(router: &SQLiteRouter, format: &str) -> Value {
+    call(router, "query", json!({ "query": JOIN, "format": format })).await
+}
+
+#[tokio::test]
+async fn arrays_preserve_duplicate_columns() {
+    let router = router().await;
+
+    let result = query(&router, "arrays").await;
+    assert_eq!(result["columns"], json!(["id", "id", "note"]));
+    assert_eq!(result["rows"], json!([[1, 10, "x, \"y\""], [1, 11, null]]));
+
+    // objects格式中重复的列名只保留最后一列
+    let result = query(&router, "objects").await;
+    assert_eq!(result["rows"][0], json!({ "id": 10, "note": "x, \"y\"" }));
+}
+
+#[tokio::test]
+async fn text_formats_are_returned_as_text() {
+    let router = router().await;
+
+    let result = query(&router, "csv").await;
+    assert_eq!(result["text"], "id,id,note\n1,10,\"x, \"\"y\"\"\"\n1,11,\n");
+    assert!(result.get("rows").is_none());
+
+    let result = query(&router, "tsv").await;
+    assert_eq!(result["text"], "id\tid\tnote\n1\t10\tx, \"y\"\n1\t11\t\n");
+
+    let result = query(&router, "markdown").await;
+    assert_eq!(
+        result["text"],
+        "| id | id | note |\n| --- | --- | --- |\n| 1 | 10 | x, \"y\" |\n| 1 | 11 |  |\n"
+    );
+}
+
+#[tokio::test]
+async fn unknown_formats_are_rejected() {
+    let router = router().await;
+
+    let error = call_err(&router, "query", json!({ "query": JOIN, "format": "xml" })).await;
+    assert!(error.contains("format must be one of"), "{}", error);
+
+    let error = call_err(
+        &router,
+        "query",
+        json!({ "query": JOIN, "format": "csv", "typed": true }),
+    )
+    .await;
+    assert!(
+        error.contains("typed results are only supported"),
+        "{}",
+        error
+    );
+}
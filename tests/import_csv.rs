@@ -0,0 +1,246 @@
+//! CSV导入的集成测试
+
+mod common;
+
+use common::{call, call_err, TempDir};
+use mcp_sqlite::{RouterOptions, SQLiteRouter};
+use serde_json::{json, Value};
+
+/// 创建带有唯一约束的表
+async fn router(options: RouterOptions) -> SQLiteRouter {
+    let router = SQLiteRouter::with_options(":memory:", options).unwrap();
+    call(
+        &router,
+        "executescript",
+        json!({
+            "script": "
+                CREATE TABLE people (id INTEGER PRIMARY KEY, name TEXT NOT NULL, zip TEXT);
+                INSERT INTO people VALUES (1, 'Alice', '00501');
+            "
+        }),
+    )
+    .await;
+    router
+}
+
+/// 按id读取表中的所有行
+async fn rows(router: &SQLiteRouter, table: &str) -> Value {
+    let query = format!("SELECT * FROM {} ORDER BY 1", table);
+    call(
+        router,
+        "query",
+        json!({ "query": query, "format": "arrays" }),
+    )
+    .await["rows"]
+        .clone()
+}
+
+const CONFLICTING: &str = "id,name,zip\n1,Alicia,00502\n2,Bob,10001\n";
+
+#[tokio::test]
+async fn new_tables_are_created_with_inferred_types() {
+    let router = router(RouterOptions::default()).await;
+
+    let result = call(
+        &router,
+        "import_csv",
+        json!({
+            "table": "measurements",
+            "csv": "station;reading;code;note\n1;2.5;007;\"semi; colon\"\n2;3;010;NA\n3;oops\n",
+            "delimiter": ";",
+            "null_tokens": ["NA"]
+        }),
+    )
+    .await;
+    assert_eq!(result["created"], true);
+    assert_eq!(
+        result["columns"],
+        json!([
+            { "name": "station", "type": "INTEGER" },
+            { "name": "reading", "type": "REAL" },
+            { "name": "code", "type": "TEXT" },
+            { "name": "note", "type": "TEXT" }
+        ])
+    );
+    assert_eq!(result["rows_read"], 3);
+    assert_eq!(result["rowcount"], 2);
+    assert_eq!(result["rejected"], 1);
+    assert_eq!(
+        result["rejected_lines"],
+        json!([{ "line": 4, "reason": "Expected 4 fields, found 2" }])
+    );
+
+    assert_eq!(
+        rows(&router, "measurements").await,
+        json!([[1, 2.5, "007", "semi; colon"], [2, 3.0, "010", null]])
+    );
+}
+
+#[tokio::test]
+async fn conflict_strategies() {
+    let router = router(RouterOptions::default()).await;
+
+    // fail：任何一行失败都会撤销整个导入
+    let error = call_err(
+        &router,
+        "import_csv",
+        json!({ "table": "people", "csv": CONFLICTING }),
+    )
+    .await;
+    assert!(error.contains("Failed to import line 2"), "{}", error);
+    assert_eq!(
+        rows(&router, "people").await,
+        json!([[1, "Alice", "00501"]])
+    );
+
+    let result = call(
+        &router,
+        "import_csv",
+        json!({ "table": "people", "csv": CONFLICTING, "on_conflict": "ignore" }),
+    )
+    .await;
+    assert_eq!(result["created"], false);
+    assert_eq!(result["rowcount"], 1);
+    assert_eq!(result["ignored"], 1);
+    assert_eq!(
+        rows(&router, "people").await,
+        json!([[1, "Alice", "00501"], [2, "Bob", "10001"]])
+    );
+
+    let result = call(
+        &router,
+        "import_csv",
+        json!({ "table": "people", "csv": CONFLICTING, "on_conflict": "replace" }),
+    )
+    .await;
+    assert_eq!(result["rowcount"], 2);
+    assert_eq!(
+        rows(&router, "people").await,
+        json!([[1, "Alicia", "00502"], [2, "Bob", "10001"]])
+    );
+
+    // 其他约束的错误在非fail策略下作为被拒绝的行返回
+    let result = call(
+        &router,
+        "import_csv",
+        json!({
+            "table": "people",
+            "csv": "id,name,zip\n3,,x\n4,Dan,y\n",
+            "null_tokens": [""],
+            "on_conflict": "replace"
+        }),
+    )
+    .await;
+    assert_eq!(result["rowcount"], 1);
+    assert_eq!(result["rejected"], 1);
+    assert_eq!(result["rejected_lines"][0]["line"], 2);
+
+    let error = call_err(
+        &router,
+        "import_csv",
+        json!({ "table": "people", "csv": CONFLICTING, "on_conflict": "upsert" }),
+    )
+    .await;
+    assert!(error.contains("on_conflict must be one of"), "{}", error);
+}
+
+#[tokio::test]
+async fn files_are_read_from_the_import_directories_only() {
+    let imports = TempDir::new("import-root");
+    let outside = TempDir::new("import-outside");
+    std::fs::write(
+        imports.path().join("people.csv"),
+        "id,name,zip\n5,Eve,02134\n",
+    )
+    .unwrap();
+    std::fs::write(
+        outside.path().join("secret.csv"),
+        "id,name,zip\n6,Mallory,x\n",
+    )
+    .unwrap();
+
+    let options = RouterOptions {
+        import_roots: vec![imports.path().to_path_buf()],
+        ..Default::default()
+    };
+    let router = router(options).await;
+
+    let result = call(
+        &router,
+        "import_csv",
+        json!({ "table": "people", "path": "people.csv" }),
+    )
+    .await;
+    assert_eq!(result["rowcount"], 1);
+
+    let error = call_err(
+        &router,
+        "import_csv",
+        json!({ "table": "people", "path": outside.file("secret.csv") }),
+    )
+    .await;
+    assert!(
+        error.contains("Path is outside the allowed directories"),
+        "{}",
+        error
+    );
+
+    let error = call_err(
+        &router,
+        "import_csv",
+        json!({ "table": "people", "path": "people.csv", "csv": "id\n1\n" }),
+    )
+    .await;
+    assert!(
+        error.contains("Pass either path or csv, not both"),
+        "{}",
+        error
+    );
+
+    // 没有导入目录时只能导入csv参数中的数据
+    let disabled = SQLiteRouter::new(":memory:").unwrap();
+    let error = call_err(
+        &disabled,
+        "import_csv",
+        json!({ "table": "people", "path": "people.csv" }),
+    )
+    .await;
+    assert!(error.contains("Importing files is disabled"), "{}", error);
+}
+
+#[tokio::test]
+async fn malformed_headers_and_unknown_columns_are_rejected() {
+    let router = router(RouterOptions::default()).await;
+
+    let error = call_err(
+        &router,
+        "import_csv",
+        json!({ "table": "people", "csv": "\"id,name\n1,Alice\n" }),
+    )
+    .await;
+    assert!(
+        error.contains("CSV header at line 1 is malformed"),
+        "{}",
+        error
+    );
+
+    let error = call_err(
+        &router,
+        "import_csv",
+        json!({ "table": "people", "csv": "id,nickname\n7,Al\n" }),
+    )
+    .await;
+    assert!(error.contains("nickname"), "{}", error);
+
+    let error = call_err(
+        &router,
+        "import_csv",
+        json!({ "table": "people", "csv": "" }),
+    )
+    .await;
+    assert!(error.contains("CSV data has no header row"), "{}", error);
+    assert_eq!(
+        rows(&router, "people").await,
+        json!([[1, "Alice", "00501"]])
+    );
+}
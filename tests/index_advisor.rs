@@ -0,0 +1,135 @@
+//! 索引建议工具的集成测试
+
+mod common;
+
+use common::{call, call_err, TempDir};
+use mcp_sqlite::{server::AttachedDatabase, RouterOptions, SQLiteRouter};
+use rusqlite::Connection;
+use serde_json::json;
+
+/// 创建带有统计信息的表
+async fn router() -> SQLiteRouter {
+    let router = SQLiteRouter::new(":memory:").unwrap();
+    call(
+        &router,
+        "executescript",
+        json!({
+            "script": "
+                CREATE TABLE users (id INTEGER PRIMARY KEY, email TEXT, country TEXT, created TEXT);
+                CREATE TABLE orders (id INTEGER PRIMARY KEY, user_id INTEGER, total REAL);
+                CREATE INDEX orders_user ON orders(user_id);
+                WITH RECURSIVE seq(n) AS (SELECT 1 UNION ALL SELECT n + 1 FROM seq WHERE n < 200)
+                INSERT INTO users SELECT n, 'user' || n || '@example.com', 'c' || (n % 10), date('2024-01-01', '+' || n || ' days') FROM seq;
+                ANALYZE;
+            "
+        }),
+    )
+    .await;
+    router
+}
+
+#[tokio::test]
+async fn suggests_indexes_for_full_scans() {
+    let router = router().await;
+
+    let result = call(
+        &router,
+        "suggest_indexes",
+        json!({
+            "queries": [
+                "SELECT * FROM users WHERE email = 'user1@example.com'",
+                "SELECT * FROM orders WHERE user_id = 1"
+            ]
+        }),
+    )
+    .await;
+    assert_eq!(result["statistics"], true);
+    assert_eq!(result["skipped"], json!([]));
+
+    let suggestions = result["suggestions"].as_array().unwrap();
+    assert_eq!(suggestions.len(), 1, "{}", result);
+    let suggestion = &suggestions[0];
+    assert_eq!(suggestion["table"], "users");
+    assert_eq!(suggestion["columns"], json!(["email"]));
+    assert_eq!(suggestion["table_rows"], 200);
+    assert_eq!(suggestion["queries"][0]["query"], 0);
+    assert_eq!(suggestion["queries"][0]["full_scans"], json!([1, 0]));
+    assert!(suggestion["sql"]
+        .as_str()
+        .unwrap()
+        .starts_with("CREATE INDEX"));
+
+    // 建议只在副本上验证，不修改数据库
+    let indexes = call(&router, "list_indexes", json!({ "table": "users" })).await;
+    assert_eq!(indexes["indexes"], json!([]));
+}
+
+#[tokio::test]
+async fn test_mode_returns_plan_changes() {
+    let router = router().await;
+
+    let result = call(
+        &router,
+        "suggest_indexes",
+        json!({
+            "queries": ["SELECT email FROM users WHERE country = 'c1' ORDER BY created"],
+            "test": true
+        }),
+    )
+    .await;
+    let suggestion = &result["suggestions"][0];
+    assert_eq!(suggestion["table"], "users");
+    let change = &suggestion["plan_changes"][0];
+    assert_eq!(change["query"], 0);
+    assert_ne!(change["before"], change["after"]);
+}
+
+#[tokio::test]
+async fn attached_databases_can_be_analyzed() {
+    let dir = TempDir::new("advisor-attached");
+    let analytics = dir.file("analytics.db");
+    Connection::open(&analytics)
+        .unwrap()
+        .execute_batch("CREATE TABLE events (id INTEGER PRIMARY KEY, kind TEXT)")
+        .unwrap();
+    let options = RouterOptions {
+        databases: vec![AttachedDatabase {
+            name: "analytics".into(),
+            path: analytics,
+        }],
+        ..Default::default()
+    };
+    let router = SQLiteRouter::with_options(":memory:", options).unwrap();
+
+    let result = call(
+        &router,
+        "suggest_indexes",
+        json!({ "queries": ["SELECT * FROM events WHERE kind = 'login'"], "database": "analytics" }),
+    )
+    .await;
+    assert_eq!(result["suggestions"][0]["table"], "events");
+    assert_eq!(result["suggestions"][0]["columns"], json!(["kind"]));
+}
+
+#[tokio::test]
+async fn invalid_queries_are_rejected() {
+    let router = router().await;
+
+    let error = call_err(&router, "suggest_indexes", json!({ "queries": [] })).await;
+    assert!(
+        error.contains("Missing required parameter: queries"),
+        "{}",
+        error
+    );
+
+    let error = call_err(&router, "suggest_indexes", json!({ "queries": [1] })).await;
+    assert!(error.contains("queries must contain strings"), "{}", error);
+
+    let error = call_err(
+        &router,
+        "suggest_indexes",
+        json!({ "queries": ["SELECT 1", "SELECT * FROM missing"] }),
+    )
+    .await;
+    assert!(error.contains("Failed to prepare queries[1]"), "{}", error);
+}
@@ -0,0 +1,124 @@
+//! 结构内省工具的集成测试
+
+mod common;
+
+use common::{call, call_err};
+use mcp_sqlite::SQLiteRouter;
+use serde_json::json;
+
+/// 创建带有表、视图、索引和外键的路由器
+async fn router() -> SQLiteRouter {
+    let router = SQLiteRouter::new(":memory:").unwrap();
+    call(
+        &router,
+        "executescript",
+        json!({
+            "script": r#"
+                CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT NOT NULL, email TEXT DEFAULT 'none');
+                CREATE TABLE orders (
+                    id INTEGER PRIMARY KEY,
+                    user_id INTEGER REFERENCES users(id) ON DELETE CASCADE,
+                    note TEXT,
+                    total REAL GENERATED ALWAYS AS (id * 2) VIRTUAL
+                );
+                CREATE UNIQUE INDEX users_email ON users(email);
+                CREATE INDEX "索引" ON orders(note) WHERE note <> 'a) WHERE (' AND user_id > 0;
+                CREATE VIEW user_names AS SELECT name FROM users;
+            "#
+        }),
+    )
+    .await;
+    router
+}
+
+#[tokio::test]
+async fn list_tables_reports_tables_and_views() {
+    let router = router().await;
+
+    let result = call(&router, "list_tables", json!({})).await;
+    let tables: Vec<(&str, &str)> = result["tables"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|t| (t["name"].as_str().unwrap(), t["type"].as_str().unwrap()))
+        .collect();
+    assert!(tables.contains(&("users", "table")));
+    assert!(tables.contains(&("orders", "table")));
+    assert!(tables.contains(&("user_names", "view")));
+    assert!(!tables.iter().any(|(name, _)| name.starts_with("sqlite_")));
+
+    let result = call(&router, "list_tables", json!({ "include_system": true })).await;
+    assert!(result["tables"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|t| t["name"] == "sqlite_schema"));
+}
+
+#[tokio::test]
+async fn describe_table_returns_column_definitions() {
+    let router = router().await;
+
+    let result = call(&router, "describe_table", json!({ "table": "users" })).await;
+    let columns = result["columns"].as_array().unwrap();
+    assert_eq!(columns.len(), 3);
+    assert_eq!(columns[0]["name"], "id");
+    assert_eq!(columns[0]["pk"], 1);
+    assert_eq!(columns[1]["nullable"], false);
+    assert_eq!(columns[2]["default"], "'none'");
+    assert_eq!(result["primary_key"], json!(["id"]));
+
+    let result = call(&router, "describe_table", json!({ "table": "orders" })).await;
+    let total = &result["columns"][3];
+    assert_eq!(total["name"], "total");
+    assert_eq!(total["generated"], "virtual");
+}
+
+#[tokio::test]
+async fn list_indexes_reports_partial_index_predicates() {
+    let router = router().await;
+
+    let result = call(&router, "list_indexes", json!({ "table": "orders" })).await;
+    let index = &result["indexes"][0];
+    assert_eq!(index["name"], "索引");
+    assert_eq!(index["partial"], true);
+    assert_eq!(index["where"], "note <> 'a) WHERE (' AND user_id > 0");
+    assert_eq!(index["columns"][0]["name"], "note");
+
+    let result = call(&router, "list_indexes", json!({ "table": "users" })).await;
+    let index = &result["indexes"][0];
+    assert_eq!(index["name"], "users_email");
+    assert_eq!(index["unique"], true);
+    assert_eq!(index["partial"], false);
+}
+
+#[tokio::test]
+async fn list_foreign_keys_reports_references_and_actions() {
+    let router = router().await;
+
+    let result = call(&router, "list_foreign_keys", json!({ "table": "orders" })).await;
+    let foreign_key = &result["foreign_keys"][0];
+    assert_eq!(foreign_key["table"], "users");
+    assert_eq!(foreign_key["from"], json!(["user_id"]));
+    assert_eq!(foreign_key["to"], json!(["id"]));
+    assert_eq!(foreign_key["on_delete"], "CASCADE");
+
+    let result = call(&router, "list_foreign_keys", json!({ "table": "users" })).await;
+    assert_eq!(result["foreign_keys"], json!([]));
+}
+
+#[tokio::test]
+async fn unknown_tables_and_databases_are_rejected() {
+    let router = router().await;
+
+    for tool in ["describe_table", "list_indexes", "list_foreign_keys"] {
+        let error = call_err(&router, tool, json!({ "table": "missing" })).await;
+        assert!(error.contains("Table not found: missing"), "{}", error);
+    }
+
+    let error = call_err(&router, "describe_table", json!({})).await;
+    assert!(error.contains("table"), "{}", error);
+
+    let error = call_err(&router, "list_tables", json!({ "database": "nope" })).await;
+    assert!(error.contains("nope"), "{}", error);
+}
@@ -0,0 +1,189 @@
+//! 数据脱敏在查询、游标、导出格式和资源中的集成测试
+
+use std::sync::Arc;
+
+use mcp_server_fishcode2025::Router;
+use mcp_sqlite::{digest::sha256_hex, masking::Masking, RouterOptions, SQLiteRouter};
+use serde_json::{json, Value};
+
+const MASKING: &str = r#"{
+    "salt": "pepper",
+    "columns": [
+        { "table": "users", "column": "email", "action": "hash" },
+        { "table": "users", "column": "phone", "action": "partial", "keep_start": 3, "keep_end": 2 },
+        { "table": "users", "column": "ssn", "action": "replace", "value": "[REDACTED]" },
+        { "table": "*", "column": "*token*", "action": "drop" }
+    ],
+    "detectors": [
+        { "name": "email", "pattern": "[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\\.[A-Za-z]{2,}", "action": "replace", "value": "[EMAIL]" },
+        { "name": "card", "pattern": "\\b\\d{4}-\\d{4}-\\d{4}-\\d{4}\\b", "action": "partial", "keep_end": 4 }
+    ]
+}"#;
+
+/// 创建带有脱敏配置和测试数据的路由器
+async fn router() -> SQLiteRouter {
+    let options = RouterOptions {
+        masking: Some(Arc::new(Masking::from_json(MASKING).unwrap())),
+        ..Default::default()
+    };
+    let router = SQLiteRouter::with_options(":memory:", options).unwrap();
+    call(
+        &router,
+        "executescript",
+        json!({
+            "script": "
+                CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT, email TEXT, phone TEXT, ssn TEXT, api_token TEXT, notes TEXT);
+                INSERT INTO users VALUES
+                    (1, 'Alice', 'alice@example.com', '13812345678', '123-45-6789', 'secret-1', 'card 4111-1111-1111-1234'),
+                    (2, 'Bob', NULL, '13900000000', NULL, 'secret-2', 'mail bob@example.org please');
+                CREATE VIEW contacts AS SELECT name, email AS contact FROM users;
+            "
+        }),
+    )
+    .await;
+    router
+}
+
+async fn call(router: &SQLiteRouter, tool: &str, arguments: Value) -> Value {
+    let content = router
+        .call_tool(tool, arguments)
+        .await
+        .unwrap_or_else(|e| panic!("{} failed: {}", tool, e));
+    serde_json::from_str(content[0].as_text().unwrap()).unwrap()
+}
+
+fn hashed(value: &str) -> String {
+    sha256_hex(format!("pepper{}", value).as_bytes())
+}
+
+#[tokio::test]
+async fn column_rules_apply_to_query_results() {
+    let router = router().await;
+    let result = call(
+        &router,
+        "query",
+        json!({ "query": "SELECT * FROM users ORDER BY id" }),
+    )
+    .await;
+
+    assert_eq!(
+        result["columns"],
+        json!(["id", "name", "email", "phone", "ssn", "notes"])
+    );
+    let alice = &result["rows"][0];
+    assert_eq!(alice["email"], json!(hashed("alice@example.com")));
+    // 与其他SHA-256实现的结果一致：sha256("pepper" || "alice@example.com")
+    assert_eq!(
+        alice["email"],
+        json!("8b8d9adc4875c0dca816e3e17b7ac87b45e40945b731fa02e3b42bf101589e21")
+    );
+    assert_eq!(alice["phone"], json!("138******78"));
+    assert_eq!(alice["ssn"], json!("[REDACTED]"));
+    assert!(alice.get("api_token").is_none());
+
+    // NULL不会被哈希或替换
+    let bob = &result["rows"][1];
+    assert_eq!(bob["email"], Value::Null);
+    assert_eq!(bob["ssn"], Value::Null);
+}
+
+#[tokio::test]
+async fn aliases_and_views_are_traced_to_source_columns() {
+    let router = router().await;
+    let result = call(
+        &router,
+        "query",
+        json!({ "query": "SELECT email AS e, api_token AS t, id FROM users WHERE id = 1", "format": "arrays" }),
+    )
+    .await;
+    assert_eq!(result["columns"], json!(["e", "id"]));
+    assert_eq!(result["rows"], json!([[hashed("alice@example.com"), 1]]));
+
+    let result = call(
+        &router,
+        "query",
+        json!({ "query": "SELECT contact FROM contacts WHERE name = 'Alice'" }),
+    )
+    .await;
+    assert_eq!(
+        result["rows"][0]["contact"],
+        json!(hashed("alice@example.com"))
+    );
+}
+
+#[tokio::test]
+async fn detectors_mask_untagged_columns() {
+    let router = router().await;
+    let result = call(
+        &router,
+        "query",
+        json!({ "query": "SELECT notes, lower(email) AS derived FROM users ORDER BY id" }),
+    )
+    .await;
+    assert_eq!(
+        result["rows"],
+        json!([
+            { "notes": "card ***************1234", "derived": "[EMAIL]" },
+            { "notes": "mail [EMAIL] please", "derived": null }
+        ])
+    );
+}
+
+#[tokio::test]
+async fn masking_applies_to_cursors_and_exports() {
+    let router = router().await;
+    let page = call(
+        &router,
+        "query",
+        json!({ "query": "SELECT id, email, api_token FROM users ORDER BY id", "cursor": true, "limit": 1 }),
+    )
+    .await;
+    let page = call(
+        &router,
+        "fetch",
+        json!({ "cursor_id": page["cursor_id"], "format": "csv" }),
+    )
+    .await;
+    assert_eq!(page["text"], json!("id,email\n2,\n"));
+
+    let result = call(
+        &router,
+        "query",
+        json!({ "query": "SELECT ssn, phone FROM users WHERE id = 1", "format": "markdown" }),
+    )
+    .await;
+    let text = result["text"].as_str().unwrap();
+    assert!(text.contains("[REDACTED]"), "{}", text);
+    assert!(!text.contains("123-45-6789"), "{}", text);
+
+    let result = call(
+        &router,
+        "query",
+        json!({ "query": "SELECT ssn FROM users WHERE id = 1", "typed": true }),
+    )
+    .await;
+    assert_eq!(
+        result["rows"][0]["ssn"],
+        json!({ "type": "text", "value": "[REDACTED]" })
+    );
+}
+
+#[tokio::test]
+async fn masking_applies_to_resources() {
+    let router = router().await;
+    let sample = router
+        .read_resource("sqlite://table/users/sample")
+        .await
+        .unwrap();
+    let sample: Value = serde_json::from_str(&sample).unwrap();
+    assert_eq!(
+        sample["columns"],
+        json!(["id", "name", "email", "phone", "ssn", "notes"])
+    );
+    assert_eq!(
+        sample["rows"][0]["email"],
+        json!(hashed("alice@example.com"))
+    );
+    assert!(!sample.to_string().contains("secret-1"));
+    assert!(!sample.to_string().contains("bob@example.org"));
+}
@@ -0,0 +1,125 @@
+//! 命名参数绑定的集成测试
+
+mod common;
+
+use common::{call, call_err};
+use mcp_sqlite::SQLiteRouter;
+use serde_json::json;
+
+/// 创建空的用户表
+async fn router() -> SQLiteRouter {
+    let router = SQLiteRouter::new(":memory:").unwrap();
+    call(
+        &router,
+        "execute",
+        json!({ "statement": "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT, age INTEGER)" }),
+    )
+    .await;
+    router
+}
+
+#[tokio::test]
+async fn named_params_bind_in_all_prefix_styles() {
+    let router = router().await;
+
+    call(
+        &router,
+        "execute",
+        json!({
+            "statement": "INSERT INTO users (id, name, age) VALUES (:id, @name, $age)",
+            "params": { ":id": 1, "name": "Alice", "$age": 30 }
+        }),
+    )
+    .await;
+    let result = call(
+        &router,
+        "executemany",
+        json!({
+            "statement": "INSERT INTO users (id, name, age) VALUES (:id, :name, :age)",
+            "params_list": [
+                { "id": 2, "name": "Bob", "age": 25 },
+                { "id": 3, "name": "Carol", "age": 41 }
+            ]
+        }),
+    )
+    .await;
+    assert_eq!(result["rowcount"], 2);
+
+    // 同一个名称可以出现多次
+    let result = call(
+        &router,
+        "query",
+        json!({
+            "query": "SELECT name FROM users WHERE age >= :min AND id <> :min ORDER BY id",
+            "params": { "min": 30 }
+        }),
+    )
+    .await;
+    assert_eq!(
+        result["rows"],
+        json!([{ "name": "Alice" }, { "name": "Carol" }])
+    );
+}
+
+#[tokio::test]
+async fn missing_unexpected_and_positional_names_are_rejected() {
+    let router = router().await;
+
+    let error = call_err(
+        &router,
+        "execute",
+        json!({
+            "statement": "INSERT INTO users (name, age) VALUES (:name, :age)",
+            "params": { "name": "Alice" }
+        }),
+    )
+    .await;
+    assert!(
+        error.contains("Missing named parameters in params: :age"),
+        "{}",
+        error
+    );
+
+    let error = call_err(
+        &router,
+        "query",
+        json!({
+            "query": "SELECT * FROM users WHERE id = :id",
+            "params": { "id": 1, "name": "Alice" }
+        }),
+    )
+    .await;
+    assert!(
+        error.contains("Unexpected named parameters in params: name"),
+        "{}",
+        error
+    );
+
+    let error = call_err(
+        &router,
+        "query",
+        json!({ "query": "SELECT * FROM users WHERE id = ?", "params": { "id": 1 } }),
+    )
+    .await;
+    assert!(error.contains("uses positional placeholders"), "{}", error);
+
+    let error = call_err(
+        &router,
+        "executemany",
+        json!({
+            "statement": "INSERT INTO users (name) VALUES (:name)",
+            "params_list": [{ "name": "Alice" }, { "nam": "Bob" }],
+            "atomic": true
+        }),
+    )
+    .await;
+    assert!(error.contains("Missing named parameters"), "{}", error);
+
+    let result = call(
+        &router,
+        "query",
+        json!({ "query": "SELECT count(*) AS n FROM users" }),
+    )
+    .await;
+    assert_eq!(result["rows"][0]["n"], 0);
+}
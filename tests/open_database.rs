@@ -0,0 +1,196 @@
+//! 运行时打开数据库的集成测试
+
+mod common;
+
+use common::{call, call_err, TempDir};
+use mcp_server_fishcode2025::Router;
+use mcp_sqlite::{RouterOptions, SQLiteRouter};
+use serde_json::json;
+
+/// 创建允许打开`root`中的数据库的路由器
+fn router(root: &TempDir) -> SQLiteRouter {
+    let options = RouterOptions {
+        open_roots: vec![root.path().to_path_buf()],
+        ..Default::default()
+    };
+    SQLiteRouter::with_options(":memory:", options).unwrap()
+}
+
+#[tokio::test]
+async fn databases_can_be_opened_used_and_closed() {
+    let root = TempDir::new("open-roundtrip");
+    let router = router(&root);
+
+    let result = call(
+        &router,
+        "open_database",
+        json!({ "name": "scratch", "path": "scratch.db", "create": true }),
+    )
+    .await;
+    assert_eq!(result["name"], "scratch");
+    assert!(result["path"].as_str().unwrap().ends_with("scratch.db"));
+
+    call(
+        &router,
+        "executescript",
+        json!({
+            "script": "CREATE TABLE notes (text TEXT); INSERT INTO notes VALUES ('hello');",
+            "database": "scratch"
+        }),
+    )
+    .await;
+    let result = call(
+        &router,
+        "query",
+        json!({ "query": "SELECT text FROM notes", "database": "scratch" }),
+    )
+    .await;
+    assert_eq!(result["rows"], json!([{ "text": "hello" }]));
+    let result = call(&router, "list_tables", json!({ "database": "scratch" })).await;
+    assert_eq!(result["tables"][0]["name"], "notes");
+
+    // 打开的数据库与主数据库相互独立
+    let error = call_err(&router, "query", json!({ "query": "SELECT * FROM notes" })).await;
+    assert!(error.contains("no such table: notes"), "{}", error);
+
+    let result = call(&router, "list_open_databases", json!({})).await;
+    assert_eq!(result["databases"][0]["name"], "scratch");
+    assert_eq!(result["databases"][0]["read_only"], false);
+
+    let result = call(&router, "close_database", json!({ "name": "scratch" })).await;
+    assert_eq!(result["closed"], true);
+    let error = call_err(
+        &router,
+        "query",
+        json!({ "query": "SELECT 1", "database": "scratch" }),
+    )
+    .await;
+    assert!(error.contains("Unknown database: scratch"), "{}", error);
+    let error = call_err(&router, "close_database", json!({ "name": "scratch" })).await;
+    assert!(
+        error.contains("Unknown opened database: scratch"),
+        "{}",
+        error
+    );
+
+    // 重新打开后数据仍在
+    call(
+        &router,
+        "open_database",
+        json!({ "name": "again", "path": "scratch.db", "read_only": true }),
+    )
+    .await;
+    let result = call(
+        &router,
+        "query",
+        json!({ "query": "SELECT count(*) AS n FROM notes", "database": "again" }),
+    )
+    .await;
+    assert_eq!(result["rows"][0]["n"], 1);
+    let error = call_err(
+        &router,
+        "execute",
+        json!({ "statement": "DELETE FROM notes", "database": "again" }),
+    )
+    .await;
+    assert!(
+        error.contains("Database again is opened read-only"),
+        "{}",
+        error
+    );
+}
+
+#[tokio::test]
+async fn paths_outside_the_allowed_directories_are_rejected() {
+    let root = TempDir::new("open-root");
+    let outside = TempDir::new("open-outside");
+    std::fs::write(outside.path().join("secret.db"), "").unwrap();
+    let router = router(&root);
+
+    for path in [
+        outside.file("secret.db"),
+        format!(
+            "../{}/secret.db",
+            outside.path().file_name().unwrap().to_string_lossy()
+        ),
+    ] {
+        let error = call_err(
+            &router,
+            "open_database",
+            json!({ "name": "secret", "path": path }),
+        )
+        .await;
+        assert!(
+            error.contains("Path is outside the allowed directories"),
+            "{}",
+            error
+        );
+    }
+
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink(outside.path(), root.path().join("link")).unwrap();
+        let error = call_err(
+            &router,
+            "open_database",
+            json!({ "name": "secret", "path": "link/secret.db" }),
+        )
+        .await;
+        assert!(
+            error.contains("Path is outside the allowed directories"),
+            "{}",
+            error
+        );
+    }
+}
+
+#[tokio::test]
+async fn invalid_names_and_files_are_rejected() {
+    let root = TempDir::new("open-invalid");
+    std::fs::write(root.path().join("notes.txt"), "not a database").unwrap();
+    let router = router(&root);
+
+    for (name, message) in [
+        ("main", "Database name is already in use: main"),
+        ("bad-name", "Invalid database name: bad-name"),
+    ] {
+        let error = call_err(
+            &router,
+            "open_database",
+            json!({ "name": name, "path": "x.db", "create": true }),
+        )
+        .await;
+        assert!(error.contains(message), "{}", error);
+    }
+
+    let error = call_err(
+        &router,
+        "open_database",
+        json!({ "name": "notes", "path": "notes.txt" }),
+    )
+    .await;
+    assert!(error.contains("Failed to open database"), "{}", error);
+    let result = call(&router, "list_open_databases", json!({})).await;
+    assert_eq!(result["databases"], json!([]));
+
+    let error = call_err(
+        &router,
+        "open_database",
+        json!({ "name": "missing", "path": "missing.db" }),
+    )
+    .await;
+    assert!(error.contains("missing.db"), "{}", error);
+
+    // 没有允许的目录时工具被隐藏
+    let disabled = SQLiteRouter::new(":memory:").unwrap();
+    assert!(!disabled
+        .list_tools()
+        .iter()
+        .any(|tool| tool.name == "open_database"));
+    call_err(
+        &disabled,
+        "open_database",
+        json!({ "name": "x", "path": "x.db", "create": true }),
+    )
+    .await;
+}
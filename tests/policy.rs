@@ -0,0 +1,275 @@
+//! SQL访问策略的集成测试
+
+mod common;
+
+use std::sync::Arc;
+
+use common::{call, call_err, TempDir};
+use mcp_sqlite::{policy::Policy, server::AttachedDatabase, RouterOptions, SQLiteRouter};
+use rusqlite::Connection;
+use serde_json::json;
+
+const POLICY: &str = r#"{
+    "default": "deny",
+    "rules": [
+        { "name": "no-ddl", "effect": "deny", "actions": ["drop", "alter"] },
+        { "name": "no-extensions", "effect": "deny", "actions": ["function"], "names": ["load_extension"] },
+        { "name": "no-settings", "effect": "deny", "actions": ["pragma"], "names": ["user_version"] },
+        { "name": "read-anything", "effect": "allow", "actions": ["read", "function", "pragma"] },
+        { "name": "write-staging", "effect": "allow", "actions": ["insert", "update"], "tables": ["staging_*"] }
+    ]
+}"#;
+
+/// 允许除删除用户和结构变更以外的所有动作
+const PROTECT_USERS: &str = r#"{
+    "default": "allow",
+    "rules": [
+        { "name": "no-ddl", "effect": "deny", "actions": ["drop", "alter"] },
+        { "name": "keep-users", "effect": "deny", "actions": ["delete"], "tables": ["users"] }
+    ]
+}"#;
+
+/// 创建带有测试数据的数据库文件，并以[`POLICY`]打开
+fn router(dir: &TempDir, options: RouterOptions) -> SQLiteRouter {
+    router_with_policy(dir, POLICY, options)
+}
+
+/// 创建带有测试数据的数据库文件，并以指定的访问策略打开
+fn router_with_policy(dir: &TempDir, policy: &str, options: RouterOptions) -> SQLiteRouter {
+    let path = dir.file("app.db");
+    let conn = Connection::open(&path).unwrap();
+    conn.execute_batch(
+        "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT);
+         CREATE TABLE staging_users (id INTEGER PRIMARY KEY, name TEXT);
+         INSERT INTO users VALUES (1, 'Alice');",
+    )
+    .unwrap();
+    drop(conn);
+
+    let options = RouterOptions {
+        policy: Some(Arc::new(Policy::from_json(policy).unwrap())),
+        ..options
+    };
+    SQLiteRouter::with_options(&path, options).unwrap()
+}
+
+#[tokio::test]
+async fn rules_allow_reads_and_staging_writes() {
+    let dir = TempDir::new("policy-allow");
+    let router = router(&dir, RouterOptions::default());
+
+    let result = call(
+        &router,
+        "query",
+        json!({ "query": "SELECT name FROM users" }),
+    )
+    .await;
+    assert_eq!(result["rows"], json!([{ "name": "Alice" }]));
+
+    let result = call(
+        &router,
+        "execute",
+        json!({ "statement": "INSERT INTO staging_users SELECT * FROM users" }),
+    )
+    .await;
+    assert_eq!(result["rowcount"], 1);
+}
+
+#[tokio::test]
+async fn denials_name_the_rule() {
+    let dir = TempDir::new("policy-deny");
+    let router = router(&dir, RouterOptions::default());
+
+    let error = call_err(
+        &router,
+        "execute",
+        json!({ "statement": "DROP TABLE users" }),
+    )
+    .await;
+    assert!(
+        error.contains("Denied by access policy rule 'no-ddl'"),
+        "{}",
+        error
+    );
+
+    let error = call_err(
+        &router,
+        "query",
+        json!({ "query": "SELECT load_extension('evil')" }),
+    )
+    .await;
+    assert!(error.contains("rule 'no-extensions'"), "{}", error);
+
+    // 没有规则匹配时使用默认的deny
+    let error = call_err(
+        &router,
+        "execute",
+        json!({ "statement": "INSERT INTO users VALUES (2, 'Bob')" }),
+    )
+    .await;
+    assert!(
+        error.contains("Denied by access policy: INSERT on users is not allowed by any rule"),
+        "{}",
+        error
+    );
+
+    let error = call_err(
+        &router,
+        "execute",
+        json!({ "statement": "PRAGMA user_version = 7" }),
+    )
+    .await;
+    assert!(error.contains("Denied by access policy"), "{}", error);
+
+    let result = call(
+        &router,
+        "query",
+        json!({ "query": "SELECT count(*) AS n FROM users" }),
+    )
+    .await;
+    assert_eq!(result["rows"][0]["n"], 1);
+}
+
+#[tokio::test]
+async fn schema_tables_cannot_be_modified_directly() {
+    let dir = TempDir::new("policy-schema");
+    let router = router_with_policy(&dir, PROTECT_USERS, RouterOptions::default());
+
+    let error = call_err(
+        &router,
+        "execute",
+        json!({ "statement": "DROP TABLE users" }),
+    )
+    .await;
+    assert!(error.contains("rule 'no-ddl'"), "{}", error);
+
+    // 打开writable_schema后直接删除sqlite_master中的记录曾经可以绕过no-ddl规则
+    let error = call_err(
+        &router,
+        "executescript",
+        json!({
+            "script": "PRAGMA writable_schema=ON; DELETE FROM sqlite_master WHERE name='users'; PRAGMA writable_schema=OFF;"
+        }),
+    )
+    .await;
+    assert!(
+        error.contains("Denied by access policy: PRAGMA writable_schema = ON is never allowed"),
+        "{}",
+        error
+    );
+    let error = call_err(
+        &router,
+        "query",
+        json!({ "query": "PRAGMA writable_schema" }),
+    )
+    .await;
+    assert!(error.contains("PRAGMA writable_schema"), "{}", error);
+
+    // 防御模式下即使没有writable_schema也不能直接修改结构表
+    let error = call_err(
+        &router,
+        "execute",
+        json!({ "statement": "DELETE FROM sqlite_master WHERE name = 'users'" }),
+    )
+    .await;
+    assert!(error.contains("may not be modified"), "{}", error);
+
+    let tables = call(&router, "list_tables", json!({})).await;
+    assert!(tables.to_string().contains("\"users\""), "{}", tables);
+    let result = call(
+        &router,
+        "query",
+        json!({ "query": "SELECT count(*) AS n FROM users" }),
+    )
+    .await;
+    assert_eq!(result["rows"][0]["n"], 1);
+}
+
+#[tokio::test]
+async fn vacuum_into_is_only_allowed_for_backups() {
+    let dir = TempDir::new("policy-vacuum");
+    let backups = TempDir::new("policy-vacuum-backups");
+    let options = RouterOptions {
+        backup_dir: Some(backups.path().to_path_buf()),
+        ..Default::default()
+    };
+    let router = router(&dir, options);
+
+    // VACUUM INTO需要附加目标文件，访问策略不允许直接执行
+    let target = dir.file("copy.db");
+    let error = call_err(
+        &router,
+        "execute",
+        json!({ "statement": "VACUUM INTO ?", "params": [target] }),
+    )
+    .await;
+    assert!(error.contains("Denied by access policy"), "{}", error);
+    assert!(!std::path::Path::new(&target).exists());
+
+    let result = call(
+        &router,
+        "backup",
+        json!({ "name": "vacuumed.db", "method": "vacuum" }),
+    )
+    .await;
+    assert_eq!(result["method"], "vacuum");
+    let copy = Connection::open(backups.path().join("vacuumed.db")).unwrap();
+    let count: i64 = copy
+        .query_row("SELECT count(*) FROM users", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(count, 1);
+}
+
+#[tokio::test]
+async fn database_scope_rejects_other_databases() {
+    let dir = TempDir::new("policy-scope");
+    let analytics = dir.file("analytics.db");
+    Connection::open(&analytics)
+        .unwrap()
+        .execute_batch("CREATE TABLE events (name TEXT); INSERT INTO events VALUES ('login');")
+        .unwrap();
+    let options = RouterOptions {
+        databases: vec![AttachedDatabase {
+            name: "analytics".into(),
+            path: analytics,
+        }],
+        ..Default::default()
+    };
+    let router = router(&dir, options);
+
+    let result = call(
+        &router,
+        "query",
+        json!({ "query": "SELECT name FROM events", "database": "analytics" }),
+    )
+    .await;
+    assert_eq!(result["rows"], json!([{ "name": "login" }]));
+
+    let error = call_err(
+        &router,
+        "query",
+        json!({ "query": "SELECT name FROM users", "database": "analytics" }),
+    )
+    .await;
+    assert!(error.contains("Denied by database scope"), "{}", error);
+    assert!(error.contains("'analytics'"), "{}", error);
+
+    let error = call_err(
+        &router,
+        "query",
+        json!({ "query": "SELECT 1", "database": "missing" }),
+    )
+    .await;
+    assert!(error.contains("missing"), "{}", error);
+}
+
+#[test]
+fn invalid_policies_are_rejected() {
+    let error = Policy::from_json(
+        r#"{ "rules": [{ "name": "bad", "effect": "allow", "actions": ["launch"] }] }"#,
+    )
+    .unwrap_err();
+    assert!(error.to_string().contains("launch"), "{}", error);
+
+    assert!(Policy::from_json("{ not json").is_err());
+}
@@ -0,0 +1,142 @@
+//! 写入预览的集成测试
+
+mod common;
+
+use common::{call, call_err};
+use mcp_sqlite::SQLiteRouter;
+use serde_json::{json, Value};
+
+/// 创建带有级联外键和测试数据的路由器
+async fn router() -> SQLiteRouter {
+    let router = SQLiteRouter::new(":memory:").unwrap();
+    call(
+        &router,
+        "executescript",
+        json!({
+            "script": "
+                PRAGMA foreign_keys = ON;
+                CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT);
+                CREATE TABLE orders (id INTEGER PRIMARY KEY, user_id INTEGER REFERENCES users(id) ON DELETE CASCADE);
+                INSERT INTO users VALUES (1, 'Alice'), (2, 'Bob');
+                INSERT INTO orders VALUES (10, 1), (11, 1), (12, 2);
+            "
+        }),
+    )
+    .await;
+    router
+}
+
+/// 读取表中的行数
+async fn count(router: &SQLiteRouter, table: &str) -> Value {
+    let query = format!("SELECT count(*) AS n FROM {}", table);
+    call(router, "query", json!({ "query": query })).await["rows"][0]["n"].clone()
+}
+
+/// 在预览中查找表的修改
+fn changes<'a>(result: &'a Value, table: &str) -> &'a Value {
+    result["changes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|change| change["table"] == table)
+        .unwrap_or_else(|| panic!("no changes for {}", table))
+}
+
+#[tokio::test]
+async fn dry_run_reports_changes_and_rolls_back() {
+    let router = router().await;
+
+    let result = call(
+        &router,
+        "execute",
+        json!({ "statement": "DELETE FROM users WHERE id = 1", "dry_run": true }),
+    )
+    .await;
+    assert_eq!(result["dry_run"], true);
+    assert_eq!(result["rowcount"], 1);
+
+    let users = changes(&result, "users");
+    assert_eq!(users["deleted"], 1);
+    assert_eq!(users["rows"][0]["action"], "delete");
+    assert_eq!(
+        users["rows"][0]["before"],
+        json!({ "id": 1, "name": "Alice" })
+    );
+    assert_eq!(users["rows"][0]["after"], Value::Null);
+
+    // 外键级联删除的行同样出现在预览中
+    assert_eq!(changes(&result, "orders")["deleted"], 2);
+
+    assert_eq!(count(&router, "users").await, 2);
+    assert_eq!(count(&router, "orders").await, 3);
+}
+
+#[tokio::test]
+async fn dry_run_covers_updates_scripts_and_schema_changes() {
+    let router = router().await;
+
+    let result = call(
+        &router,
+        "executemany",
+        json!({
+            "statement": "UPDATE users SET name = ? WHERE id = ?",
+            "params_list": [["Alicia", 1], ["Robert", 2]],
+            "dry_run": true,
+            "preview_rows": 1
+        }),
+    )
+    .await;
+    let users = changes(&result, "users");
+    assert_eq!(users["updated"], 2);
+    assert_eq!(users["rows"].as_array().unwrap().len(), 1);
+    assert_eq!(users["omitted_rows"], 1);
+    assert_eq!(users["rows"][0]["after"]["name"], "Alicia");
+
+    let result = call(
+        &router,
+        "executescript",
+        json!({
+            "script": "DROP TABLE orders; INSERT INTO users VALUES (3, 'Carol');",
+            "dry_run": true
+        }),
+    )
+    .await;
+    assert_eq!(result["schema_changes"], json!(["DROP TABLE orders"]));
+    assert_eq!(changes(&result, "users")["inserted"], 1);
+
+    assert_eq!(count(&router, "orders").await, 3);
+    let names = call(
+        &router,
+        "query",
+        json!({ "query": "SELECT name FROM users ORDER BY id", "format": "arrays" }),
+    )
+    .await;
+    assert_eq!(names["rows"], json!([["Alice"], ["Bob"]]));
+}
+
+#[tokio::test]
+async fn dry_run_rejects_transaction_control_and_keeps_failures_harmless() {
+    let router = router().await;
+
+    let error = call_err(
+        &router,
+        "executescript",
+        json!({ "script": "DELETE FROM orders; COMMIT;", "dry_run": true }),
+    )
+    .await;
+    assert!(
+        error.contains("dry_run cannot contain transaction control statements: COMMIT"),
+        "{}",
+        error
+    );
+
+    let error = call_err(
+        &router,
+        "executescript",
+        json!({ "script": "DELETE FROM orders; INSERT INTO missing VALUES (1);", "dry_run": true }),
+    )
+    .await;
+    assert!(error.contains("no such table: missing"), "{}", error);
+
+    assert_eq!(count(&router, "orders").await, 3);
+}
@@ -0,0 +1,84 @@
+//! 只读模式的集成测试
+
+mod common;
+
+use common::{call, call_err, TempDir};
+use mcp_server_fishcode2025::Router;
+use mcp_sqlite::{RouterOptions, SQLiteRouter};
+use rusqlite::Connection;
+use serde_json::json;
+
+/// 创建带有测试数据的数据库文件，并以只读模式打开
+fn router(dir: &TempDir) -> SQLiteRouter {
+    let path = dir.file("replica.db");
+    let conn = Connection::open(&path).unwrap();
+    conn.execute_batch("CREATE TABLE items (id INTEGER PRIMARY KEY, name TEXT); INSERT INTO items VALUES (1, 'a'), (2, 'b');")
+        .unwrap();
+    drop(conn);
+
+    let options = RouterOptions {
+        read_only: true,
+        ..Default::default()
+    };
+    SQLiteRouter::with_options(&path, options).unwrap()
+}
+
+#[tokio::test]
+async fn write_tools_are_hidden_and_rejected() {
+    let dir = TempDir::new("read-only-tools");
+    let router = router(&dir);
+
+    let tools: Vec<String> = router.list_tools().into_iter().map(|t| t.name).collect();
+    assert!(tools.contains(&"query".to_string()));
+    for tool in ["execute", "executemany", "executescript"] {
+        assert!(!tools.contains(&tool.to_string()), "{} is listed", tool);
+    }
+
+    let error = call_err(
+        &router,
+        "execute",
+        json!({ "statement": "DELETE FROM items" }),
+    )
+    .await;
+    assert!(error.contains("Denied by read-only policy"), "{}", error);
+
+    let result = call(
+        &router,
+        "query",
+        json!({ "query": "SELECT count(*) AS n FROM items" }),
+    )
+    .await;
+    assert_eq!(result["rows"][0]["n"], 2);
+}
+
+#[tokio::test]
+async fn query_cannot_write_attach_or_change_settings() {
+    let dir = TempDir::new("read-only-query");
+    let router = router(&dir);
+
+    for sql in [
+        "DELETE FROM items RETURNING id",
+        "INSERT INTO items VALUES (3, 'c') RETURNING id",
+        "PRAGMA writable_schema = ON",
+    ] {
+        let error = call_err(&router, "query", json!({ "query": sql })).await;
+        assert!(
+            error.contains("Denied by read-only policy"),
+            "{}: {}",
+            sql,
+            error
+        );
+    }
+
+    let attach = format!("ATTACH DATABASE '{}' AS other", dir.file("other.db"));
+    let error = call_err(&router, "query", json!({ "query": attach })).await;
+    assert!(error.contains("Denied by read-only policy"), "{}", error);
+
+    let result = call(
+        &router,
+        "query",
+        json!({ "query": "SELECT count(*) AS n FROM items" }),
+    )
+    .await;
+    assert_eq!(result["rows"][0]["n"], 2);
+}
@@ -0,0 +1,140 @@
+//! 只读连接池与WAL模式的集成测试
+
+mod common;
+
+use std::time::{Duration, Instant};
+
+use common::{call, call_err, TempDir};
+use mcp_server_fishcode2025::Router;
+use mcp_sqlite::{RouterOptions, SQLiteRouter};
+use rusqlite::Connection;
+use serde_json::{json, Value};
+
+/// 不会自行结束的查询，由timeout_ms中断
+const ENDLESS: &str =
+    "WITH RECURSIVE c(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM c) SELECT count(*) FROM c";
+
+/// 创建带有一行数据的表
+async fn create_table(router: &SQLiteRouter) {
+    call(
+        router,
+        "executescript",
+        json!({ "script": "CREATE TABLE items (name TEXT); INSERT INTO items VALUES ('a');" }),
+    )
+    .await;
+}
+
+/// 读取表中的行数
+async fn count(router: &SQLiteRouter) -> Value {
+    let result = call(
+        router,
+        "query",
+        json!({ "query": "SELECT count(*) AS n FROM items" }),
+    )
+    .await;
+    result["rows"][0]["n"].clone()
+}
+
+/// 读取数据库文件的日志模式
+fn journal_mode(path: &str) -> String {
+    Connection::open(path)
+        .unwrap()
+        .pragma_query_value(None, "journal_mode", |row| row.get(0))
+        .unwrap()
+}
+
+#[tokio::test]
+async fn file_databases_use_wal_unless_disabled() {
+    let dir = TempDir::new("readers-wal");
+
+    let path = dir.file("wal.db");
+    let router = SQLiteRouter::with_options(&path, RouterOptions::default()).unwrap();
+    create_table(&router).await;
+    assert_eq!(journal_mode(&path), "wal");
+
+    let path = dir.file("rollback.db");
+    let options = RouterOptions {
+        wal: false,
+        ..Default::default()
+    };
+    let router = SQLiteRouter::with_options(&path, options).unwrap();
+    create_table(&router).await;
+    assert_eq!(journal_mode(&path), "delete");
+    assert_eq!(count(&router).await, 1);
+}
+
+#[tokio::test]
+async fn readers_see_committed_data_only() {
+    let dir = TempDir::new("readers-isolation");
+    let router = SQLiteRouter::with_options(&dir.file("app.db"), RouterOptions::default()).unwrap();
+    create_table(&router).await;
+
+    let begin = call(&router, "begin_transaction", json!({})).await;
+    let id = begin["transaction_id"].as_str().unwrap().to_string();
+    call(
+        &router,
+        "execute",
+        json!({ "statement": "INSERT INTO items VALUES ('b')", "transaction_id": id }),
+    )
+    .await;
+
+    // 读取不等待写事务，也看不到未提交的行
+    assert_eq!(count(&router).await, 1);
+    let result = call(
+        &router,
+        "query",
+        json!({ "query": "SELECT count(*) AS n FROM items", "transaction_id": id }),
+    )
+    .await;
+    assert_eq!(result["rows"][0]["n"], 2);
+
+    call(&router, "commit", json!({ "transaction_id": id })).await;
+    assert_eq!(count(&router).await, 2);
+
+    let error = call_err(
+        &router,
+        "query",
+        json!({ "query": "SELECT 1", "transaction_id": id }),
+    )
+    .await;
+    assert!(
+        error.contains("Unknown or expired transaction"),
+        "{}",
+        error
+    );
+}
+
+#[tokio::test]
+async fn memory_databases_share_data_with_the_pool() {
+    let router = SQLiteRouter::new(":memory:").unwrap();
+    create_table(&router).await;
+    assert_eq!(count(&router).await, 1);
+
+    // 每个路由器有自己的内存数据库
+    let other = SQLiteRouter::new(":memory:").unwrap();
+    let error = call_err(&other, "query", json!({ "query": "SELECT * FROM items" })).await;
+    assert!(error.contains("no such table: items"), "{}", error);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn reads_run_concurrently() {
+    let dir = TempDir::new("readers-concurrent");
+    let options = RouterOptions {
+        readers: 2,
+        ..Default::default()
+    };
+    let router = SQLiteRouter::with_options(&dir.file("app.db"), options).unwrap();
+    create_table(&router).await;
+
+    let started = Instant::now();
+    let slow = |router: SQLiteRouter| async move {
+        router
+            .call_tool("query", json!({ "query": ENDLESS, "timeout_ms": 600 }))
+            .await
+    };
+    let (first, second) = tokio::join!(slow(router.clone()), slow(router.clone()));
+    assert!(first.is_err() && second.is_err());
+
+    // 两个查询依次执行至少需要1200毫秒
+    assert!(started.elapsed() < Duration::from_millis(1100));
+}
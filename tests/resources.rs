@@ -0,0 +1,197 @@
+//! 数据库结构资源的集成测试
+
+mod common;
+
+use common::call;
+use mcp_core_fishcode2025::handler::ResourceError;
+use mcp_server_fishcode2025::Router;
+use mcp_sqlite::SQLiteRouter;
+use serde_json::{json, Value};
+
+/// 创建带有表、视图、索引和名称需要编码的表的数据库
+async fn router() -> SQLiteRouter {
+    let router = SQLiteRouter::new(":memory:").unwrap();
+    call(
+        &router,
+        "executescript",
+        json!({
+            "script": "
+                CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT NOT NULL);
+                CREATE INDEX users_name ON users (name);
+                CREATE TABLE orders (id INTEGER PRIMARY KEY, user_id INTEGER REFERENCES users (id));
+                CREATE TABLE \"order items\" (sku TEXT);
+                CREATE VIEW user_names AS SELECT name FROM users;
+                INSERT INTO users VALUES (1, 'Alice'), (2, 'Bob');
+                INSERT INTO \"order items\" VALUES ('A/1');
+            "
+        }),
+    )
+    .await;
+    router
+}
+
+/// 列出所有资源的URI
+fn uris(router: &SQLiteRouter) -> Vec<String> {
+    router
+        .list_resources()
+        .into_iter()
+        .map(|resource| resource.uri)
+        .collect()
+}
+
+/// 读取资源并解析为JSON
+async fn read(router: &SQLiteRouter, uri: &str) -> Value {
+    let text = router.read_resource(uri).await.unwrap();
+    serde_json::from_str(&text).unwrap()
+}
+
+#[tokio::test]
+async fn resources_cover_tables_and_views() {
+    let router = router().await;
+
+    assert_eq!(
+        uris(&router),
+        [
+            "sqlite://schema",
+            "sqlite://table/order%20items/schema",
+            "sqlite://table/order%20items/sample",
+            "sqlite://table/orders/schema",
+            "sqlite://table/orders/sample",
+            "sqlite://table/users/schema",
+            "sqlite://table/users/sample",
+            "sqlite://view/user_names/definition",
+        ]
+    );
+
+    let schema = read(&router, "sqlite://schema").await;
+    let objects: Vec<(&str, &str)> = schema["objects"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|object| {
+            (
+                object["type"].as_str().unwrap(),
+                object["name"].as_str().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        objects,
+        [
+            ("table", "order items"),
+            ("table", "orders"),
+            ("table", "users"),
+            ("view", "user_names"),
+            ("index", "users_name"),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn table_resources_describe_and_sample_the_table() {
+    let router = router().await;
+
+    let table = read(&router, "sqlite://table/users/schema").await;
+    let columns: Vec<&str> = table["columns"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|column| column["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(columns, ["id", "name"]);
+    assert_eq!(table["indexes"][0]["name"], "users_name");
+    let orders = read(&router, "sqlite://table/orders/schema").await;
+    assert_eq!(orders["foreign_keys"][0]["table"], "users");
+
+    let sample = read(&router, "sqlite://table/users/sample").await;
+    assert_eq!(sample["table"], "users");
+    assert_eq!(
+        sample["rows"],
+        json!([{ "id": 1, "name": "Alice" }, { "id": 2, "name": "Bob" }])
+    );
+
+    // 表名中的空格经过百分号编码
+    let sample = read(&router, "sqlite://table/order%20items/sample").await;
+    assert_eq!(sample["table"], "order items");
+    assert_eq!(sample["rows"], json!([{ "sku": "A/1" }]));
+}
+
+#[tokio::test]
+async fn view_resources_return_the_definition() {
+    let router = router().await;
+
+    let view = read(&router, "sqlite://view/user_names/definition").await;
+    assert_eq!(view["view"], "user_names");
+    assert_eq!(
+        view["sql"],
+        "CREATE VIEW user_names AS SELECT name FROM users"
+    );
+    assert_eq!(view["columns"][0]["name"], "name");
+}
+
+#[tokio::test]
+async fn unknown_uris_and_missing_objects_are_not_found() {
+    let router = router().await;
+
+    for uri in [
+        "sqlite://tables",
+        "sqlite://table/users",
+        "file:///etc/passwd",
+        "sqlite://table/bad%zz/schema",
+    ] {
+        let error = router.read_resource(uri).await.unwrap_err();
+        assert!(
+            matches!(&error, ResourceError::NotFound(message) if message.contains("Unknown resource")),
+            "{}: {:?}",
+            uri,
+            error
+        );
+    }
+
+    for uri in [
+        "sqlite://table/missing/schema",
+        "sqlite://table/missing/sample",
+        // 视图不是表，表也不是视图
+        "sqlite://table/user_names/schema",
+        "sqlite://view/users/definition",
+        "sqlite://db/archive/schema",
+    ] {
+        let error = router.read_resource(uri).await.unwrap_err();
+        assert!(
+            matches!(&error, ResourceError::NotFound(message) if message.contains("Resource not found")),
+            "{}: {:?}",
+            uri,
+            error
+        );
+    }
+}
+
+#[tokio::test]
+async fn resources_are_recomputed_on_every_call() {
+    let router = router().await;
+    assert!(!uris(&router).contains(&"sqlite://table/events/schema".to_string()));
+
+    call(
+        &router,
+        "execute",
+        json!({ "statement": "CREATE TABLE events (name TEXT)" }),
+    )
+    .await;
+    let uris = uris(&router);
+    assert!(uris.contains(&"sqlite://table/events/schema".to_string()));
+    assert!(uris.contains(&"sqlite://table/events/sample".to_string()));
+    let sample = read(&router, "sqlite://table/events/sample").await;
+    assert_eq!(sample["rows"], json!([]));
+
+    call(
+        &router,
+        "execute",
+        json!({ "statement": "DROP VIEW user_names" }),
+    )
+    .await;
+    let error = router
+        .read_resource("sqlite://view/user_names/definition")
+        .await
+        .unwrap_err();
+    assert!(matches!(error, ResourceError::NotFound(_)), "{:?}", error);
+}
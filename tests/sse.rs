@@ -0,0 +1,204 @@
+//! 通过本地HTTP客户端驱动HTTP/SSE传输的集成测试
+
+use std::net::SocketAddr;
+
+use mcp_sqlite::{
+    sse::{serve_sse, MESSAGE_PATH, SSE_PATH},
+    SQLiteRouter,
+};
+use serde_json::{json, Value};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader, Lines},
+    net::{TcpListener, TcpStream},
+};
+
+/// 启动使用内存数据库的服务器，每个会话使用自己的路由器
+async fn start() -> SocketAddr {
+    let router = SQLiteRouter::new(":memory:").unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(serve_sse(move || router.session(), listener));
+    address
+}
+
+/// 发送一个请求并读取完整的响应，返回状态码和响应体
+///
+/// 服务器拒绝请求时可能不读完请求就关闭连接，因此忽略读写错误，只解析已经收到的响应
+async fn send(address: SocketAddr, request: String) -> (u16, String) {
+    let mut stream = TcpStream::connect(address).await.unwrap();
+    let _ = stream.write_all(request.as_bytes()).await;
+    let mut response = Vec::new();
+    let _ = stream.read_to_end(&mut response).await;
+    let response = String::from_utf8(response).unwrap();
+    let status = response[9..12].parse().unwrap();
+    let body = response
+        .split_once("\r\n\r\n")
+        .map(|(_, body)| body.to_string())
+        .unwrap_or_default();
+    (status, body)
+}
+
+/// 向消息地址发送JSON-RPC消息
+async fn post(address: SocketAddr, path: &str, origin: Option<&str>, body: &Value) -> u16 {
+    let body = body.to_string();
+    let origin = origin
+        .map(|origin| format!("Origin: {}\r\n", origin))
+        .unwrap_or_default();
+    let request = format!(
+        "POST {} HTTP/1.1\r\nHost: localhost\r\n{}Content-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        path,
+        origin,
+        body.len(),
+        body
+    );
+    send(address, request).await.0
+}
+
+/// 一个打开的事件流
+struct EventStream {
+    address: SocketAddr,
+    lines: Lines<BufReader<TcpStream>>,
+    endpoint: String,
+    next_id: u64,
+}
+
+impl EventStream {
+    /// 打开事件流并读取`endpoint`事件
+    async fn open(address: SocketAddr) -> Self {
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        let request = format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", SSE_PATH);
+        stream.write_all(request.as_bytes()).await.unwrap();
+
+        let mut lines = BufReader::new(stream).lines();
+        let status = lines.next_line().await.unwrap().unwrap();
+        assert!(status.starts_with("HTTP/1.1 200"), "{}", status);
+        while !lines.next_line().await.unwrap().unwrap().is_empty() {}
+
+        let mut stream = Self {
+            address,
+            lines,
+            endpoint: String::new(),
+            next_id: 1,
+        };
+        let (event, endpoint) = stream.next_event().await;
+        assert_eq!(event, "endpoint");
+        assert!(endpoint.starts_with(MESSAGE_PATH));
+        stream.endpoint = endpoint;
+        stream
+    }
+
+    /// 读取下一个事件，返回事件类型和数据
+    async fn next_event(&mut self) -> (String, String) {
+        let mut event = String::new();
+        let mut data = Vec::new();
+        loop {
+            let line = self.lines.next_line().await.unwrap().unwrap();
+            if let Some(name) = line.strip_prefix("event: ") {
+                event = name.to_string();
+            } else if let Some(line) = line.strip_prefix("data: ") {
+                data.push(line.to_string());
+            } else if line.is_empty() && !data.is_empty() {
+                return (event, data.join("\n"));
+            }
+        }
+    }
+
+    /// 调用工具并从事件流读取结果的文本，工具出错时文本是错误信息
+    async fn call(&mut self, tool: &str, arguments: Value) -> (bool, String) {
+        let id = self.next_id;
+        self.next_id += 1;
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "tools/call",
+            "params": { "name": tool, "arguments": arguments }
+        });
+        assert_eq!(
+            post(self.address, &self.endpoint, None, &request).await,
+            202
+        );
+
+        let (event, data) = self.next_event().await;
+        assert_eq!(event, "message");
+        let response: Value = serde_json::from_str(&data).unwrap();
+        assert_eq!(response["id"], id);
+        if let Some(error) = response.get("error") {
+            return (false, error["message"].as_str().unwrap().to_string());
+        }
+        let result = &response["result"];
+        let text = result["content"][0]["text"].as_str().unwrap().to_string();
+        (result["isError"] != true, text)
+    }
+}
+
+#[tokio::test]
+async fn each_stream_is_a_separate_session() {
+    let address = start().await;
+    let mut first = EventStream::open(address).await;
+    let mut second = EventStream::open(address).await;
+    assert_ne!(first.endpoint, second.endpoint);
+
+    let (ok, text) = first.call("begin_transaction", json!({})).await;
+    assert!(ok, "{}", text);
+    let transaction: Value = serde_json::from_str(&text).unwrap();
+    let id = transaction["transaction_id"].as_str().unwrap();
+
+    // 其他会话不能使用这个事务
+    let (ok, text) = second.call("commit", json!({ "transaction_id": id })).await;
+    assert!(!ok);
+    assert!(text.contains("Unknown or expired transaction"), "{}", text);
+
+    let (ok, text) = first.call("commit", json!({ "transaction_id": id })).await;
+    assert!(ok, "{}", text);
+}
+
+#[tokio::test]
+async fn foreign_origins_are_rejected() {
+    let address = start().await;
+    let stream = EventStream::open(address).await;
+    let ping = json!({ "jsonrpc": "2.0", "id": 1, "method": "ping" });
+
+    let status = post(
+        address,
+        &stream.endpoint,
+        Some("https://evil.example"),
+        &ping,
+    )
+    .await;
+    assert_eq!(status, 403);
+    let status = post(
+        address,
+        &stream.endpoint,
+        Some("http://localhost:3000"),
+        &ping,
+    )
+    .await;
+    assert_eq!(status, 202);
+
+    let request = format!(
+        "GET {} HTTP/1.1\r\nHost: localhost\r\nOrigin: https://evil.example\r\n\r\n",
+        SSE_PATH
+    );
+    let (status, body) = send(address, request).await;
+    assert_eq!(status, 403);
+    assert_eq!(body, "Forbidden origin");
+}
+
+#[tokio::test]
+async fn invalid_requests_are_rejected() {
+    let address = start().await;
+    let ping = json!({ "jsonrpc": "2.0", "id": 1, "method": "ping" });
+
+    assert_eq!(post(address, MESSAGE_PATH, None, &ping).await, 400);
+    let path = format!("{}?sessionId=nope", MESSAGE_PATH);
+    assert_eq!(post(address, &path, None, &ping).await, 404);
+
+    // 请求头的长度有上限
+    let request = format!(
+        "GET {} HTTP/1.1\r\nHost: localhost\r\nX-Padding: {}\r\n\r\n",
+        SSE_PATH,
+        "a".repeat(128 * 1024)
+    );
+    let (status, _) = send(address, request).await;
+    assert_eq!(status, 431);
+}
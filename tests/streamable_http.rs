@@ -0,0 +1,415 @@
+//! 通过本地HTTP客户端驱动Streamable HTTP传输的集成测试
+
+use std::net::SocketAddr;
+
+use mcp_sqlite::{
+    streamable::{serve_streamable_http, MCP_PATH, SESSION_HEADER},
+    SQLiteRouter,
+};
+use serde_json::{json, Value};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+/// 客户端期望的响应形式
+const JSON: &str = "application/json";
+const STREAM: &str = "application/json, text/event-stream";
+
+/// HTTP响应
+struct HttpResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+impl HttpResponse {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// 解析JSON响应体
+    fn json(&self) -> Value {
+        serde_json::from_str(&self.body).expect("response body is not JSON")
+    }
+
+    /// 解析事件流中的所有消息
+    fn events(&self) -> Vec<Value> {
+        self.body
+            .split("\n\n")
+            .filter_map(|event| {
+                let data: Vec<_> = event
+                    .lines()
+                    .filter_map(|line| line.strip_prefix("data: "))
+                    .collect();
+                (!data.is_empty()).then(|| serde_json::from_str(&data.join("\n")).unwrap())
+            })
+            .collect()
+    }
+}
+
+/// 一个MCP会话的客户端
+struct Client {
+    address: SocketAddr,
+    session_id: Option<String>,
+    next_id: u64,
+}
+
+impl Client {
+    /// 发送`initialize`请求并记录会话ID
+    async fn connect(address: SocketAddr) -> Self {
+        let mut client = Self {
+            address,
+            session_id: None,
+            next_id: 1,
+        };
+        let response = client
+            .post(
+                &json!({
+                    "jsonrpc": "2.0",
+                    "id": 0,
+                    "method": "initialize",
+                    "params": {
+                        "protocolVersion": "2025-03-26",
+                        "capabilities": {},
+                        "clientInfo": { "name": "streamable-test", "version": "1.0.0" }
+                    }
+                }),
+                JSON,
+            )
+            .await;
+        assert_eq!(response.status, 200, "{}", response.body);
+        assert!(response.json()["result"]["serverInfo"].is_object());
+        client.session_id = response.header(SESSION_HEADER).map(str::to_string);
+        assert!(client.session_id.is_some(), "missing session header");
+
+        let response = client
+            .post(
+                &json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }),
+                JSON,
+            )
+            .await;
+        assert_eq!(response.status, 202);
+        client
+    }
+
+    /// 发送一个HTTP请求
+    async fn send(&self, method: &str, body: Option<&Value>, accept: &str) -> HttpResponse {
+        let body = body.map(Value::to_string).unwrap_or_default();
+        let mut request = format!(
+            "{} {} HTTP/1.1\r\nHost: {}\r\nAccept: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n",
+            method,
+            MCP_PATH,
+            self.address,
+            accept,
+            body.len()
+        );
+        if let Some(session_id) = &self.session_id {
+            request.push_str(&format!("{}: {}\r\n", SESSION_HEADER, session_id));
+        }
+        request.push_str("\r\n");
+        request.push_str(&body);
+
+        let mut stream = TcpStream::connect(self.address).await.unwrap();
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut raw = String::new();
+        stream.read_to_string(&mut raw).await.unwrap();
+
+        let (head, body) = raw.split_once("\r\n\r\n").expect("incomplete response");
+        let mut lines = head.lines();
+        let status = lines
+            .next()
+            .and_then(|line| line.split_whitespace().nth(1))
+            .and_then(|status| status.parse().ok())
+            .expect("malformed status line");
+        let headers = lines
+            .filter_map(|line| line.split_once(':'))
+            .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+            .collect();
+        HttpResponse {
+            status,
+            headers,
+            body: body.to_string(),
+        }
+    }
+
+    async fn post(&self, message: &Value, accept: &str) -> HttpResponse {
+        self.send("POST", Some(message), accept).await
+    }
+
+    /// 调用工具，返回结果文本和是否出错
+    async fn call_tool(&mut self, name: &str, arguments: Value) -> (String, bool) {
+        let id = self.next_id;
+        self.next_id += 1;
+        let response = self
+            .post(
+                &json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "method": "tools/call",
+                    "params": { "name": name, "arguments": arguments }
+                }),
+                JSON,
+            )
+            .await;
+        assert_eq!(response.status, 200, "{}", response.body);
+        let message = response.json();
+        assert_eq!(message["id"], json!(id));
+        let result = &message["result"];
+        (
+            result["content"][0]["text"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string(),
+            result["isError"] == json!(true),
+        )
+    }
+
+    /// 调用工具并解析成功的JSON结果
+    async fn call_ok(&mut self, name: &str, arguments: Value) -> Value {
+        let (text, is_error) = self.call_tool(name, arguments).await;
+        assert!(!is_error, "{} failed: {}", name, text);
+        serde_json::from_str(&text).unwrap()
+    }
+}
+
+/// 在随机端口上启动服务器
+async fn start_server() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    let router = SQLiteRouter::new(":memory:").unwrap();
+    tokio::spawn(serve_streamable_http(move || router.session(), listener));
+    address
+}
+
+fn tools_list(id: u64) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "method": "tools/list" })
+}
+
+#[tokio::test]
+async fn initialize_creates_session() {
+    let address = start_server().await;
+    let client = Client::connect(address).await;
+
+    let response = client.post(&tools_list(1), JSON).await;
+    assert_eq!(response.status, 200);
+    assert_eq!(response.header("content-type"), Some("application/json"));
+    assert_eq!(
+        response.header(SESSION_HEADER),
+        client.session_id.as_deref()
+    );
+    assert!(response.json()["result"]["tools"].as_array().unwrap().len() > 1);
+
+    // 另一个会话得到不同的ID
+    let other = Client::connect(address).await;
+    assert_ne!(other.session_id, client.session_id);
+}
+
+#[tokio::test]
+async fn rejects_missing_or_unknown_session() {
+    let address = start_server().await;
+    let mut client = Client {
+        address,
+        session_id: None,
+        next_id: 1,
+    };
+    assert_eq!(client.post(&tools_list(1), JSON).await.status, 400);
+
+    client.session_id = Some("unknown".to_string());
+    assert_eq!(client.post(&tools_list(1), JSON).await.status, 404);
+    assert_eq!(client.send("DELETE", None, JSON).await.status, 404);
+    assert_eq!(client.send("GET", None, STREAM).await.status, 405);
+}
+
+#[tokio::test]
+async fn invalid_requests_are_rejected() {
+    let address = start_server().await;
+    let client = Client::connect(address).await;
+
+    let response = client.post(&tools_list(1), "text/html").await;
+    assert_eq!(response.status, 406);
+
+    let response = client.post(&json!([]), JSON).await;
+    assert_eq!(response.status, 400);
+
+    let mut stream = TcpStream::connect(address).await.unwrap();
+    let body = "{not json";
+    stream
+        .write_all(
+            format!(
+                "POST {} HTTP/1.1\r\nHost: {}\r\nOrigin: https://example.com\r\nContent-Length: {}\r\n\r\n{}",
+                MCP_PATH,
+                address,
+                body.len(),
+                body
+            )
+            .as_bytes(),
+        )
+        .await
+        .unwrap();
+    let mut raw = String::new();
+    stream.read_to_string(&mut raw).await.unwrap();
+    assert!(raw.starts_with("HTTP/1.1 403"), "{}", raw);
+}
+
+#[tokio::test]
+async fn streams_responses_when_accepted() {
+    let address = start_server().await;
+    let mut client = Client::connect(address).await;
+    client
+        .call_ok(
+            "execute",
+            json!({ "statement": "CREATE TABLE t (x INTEGER)" }),
+        )
+        .await;
+
+    let batch = json!([
+        tools_list(10),
+        {
+            "jsonrpc": "2.0",
+            "id": 11,
+            "method": "tools/call",
+            "params": { "name": "query", "arguments": { "query": "SELECT 42 AS answer" } }
+        },
+        { "jsonrpc": "2.0", "method": "notifications/initialized" }
+    ]);
+    let response = client.post(&batch, STREAM).await;
+    assert_eq!(response.status, 200);
+    assert_eq!(response.header("content-type"), Some("text/event-stream"));
+    let mut ids: Vec<_> = response
+        .events()
+        .iter()
+        .map(|message| message["id"].as_u64().unwrap())
+        .collect();
+    ids.sort();
+    assert_eq!(ids, [10, 11]);
+
+    // 不接受事件流时，批量请求的响应以数组返回
+    let response = client.post(&batch, JSON).await;
+    assert_eq!(response.status, 200);
+    assert_eq!(response.json().as_array().map(Vec::len), Some(2));
+}
+
+#[tokio::test]
+async fn sessions_have_separate_transactions() {
+    let address = start_server().await;
+    let mut a = Client::connect(address).await;
+    let mut b = Client::connect(address).await;
+    a.call_ok(
+        "execute",
+        json!({ "statement": "CREATE TABLE t (x INTEGER)" }),
+    )
+    .await;
+
+    let transaction = a.call_ok("begin_transaction", json!({})).await;
+    let transaction_id = transaction["transaction_id"].as_str().unwrap().to_string();
+    a.call_ok(
+        "execute",
+        json!({ "statement": "INSERT INTO t VALUES (1)", "transaction_id": transaction_id }),
+    )
+    .await;
+
+    // 会话B不能在会话A的事务中写入，也不能提交它
+    let (text, is_error) = b
+        .call_tool(
+            "execute",
+            json!({ "statement": "INSERT INTO t VALUES (2)" }),
+        )
+        .await;
+    assert!(is_error);
+    assert!(text.contains("another session"), "{}", text);
+    let (text, is_error) = b
+        .call_tool("commit", json!({ "transaction_id": transaction_id }))
+        .await;
+    assert!(is_error);
+    assert!(text.contains("Unknown or expired transaction"), "{}", text);
+
+    a.call_ok("commit", json!({ "transaction_id": transaction_id }))
+        .await;
+    b.call_ok(
+        "execute",
+        json!({ "statement": "INSERT INTO t VALUES (2)" }),
+    )
+    .await;
+    let rows = a
+        .call_ok("query", json!({ "query": "SELECT x FROM t ORDER BY x" }))
+        .await;
+    assert_eq!(rows["rows"], json!([{ "x": 1 }, { "x": 2 }]));
+}
+
+#[tokio::test]
+async fn sessions_have_separate_cursors() {
+    let address = start_server().await;
+    let mut a = Client::connect(address).await;
+    let mut b = Client::connect(address).await;
+
+    let page = a
+        .call_ok(
+            "query",
+            json!({
+                "query": "WITH RECURSIVE c(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM c LIMIT 10) SELECT x FROM c",
+                "cursor": true,
+                "limit": 3
+            }),
+        )
+        .await;
+    let cursor_id = page["cursor_id"].clone();
+    assert!(cursor_id.is_string());
+
+    let (_, is_error) = b
+        .call_tool("fetch", json!({ "cursor_id": cursor_id }))
+        .await;
+    assert!(is_error);
+
+    let page = a.call_ok("fetch", json!({ "cursor_id": cursor_id })).await;
+    assert_eq!(page["rows"][0], json!({ "x": 4 }));
+}
+
+#[tokio::test]
+async fn delete_ends_session_and_rolls_back() {
+    let address = start_server().await;
+    let mut a = Client::connect(address).await;
+    let mut b = Client::connect(address).await;
+    a.call_ok(
+        "execute",
+        json!({ "statement": "CREATE TABLE t (x INTEGER)" }),
+    )
+    .await;
+
+    let transaction = a.call_ok("begin_transaction", json!({})).await;
+    a.call_ok(
+        "execute",
+        json!({
+            "statement": "INSERT INTO t VALUES (1)",
+            "transaction_id": transaction["transaction_id"]
+        }),
+    )
+    .await;
+
+    assert_eq!(a.send("DELETE", None, JSON).await.status, 204);
+    assert_eq!(a.post(&tools_list(1), JSON).await.status, 404);
+
+    // 会话结束后它的事务在后台回滚，之后其他会话可以写入
+    let mut attempts = 0;
+    loop {
+        let (text, is_error) = b
+            .call_tool(
+                "execute",
+                json!({ "statement": "INSERT INTO t VALUES (2)" }),
+            )
+            .await;
+        if !is_error {
+            break;
+        }
+        attempts += 1;
+        assert!(attempts < 50, "transaction was not rolled back: {}", text);
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    let rows = b
+        .call_ok("query", json!({ "query": "SELECT x FROM t" }))
+        .await;
+    assert_eq!(rows["rows"], json!([{ "x": 2 }]));
+}
@@ -0,0 +1,137 @@
+//! 语句超时与取消的集成测试
+
+mod common;
+
+use std::time::{Duration, Instant};
+
+use common::{call, call_err};
+use mcp_core_fishcode2025::protocol::JsonRpcMessage;
+use mcp_sqlite::{serve::Session, RouterOptions, SQLiteRouter};
+use serde_json::{json, Value};
+use tokio::sync::mpsc;
+
+/// 永远不会结束的查询
+const ENDLESS: &str =
+    "WITH RECURSIVE c(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM c) SELECT count(*) FROM c";
+
+/// 创建只有一个连接的路由器，被阻塞的语句会阻塞之后的所有调用
+fn router(statement_timeout: Option<Duration>) -> SQLiteRouter {
+    let options = RouterOptions {
+        readers: 0,
+        statement_timeout,
+        ..Default::default()
+    };
+    SQLiteRouter::with_options(":memory:", options).unwrap()
+}
+
+/// 构造调用工具的JSON-RPC请求
+fn tool_request(id: u64, tool: &str, arguments: Value) -> String {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "method": "tools/call",
+        "params": { "name": tool, "arguments": arguments }
+    })
+    .to_string()
+}
+
+/// 在限定时间内接收下一条消息
+async fn next_message(rx: &mut mpsc::UnboundedReceiver<JsonRpcMessage>) -> Value {
+    let message = tokio::time::timeout(Duration::from_secs(10), rx.recv())
+        .await
+        .expect("no response in time")
+        .expect("channel closed");
+    serde_json::to_value(message).unwrap()
+}
+
+#[tokio::test]
+async fn timeout_ms_interrupts_the_statement() {
+    let router = router(None);
+
+    let started = Instant::now();
+    let error = call_err(
+        &router,
+        "query",
+        json!({ "query": ENDLESS, "timeout_ms": 100 }),
+    )
+    .await;
+    assert!(
+        error.contains("Statement timed out after 100 ms"),
+        "{}",
+        error
+    );
+    assert!(started.elapsed() < Duration::from_secs(5));
+
+    let error = call_err(
+        &router,
+        "execute",
+        json!({ "statement": format!("CREATE TABLE t AS {}", ENDLESS), "timeout_ms": 100 }),
+    )
+    .await;
+    assert!(error.contains("Statement timed out"), "{}", error);
+
+    // 被中断后连接仍然可用
+    let result = call(&router, "query", json!({ "query": "SELECT 1 AS one" })).await;
+    assert_eq!(result["rows"][0]["one"], 1);
+
+    let error = call_err(
+        &router,
+        "query",
+        json!({ "query": ENDLESS, "timeout_ms": -1 }),
+    )
+    .await;
+    assert!(error.contains("timeout_ms"), "{}", error);
+}
+
+#[tokio::test]
+async fn server_default_timeout_can_be_overridden() {
+    let router = router(Some(Duration::from_millis(100)));
+
+    let error = call_err(&router, "query", json!({ "query": ENDLESS })).await;
+    assert!(
+        error.contains("Statement timed out after 100 ms"),
+        "{}",
+        error
+    );
+
+    // timeout_ms为0时不限制
+    let query = "WITH RECURSIVE c(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM c WHERE x < 10) SELECT count(*) AS n FROM c";
+    let result = call(&router, "query", json!({ "query": query, "timeout_ms": 0 })).await;
+    assert_eq!(result["rows"][0]["n"], 10);
+}
+
+#[tokio::test]
+async fn cancelled_requests_are_interrupted_without_a_response() {
+    let session = Session::new(router(None));
+    let (tx, mut rx) = mpsc::unbounded_channel();
+
+    assert!(session.handle(&tool_request(1, "query", json!({ "query": ENDLESS })), &tx));
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    // 只有一个连接，第二个请求要等到第一个语句被中断后才能执行
+    assert!(session.handle(
+        &tool_request(2, "query", json!({ "query": "SELECT 1 AS one" })),
+        &tx
+    ));
+    let cancel = json!({
+        "jsonrpc": "2.0",
+        "method": "notifications/cancelled",
+        "params": { "requestId": 1, "reason": "user aborted" }
+    });
+    assert!(!session.handle(&cancel.to_string(), &tx));
+
+    let response = next_message(&mut rx).await;
+    assert_eq!(response["id"], 2);
+    let text = response["result"]["content"][0]["text"].as_str().unwrap();
+    assert_eq!(
+        serde_json::from_str::<Value>(text).unwrap()["rows"][0]["one"],
+        1
+    );
+
+    // 被取消的请求不发送响应
+    drop(tx);
+    assert!(tokio::time::timeout(Duration::from_secs(5), rx.recv())
+        .await
+        .unwrap()
+        .is_none());
+}
@@ -0,0 +1,224 @@
+//! 显式事务工具的集成测试
+
+mod common;
+
+use std::time::Duration;
+
+use common::{call, call_err};
+use mcp_sqlite::{RouterOptions, SQLiteRouter};
+use serde_json::{json, Value};
+
+/// 创建带有空表的路由器
+async fn router(options: RouterOptions) -> SQLiteRouter {
+    let router = SQLiteRouter::with_options(":memory:", options).unwrap();
+    call(
+        &router,
+        "execute",
+        json!({ "statement": "CREATE TABLE items (id INTEGER PRIMARY KEY, name TEXT NOT NULL)" }),
+    )
+    .await;
+    router
+}
+
+/// 读取表中的行数
+async fn count(router: &SQLiteRouter) -> Value {
+    let result = call(
+        router,
+        "query",
+        json!({ "query": "SELECT count(*) AS n FROM items" }),
+    )
+    .await;
+    result["rows"][0]["n"].clone()
+}
+
+#[tokio::test]
+async fn commit_and_rollback_span_calls() {
+    let router = router(RouterOptions::default()).await;
+
+    let begin = call(&router, "begin_transaction", json!({})).await;
+    let id = begin["transaction_id"].as_str().unwrap().to_string();
+    call(
+        &router,
+        "execute",
+        json!({ "statement": "INSERT INTO items (name) VALUES ('a')", "transaction_id": id }),
+    )
+    .await;
+    call(
+        &router,
+        "savepoint",
+        json!({ "transaction_id": id, "name": "before_b" }),
+    )
+    .await;
+    call(
+        &router,
+        "execute",
+        json!({ "statement": "INSERT INTO items (name) VALUES ('b')", "transaction_id": id }),
+    )
+    .await;
+    call(
+        &router,
+        "rollback",
+        json!({ "transaction_id": id, "savepoint": "before_b" }),
+    )
+    .await;
+    let result = call(&router, "commit", json!({ "transaction_id": id })).await;
+    assert_eq!(result["committed"], true);
+    assert_eq!(count(&router).await, 1);
+
+    let begin = call(&router, "begin_transaction", json!({ "mode": "immediate" })).await;
+    let id = begin["transaction_id"].as_str().unwrap().to_string();
+    call(
+        &router,
+        "execute",
+        json!({ "statement": "DELETE FROM items", "transaction_id": id }),
+    )
+    .await;
+    call(&router, "rollback", json!({ "transaction_id": id })).await;
+    assert_eq!(count(&router).await, 1);
+
+    let error = call_err(&router, "commit", json!({ "transaction_id": id })).await;
+    assert!(
+        error.contains("Unknown or expired transaction"),
+        "{}",
+        error
+    );
+}
+
+#[tokio::test]
+async fn writes_need_the_handle_of_the_open_transaction() {
+    let router = router(RouterOptions::default()).await;
+
+    let begin = call(&router, "begin_transaction", json!({})).await;
+    let id = begin["transaction_id"].as_str().unwrap().to_string();
+
+    let error = call_err(
+        &router,
+        "execute",
+        json!({ "statement": "INSERT INTO items (name) VALUES ('a')" }),
+    )
+    .await;
+    assert!(error.contains("pass its transaction_id"), "{}", error);
+
+    let error = call_err(
+        &router,
+        "execute",
+        json!({ "statement": "INSERT INTO items (name) VALUES ('a')", "transaction_id": "tx-nope" }),
+    )
+    .await;
+    assert!(
+        error.contains("Unknown or expired transaction: tx-nope"),
+        "{}",
+        error
+    );
+
+    let error = call_err(&router, "begin_transaction", json!({})).await;
+    assert!(error.contains("already in progress"), "{}", error);
+
+    let error = call_err(&router, "begin_transaction", json!({ "mode": "eager" })).await;
+    assert!(error.contains("mode must be one of"), "{}", error);
+
+    call(&router, "rollback", json!({ "transaction_id": id })).await;
+}
+
+#[tokio::test]
+async fn transactions_belong_to_their_session() {
+    let router = router(RouterOptions::default()).await;
+    let other = router.session();
+
+    let begin = call(&router, "begin_transaction", json!({})).await;
+    let id = begin["transaction_id"].as_str().unwrap().to_string();
+
+    // 其他会话无法使用该事务的句柄
+    let error = call_err(&other, "commit", json!({ "transaction_id": id })).await;
+    assert!(
+        error.contains("Unknown or expired transaction"),
+        "{}",
+        error
+    );
+
+    let error = call_err(
+        &other,
+        "execute",
+        json!({ "statement": "INSERT INTO items (name) VALUES ('x')" }),
+    )
+    .await;
+    assert!(error.contains("another session"), "{}", error);
+
+    let error = call_err(&other, "begin_transaction", json!({})).await;
+    assert!(
+        error.contains("of another session is already in progress"),
+        "{}",
+        error
+    );
+
+    call(&router, "commit", json!({ "transaction_id": id })).await;
+    call(
+        &other,
+        "execute",
+        json!({ "statement": "INSERT INTO items (name) VALUES ('x')" }),
+    )
+    .await;
+    assert_eq!(count(&router).await, 1);
+}
+
+#[tokio::test]
+async fn idle_transactions_are_rolled_back() {
+    let options = RouterOptions {
+        transaction_timeout: Duration::from_millis(200),
+        ..Default::default()
+    };
+    let router = router(options).await;
+
+    let begin = call(&router, "begin_transaction", json!({})).await;
+    assert_eq!(begin["idle_timeout_ms"], 200);
+    let id = begin["transaction_id"].as_str().unwrap().to_string();
+    call(
+        &router,
+        "execute",
+        json!({ "statement": "INSERT INTO items (name) VALUES ('a')", "transaction_id": id }),
+    )
+    .await;
+
+    tokio::time::sleep(Duration::from_millis(600)).await;
+
+    let error = call_err(&router, "commit", json!({ "transaction_id": id })).await;
+    assert!(
+        error.contains("Unknown or expired transaction"),
+        "{}",
+        error
+    );
+    assert_eq!(count(&router).await, 0);
+
+    // 事务回滚后可以不带句柄写入
+    call(
+        &router,
+        "execute",
+        json!({ "statement": "INSERT INTO items (name) VALUES ('b')" }),
+    )
+    .await;
+    assert_eq!(count(&router).await, 1);
+}
+
+#[tokio::test]
+async fn atomic_executemany_undoes_partial_writes() {
+    let router = router(RouterOptions::default()).await;
+    let statement = "INSERT INTO items (id, name) VALUES (?, ?)";
+    let params_list = json!([[1, "a"], [2, "b"], [3, null]]);
+
+    let error = call_err(
+        &router,
+        "executemany",
+        json!({ "statement": statement, "params_list": params_list, "atomic": true }),
+    )
+    .await;
+    assert!(error.contains("NOT NULL"), "{}", error);
+    assert_eq!(count(&router).await, 0);
+
+    call_err(
+        &router,
+        "executemany",
+        json!({ "statement": statement, "params_list": params_list }),
+    )
+    .await;
+    assert_eq!(count(&router).await, 2);
+}
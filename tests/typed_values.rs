@@ -0,0 +1,141 @@
+//! 类型化值编码的集成测试
+
+mod common;
+
+use common::{call, call_err};
+use mcp_sqlite::SQLiteRouter;
+use serde_json::json;
+
+/// 创建没有声明列类型的表
+async fn router() -> SQLiteRouter {
+    let router = SQLiteRouter::new(":memory:").unwrap();
+    call(
+        &router,
+        "execute",
+        json!({ "statement": "CREATE TABLE cells (id INTEGER PRIMARY KEY, v)" }),
+    )
+    .await;
+    router
+}
+
+#[tokio::test]
+async fn typed_values_round_trip() {
+    let router = router().await;
+    let values = json!([
+        { "type": "blob", "base64": "AAEC" },
+        { "type": "integer", "value": "9223372036854775807" },
+        { "type": "real", "value": "-Infinity" },
+        { "type": "real", "value": 2 },
+        { "type": "text", "value": "AAEC" },
+        { "type": "null" }
+    ]);
+    let params_list: Vec<_> = values
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|value| json!([value]))
+        .collect();
+    call(
+        &router,
+        "executemany",
+        json!({
+            "statement": "INSERT INTO cells (v) VALUES (?)",
+            "params_list": params_list,
+            "typed": true
+        }),
+    )
+    .await;
+
+    let result = call(
+        &router,
+        "query",
+        json!({ "query": "SELECT v FROM cells ORDER BY id", "format": "arrays", "typed": true }),
+    )
+    .await;
+    let rows: Vec<_> = result["rows"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|row| row[0].clone())
+        .collect();
+    assert_eq!(rows[0], json!({ "type": "blob", "base64": "AAEC" }));
+    assert_eq!(
+        rows[1],
+        json!({ "type": "integer", "value": 9223372036854775807i64 })
+    );
+    assert_eq!(rows[2], json!({ "type": "real", "value": "-Infinity" }));
+    assert_eq!(rows[3], json!({ "type": "real", "value": 2.0 }));
+    assert_eq!(rows[4], json!({ "type": "text", "value": "AAEC" }));
+    assert_eq!(rows[5], json!({ "type": "null" }));
+
+    // 类型化的参数也可以用于查询条件
+    let result = call(
+        &router,
+        "query",
+        json!({
+            "query": "SELECT typeof(v) AS t FROM cells WHERE v = ?",
+            "params": [{ "type": "blob", "base64": "AAEC" }],
+            "typed": true
+        }),
+    )
+    .await;
+    assert_eq!(
+        result["rows"],
+        json!([{ "t": { "type": "text", "value": "blob" } }])
+    );
+
+    // 默认模式下BLOB是base64字符串，非有限的实数是null
+    let result = call(
+        &router,
+        "query",
+        json!({ "query": "SELECT v FROM cells WHERE id <= 3 ORDER BY id", "format": "arrays" }),
+    )
+    .await;
+    assert_eq!(
+        result["rows"],
+        json!([["AAEC"], [9223372036854775807i64], [null]])
+    );
+}
+
+#[tokio::test]
+async fn invalid_and_out_of_range_values_are_rejected() {
+    let router = router().await;
+    let insert = "INSERT INTO cells (v) VALUES (?)";
+
+    let error = call_err(
+        &router,
+        "execute",
+        json!({ "statement": insert, "params": [9223372036854775808u64] }),
+    )
+    .await;
+    assert!(error.contains("out of range"), "{}", error);
+
+    for (value, message) in [
+        (
+            json!({ "type": "integer", "value": "9223372036854775808" }),
+            "not an integer in the range",
+        ),
+        (json!({ "type": "blob", "base64": "***" }), "invalid base64"),
+        (
+            json!({ "type": "date", "value": "2024" }),
+            "unknown value type: date",
+        ),
+        (json!({ "type": "text" }), "must have a \"value\" field"),
+    ] {
+        let error = call_err(
+            &router,
+            "execute",
+            json!({ "statement": insert, "params": [value], "typed": true }),
+        )
+        .await;
+        assert!(error.contains(message), "{}", error);
+    }
+
+    let result = call(
+        &router,
+        "query",
+        json!({ "query": "SELECT count(*) AS n FROM cells" }),
+    )
+    .await;
+    assert_eq!(result["rows"][0]["n"], 0);
+}
@@ -0,0 +1,193 @@
+//! Unix域套接字传输的集成测试
+
+#![cfg(unix)]
+
+mod common;
+
+use std::{
+    os::unix::fs::{FileTypeExt, PermissionsExt},
+    path::Path,
+    time::Duration,
+};
+
+use common::TempDir;
+use mcp_sqlite::{
+    unix::{bind, serve_unix},
+    SQLiteRouter,
+};
+use serde_json::{json, Value};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines},
+    net::{
+        unix::{OwnedReadHalf, OwnedWriteHalf},
+        UnixStream,
+    },
+    sync::oneshot,
+    task::JoinHandle,
+};
+
+/// 在套接字上启动服务器，返回服务器任务和触发关闭的发送端
+fn start(path: &Path, router: SQLiteRouter) -> (JoinHandle<()>, oneshot::Sender<()>) {
+    let listener = bind(path, 0o600).unwrap();
+    let (stop, stopped) = oneshot::channel::<()>();
+    let server = tokio::spawn(async move {
+        serve_unix(move || router.session(), listener, async {
+            let _ = stopped.await;
+        })
+        .await
+        .unwrap();
+    });
+    (server, stop)
+}
+
+/// 一个客户端连接
+struct Client {
+    lines: Lines<BufReader<OwnedReadHalf>>,
+    writer: OwnedWriteHalf,
+    next_id: u64,
+}
+
+impl Client {
+    async fn connect(path: &Path) -> Self {
+        let (reader, writer) = UnixStream::connect(path).await.unwrap().into_split();
+        Self {
+            lines: BufReader::new(reader).lines(),
+            writer,
+            next_id: 1,
+        }
+    }
+
+    /// 发送工具调用请求，返回请求ID
+    async fn send(&mut self, tool: &str, arguments: Value) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "tools/call",
+            "params": { "name": tool, "arguments": arguments }
+        });
+        let line = format!("{}\n", request);
+        self.writer.write_all(line.as_bytes()).await.unwrap();
+        id
+    }
+
+    /// 读取一个响应，返回工具是否成功和结果的文本
+    async fn receive(&mut self, id: u64) -> (bool, String) {
+        let line = tokio::time::timeout(Duration::from_secs(10), self.lines.next_line())
+            .await
+            .expect("no response in time")
+            .unwrap()
+            .expect("connection closed");
+        let response: Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(response["id"], id);
+        if let Some(error) = response.get("error") {
+            return (false, error["message"].as_str().unwrap().to_string());
+        }
+        let result = &response["result"];
+        let text = result["content"][0]["text"].as_str().unwrap().to_string();
+        (result["isError"] != true, text)
+    }
+
+    async fn call(&mut self, tool: &str, arguments: Value) -> (bool, String) {
+        let id = self.send(tool, arguments).await;
+        self.receive(id).await
+    }
+}
+
+#[tokio::test]
+async fn socket_is_private_and_removed_on_shutdown() {
+    let dir = TempDir::new("unix-mode");
+    let path = dir.path().join("mcp.sock");
+    let (server, stop) = start(&path, SQLiteRouter::new(":memory:").unwrap());
+
+    let metadata = std::fs::metadata(&path).unwrap();
+    assert!(metadata.file_type().is_socket());
+    assert_eq!(metadata.permissions().mode() & 0o777, 0o600);
+
+    // 正在执行的请求在关闭时完成并写出响应
+    let mut client = Client::connect(&path).await;
+    let query = "WITH RECURSIVE c(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM c WHERE x < 3000000) SELECT count(*) AS n FROM c";
+    let id = client.send("query", json!({ "query": query })).await;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    stop.send(()).unwrap();
+
+    let (ok, text) = client.receive(id).await;
+    assert!(ok, "{}", text);
+    assert_eq!(
+        serde_json::from_str::<Value>(&text).unwrap()["rows"][0]["n"],
+        3000000
+    );
+    tokio::time::timeout(Duration::from_secs(10), server)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(!path.exists());
+}
+
+#[tokio::test]
+async fn closing_a_connection_rolls_back_its_transaction() {
+    let dir = TempDir::new("unix-sessions");
+    let path = dir.path().join("mcp.sock");
+    let router = SQLiteRouter::new(":memory:").unwrap();
+    let (_server, _stop) = start(&path, router);
+
+    let mut first = Client::connect(&path).await;
+    let mut second = Client::connect(&path).await;
+    let (ok, text) = first
+        .call("execute", json!({ "statement": "CREATE TABLE t (x)" }))
+        .await;
+    assert!(ok, "{}", text);
+    let (ok, text) = first.call("begin_transaction", json!({})).await;
+    assert!(ok, "{}", text);
+
+    let (ok, text) = second
+        .call(
+            "execute",
+            json!({ "statement": "INSERT INTO t VALUES (1)" }),
+        )
+        .await;
+    assert!(!ok);
+    assert!(text.contains("another session"), "{}", text);
+
+    // 连接关闭后事务被回滚，其他连接可以写入
+    drop(first);
+    let mut written = false;
+    for _ in 0..50 {
+        let (ok, _) = second
+            .call(
+                "execute",
+                json!({ "statement": "INSERT INTO t VALUES (1)" }),
+            )
+            .await;
+        if ok {
+            written = true;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(written);
+}
+
+#[tokio::test]
+async fn bind_refuses_files_and_live_sockets() {
+    let dir = TempDir::new("unix-bind");
+
+    let file = dir.path().join("not-a-socket");
+    std::fs::write(&file, "data").unwrap();
+    let error = bind(&file, 0o600).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::AlreadyExists);
+    assert_eq!(std::fs::read_to_string(&file).unwrap(), "data");
+
+    let path = dir.path().join("mcp.sock");
+    let listener = bind(&path, 0o600).unwrap();
+    let error = bind(&path, 0o600).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::AddrInUse);
+
+    // 没有进程监听的套接字文件被视为遗留文件并替换
+    drop(listener);
+    assert!(path.exists());
+    bind(&path, 0o660).unwrap();
+    let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o660);
+}
@@ -0,0 +1,68 @@
+//! 数据库工作线程的集成测试
+
+mod common;
+
+use std::time::{Duration, Instant};
+
+use common::call;
+use mcp_server_fishcode2025::Router;
+use mcp_sqlite::{
+    worker::{DbWorker, WorkerStopped},
+    RouterOptions, SQLiteRouter,
+};
+use rusqlite::Connection;
+use serde_json::json;
+
+/// 不会自行结束的查询，由timeout_ms中断
+const SLOW: &str =
+    "WITH RECURSIVE c(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM c) SELECT count(*) FROM c";
+
+#[tokio::test]
+async fn slow_statements_do_not_block_the_runtime() {
+    // 单线程运行时：如果语句在异步任务中执行，其他调用要等它结束
+    let options = RouterOptions {
+        readers: 0,
+        ..Default::default()
+    };
+    let router = SQLiteRouter::with_options(":memory:", options).unwrap();
+
+    let slow = tokio::spawn({
+        let router = router.clone();
+        async move {
+            let started = Instant::now();
+            let _ = router
+                .call_tool("query", json!({ "query": SLOW, "timeout_ms": 1000 }))
+                .await;
+            started.elapsed()
+        }
+    });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    // 与数据库无关的请求和计时器在语句执行期间仍然被处理
+    let started = Instant::now();
+    let tools = router.list_tools();
+    assert!(!tools.is_empty());
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    assert!(started.elapsed() < Duration::from_millis(500));
+
+    // 同一个连接上的调用排队等待语句结束
+    let result = call(&router, "query", json!({ "query": "SELECT 1 AS one" })).await;
+    assert_eq!(result["rows"][0]["one"], 1);
+    assert!(slow.await.unwrap() >= Duration::from_millis(900));
+}
+
+#[tokio::test]
+async fn panicking_operations_report_worker_stopped() {
+    let worker = DbWorker::spawn("test-worker", Connection::open_in_memory().unwrap());
+
+    let result: Result<(), WorkerStopped> = worker.run(|_| panic!("boom")).await;
+    assert_eq!(result, Err(WorkerStopped));
+
+    // panic只影响那一次调用，线程继续处理之后的操作
+    let n: i64 = worker
+        .run(|conn| conn.query_row("SELECT 40 + 2", [], |row| row.get(0)))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(n, 42);
+}